@@ -0,0 +1,24 @@
+-- Points the player at the exit, leaves some ammo in the pit the first time they find it and
+-- trades the idol for health on the platform.
+local rewarded = false
+
+function on_load()
+    message("Find the exit in the far corner")
+end
+
+function on_enter(region)
+    if region == "pit" and not rewarded then
+        rewarded = true
+        spawn_pickup(8.0, 3.0, { type = "ammo", amount = 5 })
+        message("Something glints in the pit")
+    elseif region == "platform" and take("brass idol") then
+        message("The idol sinks into the platform")
+        spawn_pickup(7.5, 7.0, { type = "health", amount = 50 })
+    end
+end
+
+function on_use(entity)
+    if entity.kind == "switch" then
+        message("Something stirs in the dark")
+    end
+end
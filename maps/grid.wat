@@ -0,0 +1,10 @@
+;; Makes the light in the far corner flare up once the player finds the pit, the first of the
+;; map's trigger regions.
+(module
+  (import "rhywbeth" "set_light" (func $set_light (param i32 i32 f32)))
+  (func (export "api_version") (result i32)
+    i32.const 1)
+  (func (export "on_enter") (param $region i32)
+    (if (i32.eqz (local.get $region))
+      (then
+        (call $set_light (i32.const 1) (i32.const 0xff4000) (f32.const 2.0))))))
@@ -0,0 +1,472 @@
+use std::{
+    f32::consts::PI,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use rodio::{
+    mixer::Mixer,
+    source::{SawtoothWave, SineWave, SquareWave},
+    ChannelCount, Decoder, DeviceSinkBuilder, Player, Sample, SampleRate, Source,
+};
+
+use crate::{
+    event::GameEvent,
+    sound::{Ambience, AmbientSound, Cue},
+    vec2::Vec2,
+};
+
+// How far away a positioned effect like a door can still be heard.
+const EFFECT_RADIUS: f32 = 12.0;
+
+// How long one music track takes to fade into the next.
+const CROSSFADE: Duration = Duration::from_secs(2);
+// How often the audio thread wakes up to move fades along.
+const FADE_STEP: Duration = Duration::from_millis(20);
+
+type Effect = Box<dyn Source + Send>;
+
+enum Message {
+    Effect(Effect),
+    // `None` fades the current track out without starting another.
+    Music(Option<Effect>),
+    MusicVolume(f32),
+}
+
+// Plays sounds on a thread of its own, so starting a sound never holds up a frame.
+pub struct Audio {
+    sender: Sender<Message>,
+    ambience: Vec<(AmbientSound, Arc<Gains>)>,
+    position: Vec2,
+    rotation: f32,
+    music: Option<PathBuf>,
+}
+
+impl Audio {
+    pub fn new(sounds: &[AmbientSound]) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let (ready, opened) = mpsc::channel();
+        // The output device can't be moved between threads, so it's opened on the one that
+        // uses it.
+        thread::spawn(move || {
+            let mut sink = match DeviceSinkBuilder::open_default_sink() {
+                Ok(sink) => sink,
+                Err(e) => {
+                    let _ = ready.send(Err(anyhow!("Could not open audio output: {e}")));
+                    return;
+                }
+            };
+            sink.log_on_drop(false);
+            let _ = ready.send(Ok(()));
+            let mut music = Music::new();
+            let mut last_step = Instant::now();
+            loop {
+                match receiver.recv_timeout(FADE_STEP) {
+                    Ok(Message::Effect(effect)) => sink.mixer().add(effect),
+                    Ok(Message::Music(track)) => music.switch(sink.mixer(), track),
+                    Ok(Message::MusicVolume(volume)) => music.volume = volume,
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                let now = Instant::now();
+                music.step(now - last_step);
+                last_step = now;
+            }
+        });
+        opened.recv()??;
+        let mut audio = Self {
+            sender,
+            ambience: Vec::new(),
+            position: Vec2::ZERO,
+            rotation: 0.0,
+            music: None,
+        };
+        for &sound in sounds {
+            // Silent until the first call to `listen` says where the player is.
+            let gains = Arc::new(Gains::new(0.0, 0.0));
+            audio.send(Panned::new(ambience(sound.sound), gains.clone()));
+            audio.ambience.push((sound, gains));
+        }
+        Ok(audio)
+    }
+
+    // Moves the listener, re-panning every ambient sound around them.
+    pub fn listen(&mut self, position: Vec2, rotation: f32) {
+        self.position = position;
+        self.rotation = rotation;
+        for (sound, gains) in self.ambience.iter() {
+            let (left, right) = stereo_gains(position, rotation, sound.position, sound.radius);
+            gains.set(left * sound.volume, right * sound.volume);
+        }
+    }
+
+    pub fn play(&self, event: GameEvent) {
+        let (left, right) = match event.position() {
+            Some(source) => stereo_gains(self.position, self.rotation, source, EFFECT_RADIUS),
+            None => (1.0, 1.0),
+        };
+        let Some(effect) = effect(event) else {
+            return;
+        };
+        self.send(Panned::new(effect, Arc::new(Gains::new(left, right))));
+    }
+
+    // Cross-fades to a looping track, or out to silence for `None`. Asking for the track
+    // that's already playing leaves it be.
+    pub fn play_music(&mut self, path: Option<&Path>) -> Result<()> {
+        if self.music.as_deref() == path {
+            return Ok(());
+        }
+        let track = match path {
+            Some(path) => {
+                let file = File::open(path)
+                    .with_context(|| format!("Could not open music {}", path.display()))?;
+                let decoder = Decoder::new_looped(BufReader::new(file))
+                    .with_context(|| format!("Could not decode music {}", path.display()))?;
+                Some(Box::new(decoder) as Effect)
+            }
+            None => None,
+        };
+        self.music = path.map(Path::to_path_buf);
+        let _ = self.sender.send(Message::Music(track));
+        Ok(())
+    }
+
+    pub fn set_music_volume(&self, volume: f32) {
+        let _ = self.sender.send(Message::MusicVolume(volume));
+    }
+
+    fn send(&self, effect: impl Source + Send + 'static) {
+        // The thread only goes away if the device failed, in which case there's nothing to do.
+        let _ = self.sender.send(Message::Effect(Box::new(effect)));
+    }
+}
+
+// The track that's playing, plus any that are still fading out after being replaced. Each has
+// a fade from 0.0 (silent) to 1.0 (full volume).
+struct Music {
+    volume: f32,
+    current: Option<(Player, f32)>,
+    fading: Vec<(Player, f32)>,
+}
+
+impl Music {
+    fn new() -> Self {
+        Self {
+            volume: 1.0,
+            current: None,
+            fading: Vec::new(),
+        }
+    }
+
+    fn switch(&mut self, mixer: &Mixer, track: Option<Effect>) {
+        self.fading.extend(self.current.take());
+        self.current = track.map(|track| {
+            let player = Player::connect_new(mixer);
+            player.set_volume(0.0);
+            player.append(track);
+            (player, 0.0)
+        });
+    }
+
+    fn step(&mut self, dt: Duration) {
+        let change = dt.as_secs_f32() / CROSSFADE.as_secs_f32();
+        if let Some((player, fade)) = &mut self.current {
+            *fade = (*fade + change).min(1.0);
+            player.set_volume(*fade * self.volume);
+        }
+        for (player, fade) in self.fading.iter_mut() {
+            *fade = (*fade - change).max(0.0);
+            player.set_volume(*fade * self.volume);
+        }
+        self.fading.retain(|(_, fade)| *fade > 0.0);
+    }
+}
+
+// Left and right volume for a sound at `source` heard from `position` while facing `rotation`.
+// Sounds fade out linearly towards `radius` and move between the ears as the angle to them
+// changes, staying at full volume in both when straight ahead or behind.
+pub fn stereo_gains(position: Vec2, rotation: f32, source: Vec2, radius: f32) -> (f32, f32) {
+    let offset = source - position;
+    let attenuation = (1.0 - offset.length() / radius).clamp(0.0, 1.0);
+    if offset.length() <= f32::EPSILON {
+        return (attenuation, attenuation);
+    }
+    let angle = (offset.angle() - rotation + PI).rem_euclid(2.0 * PI) - PI;
+    // -1.0 fully left, 1.0 fully right. Angles grow anticlockwise, so positive ones are left.
+    let pan = -angle.sin();
+    (
+        attenuation * (1.0 - pan).min(1.0),
+        attenuation * (1.0 + pan).min(1.0),
+    )
+}
+
+// Channel gains shared with the audio thread, stored as bits so they can change while playing.
+struct Gains {
+    left: AtomicU32,
+    right: AtomicU32,
+}
+
+impl Gains {
+    fn new(left: f32, right: f32) -> Self {
+        Self {
+            left: AtomicU32::new(left.to_bits()),
+            right: AtomicU32::new(right.to_bits()),
+        }
+    }
+
+    fn set(&self, left: f32, right: f32) {
+        self.left.store(left.to_bits(), Ordering::Relaxed);
+        self.right.store(right.to_bits(), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> (f32, f32) {
+        (
+            f32::from_bits(self.left.load(Ordering::Relaxed)),
+            f32::from_bits(self.right.load(Ordering::Relaxed)),
+        )
+    }
+}
+
+// Spreads a mono source over two channels with separate volumes.
+struct Panned<S> {
+    source: S,
+    gains: Arc<Gains>,
+    // The right half of the frame whose left half was just returned.
+    right: Option<Sample>,
+}
+
+impl<S: Source> Panned<S> {
+    fn new(source: S, gains: Arc<Gains>) -> Self {
+        Self {
+            source,
+            gains,
+            right: None,
+        }
+    }
+}
+
+impl<S: Source> Iterator for Panned<S> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        let sample = self.source.next()?;
+        let (left, right) = self.gains.get();
+        self.right = Some(sample * right);
+        Some(sample * left)
+    }
+}
+
+impl<S: Source> Source for Panned<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.source.current_span_len().map(|len| len * 2)
+    }
+
+    fn channels(&self) -> ChannelCount {
+        ChannelCount::new(2).unwrap()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+// Effects are synthesized rather than loaded so the game doesn't need any sound files.
+fn effect(event: GameEvent) -> Option<Effect> {
+    Some(match event {
+        GameEvent::Footstep => Box::new(
+            SquareWave::new(70.0)
+                .take_duration(Duration::from_millis(50))
+                .low_pass(400)
+                .amplify(0.2),
+        ),
+        GameEvent::DoorOpened(_) => Box::new(
+            SawtoothWave::new(110.0)
+                .take_duration(Duration::from_millis(400))
+                .low_pass(600)
+                .fade_in(Duration::from_millis(50))
+                .amplify(0.15),
+        ),
+        GameEvent::DoorClosed(_) => Box::new(
+            SawtoothWave::new(82.0)
+                .take_duration(Duration::from_millis(300))
+                .low_pass(500)
+                .amplify(0.15),
+        ),
+        GameEvent::ExitReached => Box::new(
+            SineWave::new(440.0)
+                .take_duration(Duration::from_millis(150))
+                .mix(
+                    SineWave::new(660.0)
+                        .take_duration(Duration::from_millis(300))
+                        .delay(Duration::from_millis(150)),
+                )
+                .amplify(0.2),
+        ),
+        GameEvent::Fired | GameEvent::Shot(_) => Box::new(
+            SquareWave::new(220.0)
+                .take_duration(Duration::from_millis(80))
+                .low_pass(1200)
+                .amplify(0.15),
+        ),
+        GameEvent::Impact(_) => Box::new(
+            SquareWave::new(90.0)
+                .take_duration(Duration::from_millis(60))
+                .low_pass(500)
+                .amplify(0.15),
+        ),
+        GameEvent::Hit { .. } => Box::new(
+            SawtoothWave::new(140.0)
+                .take_duration(Duration::from_millis(120))
+                .low_pass(700)
+                .amplify(0.2),
+        ),
+        GameEvent::PickedUp(_) => Box::new(
+            SineWave::new(880.0)
+                .take_duration(Duration::from_millis(60))
+                .mix(
+                    SineWave::new(1320.0)
+                        .take_duration(Duration::from_millis(90))
+                        .delay(Duration::from_millis(60)),
+                )
+                .amplify(0.15),
+        ),
+        GameEvent::Teleported { .. } => Box::new(
+            SineWave::new(300.0)
+                .take_duration(Duration::from_millis(100))
+                .mix(
+                    SineWave::new(600.0)
+                        .take_duration(Duration::from_millis(100))
+                        .delay(Duration::from_millis(80)),
+                )
+                .mix(
+                    SineWave::new(1200.0)
+                        .take_duration(Duration::from_millis(150))
+                        .delay(Duration::from_millis(160)),
+                )
+                .amplify(0.15),
+        ),
+        GameEvent::Portalled { .. } => Box::new(
+            SineWave::new(180.0)
+                .take_duration(Duration::from_millis(200))
+                .low_pass(400)
+                .fade_in(Duration::from_millis(100))
+                .amplify(0.05),
+        ),
+        GameEvent::Switched(_) => Box::new(
+            SquareWave::new(600.0)
+                .take_duration(Duration::from_millis(25))
+                .mix(
+                    SquareWave::new(400.0)
+                        .take_duration(Duration::from_millis(25))
+                        .delay(Duration::from_millis(50)),
+                )
+                .low_pass(2000)
+                .amplify(0.15),
+        ),
+        GameEvent::Locked(_) => Box::new(
+            SquareWave::new(120.0)
+                .take_duration(Duration::from_millis(40))
+                .mix(
+                    SquareWave::new(120.0)
+                        .take_duration(Duration::from_millis(40))
+                        .delay(Duration::from_millis(90)),
+                )
+                .low_pass(600)
+                .amplify(0.15),
+        ),
+        GameEvent::OutOfAmmo => Box::new(
+            SquareWave::new(1000.0)
+                .take_duration(Duration::from_millis(15))
+                .amplify(0.1),
+        ),
+        GameEvent::Killed(_) => Box::new(
+            SawtoothWave::new(180.0)
+                .take_duration(Duration::from_millis(250))
+                .mix(
+                    SawtoothWave::new(90.0)
+                        .take_duration(Duration::from_millis(350))
+                        .delay(Duration::from_millis(100)),
+                )
+                .low_pass(800)
+                .amplify(0.2),
+        ),
+        GameEvent::Died => Box::new(
+            SineWave::new(330.0)
+                .take_duration(Duration::from_millis(300))
+                .mix(
+                    SineWave::new(220.0)
+                        .take_duration(Duration::from_millis(600))
+                        .delay(Duration::from_millis(300)),
+                )
+                .amplify(0.25),
+        ),
+        GameEvent::Bump => Box::new(
+            SineWave::new(55.0)
+                .take_duration(Duration::from_millis(120))
+                .amplify(0.5),
+        ),
+        GameEvent::Cue(cue, _) => cue_effect(cue),
+        // Only there for scripts to react to.
+        GameEvent::Used(_) | GameEvent::Entered(_) | GameEvent::Left(_) => return None,
+        // Only there for the renderer, and anything that shakes the view has a sound of its own.
+        GameEvent::Shake(_) => return None,
+    })
+}
+
+fn cue_effect(cue: Cue) -> Effect {
+    match cue {
+        Cue::Chime => Box::new(
+            SineWave::new(660.0)
+                .take_duration(Duration::from_millis(400))
+                .mix(
+                    SineWave::new(990.0)
+                        .take_duration(Duration::from_millis(600))
+                        .delay(Duration::from_millis(120)),
+                )
+                .fade_in(Duration::from_millis(10))
+                .amplify(0.15),
+        ),
+        Cue::Rumble => Box::new(
+            SawtoothWave::new(40.0)
+                .take_duration(Duration::from_millis(1200))
+                .low_pass(200)
+                .fade_in(Duration::from_millis(300))
+                .amplify(0.3),
+        ),
+        Cue::Alarm => Box::new(
+            SquareWave::new(880.0)
+                .take_duration(Duration::from_millis(150))
+                .mix(
+                    SquareWave::new(660.0)
+                        .take_duration(Duration::from_millis(150))
+                        .delay(Duration::from_millis(200)),
+                )
+                .low_pass(2000)
+                .amplify(0.1),
+        ),
+    }
+}
+
+fn ambience(sound: Ambience) -> Effect {
+    match sound {
+        Ambience::Hum => Box::new(SineWave::new(60.0).mix(SineWave::new(120.0)).amplify(0.1)),
+        Ambience::Buzz => Box::new(SawtoothWave::new(120.0).low_pass(800).amplify(0.05)),
+    }
+}
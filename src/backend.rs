@@ -0,0 +1,197 @@
+use std::{collections::VecDeque, io::Write, time::Duration};
+
+use anyhow::Result;
+
+use self::event::Event;
+use crate::{canvas::Canvas, colour::ColourMode, framebuffer::FrameBuffer};
+
+// Keys and mouse drags are crossterm's everywhere it builds. On the web, where it doesn't, the
+// same types are defined here instead, so nothing else has to know which it's getting.
+#[cfg(not(target_arch = "wasm32"))]
+pub use crossterm::event;
+
+#[cfg(target_arch = "wasm32")]
+pub mod event {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub enum Event {
+        FocusGained,
+        FocusLost,
+        Key(KeyEvent),
+        Mouse(MouseEvent),
+        Paste(String),
+        Resize(u16, u16),
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub struct KeyEvent {
+        pub code: KeyCode,
+        pub modifiers: KeyModifiers,
+        pub kind: KeyEventKind,
+        pub state: KeyEventState,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub enum KeyCode {
+        Backspace,
+        Enter,
+        Left,
+        Right,
+        Up,
+        Down,
+        Home,
+        End,
+        PageUp,
+        PageDown,
+        Tab,
+        Delete,
+        Insert,
+        F(u8),
+        Char(char),
+        Esc,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub enum KeyEventKind {
+        Press,
+        Repeat,
+        Release,
+    }
+
+    // Only the modifiers the game looks at, as bits like crossterm's.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub struct KeyModifiers(u8);
+
+    impl KeyModifiers {
+        pub const NONE: Self = Self(0);
+        pub const SHIFT: Self = Self(1);
+        pub const CONTROL: Self = Self(2);
+
+        pub fn contains(self, other: Self) -> bool {
+            self.0 & other.0 == other.0
+        }
+    }
+
+    // Key states aren't used by the game, so they're never set.
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub struct KeyEventState;
+
+    impl KeyEventState {
+        pub const NONE: Self = Self;
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub struct MouseEvent {
+        pub kind: MouseEventKind,
+        pub column: u16,
+        pub row: u16,
+        pub modifiers: KeyModifiers,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub enum MouseEventKind {
+        Down(MouseButton),
+        Up(MouseButton),
+        Drag(MouseButton),
+        Moved,
+        ScrollDown,
+        ScrollUp,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub enum MouseButton {
+        Left,
+        Right,
+        Middle,
+    }
+}
+
+// Somewhere frames are shown and keys come from: the terminal, a window, a web page or nothing
+// at all. Frames are handed over as terminal cells, whatever ends up showing them.
+pub trait Backend {
+    // Size of the screen in cells.
+    fn size(&self) -> Result<(u16, u16)>;
+    fn present(&mut self, frame: &FrameBuffer) -> Result<()>;
+    // Waits up to `timeout` for a key, mouse drag or resize.
+    fn poll_event(&mut self, timeout: Duration) -> Result<Option<Event>>;
+
+    // Backends that show real pixels give how many of them each cell covers, so the scene can
+    // be drawn at their full resolution and shown with `present_pixels` instead.
+    fn cell_pixels(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    // Shows `scene`, drawn at `cell_pixels` per cell, with the frame's non-empty cells over it.
+    fn present_pixels(&mut self, _scene: &mut Canvas, frame: &FrameBuffer) -> Result<()> {
+        self.present(frame)
+    }
+
+    // Whether the player has closed the screen, e.g. a window, rather than quitting from the
+    // game.
+    fn closed(&self) -> bool {
+        false
+    }
+}
+
+// Turns frames into the escape codes that draw them, for backends that end in a terminal. Only
+// the cells that changed since the last frame are written.
+pub struct AnsiWriter {
+    pub colours: ColourMode,
+    previous: FrameBuffer,
+}
+
+impl AnsiWriter {
+    pub fn new(colours: ColourMode) -> Self {
+        Self {
+            colours,
+            previous: FrameBuffer::new(0, 0),
+        }
+    }
+
+    pub fn write(&mut self, frame: &FrameBuffer, out: &mut impl Write) -> Result<()> {
+        frame.write_diff(&self.previous, self.colours, out)?;
+        self.previous.clone_from(frame);
+        Ok(())
+    }
+
+    // Forgets what is on screen so the next frame is drawn in full, e.g. after a resize.
+    pub fn invalidate(&mut self) {
+        self.previous = FrameBuffer::new(0, 0);
+    }
+}
+
+// A screen that's never shown, which keeps the last frame and plays back events queued up for
+// it. Drawing works the same as anywhere else, e.g. for tests or running without a terminal.
+pub struct Headless {
+    pub size: (u16, u16),
+    pub frame: FrameBuffer,
+    pub events: VecDeque<Event>,
+}
+
+impl Headless {
+    pub fn new(size: (u16, u16)) -> Self {
+        Self {
+            size,
+            frame: FrameBuffer::new(0, 0),
+            events: VecDeque::new(),
+        }
+    }
+}
+
+impl Backend for Headless {
+    fn size(&self) -> Result<(u16, u16)> {
+        Ok(self.size)
+    }
+
+    fn present(&mut self, frame: &FrameBuffer) -> Result<()> {
+        self.frame.clone_from(frame);
+        Ok(())
+    }
+
+    // Never waits, since nothing more is coming.
+    fn poll_event(&mut self, _timeout: Duration) -> Result<Option<Event>> {
+        Ok(self.events.pop_front())
+    }
+}
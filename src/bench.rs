@@ -0,0 +1,68 @@
+use std::{
+    f32::consts::PI,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    canvas::Canvas,
+    render::{draw_scene, Camera},
+    screenshot::Resolution,
+    world::World,
+};
+
+// Frame timings from drawing a world over and over with nothing else going on.
+pub struct Report {
+    pub segments: usize,
+    pub frames: usize,
+    pub rays: usize,
+    pub total: Duration,
+    pub min: Duration,
+    pub average: Duration,
+    pub p99: Duration,
+}
+
+// Turns the camera a full circle on the spot over `frames` frames, nodding up and down as it
+// goes, and times drawing each one as fast as possible.
+pub fn run(world: &World, camera: Camera, resolution: Resolution, frames: usize) -> Report {
+    let mut canvas = Canvas::new(resolution.width, resolution.height);
+    let mut times = Vec::with_capacity(frames);
+    for frame in 0..frames {
+        let progress = frame as f32 / frames as f32;
+        let mut camera = camera;
+        camera.rotate(progress * 2.0 * PI);
+        camera.look((progress * 4.0 * PI).sin() * 0.25);
+        let start = Instant::now();
+        draw_scene(&mut canvas, &camera, world);
+        times.push(start.elapsed());
+    }
+    times.sort();
+    let total = times.iter().sum::<Duration>();
+    Report {
+        segments: world.segments.len(),
+        frames,
+        rays: resolution.width * frames,
+        total,
+        min: times.first().copied().unwrap_or_default(),
+        average: total / frames.max(1) as u32,
+        p99: times
+            .get((frames * 99).div_ceil(100).saturating_sub(1))
+            .copied()
+            .unwrap_or_default(),
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "segments: {}", self.segments)?;
+        writeln!(f, "frames:   {}", self.frames)?;
+        writeln!(f, "min:      {:.2?}", self.min)?;
+        writeln!(f, "average:  {:.2?}", self.average)?;
+        writeln!(f, "p99:      {:.2?}", self.p99)?;
+        write!(
+            f,
+            "rays/sec: {:.0}",
+            self.rays as f64 / self.total.as_secs_f64().max(f64::EPSILON)
+        )
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+// The file at the top of a campaign's directory or zip that lists its levels.
+pub const MANIFEST: &str = "campaign.toml";
+
+// An ordered pack of maps, played one after another.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Campaign {
+    pub name: String,
+    pub levels: Vec<CampaignLevel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CampaignLevel {
+    pub name: String,
+    // The map file, relative to the campaign.
+    pub map: PathBuf,
+    // Music to play instead of the map's own, relative to the campaign.
+    pub music: Option<PathBuf>,
+    // Seconds a good run through the level should take.
+    pub par: Option<f32>,
+}
+
+impl Campaign {
+    // Finds the directory a campaign's files are in. Zips are unpacked into the data directory
+    // first, so their maps and music load like any others.
+    pub fn locate(path: &Path) -> Result<PathBuf> {
+        let dir = if path.is_dir() {
+            path.to_path_buf()
+        } else {
+            let file = File::open(path)
+                .with_context(|| format!("Could not open campaign {}", path.display()))?;
+            let mut archive = ZipArchive::new(file)
+                .with_context(|| format!("Could not read campaign {}", path.display()))?;
+            let name = path.file_stem().unwrap_or_default();
+            let dir = dirs::data_dir()
+                .map(|dir| dir.join("rhywbeth").join("campaigns"))
+                .unwrap_or_else(|| PathBuf::from("campaigns"))
+                .join(name);
+            archive
+                .extract(&dir)
+                .with_context(|| format!("Could not unpack campaign {}", path.display()))?;
+            dir
+        };
+        fs::canonicalize(&dir).with_context(|| format!("Could not find {}", dir.display()))
+    }
+
+    // Reads the manifest in `dir`, with every path made relative to it.
+    pub fn read(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST);
+        let source = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read campaign {}", path.display()))?;
+        let mut campaign: Campaign = toml::from_str(&source)
+            .with_context(|| format!("Could not parse campaign {}", path.display()))?;
+        if campaign.levels.is_empty() {
+            bail!("Campaign {} has no levels", path.display());
+        }
+        for level in campaign.levels.iter_mut() {
+            level.map = dir.join(&level.map);
+            if let Some(music) = level.music.as_mut() {
+                *music = dir.join(&*music);
+            }
+        }
+        Ok(campaign)
+    }
+}
+
+// How far the player has got through each campaign, by name.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Progress {
+    unlocked: HashMap<String, usize>,
+}
+
+impl Progress {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rhywbeth").join("progress.ron"))
+    }
+
+    // Nothing has been unlocked yet if there's no progress file.
+    pub fn read(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Could not read progress {}", path.display()))?;
+        ron::from_str(&source)
+            .with_context(|| format!("Could not parse progress {}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Could not create {}", parent.display()))?;
+        }
+        let source = ron::ser::to_string_pretty(self, PrettyConfig::default())?;
+        fs::write(path, source)
+            .with_context(|| format!("Could not write progress {}", path.display()))
+    }
+
+    // How many levels from the start of a campaign can be played. The first always can.
+    pub fn unlocked(&self, campaign: &str) -> usize {
+        self.unlocked.get(campaign).copied().unwrap_or(0).max(1)
+    }
+
+    // Opens up the level after `index` once it has been finished.
+    pub fn complete(&mut self, campaign: &str, index: usize) {
+        let unlocked = self.unlocked.entry(campaign.to_string()).or_insert(1);
+        *unlocked = (*unlocked).max(index + 2);
+    }
+}
@@ -0,0 +1,60 @@
+use image::RgbImage;
+
+use crate::colour::Rgb;
+
+// A grid of pixels the scene is rasterized into before being turned into terminal cells.
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+    // The height of a pixel relative to its width, which depends on how the canvas ends up
+    // on screen.
+    pub aspect: f32,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Rgb::BLACK; width * height],
+            aspect: 1.0,
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, colour: Rgb) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = colour;
+        }
+    }
+
+    // Copies all of `other` into this canvas with its top left corner at `x`, `y`.
+    pub fn blit(&mut self, other: &Canvas, x: usize, y: usize) {
+        for row in 0..other.height {
+            for column in 0..other.width {
+                self.set(x + column, y + row, other.get(column, row));
+            }
+        }
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.pixels.clear();
+        self.pixels.resize(width * height, Rgb::BLACK);
+    }
+
+    pub fn to_image(&self) -> RgbImage {
+        let data = self
+            .pixels
+            .iter()
+            .flat_map(|colour| [colour.r, colour.g, colour.b])
+            .collect();
+        // The data is exactly width by height pixels, so it always fits.
+        RgbImage::from_raw(self.width as u32, self.height as u32, data).unwrap()
+    }
+}
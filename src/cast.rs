@@ -0,0 +1,63 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde_json::json;
+
+// Hides the cursor when the cast starts playing, as the game does in the terminal.
+const HIDE_CURSOR: &str = "\x1b[?25l";
+
+// Everything written to the terminal, with when it was written, as an asciinema v2 cast that
+// plays back exactly what was on screen.
+pub struct Cast {
+    out: BufWriter<File>,
+    start: Instant,
+    size: (u16, u16),
+}
+
+impl Cast {
+    pub fn create(path: &Path, size: (u16, u16)) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Could not create {}", path.display()))?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let header = json!({
+            "version": 2,
+            "width": size.0,
+            "height": size.1,
+            "timestamp": timestamp,
+            "title": "rhywbeth",
+        });
+        let mut cast = Self {
+            out: BufWriter::new(file),
+            start: Instant::now(),
+            size,
+        };
+        writeln!(cast.out, "{header}")?;
+        cast.event("o", HIDE_CURSOR)?;
+        Ok(cast)
+    }
+
+    // Adds a frame's output, noting the terminal's new size first if it's changed.
+    pub fn frame(&mut self, size: (u16, u16), output: &[u8]) -> Result<()> {
+        if size != self.size {
+            self.size = size;
+            self.event("r", &format!("{}x{}", size.0, size.1))?;
+        }
+        if !output.is_empty() {
+            self.event("o", &String::from_utf8_lossy(output))?;
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, kind: &str, data: &str) -> Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+        writeln!(self.out, "{}", json!([time, kind, data])).context("Could not write the cast")
+    }
+}
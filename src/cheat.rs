@@ -0,0 +1,73 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{bail, Error, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cheat {
+    // Walk through walls.
+    Noclip,
+    // Take no damage.
+    God,
+    // Show the whole map on the minimap, with everything on it.
+    Reveal,
+}
+
+impl Cheat {
+    pub const ALL: [Cheat; 3] = [Cheat::Noclip, Cheat::God, Cheat::Reveal];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Cheat::Noclip => "noclip",
+            Cheat::God => "god",
+            Cheat::Reveal => "reveal",
+        }
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Cheat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Cheat::ALL.into_iter().find(|cheat| cheat.name() == s) {
+            Some(cheat) => Ok(cheat),
+            None => bail!("Unknown cheat {s}"),
+        }
+    }
+}
+
+// Which cheats are on, for players stuck on a map and for trying out new ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cheats {
+    pub noclip: bool,
+    pub god: bool,
+    pub reveal: bool,
+}
+
+impl Cheats {
+    pub fn get(&self, cheat: Cheat) -> bool {
+        match cheat {
+            Cheat::Noclip => self.noclip,
+            Cheat::God => self.god,
+            Cheat::Reveal => self.reveal,
+        }
+    }
+
+    // Turns the cheat on or off, returning whether it's now on.
+    pub fn toggle(&mut self, cheat: Cheat) -> bool {
+        let on = match cheat {
+            Cheat::Noclip => &mut self.noclip,
+            Cheat::God => &mut self.god,
+            Cheat::Reveal => &mut self.reveal,
+        };
+        *on = !*on;
+        *on
+    }
+}
@@ -0,0 +1,28 @@
+use crate::{sector::Reach, vec2::Vec2, world::World};
+
+pub const PLAYER_RADIUS: f32 = 0.2;
+
+// Bounds how many walls a single move can slide along, e.g. when pushing into a corner.
+const MAX_SLIDES: usize = 3;
+
+// Moves from `position` by `delta`, stopping `PLAYER_RADIUS` short of any wall or ledge in the
+// way and sliding the rest of the motion along the wall's tangent. `reach` decides which
+// ledges are in the way.
+pub fn slide(world: &World, mut position: Vec2, mut delta: Vec2, reach: Reach) -> Vec2 {
+    for _ in 0..MAX_SLIDES {
+        let distance = delta.length();
+        if distance <= f32::EPSILON {
+            break;
+        }
+        let Some(hit) =
+            world.cast_movement(position, delta.angle(), distance + PLAYER_RADIUS, reach)
+        else {
+            return position + delta;
+        };
+        let allowed = (hit.distance - PLAYER_RADIUS).clamp(0.0, distance) / distance;
+        position += delta * allowed;
+        let remaining = delta * (1.0 - allowed);
+        delta = remaining - hit.normal * remaining.dot(hit.normal);
+    }
+    position
+}
@@ -0,0 +1,248 @@
+use std::{env, fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Error, Result};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+// The classic 16 terminal colours in the order terminals number them, using xterm's default
+// values.
+pub const NAMED: [(&str, Rgb); 16] = [
+    ("black", Rgb::new(0, 0, 0)),
+    ("dark_red", Rgb::new(205, 0, 0)),
+    ("dark_green", Rgb::new(0, 205, 0)),
+    ("dark_yellow", Rgb::new(205, 205, 0)),
+    ("dark_blue", Rgb::new(0, 0, 238)),
+    ("dark_magenta", Rgb::new(205, 0, 205)),
+    ("dark_cyan", Rgb::new(0, 205, 205)),
+    ("grey", Rgb::new(229, 229, 229)),
+    ("dark_grey", Rgb::new(127, 127, 127)),
+    ("red", Rgb::new(255, 0, 0)),
+    ("green", Rgb::new(0, 255, 0)),
+    ("yellow", Rgb::new(255, 255, 0)),
+    ("blue", Rgb::new(92, 92, 255)),
+    ("magenta", Rgb::new(255, 0, 255)),
+    ("cyan", Rgb::new(0, 255, 255)),
+    ("white", Rgb::new(255, 255, 255)),
+];
+
+// The channel values in xterm's 6x6x6 colour cube.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+// Thresholds for ordered dithering, spread so that neighbouring cells get very different ones.
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+impl Rgb {
+    pub const BLACK: Self = Self::new(0, 0, 0);
+    pub const WHITE: Self = Self::new(255, 255, 255);
+    pub const RED: Self = Self::new(255, 0, 0);
+    pub const BLUE: Self = Self::new(92, 92, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    pub fn named(name: &str) -> Option<Self> {
+        NAMED.iter().find(|(n, ..)| *n == name).map(|(_, rgb)| *rgb)
+    }
+
+    pub fn scale(self, factor: f32) -> Self {
+        let factor = factor.max(0.0);
+        Self::new(
+            (self.r as f32 * factor).min(255.0) as u8,
+            (self.g as f32 * factor).min(255.0) as u8,
+            (self.b as f32 * factor).min(255.0) as u8,
+        )
+    }
+
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Self::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+        )
+    }
+
+    fn distance_squared(self, other: Self) -> i32 {
+        let dr = self.r as i32 - other.r as i32;
+        let dg = self.g as i32 - other.g as i32;
+        let db = self.b as i32 - other.b as i32;
+        dr * dr + dg * dg + db * db
+    }
+
+    pub fn to_ansi(self, mode: ColourMode) -> Ansi {
+        match mode {
+            ColourMode::TrueColour => Ansi::Rgb(self),
+            ColourMode::Ansi256 => Ansi::Indexed(self.ansi256()),
+            ColourMode::Ansi16 => {
+                let nearest = (0..NAMED.len())
+                    .min_by_key(|&i| NAMED[i].1.distance_squared(self))
+                    .unwrap();
+                Ansi::Indexed(nearest as u8)
+            }
+        }
+    }
+
+    // Like `to_ansi`, but mixes the nearest palette colour with the next one out past this one,
+    // by an ordered dither for the cell at `x`, `y`. A gradient between two palette colours comes
+    // out as a blend of both instead of a hard band, and colours already in the palette stay as
+    // they are.
+    pub fn to_ansi_dithered(self, mode: ColourMode, x: u16, y: u16) -> Ansi {
+        let Ansi::Indexed(near) = self.to_ansi(mode) else {
+            return Ansi::Rgb(self);
+        };
+        let near_colour = indexed(near);
+        let offset = self.minus(near_colour);
+        // Heads further out the way this colour is off from the nearest one until it reaches
+        // another.
+        let far = [2, 4, 8, 16, 32].into_iter().find_map(|k| {
+            let out = |n: u8, d: i32| (n as i32 + d * k).clamp(0, 255) as u8;
+            let beyond = Self::new(
+                out(near_colour.r, offset[0]),
+                out(near_colour.g, offset[1]),
+                out(near_colour.b, offset[2]),
+            );
+            match beyond.to_ansi(mode) {
+                Ansi::Indexed(far) if far != near => Some(far),
+                _ => None,
+            }
+        });
+        let Some(far) = far else {
+            return Ansi::Indexed(near);
+        };
+        let far_colour = indexed(far);
+        let span = far_colour.distance_squared(near_colour);
+        // How far this colour is along the way from the nearest one to the other.
+        let to_far = far_colour.minus(near_colour);
+        let dot: i32 = offset.iter().zip(to_far).map(|(a, b)| a * b).sum();
+        let along = dot as f32 / span as f32;
+        let threshold = (BAYER[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16.0;
+        Ansi::Indexed(if along > threshold { far } else { near })
+    }
+
+    fn minus(self, other: Self) -> [i32; 3] {
+        [
+            self.r as i32 - other.r as i32,
+            self.g as i32 - other.g as i32,
+            self.b as i32 - other.b as i32,
+        ]
+    }
+
+    // The nearest entry in xterm's 6x6x6 colour cube or its greyscale ramp.
+    fn ansi256(self) -> u8 {
+        let level = |c: u8| {
+            (0..6)
+                .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs())
+                .unwrap()
+        };
+        let (r, g, b) = (level(self.r), level(self.g), level(self.b));
+        let cube = Self::new(CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+        let average = (self.r as u32 + self.g as u32 + self.b as u32) / 3;
+        let grey_index = ((average.saturating_sub(8)) / 10).min(23) as u8;
+        let grey_value = 8 + grey_index * 10;
+        let grey = Self::new(grey_value, grey_value, grey_value);
+        if grey.distance_squared(self) < cube.distance_squared(self) {
+            232 + grey_index
+        } else {
+            16 + 36 * r as u8 + 6 * g as u8 + b as u8
+        }
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(hex) = s.strip_prefix('#') {
+            if hex.len() != 6 {
+                bail!("Invalid colour {s}, expected #rrggbb");
+            }
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+            return Ok(Self::new(channel(0)?, channel(2)?, channel(4)?));
+        }
+        Self::named(&s.to_lowercase()).ok_or_else(|| anyhow!("Unknown colour {s}"))
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl Serialize for Rgb {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+// Colours are written either as a name ("dark_red"), a hex string ("#cd0000") or an array of
+// channels ([205, 0, 0]).
+impl<'de> Deserialize<'de> for Rgb {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Text(String),
+            Channels(u8, u8, u8),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Text(text) => text.parse().map_err(de::Error::custom),
+            Repr::Channels(r, g, b) => Ok(Self::new(r, g, b)),
+        }
+    }
+}
+
+// What a terminal shows for one of its 256 numbered colours.
+fn indexed(index: u8) -> Rgb {
+    match index {
+        0..=15 => NAMED[index as usize].1,
+        16..=231 => {
+            let cube = (index - 16) as usize;
+            Rgb::new(
+                CUBE_LEVELS[cube / 36],
+                CUBE_LEVELS[cube / 6 % 6],
+                CUBE_LEVELS[cube % 6],
+            )
+        }
+        _ => {
+            let grey = 8 + (index - 232) * 10;
+            Rgb::new(grey, grey, grey)
+        }
+    }
+}
+
+// A colour as it's written in an escape code, either exactly or as one of the terminal's
+// numbered colours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ansi {
+    Rgb(Rgb),
+    Indexed(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColourMode {
+    TrueColour,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColourMode {
+    // Guesses what the terminal supports from the same variables most terminal apps check.
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        if colorterm.contains("truecolor") || colorterm.contains("24bit") {
+            Self::TrueColour
+        } else if env::var("TERM").unwrap_or_default().contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+}
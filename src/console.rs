@@ -0,0 +1,38 @@
+use crate::backend::event::{Event, KeyCode, KeyEventKind};
+
+// A line of text typed over the game to run commands, like cheats. The game keeps going while
+// it's open, but keys go to it instead of moving the player.
+#[derive(Debug, Clone, Default)]
+pub struct Console {
+    pub line: String,
+}
+
+// What a key press in the console asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsoleInput {
+    None,
+    // Enter was pressed on the line, which the console is cleared of.
+    Run(String),
+    Close,
+}
+
+impl Console {
+    pub fn handle(&mut self, event: &Event) -> ConsoleInput {
+        let Event::Key(key) = event else {
+            return ConsoleInput::None;
+        };
+        if key.kind == KeyEventKind::Release {
+            return ConsoleInput::None;
+        }
+        match key.code {
+            KeyCode::Enter => return ConsoleInput::Run(std::mem::take(&mut self.line)),
+            KeyCode::Esc => return ConsoleInput::Close,
+            KeyCode::Backspace => {
+                self.line.pop();
+            }
+            KeyCode::Char(c) => self.line.push(c),
+            _ => {}
+        }
+        ConsoleInput::None
+    }
+}
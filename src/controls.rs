@@ -0,0 +1,261 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Error, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::event::KeyCode;
+
+// A key written the way it appears in controls.toml, e.g. "w", "space", "up" or "f1".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Key(pub KeyCode);
+
+const NAMED: &[(&str, KeyCode)] = &[
+    ("space", KeyCode::Char(' ')),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("page_up", KeyCode::PageUp),
+    ("page_down", KeyCode::PageDown),
+    ("insert", KeyCode::Insert),
+    ("delete", KeyCode::Delete),
+];
+
+impl FromStr for Key {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(Key(KeyCode::Char(c)));
+        }
+        let lower = s.to_lowercase();
+        if let Some(&(_, code)) = NAMED.iter().find(|(name, _)| *name == lower) {
+            return Ok(Key(code));
+        }
+        if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse().ok()) {
+            return Ok(Key(KeyCode::F(n)));
+        }
+        bail!("Unknown key {s}")
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((name, _)) = NAMED.iter().find(|(_, code)| *code == self.0) {
+            return write!(f, "{name}");
+        }
+        match self.0 {
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+impl TryFrom<String> for Key {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Key> for String {
+    fn from(key: Key) -> Self {
+        key.to_string()
+    }
+}
+
+// Every action and the keys bound to it. Actions missing from the file keep their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controls {
+    pub forward: Vec<Key>,
+    pub back: Vec<Key>,
+    pub strafe_left: Vec<Key>,
+    pub strafe_right: Vec<Key>,
+    pub turn_left: Vec<Key>,
+    pub turn_right: Vec<Key>,
+    pub jump: Vec<Key>,
+    pub crouch: Vec<Key>,
+    pub widen_fov: Vec<Key>,
+    pub narrow_fov: Vec<Key>,
+    #[serde(rename = "use")]
+    pub use_door: Vec<Key>,
+    pub fire: Vec<Key>,
+    pub minimap: Vec<Key>,
+    pub stats: Vec<Key>,
+    pub overhead: Vec<Key>,
+    pub quick_save: Vec<Key>,
+    pub quick_load: Vec<Key>,
+    pub screenshot: Vec<Key>,
+    pub export_ansi: Vec<Key>,
+    pub pause: Vec<Key>,
+    pub quit: Vec<Key>,
+    pub console: Vec<Key>,
+    pub noclip: Vec<Key>,
+    pub god: Vec<Key>,
+    pub reveal: Vec<Key>,
+    pub mouse: Mouse,
+    pub speeds: Speeds,
+}
+
+// How dragging the mouse looks around. `smoothing` is how much of each drag carries on from the
+// ones before, from 0.0 for none up to `MAX_SMOOTHING`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mouse {
+    pub sensitivity: f32,
+    pub invert_x: bool,
+    pub invert_y: bool,
+    pub smoothing: f32,
+}
+
+pub const MAX_SMOOTHING: f32 = 0.9;
+
+impl Default for Mouse {
+    fn default() -> Self {
+        Self {
+            sensitivity: 1.0,
+            invert_x: false,
+            invert_y: false,
+            smoothing: 0.0,
+        }
+    }
+}
+
+// How much faster than `--speed` the player moves holding Shift, and how much slower holding
+// Ctrl.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Speeds {
+    pub sprint: f32,
+    pub walk: f32,
+}
+
+impl Default for Speeds {
+    fn default() -> Self {
+        Self {
+            sprint: 1.75,
+            walk: 0.5,
+        }
+    }
+}
+
+fn keys(names: &[char]) -> Vec<Key> {
+    names.iter().map(|&c| Key(KeyCode::Char(c))).collect()
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            forward: keys(&['w']),
+            back: keys(&['s']),
+            strafe_left: keys(&['a']),
+            strafe_right: keys(&['d']),
+            turn_left: keys(&['h']),
+            turn_right: keys(&['l']),
+            jump: keys(&['j']),
+            crouch: keys(&['c']),
+            widen_fov: keys(&['+', '=']),
+            narrow_fov: keys(&['-']),
+            use_door: keys(&['e']),
+            fire: keys(&[' ']),
+            minimap: keys(&['m']),
+            stats: vec![Key(KeyCode::F(3))],
+            overhead: vec![Key(KeyCode::Tab)],
+            quick_save: vec![Key(KeyCode::F(5))],
+            quick_load: vec![Key(KeyCode::F(9))],
+            screenshot: vec![Key(KeyCode::F(12))],
+            export_ansi: vec![Key(KeyCode::F(8))],
+            pause: vec![Key(KeyCode::Esc)],
+            quit: keys(&['q']),
+            console: keys(&['`']),
+            noclip: vec![Key(KeyCode::F(6))],
+            god: vec![Key(KeyCode::F(7))],
+            reveal: vec![Key(KeyCode::F(4))],
+            mouse: Mouse::default(),
+            speeds: Speeds::default(),
+        }
+    }
+}
+
+impl Controls {
+    // Reads the controls at `path`, writing the defaults there first if there's nothing yet.
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            let controls = Self::default();
+            controls.write(path)?;
+            return Ok(controls);
+        }
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Could not read controls {}", path.display()))?;
+        toml::from_str(&source)
+            .with_context(|| format!("Could not parse controls {}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Could not create {}", parent.display()))?;
+        }
+        fs::write(path, toml::to_string(self)?)
+            .with_context(|| format!("Could not write controls {}", path.display()))
+    }
+
+    // The keys for a second player sharing the keyboard in split-screen: the arrows and the
+    // keys around Enter. Everything that isn't about moving their own player is left to the
+    // first player's keys.
+    pub fn second_player() -> Self {
+        Self {
+            forward: vec![Key(KeyCode::Up)],
+            back: vec![Key(KeyCode::Down)],
+            strafe_left: keys(&[',']),
+            strafe_right: keys(&['.']),
+            turn_left: vec![Key(KeyCode::Left)],
+            turn_right: vec![Key(KeyCode::Right)],
+            jump: keys(&[';']),
+            crouch: keys(&['\'']),
+            widen_fov: keys(&[']']),
+            narrow_fov: keys(&['[']),
+            use_door: keys(&['/']),
+            fire: vec![Key(KeyCode::Enter)],
+            minimap: Vec::new(),
+            stats: Vec::new(),
+            overhead: Vec::new(),
+            quick_save: Vec::new(),
+            quick_load: Vec::new(),
+            screenshot: Vec::new(),
+            export_ansi: Vec::new(),
+            pause: Vec::new(),
+            quit: Vec::new(),
+            console: Vec::new(),
+            noclip: Vec::new(),
+            god: Vec::new(),
+            reveal: Vec::new(),
+            mouse: Mouse::default(),
+            speeds: Speeds::default(),
+        }
+    }
+
+    // Where controls.toml lives by default, e.g. ~/.config/rhywbeth/controls.toml on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rhywbeth").join("controls.toml"))
+    }
+}
+
+pub fn bound(keys: &[Key], code: KeyCode) -> bool {
+    keys.iter().any(|k| k.0 == code)
+}
@@ -0,0 +1,86 @@
+use std::f32::consts::PI;
+
+use crate::{
+    canvas::Canvas,
+    colour::Rgb,
+    font::Lettering,
+    render::{Camera, Projection},
+    vec2::Vec2,
+};
+
+// Seconds a number floats for, and world units a second it rises at.
+pub const LIFETIME: f32 = 0.8;
+const RISE: f32 = 0.6;
+// How tall the digits are in world units, though their pixels are never smaller than the
+// screen's.
+const SIZE: f32 = 0.2;
+const COLOUR: Rgb = Rgb::new(255, 220, 64);
+const FADED: Rgb = Rgb::new(160, 48, 0);
+
+// How much damage a shot did, floating up off whatever it hit.
+#[derive(Debug, Clone, Copy)]
+pub struct DamageNumber {
+    pub position: Vec2,
+    // Height of the bottom of the digits when they appeared.
+    pub height: f32,
+    pub amount: f32,
+    pub age: f32,
+}
+
+impl DamageNumber {
+    pub fn new(position: Vec2, height: f32, amount: f32) -> Self {
+        Self {
+            position,
+            height,
+            amount,
+            age: 0.0,
+        }
+    }
+}
+
+// Draws every damage number in the built-in font, centred over where it is and facing the
+// camera, skipping any column where a wall is in front of it.
+pub fn draw_damage_numbers(
+    canvas: &mut Canvas,
+    camera: &Camera,
+    numbers: &[DamageNumber],
+    projection: &Projection,
+    depth: &[f32],
+) {
+    for number in numbers {
+        let to_number = number.position - camera.position;
+        let offset = (to_number.angle() - camera.rotation + PI).rem_euclid(2.0 * PI) - PI;
+        let distance = to_number.length() * offset.cos();
+        if distance <= 0.1 {
+            continue;
+        }
+        let lettering = Lettering::new(&format!("{:.0}", number.amount));
+        let scale = (projection.wall_height(distance) * SIZE / lettering.height() as f32)
+            .round()
+            .max(1.0) as usize;
+        let width = lettering.width() * scale;
+        let height = lettering.height() * scale;
+        let centre = projection.column(offset);
+        let bottom = projection.row(number.height + RISE * number.age, distance);
+        let left = centre - width as f32 / 2.0;
+        let top = bottom - height as f32;
+        let colour = COLOUR.lerp(FADED, number.age / LIFETIME);
+        for x in 0..width {
+            let column = left + x as f32;
+            if column < 0.0 || column as usize >= canvas.width {
+                continue;
+            }
+            let column = column as usize;
+            if depth[column] < distance {
+                continue;
+            }
+            for y in 0..height {
+                let row = top + y as f32;
+                if row < 0.0 || !lettering.lit(x / scale, y / scale) {
+                    continue;
+                }
+                canvas.set(column, row as usize, colour);
+            }
+        }
+    }
+}
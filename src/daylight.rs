@@ -0,0 +1,46 @@
+use std::f32::consts::TAU;
+
+use crate::{colour::Rgb, world::Sky};
+
+pub const HOURS_PER_DAY: f32 = 24.0;
+
+// A day passing every `length` seconds, starting `start` hours after midnight. The ambient
+// light and sky fade from how the map describes them at noon to their night versions at
+// midnight and back.
+#[derive(Debug, Clone, Copy)]
+pub struct DayCycle {
+    pub length: f32,
+    pub start: f32,
+    pub day_ambient: f32,
+    pub night_ambient: f32,
+    pub day_sky: Option<Sky>,
+    pub night_top: Rgb,
+    pub night_bottom: Rgb,
+}
+
+impl DayCycle {
+    // Hours after midnight `clock` seconds into the level.
+    pub fn hour(&self, clock: f32) -> f32 {
+        (self.start + clock / self.length.max(f32::EPSILON) * HOURS_PER_DAY)
+            .rem_euclid(HOURS_PER_DAY)
+    }
+
+    // 0.0 at midnight rising smoothly to 1.0 at noon.
+    pub fn daylight(&self, clock: f32) -> f32 {
+        0.5 - 0.5 * (self.hour(clock) / HOURS_PER_DAY * TAU).cos()
+    }
+
+    // The ambient light and sky `clock` seconds into the level.
+    pub fn at(&self, clock: f32) -> (f32, Option<Sky>) {
+        let daylight = self.daylight(clock);
+        let ambient = self.night_ambient + (self.day_ambient - self.night_ambient) * daylight;
+        let sky = self.day_sky.map(|sky| Sky {
+            top: self.night_top.lerp(sky.top, daylight),
+            bottom: self.night_bottom.lerp(sky.bottom, daylight),
+            brightness: sky.brightness
+                * (self.night_ambient + (1.0 - self.night_ambient) * daylight),
+            ..sky
+        });
+        (ambient, sky)
+    }
+}
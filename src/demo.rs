@@ -0,0 +1,57 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::event::Event,
+    controls::{Controls, Mouse},
+    game::Level,
+    input::{Action, Analog},
+    render::Camera,
+    save::SaveState,
+};
+
+// A recorded run: where it started and every frame of input after that. Stepping a fresh game
+// through the frames reproduces the run exactly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Demo {
+    pub level: Level,
+    pub camera: Camera,
+    pub speed: f32,
+    pub turn_speed: f32,
+    pub sensitivity: f32,
+    pub controls: Controls,
+    // Whether a second player shared the screen, moved by the same frames of input.
+    #[serde(default)]
+    pub split: bool,
+    pub frames: Vec<Frame>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Frame {
+    // Seconds since the previous frame.
+    pub dt: f32,
+    pub events: Vec<Event>,
+    pub gamepad: Vec<Action>,
+    pub analog: Analog,
+    // The save restored by a quick load this frame, so playback doesn't depend on whatever
+    // save file happens to be around.
+    pub loaded: Option<SaveState>,
+    // The mouse settings, if they were changed in the menu just before this frame.
+    #[serde(default)]
+    pub mouse: Option<Mouse>,
+}
+
+impl Demo {
+    pub fn read(path: &Path) -> Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Could not read demo {}", path.display()))?;
+        ron::from_str(&source).with_context(|| format!("Could not parse demo {}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let source = ron::to_string(self)?;
+        fs::write(path, source).with_context(|| format!("Could not write demo {}", path.display()))
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{colour::Rgb, geometry::LineSegment, pickup::KeyColour, vec2::Vec2};
+
+// Fraction of the door that opens or closes per second.
+const SPEED: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DoorState {
+    Closed,
+    Opening,
+    // Seconds left before the door starts closing again.
+    Open(f32),
+    Closing,
+}
+
+// A wall segment that slides towards its end point when opened.
+pub struct Door {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub colour: Rgb,
+    pub texture: Option<usize>,
+    // How long the door stays open before closing by itself.
+    pub open_time: f32,
+    // The key needed to open the door, if it's locked.
+    pub lock: Option<KeyColour>,
+    // Names the door for switches to open.
+    pub id: Option<String>,
+    pub state: DoorState,
+    // 0.0 when closed, 1.0 when fully open.
+    pub openness: f32,
+}
+
+impl Door {
+    pub fn new(start: Vec2, end: Vec2, colour: Rgb, open_time: f32) -> Self {
+        Self {
+            start,
+            end,
+            colour,
+            texture: None,
+            open_time,
+            lock: None,
+            id: None,
+            state: DoorState::Closed,
+            openness: 0.0,
+        }
+    }
+
+    pub fn with_texture(mut self, texture: Option<usize>) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    pub fn with_lock(mut self, lock: Option<KeyColour>) -> Self {
+        self.lock = lock;
+        self
+    }
+
+    pub fn with_id(mut self, id: Option<String>) -> Self {
+        self.id = id;
+        self
+    }
+
+    // The part of the door still blocking the doorway, if any.
+    pub fn segment(&self) -> Option<LineSegment> {
+        if self.openness >= 1.0 {
+            return None;
+        }
+        let start = self.start.lerp(self.end, self.openness);
+        Some(LineSegment::from_points(start, self.end, self.colour).with_texture(self.texture))
+    }
+
+    pub fn activate(&mut self) {
+        self.state = match self.state {
+            DoorState::Closed | DoorState::Closing => DoorState::Opening,
+            DoorState::Opening | DoorState::Open(_) => DoorState::Closing,
+        };
+    }
+
+    // `blocked` keeps the door from closing on whatever is standing in the doorway.
+    pub fn update(&mut self, dt: f32, blocked: bool) {
+        self.state = match self.state {
+            DoorState::Opening => {
+                self.openness = (self.openness + SPEED * dt).min(1.0);
+                if self.openness >= 1.0 {
+                    DoorState::Open(self.open_time)
+                } else {
+                    DoorState::Opening
+                }
+            }
+            DoorState::Open(remaining) if remaining - dt > 0.0 || blocked => {
+                DoorState::Open((remaining - dt).max(0.0))
+            }
+            DoorState::Open(_) => DoorState::Closing,
+            DoorState::Closing if blocked => DoorState::Opening,
+            DoorState::Closing => {
+                self.openness = (self.openness - SPEED * dt).max(0.0);
+                if self.openness <= 0.0 {
+                    DoorState::Closed
+                } else {
+                    DoorState::Closing
+                }
+            }
+            DoorState::Closed => DoorState::Closed,
+        };
+    }
+
+    pub fn distance_to(&self, point: Vec2) -> f32 {
+        LineSegment::from_points(self.start, self.end, self.colour).distance_to(point)
+    }
+}
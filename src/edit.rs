@@ -0,0 +1,97 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use clap::Args;
+
+use crate::{
+    backend::event::Event,
+    controls::Controls,
+    editor::Editor,
+    input::{Action, Input},
+    render::{Camera, Renderer},
+    session::{open_backend, OutputArgs},
+    theme::Theme,
+    world::World,
+};
+
+// Frames per second the editor draws at most.
+const EDITOR_FPS: f32 = 30.0;
+
+#[derive(Args)]
+pub struct EditArgs {
+    #[arg(help = "TOML or JSON map to edit, created when saved if it doesn't exist")]
+    pub map: PathBuf,
+    #[command(flatten)]
+    pub output: OutputArgs,
+    #[arg(
+        long,
+        help = "Key bindings to walk around with instead of the ones in the config directory"
+    )]
+    pub controls: Option<PathBuf>,
+}
+
+// The editor's view from above, swapped for walking around the map as it stands when asked
+// to. Leaving the walk goes back to editing.
+pub fn run(args: EditArgs) -> Result<()> {
+    let mut editor = Editor::open(&args.map)?;
+    let mut input = Input::new();
+    input.controls = match args.controls.or_else(Controls::default_path) {
+        Some(path) => Controls::load_or_create(&path)?,
+        None => Controls::default(),
+    };
+    let output = &args.output;
+    let mut renderer = Renderer::new(output.renderer);
+    renderer.theme = output.theme.clone();
+    renderer.narration = output.narrate;
+    let mut backend = open_backend(output)?;
+    let mut size = backend.size()?;
+    let mut walking: Option<(World, Camera)> = None;
+
+    let frame_time = Duration::from_secs_f32(1.0 / EDITOR_FPS);
+    let mut last_frame = Instant::now();
+    loop {
+        let next_frame = last_frame + frame_time;
+        let timeout = || next_frame.saturating_duration_since(Instant::now());
+        while let Some(event) = backend.poll_event(timeout())? {
+            if let Event::Resize(width, height) = event {
+                size = (width, height);
+                continue;
+            }
+            match &mut walking {
+                Some((world, camera)) => match input.handle(event, camera) {
+                    Action::Pause | Action::Quit | Action::ToggleOverhead => walking = None,
+                    Action::Use => {
+                        world.use_in_front(camera.position, camera.rotation, &mut Vec::new())
+                    }
+                    _ => {}
+                },
+                None => editor.handle(event),
+            }
+        }
+        if editor.quit || backend.closed() {
+            return Ok(());
+        }
+        if editor.preview {
+            editor.preview = false;
+            walking = editor.play().map(|(mut world, camera)| {
+                output.theme.recolour(&mut world, &Theme::classic());
+                (world, camera)
+            });
+        }
+        let now = Instant::now();
+        let dt = (now - last_frame).as_secs_f32();
+        last_frame = now;
+        match &mut walking {
+            Some((world, camera)) => {
+                let mut events = Vec::new();
+                input.update(camera, world, dt, &mut events);
+                world.update(dt, camera.position, &mut events);
+                renderer.render(size, camera, world, backend.as_mut())?;
+            }
+            None => backend.present(editor.draw(size))?,
+        }
+    }
+}
@@ -0,0 +1,676 @@
+use std::{
+    f32::consts::{FRAC_PI_4, TAU},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+
+use crate::{
+    backend::event::{Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind},
+    canvas::Canvas,
+    colour::Rgb,
+    framebuffer::FrameBuffer,
+    geometry::LineSegment,
+    hud::{Anchor, Overlay, Widget},
+    map::{Map, Segment},
+    minimap::{draw_line, draw_region},
+    render::{encode, Camera, Output},
+    theme::Theme,
+    vec2::Vec2,
+    world::World,
+};
+
+const BACKGROUND: Rgb = Rgb::new(16, 16, 16);
+const GRID: Rgb = Rgb::new(40, 40, 40);
+const AXIS: Rgb = Rgb::new(72, 72, 72);
+const SECTOR: Rgb = Rgb::new(96, 64, 128);
+const PORTAL: Rgb = Rgb::new(128, 0, 255);
+const SELECTED: Rgb = Rgb::new(255, 255, 0);
+const END: Rgb = Rgb::new(160, 160, 160);
+const SPAWN: Rgb = Rgb::new(0, 255, 0);
+const FACING: Rgb = Rgb::new(255, 160, 0);
+const EXIT: Rgb = Rgb::new(0, 255, 128);
+const TEXT: Rgb = Rgb::WHITE;
+const HINT: Rgb = Rgb::new(160, 160, 160);
+
+// Pixels per map unit to start with, and how far zooming can go either way.
+const SCALE: f32 = 4.0;
+const MIN_SCALE: f32 = 0.5;
+const MAX_SCALE: f32 = 64.0;
+const ZOOM: f32 = 1.25;
+// Pixels the view moves per press of an arrow key.
+const PAN: f32 = 4.0;
+// How close in pixels the mouse has to be to pick up a wall end, the spawn or a wall.
+const REACH: f32 = 2.0;
+// Points snap to this many units when snapping is on.
+const SNAP: f32 = 0.5;
+// Edits that can be undone.
+const HISTORY: usize = 100;
+// Steps around a whole circle when drawing arcs.
+const ARC_STEPS: usize = 32;
+
+// What the mouse is holding on to while its button is down.
+enum Drag {
+    // Wall ends sitting on the same point, moved together so walls stay joined. Each is the
+    // wall's index and whether it's the wall's end rather than its start.
+    Ends(Vec<(usize, bool)>),
+    Spawn,
+    // Pressed over nothing, where a new wall starts if the mouse moves before it's let go.
+    Empty(Vec2),
+    // The end of a wall being drawn.
+    New(usize),
+}
+
+// A map seen from above, with walls drawn and dragged about with the mouse. The map is kept as
+// it was read, with the files it names still relative to it, so it's saved back unchanged apart
+// from the edits.
+pub struct Editor {
+    pub map: Map,
+    pub path: PathBuf,
+    // Whether there are edits that haven't been saved.
+    pub modified: bool,
+    pub quit: bool,
+    // Set when the player asks to walk around the map, for whoever's running the editor to do.
+    pub preview: bool,
+    // The map point in the middle of the screen, and pixels per map unit.
+    origin: Vec2,
+    scale: f32,
+    snap: bool,
+    selected: Option<usize>,
+    drag: Option<Drag>,
+    // Where the mouse last was on the map.
+    mouse: Vec2,
+    // What new walls are drawn with.
+    colour: Rgb,
+    texture: Option<String>,
+    message: String,
+    undo: Vec<Map>,
+    // Quitting with unsaved edits has to be asked for twice.
+    confirm_quit: bool,
+    canvas: Canvas,
+    frame: FrameBuffer,
+}
+
+impl Editor {
+    // Opens the map at `path`, or starts an empty one to be saved there if there's none yet.
+    pub fn open(path: &Path) -> Result<Self> {
+        let (map, message) = if path.exists() {
+            (Map::read(path)?, format!("Editing {}", path.display()))
+        } else {
+            (Map::default(), format!("New map {}", path.display()))
+        };
+        let mut editor = Self {
+            map,
+            path: path.to_path_buf(),
+            modified: false,
+            quit: false,
+            preview: false,
+            origin: Vec2::ZERO,
+            scale: SCALE,
+            snap: true,
+            selected: None,
+            drag: None,
+            mouse: Vec2::ZERO,
+            colour: Theme::classic().walls[0],
+            texture: None,
+            message,
+            undo: Vec::new(),
+            confirm_quit: false,
+            canvas: Canvas::new(0, 0),
+            frame: FrameBuffer::new(0, 0),
+        };
+        editor.origin = editor.centre();
+        Ok(editor)
+    }
+
+    pub fn notify(&mut self, message: impl Into<String>) {
+        self.message = message.into();
+    }
+
+    pub fn handle(&mut self, event: Event) {
+        match event {
+            Event::Key(key) if key.kind != KeyEventKind::Release => self.key(key.code),
+            Event::Mouse(mouse) => {
+                let point = self.to_map(mouse.column, mouse.row);
+                self.mouse = point;
+                match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        self.press(point, mouse.modifiers.contains(KeyModifiers::CONTROL))
+                    }
+                    MouseEventKind::Drag(MouseButton::Left) => self.drag_to(point),
+                    MouseEventKind::Up(MouseButton::Left) => self.release(),
+                    MouseEventKind::ScrollUp => self.zoom(ZOOM),
+                    MouseEventKind::ScrollDown => self.zoom(1.0 / ZOOM),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // The map as it's played, for walking around it, and where the player starts.
+    pub fn play(&mut self) -> Option<(World, Camera)> {
+        let mut map = self.map.clone();
+        if let Some(directory) = self.path.parent() {
+            map.relative_to(directory);
+        }
+        match map.world() {
+            Ok(world) => Some((world, map.camera())),
+            Err(e) => {
+                self.message = format!("{e:#}");
+                None
+            }
+        }
+    }
+
+    pub fn save(&mut self) {
+        match self.map.write(&self.path) {
+            Ok(()) => {
+                self.modified = false;
+                self.message = format!("Saved {}", self.path.display());
+            }
+            Err(e) => self.message = format!("{e:#}"),
+        }
+    }
+
+    fn key(&mut self, code: KeyCode) {
+        let quitting = matches!(code, KeyCode::Char('q') | KeyCode::Esc);
+        if !quitting {
+            self.confirm_quit = false;
+        }
+        let pan = PAN / self.scale;
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if self.modified && !self.confirm_quit {
+                    self.confirm_quit = true;
+                    self.message = "Unsaved changes, press q again to quit".to_string();
+                } else {
+                    self.quit = true;
+                }
+            }
+            KeyCode::Left => self.origin.x -= pan,
+            KeyCode::Right => self.origin.x += pan,
+            KeyCode::Up => self.origin.y += pan,
+            KeyCode::Down => self.origin.y -= pan,
+            KeyCode::Char('+' | '=') => self.zoom(ZOOM),
+            KeyCode::Char('-') => self.zoom(1.0 / ZOOM),
+            KeyCode::Char('f') => {
+                self.origin = self.centre();
+                self.scale = SCALE;
+            }
+            KeyCode::Char('g') => {
+                self.snap = !self.snap;
+                let state = if self.snap { "on" } else { "off" };
+                self.message = format!("Snapping {state}");
+            }
+            KeyCode::Char('c') => self.cycle_colour(),
+            KeyCode::Char('t') => self.cycle_texture(),
+            KeyCode::Char('x') | KeyCode::Delete | KeyCode::Backspace => {
+                if let Some(i) = self.selected.take() {
+                    self.remember();
+                    self.map.segments.remove(i);
+                    // Whatever was being dragged may have been the wall, or come after it.
+                    self.drag = None;
+                    self.message = "Deleted wall".to_string();
+                }
+            }
+            KeyCode::Char('p') => {
+                self.remember();
+                self.map.spawn.position = self.snapped(self.mouse);
+            }
+            KeyCode::Char('r') => {
+                self.remember();
+                let rotation = self.map.spawn.rotation + FRAC_PI_4;
+                self.map.spawn.rotation = rotation.rem_euclid(TAU);
+            }
+            KeyCode::Char('u') => match self.undo.pop() {
+                Some(map) => {
+                    self.map = map;
+                    self.selected = self.selected.filter(|&i| i < self.map.segments.len());
+                    self.drag = None;
+                    self.modified = true;
+                    self.message = "Undone".to_string();
+                }
+                None => self.message = "Nothing to undo".to_string(),
+            },
+            KeyCode::Char('s') => self.save(),
+            KeyCode::Tab => self.preview = true,
+            _ => {}
+        }
+    }
+
+    // Picks up whatever is under the mouse. With Ctrl held a new wall is drawn from there
+    // instead, starting on the wall end under the mouse if there is one.
+    fn press(&mut self, point: Vec2, new: bool) {
+        let reach = REACH / self.scale;
+        let nearest = self.nearest_end(point);
+        if new {
+            let start = nearest.map_or_else(|| self.snapped(point), |(_, at)| at);
+            self.drag = Some(Drag::Empty(start));
+            return;
+        }
+        if point.distance(self.map.spawn.position) <= reach {
+            self.remember();
+            self.drag = Some(Drag::Spawn);
+            return;
+        }
+        if let Some((i, at)) = nearest {
+            self.remember();
+            self.selected = Some(i);
+            self.drag = Some(Drag::Ends(self.ends_at(at)));
+            return;
+        }
+        let wall = self.map.segments.iter().position(|s| {
+            LineSegment::from_points(s.start, s.end, s.colour).distance_to(point) <= reach
+        });
+        self.selected = wall;
+        self.drag = wall.is_none().then(|| Drag::Empty(self.snapped(point)));
+    }
+
+    fn drag_to(&mut self, point: Vec2) {
+        let point = self.snapped(point);
+        match self.drag.take() {
+            Some(Drag::Ends(ends)) => {
+                for &(i, end) in ends.iter() {
+                    let Some(segment) = self.map.segments.get_mut(i) else {
+                        return;
+                    };
+                    *(if end {
+                        &mut segment.end
+                    } else {
+                        &mut segment.start
+                    }) = point;
+                }
+                self.modified = true;
+                self.drag = Some(Drag::Ends(ends));
+            }
+            Some(Drag::Spawn) => {
+                self.map.spawn.position = point;
+                self.modified = true;
+                self.drag = Some(Drag::Spawn);
+            }
+            Some(Drag::Empty(start)) if start.distance(point) > 0.0 => {
+                self.remember();
+                self.map.segments.push(Segment {
+                    start,
+                    end: point,
+                    colour: self.colour,
+                    texture: self.texture.clone(),
+                    opacity: 1.0,
+                    mirror: false,
+                    id: None,
+                    hidden: false,
+                });
+                let i = self.map.segments.len() - 1;
+                self.selected = Some(i);
+                self.drag = Some(Drag::New(i));
+            }
+            Some(Drag::New(i)) => {
+                if let Some(segment) = self.map.segments.get_mut(i) {
+                    segment.end = point;
+                    self.drag = Some(Drag::New(i));
+                }
+            }
+            drag => self.drag = drag,
+        }
+    }
+
+    fn release(&mut self) {
+        if let Some(Drag::New(i)) = self.drag.take() {
+            let Some(segment) = self.map.segments.get(i) else {
+                return;
+            };
+            // New walls let go of near another wall's end are joined on to it.
+            let end = segment.end;
+            let others = self.map.segments[..i].iter().flat_map(|s| [s.start, s.end]);
+            let reach = REACH / self.scale;
+            if let Some(at) = others
+                .filter(|at| at.distance(end) <= reach)
+                .min_by(|a, b| a.distance(end).total_cmp(&b.distance(end)))
+            {
+                self.map.segments[i].end = at;
+            }
+            // A wall dragged back to where it started has no length, so it's taken back out.
+            let segment = &self.map.segments[i];
+            if segment.start.distance(segment.end) <= f32::EPSILON {
+                self.map.segments.remove(i);
+                self.selected = None;
+                self.undo.pop();
+            }
+        }
+    }
+
+    // Gives the selected wall the next colour of the classic theme's walls, and draws new walls
+    // in it from then on.
+    fn cycle_colour(&mut self) {
+        let walls = Theme::classic().walls;
+        let current = self
+            .selected
+            .map_or(self.colour, |i| self.map.segments[i].colour);
+        let next = walls
+            .iter()
+            .position(|&c| c == current)
+            .map_or(0, |i| i + 1);
+        self.colour = walls[next % walls.len()];
+        if let Some(i) = self.selected {
+            self.remember();
+            self.map.segments[i].colour = self.colour;
+        }
+        self.message = format!("Colour {}", self.colour);
+    }
+
+    // Gives the selected wall the next of the map's textures by name, or none after the last,
+    // and draws new walls with it from then on.
+    fn cycle_texture(&mut self) {
+        let mut names = self
+            .map
+            .textures
+            .keys()
+            .cloned()
+            .map(Some)
+            .collect::<Vec<_>>();
+        names.sort();
+        names.push(None);
+        let current = match self.selected {
+            Some(i) => self.map.segments[i].texture.clone(),
+            None => self.texture.clone(),
+        };
+        let next = names
+            .iter()
+            .position(|n| *n == current)
+            .map_or(0, |i| i + 1);
+        self.texture = names[next % names.len()].clone();
+        if let Some(i) = self.selected {
+            self.remember();
+            self.map.segments[i].texture = self.texture.clone();
+        }
+        self.message = match &self.texture {
+            Some(name) => format!("Texture {name}"),
+            None => "No texture".to_string(),
+        };
+    }
+
+    // Keeps the map as it is before an edit, to go back to.
+    fn remember(&mut self) {
+        if self.undo.len() == HISTORY {
+            self.undo.remove(0);
+        }
+        self.undo.push(self.map.clone());
+        self.modified = true;
+    }
+
+    // The wall end closest to `point` within reach of the mouse, and which wall it's on.
+    fn nearest_end(&self, point: Vec2) -> Option<(usize, Vec2)> {
+        let ends = self.map.segments.iter().enumerate();
+        let ends = ends.flat_map(|(i, s)| [(i, s.start), (i, s.end)]);
+        ends.filter(|(_, at)| at.distance(point) <= REACH / self.scale)
+            .min_by(|a, b| a.1.distance(point).total_cmp(&b.1.distance(point)))
+    }
+
+    // Every wall end at `point`.
+    fn ends_at(&self, point: Vec2) -> Vec<(usize, bool)> {
+        let near = |p: Vec2| p.distance(point) <= 1e-4;
+        let mut ends = Vec::new();
+        for (i, segment) in self.map.segments.iter().enumerate() {
+            if near(segment.start) {
+                ends.push((i, false));
+            }
+            if near(segment.end) {
+                ends.push((i, true));
+            }
+        }
+        ends
+    }
+
+    fn snapped(&self, point: Vec2) -> Vec2 {
+        if self.snap {
+            Vec2::new(
+                (point.x / SNAP).round() * SNAP,
+                (point.y / SNAP).round() * SNAP,
+            )
+        } else {
+            point
+        }
+    }
+
+    fn zoom(&mut self, factor: f32) {
+        self.scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+    }
+
+    // The middle of all the walls and the spawn.
+    fn centre(&self) -> Vec2 {
+        let points = self.map.segments.iter().flat_map(|s| [s.start, s.end]);
+        let (min, max) = points.fold(
+            (self.map.spawn.position, self.map.spawn.position),
+            |(min, max), p| {
+                (
+                    Vec2::new(min.x.min(p.x), min.y.min(p.y)),
+                    Vec2::new(max.x.max(p.x), max.y.max(p.y)),
+                )
+            },
+        );
+        min.lerp(max, 0.5)
+    }
+
+    // The map point under the middle of a cell, with each cell two pixels tall.
+    fn to_map(&self, column: u16, row: u16) -> Vec2 {
+        self.at_pixel(column as f32 + 0.5, row as f32 * 2.0 + 1.0)
+    }
+
+    // The map point under a pixel of the canvas.
+    fn at_pixel(&self, x: f32, y: f32) -> Vec2 {
+        let (width, height) = (self.frame.width as f32, self.frame.height as f32 * 2.0);
+        self.origin
+            + Vec2::new(
+                (x - width / 2.0) / self.scale,
+                (height / 2.0 - y) / self.scale,
+            )
+    }
+
+    // Draws the map from above with what's selected and how to edit it over the top.
+    pub fn draw(&mut self, size: (u16, u16)) -> &FrameBuffer {
+        self.frame.resize(size.0, size.1);
+        let mut canvas = std::mem::replace(&mut self.canvas, Canvas::new(0, 0));
+        canvas.resize(size.0 as usize, size.1 as usize * 2);
+        self.draw_map(&mut canvas);
+        encode(&canvas, &mut self.frame, Output::HalfBlocks);
+        self.canvas = canvas;
+        self.overlay().draw(&mut self.frame);
+        &self.frame
+    }
+
+    fn draw_map(&self, canvas: &mut Canvas) {
+        canvas.pixels.fill(BACKGROUND);
+        let (width, height) = (canvas.width as f32, canvas.height as f32);
+        let (origin, scale) = (self.origin, self.scale);
+        let to_screen = |p: Vec2| {
+            Vec2::new(
+                width / 2.0 + (p.x - origin.x) * scale,
+                height / 2.0 - (p.y - origin.y) * scale,
+            )
+        };
+        let inside = |p: Vec2| p.x >= 0.0 && p.x < width && p.y >= 0.0 && p.y < height;
+        let line = |canvas: &mut Canvas, from: Vec2, to: Vec2, colour: Rgb| {
+            draw_line(canvas, to_screen(from), to_screen(to), colour, &inside);
+        };
+        let point = |canvas: &mut Canvas, at: Vec2, colour: Rgb| {
+            let p = to_screen(at);
+            if inside(p) {
+                canvas.set(p.x as usize, p.y as usize, colour);
+            }
+        };
+
+        // A line every unit, when they're far enough apart to tell apart.
+        if scale >= 4.0 {
+            let (from, to) = (self.at_pixel(0.0, height), self.at_pixel(width, 0.0));
+            for x in from.x.floor() as i32..=to.x.ceil() as i32 {
+                let colour = if x == 0 { AXIS } else { GRID };
+                let x = x as f32;
+                line(canvas, Vec2::new(x, from.y), Vec2::new(x, to.y), colour);
+            }
+            for y in from.y.floor() as i32..=to.y.ceil() as i32 {
+                let colour = if y == 0 { AXIS } else { GRID };
+                let y = y as f32;
+                line(canvas, Vec2::new(from.x, y), Vec2::new(to.x, y), colour);
+            }
+        }
+
+        for sector in self.map.sectors.iter() {
+            for (i, &a) in sector.points.iter().enumerate() {
+                let b = sector.points[(i + 1) % sector.points.len()];
+                line(canvas, a, b, SECTOR);
+            }
+        }
+        if let Some(exit) = &self.map.exit {
+            draw_region(canvas, exit.region, EXIT, &to_screen, &inside);
+        }
+        for portal in self.map.portals.iter() {
+            for side in portal.sides.iter() {
+                line(canvas, side.start, side.end, PORTAL);
+            }
+        }
+        for arc in self.map.arcs.iter() {
+            let start = arc.start.unwrap_or(0.0);
+            let end = arc.end.unwrap_or(start + TAU);
+            let sweep = (end - start).rem_euclid(TAU);
+            let sweep = if sweep == 0.0 { TAU } else { sweep };
+            let steps = ((ARC_STEPS as f32 * sweep / TAU).ceil() as usize).max(1);
+            let at = |i: usize| {
+                arc.centre + Vec2::from_angle(start + sweep * i as f32 / steps as f32) * arc.radius
+            };
+            for i in 0..steps {
+                line(canvas, at(i), at(i + 1), arc.colour);
+            }
+        }
+        for door in self.map.doors.iter() {
+            line(canvas, door.start, door.end, door.colour);
+        }
+        for switch in self.map.switches.iter() {
+            line(canvas, switch.start, switch.end, switch.colour);
+        }
+        for (i, segment) in self.map.segments.iter().enumerate() {
+            let colour = if self.selected == Some(i) {
+                SELECTED
+            } else {
+                segment.colour
+            };
+            line(canvas, segment.start, segment.end, colour);
+        }
+        for segment in self.map.segments.iter() {
+            point(canvas, segment.start, END);
+            point(canvas, segment.end, END);
+        }
+
+        for sprite in self.map.sprites.iter() {
+            point(canvas, sprite.position, sprite.colour);
+        }
+        for pickup in self.map.pickups.iter() {
+            let colour = pickup.colour.unwrap_or(pickup.pickup.sprite().colour);
+            point(canvas, pickup.position, colour);
+        }
+        for enemy in self.map.enemies.iter() {
+            point(canvas, enemy.position, enemy.colour);
+        }
+
+        let spawn = &self.map.spawn;
+        let facing = spawn.position + Vec2::from_angle(spawn.rotation) * (REACH * 2.0 / scale);
+        line(canvas, spawn.position, facing, FACING);
+        point(canvas, spawn.position, SPAWN);
+    }
+
+    fn overlay(&self) -> Overlay {
+        let mut overlay = Overlay::default();
+        overlay.add(
+            Anchor::Top,
+            Widget::text(
+                "drag: move/draw  ctrl+drag: draw from end  c: colour  t: texture  x: delete  p/r: spawn  g: snap  \
+                 u: undo  s: save  tab: walk  q: quit",
+                HINT,
+            ),
+        );
+        if let Some(segment) = self.selected.and_then(|i| self.map.segments.get(i)) {
+            let texture = segment.texture.as_deref().unwrap_or("none");
+            overlay.add(
+                Anchor::TopLeft,
+                Widget::padded(
+                    Widget::text(
+                        format!(
+                            "({}, {}) to ({}, {})  {}  texture {texture}",
+                            segment.start.x,
+                            segment.start.y,
+                            segment.end.x,
+                            segment.end.y,
+                            segment.colour,
+                        ),
+                        TEXT,
+                    ),
+                    (0, 1),
+                ),
+            );
+        }
+        let name = self.path.display();
+        let changed = if self.modified { "*" } else { "" };
+        let snap = if self.snap { "snap" } else { "free" };
+        let status = format!(
+            "{name}{changed}  ({:.1}, {:.1})  {snap}  {}",
+            self.mouse.x, self.mouse.y, self.message
+        );
+        overlay.add(Anchor::BottomLeft, Widget::text(status, TEXT));
+        overlay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::event::{KeyEvent, MouseEvent};
+
+    fn mouse(kind: MouseEventKind, column: u16, modifiers: KeyModifiers) -> Event {
+        Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row: 0,
+            modifiers,
+        })
+    }
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn undo_and_delete_let_go_of_drags() {
+        let mut editor = Editor::open(Path::new("no such map.toml")).unwrap();
+        let drag = MouseEventKind::Drag(MouseButton::Left);
+        editor.handle(mouse(
+            MouseEventKind::Down(MouseButton::Left),
+            0,
+            KeyModifiers::CONTROL,
+        ));
+        editor.handle(mouse(drag, 10, KeyModifiers::NONE));
+        assert_eq!(editor.map.segments.len(), 1);
+        editor.handle(key(KeyCode::Char('u')));
+        editor.handle(mouse(drag, 20, KeyModifiers::NONE));
+        editor.handle(mouse(
+            MouseEventKind::Up(MouseButton::Left),
+            20,
+            KeyModifiers::NONE,
+        ));
+        assert!(editor.map.segments.is_empty());
+
+        editor.handle(mouse(
+            MouseEventKind::Down(MouseButton::Left),
+            0,
+            KeyModifiers::CONTROL,
+        ));
+        editor.handle(mouse(drag, 10, KeyModifiers::NONE));
+        editor.handle(key(KeyCode::Delete));
+        editor.handle(mouse(drag, 20, KeyModifiers::NONE));
+        editor.handle(mouse(
+            MouseEventKind::Up(MouseButton::Left),
+            20,
+            KeyModifiers::NONE,
+        ));
+        assert!(editor.map.segments.is_empty());
+    }
+}
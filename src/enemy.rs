@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{colour::Rgb, sprite::Sprite};
+
+// How close an enemy gets before it stops to stand in front of the player.
+pub const REACH: f32 = 0.6;
+// How close an enemy has to be to hit the player by touching them, and seconds between hits.
+pub const BITE_RANGE: f32 = 0.8;
+pub const BITE_TIME: f32 = 1.0;
+
+// Makes an entity chase the player whenever it can see them.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Enemy {
+    // World units per second.
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    // How far away the player can be seen from.
+    #[serde(default = "default_sight")]
+    pub sight: f32,
+    // Hit points it starts with.
+    #[serde(default = "default_health")]
+    pub health: f32,
+    // Seconds between shots at the player. Enemies without one only chase.
+    pub reload: Option<f32>,
+    // Damage done by each shot, or touch.
+    #[serde(default = "default_damage")]
+    pub damage: f32,
+    // Seconds until the enemy can attack again.
+    #[serde(skip)]
+    pub cooldown: f32,
+}
+
+fn default_speed() -> f32 {
+    2.0
+}
+
+fn default_sight() -> f32 {
+    12.0
+}
+
+fn default_health() -> f32 {
+    50.0
+}
+
+fn default_damage() -> f32 {
+    10.0
+}
+
+impl Default for Enemy {
+    fn default() -> Self {
+        Self {
+            speed: default_speed(),
+            sight: default_sight(),
+            health: default_health(),
+            reload: None,
+            damage: default_damage(),
+            cooldown: 0.0,
+        }
+    }
+}
+
+// An enemy added while the level is played, e.g. by a script, which can't name a texture.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct EnemySpawn {
+    #[serde(default = "default_spawn_colour")]
+    pub colour: Rgb,
+    #[serde(default = "default_spawn_size")]
+    pub size: f32,
+    #[serde(flatten)]
+    pub enemy: Enemy,
+}
+
+fn default_spawn_colour() -> Rgb {
+    Rgb::RED
+}
+
+fn default_spawn_size() -> f32 {
+    0.8
+}
+
+impl EnemySpawn {
+    pub fn sprite(&self) -> Sprite {
+        Sprite::new(self.colour, self.size)
+    }
+}
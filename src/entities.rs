@@ -0,0 +1,249 @@
+use crate::{
+    door::Door,
+    enemy::Enemy,
+    health::Health,
+    pickup::Pickup,
+    portal::Portal,
+    projectile::{Projectile, SPEED},
+    sprite::Sprite,
+    switch::{Dormant, Switch, Wall},
+    teleporter::Teleporter,
+    vec2::Vec2,
+};
+
+// A handle to something in the world. Handles to a despawned entity stop matching anything,
+// even once its slot has been reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+// One kind of component, stored by entity index.
+pub struct Components<T> {
+    slots: Vec<Option<(u32, T)>>,
+}
+
+impl<T> Default for Components<T> {
+    fn default() -> Self {
+        Self { slots: Vec::new() }
+    }
+}
+
+impl<T> Components<T> {
+    pub fn insert(&mut self, entity: Entity, component: T) {
+        let index = entity.index as usize;
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+        self.slots[index] = Some((entity.generation, component));
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.slots.get_mut(entity.index as usize)?;
+        match slot {
+            Some((generation, _)) if *generation == entity.generation => {
+                slot.take().map(|(_, c)| c)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.slots.get(entity.index as usize)? {
+            Some((generation, component)) if *generation == entity.generation => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.slots.get_mut(entity.index as usize)? {
+            Some((generation, component)) if *generation == entity.generation => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Components in the order their entities were first spawned.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref().map(|(generation, component)| {
+                let entity = Entity {
+                    index: index as u32,
+                    generation: *generation,
+                };
+                (entity, component)
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                slot.as_mut().map(|(generation, component)| {
+                    let entity = Entity {
+                        index: index as u32,
+                        generation: *generation,
+                    };
+                    (entity, component)
+                })
+            })
+    }
+}
+
+// Everything in the world that isn't a wall: each entity is just an index, and whatever
+// components it has say how it behaves.
+#[derive(Default)]
+pub struct Entities {
+    // The current generation of every slot and whether it's in use.
+    slots: Vec<(u32, bool)>,
+    free: Vec<u32>,
+    pub positions: Components<Vec2>,
+    // World units per second, applied by `World::movement`.
+    pub velocities: Components<Vec2>,
+    pub sprites: Components<Sprite>,
+    pub doors: Components<Door>,
+    pub enemies: Components<Enemy>,
+    pub health: Components<Health>,
+    pub projectiles: Components<Projectile>,
+    pub pickups: Components<Pickup>,
+    pub switches: Components<Switch>,
+    pub walls: Components<Wall>,
+    pub dormant: Components<Dormant>,
+    pub teleporters: Components<Teleporter>,
+    pub portals: Components<Portal>,
+}
+
+impl Entities {
+    pub fn spawn(&mut self) -> Entity {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push((0, false));
+                self.slots.len() as u32 - 1
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.1 = true;
+        Entity {
+            index,
+            generation: slot.0,
+        }
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        if !self.is_alive(entity) {
+            return;
+        }
+        self.positions.remove(entity);
+        self.velocities.remove(entity);
+        self.sprites.remove(entity);
+        self.doors.remove(entity);
+        self.enemies.remove(entity);
+        self.health.remove(entity);
+        self.projectiles.remove(entity);
+        self.pickups.remove(entity);
+        self.switches.remove(entity);
+        self.walls.remove(entity);
+        self.dormant.remove(entity);
+        self.teleporters.remove(entity);
+        self.portals.remove(entity);
+        let slot = &mut self.slots[entity.index as usize];
+        *slot = (slot.0.wrapping_add(1), false);
+        self.free.push(entity.index);
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.slots
+            .get(entity.index as usize)
+            .is_some_and(|&(generation, alive)| alive && generation == entity.generation)
+    }
+
+    pub fn spawn_sprite(&mut self, position: Vec2, sprite: Sprite) -> Entity {
+        let entity = self.spawn();
+        self.positions.insert(entity, position);
+        self.sprites.insert(entity, sprite);
+        entity
+    }
+
+    pub fn spawn_door(&mut self, door: Door) -> Entity {
+        let entity = self.spawn();
+        self.doors.insert(entity, door);
+        entity
+    }
+
+    pub fn spawn_enemy(&mut self, position: Vec2, sprite: Sprite, enemy: Enemy) -> Entity {
+        let entity = self.spawn();
+        self.positions.insert(entity, position);
+        self.insert_enemy(entity, sprite, enemy);
+        entity
+    }
+
+    fn insert_enemy(&mut self, entity: Entity, sprite: Sprite, enemy: Enemy) {
+        self.sprites.insert(entity, sprite);
+        self.velocities.insert(entity, Vec2::ZERO);
+        self.health.insert(entity, Health::new(enemy.health));
+        self.enemies.insert(entity, enemy);
+    }
+
+    // An enemy that stays hidden and still until `wake` is called on it.
+    pub fn spawn_dormant(&mut self, position: Vec2, dormant: Dormant) -> Entity {
+        let entity = self.spawn();
+        self.positions.insert(entity, position);
+        self.dormant.insert(entity, dormant);
+        entity
+    }
+
+    pub fn wake(&mut self, entity: Entity) {
+        if let Some(dormant) = self.dormant.remove(entity) {
+            self.insert_enemy(entity, dormant.sprite, dormant.enemy);
+        }
+    }
+
+    pub fn spawn_switch(&mut self, switch: Switch) -> Entity {
+        let entity = self.spawn();
+        self.switches.insert(entity, switch);
+        entity
+    }
+
+    pub fn spawn_wall(&mut self, wall: Wall) -> Entity {
+        let entity = self.spawn();
+        self.walls.insert(entity, wall);
+        entity
+    }
+
+    pub fn spawn_projectile(
+        &mut self,
+        position: Vec2,
+        angle: f32,
+        projectile: Projectile,
+    ) -> Entity {
+        let entity = self.spawn_sprite(position, projectile.sprite());
+        self.velocities
+            .insert(entity, Vec2::from_angle(angle) * SPEED);
+        self.projectiles.insert(entity, projectile);
+        entity
+    }
+
+    pub fn spawn_pickup(&mut self, position: Vec2, sprite: Sprite, pickup: Pickup) -> Entity {
+        let entity = self.spawn_sprite(position, sprite);
+        self.pickups.insert(entity, pickup);
+        entity
+    }
+
+    // Every sprite that has somewhere to be drawn.
+    pub fn visible(&self) -> impl Iterator<Item = (Vec2, &Sprite)> {
+        self.sprites
+            .iter()
+            .filter_map(|(entity, sprite)| Some((*self.positions.get(entity)?, sprite)))
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    entities::Entity,
+    pickup::{KeyColour, Pickup},
+    sound::Cue,
+    vec2::Vec2,
+};
+
+// Things that happen in the simulation for other systems, like audio, to react to. They're
+// collected every frame and handed out after the simulation has stepped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    Footstep,
+    DoorOpened(Vec2),
+    DoorClosed(Vec2),
+    // The player walked into a wall.
+    Bump,
+    // The player walked into the level's exit.
+    ExitReached,
+    // The player fired.
+    Fired,
+    // An enemy fired from where it's standing.
+    Shot(Vec2),
+    // A projectile hit a wall.
+    Impact(Vec2),
+    // A projectile or an enemy's touch hit someone.
+    Hit {
+        target: Target,
+        damage: f32,
+        position: Vec2,
+    },
+    // The player walked over a pickup and took it.
+    PickedUp(Pickup),
+    // The player stepped on a teleporter and should be moved to `position`, turning to
+    // `facing` if there is one.
+    Teleported {
+        position: Vec2,
+        facing: Option<f32>,
+    },
+    // The player walked through a portal and should be moved to `position`, turning by `turn`.
+    Portalled {
+        position: Vec2,
+        turn: f32,
+    },
+    // The player flipped a switch.
+    Switched(Vec2),
+    // The player used a door or switch.
+    Used(Entity),
+    // The player walked into the trigger region at this index in `World::triggers`.
+    Entered(usize),
+    // The player walked out of it.
+    Left(usize),
+    // A trigger region played a sound, from its middle.
+    Cue(Cue, Vec2),
+    // The player tried to open a door without its key.
+    Locked(KeyColour),
+    // The player tried to fire with nothing left to fire.
+    OutOfAmmo,
+    // An enemy ran out of health.
+    Killed(Vec2),
+    // The player ran out of health.
+    Died,
+    // A script asked for the view to shake this hard, from 0.0 to 1.0.
+    Shake(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Player,
+    Entity(Entity),
+}
+
+impl GameEvent {
+    // Where in the world the event happened, or `None` for things the player does themselves.
+    pub fn position(&self) -> Option<Vec2> {
+        match self {
+            GameEvent::DoorOpened(position)
+            | GameEvent::DoorClosed(position)
+            | GameEvent::Shot(position)
+            | GameEvent::Impact(position)
+            | GameEvent::Killed(position)
+            | GameEvent::Switched(position)
+            | GameEvent::Cue(_, position)
+            | GameEvent::Hit { position, .. } => Some(*position),
+            GameEvent::Footstep
+            | GameEvent::Bump
+            | GameEvent::ExitReached
+            | GameEvent::Fired
+            | GameEvent::PickedUp(_)
+            | GameEvent::Teleported { .. }
+            | GameEvent::Portalled { .. }
+            | GameEvent::Used(_)
+            | GameEvent::Entered(_)
+            | GameEvent::Left(_)
+            | GameEvent::Locked(_)
+            | GameEvent::OutOfAmmo
+            | GameEvent::Died
+            | GameEvent::Shake(_) => None,
+        }
+    }
+}
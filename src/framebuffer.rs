@@ -0,0 +1,163 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::colour::{Ansi, ColourMode, Rgb};
+
+// Escape codes are written out by hand rather than through a terminal library, so frames can be
+// drawn anywhere ANSI text goes, like a terminal in a web page.
+const RESET_COLOUR: &str = "\x1b[0m";
+const CLEAR: &str = "\x1b[2J";
+
+// A single terminal cell. `None` colours fall back to the terminal's defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub glyph: char,
+    pub fg: Option<Rgb>,
+    pub bg: Option<Rgb>,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            glyph: ' ',
+            fg: None,
+            bg: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FrameBuffer {
+    pub width: u16,
+    pub height: u16,
+    pub cells: Vec<Cell>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+        }
+    }
+
+    pub fn resize(&mut self, width: u16, height: u16) {
+        if (width, height) != (self.width, self.height) {
+            *self = Self::new(width, height);
+        }
+    }
+
+    pub fn get(&self, x: u16, y: u16) -> Cell {
+        self.cells[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn set(&mut self, x: u16, y: u16, cell: Cell) {
+        if x < self.width && y < self.height {
+            self.cells[y as usize * self.width as usize + x as usize] = cell;
+        }
+    }
+
+    // Copies all of `other` into this frame with its top left corner at `x`, `y`.
+    pub fn blit(&mut self, other: &FrameBuffer, x: u16, y: u16) {
+        for row in 0..other.height {
+            for column in 0..other.width {
+                self.set(x + column, y + row, other.get(column, row));
+            }
+        }
+    }
+
+    // Writes text over the frame, keeping the background of the cells underneath.
+    pub fn put_str(&mut self, x: u16, y: u16, text: &str, fg: Rgb) {
+        for (i, glyph) in text.chars().enumerate() {
+            let x = x + i as u16;
+            if x >= self.width || y >= self.height {
+                break;
+            }
+            let bg = self.get(x, y).bg;
+            self.set(
+                x,
+                y,
+                Cell {
+                    glyph,
+                    fg: Some(fg),
+                    bg,
+                },
+            );
+        }
+    }
+
+    // Emits escape sequences for every cell that differs from `previous`, or clears the screen
+    // and draws the whole frame when `previous` has different dimensions, so nothing from a
+    // differently sized frame is left behind.
+    pub fn write_diff(
+        &self,
+        previous: &FrameBuffer,
+        colours: ColourMode,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        let full = (previous.width, previous.height) != (self.width, self.height);
+        if full {
+            write!(out, "{RESET_COLOUR}{CLEAR}")?;
+        }
+        let mut cursor = None;
+        let mut style = None;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let cell = self.get(x, y);
+                if !full && previous.get(x, y) == cell {
+                    continue;
+                }
+                if cursor != Some((x, y)) {
+                    write!(out, "\x1b[{};{}H", y + 1, x + 1)?;
+                }
+                let ansi = cell_colours(cell, colours, x, y);
+                if style != Some(ansi) {
+                    set_colours(out, ansi)?;
+                    style = Some(ansi);
+                }
+                write!(out, "{}", cell.glyph)?;
+                cursor = Some((x + 1, y));
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the whole frame as lines of coloured text, for saving to a file that can be `cat`-ed
+    // rather than drawing onto a screen.
+    pub fn write_ansi(&self, colours: ColourMode, out: &mut impl Write) -> Result<()> {
+        for y in 0..self.height {
+            let mut style = None;
+            for x in 0..self.width {
+                let cell = self.get(x, y);
+                let ansi = cell_colours(cell, colours, x, y);
+                if style != Some(ansi) {
+                    set_colours(out, ansi)?;
+                    style = Some(ansi);
+                }
+                write!(out, "{}", cell.glyph)?;
+            }
+            writeln!(out, "{RESET_COLOUR}")?;
+        }
+        Ok(())
+    }
+}
+
+// The foreground and background the cell at `x`, `y` is written in. Limited palettes are
+// dithered across cells, so fog and light falloff don't turn into hard bands.
+fn cell_colours(cell: Cell, mode: ColourMode, x: u16, y: u16) -> (Option<Ansi>, Option<Ansi>) {
+    let ansi = |colour: Option<Rgb>| colour.map(|c| c.to_ansi_dithered(mode, x, y));
+    (ansi(cell.fg), ansi(cell.bg))
+}
+
+// Sets the colours the cell's glyph is drawn in, with the terminal's own for any it hasn't got.
+fn set_colours(out: &mut impl Write, (fg, bg): (Option<Ansi>, Option<Ansi>)) -> Result<()> {
+    let code = |base: u8, colour: Option<Ansi>| match colour {
+        None => format!("{}", base + 1),
+        Some(Ansi::Indexed(n)) => format!("{base};5;{n}"),
+        Some(Ansi::Rgb(c)) => format!("{base};2;{};{};{}", c.r, c.g, c.b),
+    };
+    write!(out, "\x1b[{}m\x1b[{}m", code(38, fg), code(48, bg))?;
+    Ok(())
+}
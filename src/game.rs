@@ -0,0 +1,464 @@
+use std::{f32::consts::PI, fs, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+#[cfg(not(all(feature = "scripting", feature = "plugins")))]
+use tracing::warn;
+
+#[cfg(any(feature = "scripting", feature = "plugins"))]
+use crate::event::GameEvent;
+#[cfg(feature = "plugins")]
+use crate::plugin::Plugin;
+#[cfg(feature = "scripting")]
+use crate::script::Script;
+use crate::{
+    campaign::Campaign,
+    inventory::Inventory,
+    map::Map,
+    maze::{self, MazeSize},
+    render::{Camera, EYE_HEIGHT},
+    save::{DoorSave, EnemySave, SaveState},
+    vec2::Vec2,
+    wad,
+    world::World,
+};
+
+// Where a game's map comes from, enough to load the same one again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Level {
+    Demo,
+    File(PathBuf),
+    Maze { size: MazeSize, seed: u64 },
+    // A level of the campaign unpacked in `dir`, counting from 0.
+    Campaign { dir: PathBuf, index: usize },
+    // A level of a Doom WAD, named like "E1M1" or "MAP01".
+    Wad { path: PathBuf, map: String },
+}
+
+impl Level {
+    // Map files are named by their full path so the level loads the same from anywhere.
+    pub fn file(path: PathBuf) -> Result<Self> {
+        let path = fs::canonicalize(&path)
+            .with_context(|| format!("Could not find map {}", path.display()))?;
+        Ok(Level::File(path))
+    }
+
+    pub fn wad(path: PathBuf, map: String) -> Result<Self> {
+        let path = fs::canonicalize(&path)
+            .with_context(|| format!("Could not find WAD {}", path.display()))?;
+        Ok(Level::Wad { path, map })
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Level::Demo => "demo".to_string(),
+            Level::File(path) => path.display().to_string(),
+            Level::Maze { size, seed } => format!("maze {}x{} {seed}", size.width, size.height),
+            Level::Campaign { dir, index } => format!("{} level {}", dir.display(), index + 1),
+            Level::Wad { path, map } => format!("{} {map}", path.display()),
+        }
+    }
+
+    // The map file the level is read from, for levels that have one.
+    pub fn path(&self) -> Option<PathBuf> {
+        match self {
+            Level::File(path) | Level::Wad { path, .. } => Some(path.clone()),
+            Level::Campaign { dir, index } => {
+                let campaign = Campaign::read(dir).ok()?;
+                campaign.levels.get(*index).map(|level| level.map.clone())
+            }
+            Level::Demo | Level::Maze { .. } => None,
+        }
+    }
+
+    // The map the level is built from, with the files it names found beside it. The demo is
+    // built in code and has none.
+    pub fn map(&self) -> Result<Option<Map>> {
+        let map = match self {
+            Level::Demo => return Ok(None),
+            Level::File(path) => Map::from_path(path)?,
+            Level::Maze { size, seed } => maze::generate(*size, *seed),
+            Level::Campaign { dir, index } => {
+                let campaign = Campaign::read(dir)?;
+                let level = campaign
+                    .levels
+                    .get(*index)
+                    .with_context(|| format!("{} has no level {}", campaign.name, index + 1))?;
+                let mut map = Map::from_path(&level.map)?;
+                if level.music.is_some() {
+                    map.music = level.music.clone();
+                }
+                map
+            }
+            Level::Wad { path, map } => wad::import(path, map)?,
+        };
+        Ok(Some(map))
+    }
+
+    pub fn load(&self) -> Result<Game> {
+        let mut game = Game::from_map(self.name(), self.map()?.as_ref())?;
+        game.load_script()?;
+        game.load_plugins()?;
+        Ok(game)
+    }
+
+    // The level that follows this one once its exit is reached, given where the exit leads.
+    // Campaigns go on to their next level whatever their maps say.
+    pub fn next(&self, exit: Option<PathBuf>) -> Result<Option<Level>> {
+        match self {
+            Level::Campaign { dir, index } => {
+                let campaign = Campaign::read(dir)?;
+                Ok(
+                    (index + 1 < campaign.levels.len()).then(|| Level::Campaign {
+                        dir: dir.clone(),
+                        index: index + 1,
+                    }),
+                )
+            }
+            _ => exit.map(Level::file).transpose(),
+        }
+    }
+}
+
+// A map being played: the world and the player looking at it.
+pub struct Game {
+    // Names the map, e.g. its path, for telling saves of different maps apart.
+    pub map: String,
+    pub world: World,
+    pub camera: Camera,
+    #[cfg(feature = "scripting")]
+    pub script: Option<Script>,
+    #[cfg(feature = "plugins")]
+    pub plugins: Vec<Plugin>,
+}
+
+impl Game {
+    pub fn new(map: String, world: World, mut camera: Camera) -> Self {
+        camera.eye = world.floor_at(camera.position) + EYE_HEIGHT;
+        Self {
+            map,
+            world,
+            camera,
+            #[cfg(feature = "scripting")]
+            script: None,
+            #[cfg(feature = "plugins")]
+            plugins: Vec::new(),
+        }
+    }
+
+    // Starts a game on `map`, or on the demo if there's none, without running its script or
+    // plugins.
+    pub fn from_map(name: String, map: Option<&Map>) -> Result<Self> {
+        Ok(match map {
+            Some(map) => Self::new(name, map.world()?, map.camera()),
+            None => Self::new(name, World::demo(), Camera::new(Vec2::ZERO, PI / 2.0)),
+        })
+    }
+
+    // Loads the map's script, if it has one, and runs its `on_load`.
+    #[cfg(feature = "scripting")]
+    fn load_script(&mut self) -> Result<()> {
+        let Some(path) = self.world.script.clone() else {
+            return Ok(());
+        };
+        let mut script = Script::load(&path)?;
+        script.on_load(&mut self.world, self.camera.position)?;
+        self.script = Some(script);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    fn load_script(&mut self) -> Result<()> {
+        if let Some(path) = &self.world.script {
+            warn!(
+                "Ignoring script {}, built without scripting",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+
+    // Runs the map's script for a frame, handing back the messages it wants shown. A script
+    // that fails is stopped, so the same error doesn't come up every frame.
+    #[cfg(feature = "scripting")]
+    pub fn run_script(&mut self, dt: f32, events: &mut Vec<GameEvent>) -> Result<Vec<String>> {
+        let Some(script) = self.script.as_mut() else {
+            return Ok(Vec::new());
+        };
+        if let Err(e) = script.update(dt, &mut self.world, self.camera.position, events) {
+            self.script = None;
+            return Err(e);
+        }
+        Ok(std::mem::take(&mut script.messages))
+    }
+
+    // Loads the map's plugins, if it has any, and runs their `on_load`.
+    #[cfg(feature = "plugins")]
+    fn load_plugins(&mut self) -> Result<()> {
+        for path in self.world.plugins.clone() {
+            let mut plugin = Plugin::load(&path)?;
+            plugin.on_load(&mut self.world, self.camera.position)?;
+            self.plugins.push(plugin);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    fn load_plugins(&mut self) -> Result<()> {
+        for path in self.world.plugins.iter() {
+            warn!("Ignoring plugin {}, built without plugins", path.display());
+        }
+        Ok(())
+    }
+
+    // Runs the map's plugins for a frame like `run_script`, stopping any that fail.
+    #[cfg(feature = "plugins")]
+    pub fn run_plugins(&mut self, dt: f32, events: &mut Vec<GameEvent>) -> Result<Vec<String>> {
+        let mut messages = Vec::new();
+        let mut failed = None;
+        for (i, plugin) in self.plugins.iter_mut().enumerate() {
+            match plugin.update(dt, &mut self.world, self.camera.position, events) {
+                Ok(()) => messages.append(&mut plugin.messages),
+                Err(e) => {
+                    failed = Some((i, e));
+                    break;
+                }
+            }
+        }
+        if let Some((i, e)) = failed {
+            self.plugins.remove(i);
+            return Err(e);
+        }
+        Ok(messages)
+    }
+
+    pub fn save(&self) -> SaveState {
+        SaveState {
+            map: self.map.clone(),
+            camera: self.camera,
+            doors: self
+                .world
+                .entities
+                .doors
+                .iter()
+                .map(|(_, door)| DoorSave {
+                    state: door.state,
+                    openness: door.openness,
+                })
+                .collect(),
+            health: self.world.health.current,
+            ammo: self.world.inventory.ammo,
+            keys: self.world.inventory.keys.clone(),
+            items: self.world.inventory.items.clone(),
+            enemies: self
+                .world
+                .enemy_roster
+                .iter()
+                .map(|&entity| {
+                    let entities = &self.world.entities;
+                    let position = *entities.positions.get(entity)?;
+                    if let Some(dormant) = entities.dormant.get(entity) {
+                        return Some(EnemySave {
+                            position,
+                            health: dormant.enemy.health,
+                            awake: false,
+                        });
+                    }
+                    Some(EnemySave {
+                        position,
+                        health: entities.health.get(entity)?.current,
+                        awake: true,
+                    })
+                })
+                .collect(),
+            pickups: self
+                .world
+                .pickup_roster
+                .iter()
+                .map(|&entity| self.world.entities.is_alive(entity))
+                .collect(),
+            switches: self
+                .world
+                .entities
+                .switches
+                .iter()
+                .map(|(_, switch)| switch.on)
+                .collect(),
+            walls: self
+                .world
+                .entities
+                .walls
+                .iter()
+                .map(|(_, wall)| wall.solid)
+                .collect(),
+        }
+    }
+
+    // Enemies killed since the save was made can't come back, so saves are best restored into
+    // a freshly loaded level.
+    pub fn restore(&mut self, save: SaveState) -> Result<()> {
+        if save.map != self.map {
+            bail!("That save is for {}", save.map);
+        }
+        if save.doors.len() != self.world.entities.doors.len() {
+            bail!("That save doesn't match this map's doors");
+        }
+        if save.enemies.len() != self.world.enemy_roster.len() {
+            bail!("That save doesn't match this map's enemies");
+        }
+        if save.pickups.len() != self.world.pickup_roster.len() {
+            bail!("That save doesn't match this map's pickups");
+        }
+        if save.switches.len() != self.world.entities.switches.len()
+            || save.walls.len() != self.world.entities.walls.len()
+        {
+            bail!("That save doesn't match this map's switches");
+        }
+        self.camera = save.camera;
+        self.world.health.current = save.health;
+        self.world.inventory = Inventory {
+            ammo: save.ammo,
+            keys: save.keys,
+            items: save.items,
+        };
+        for ((_, door), saved) in self.world.entities.doors.iter_mut().zip(save.doors) {
+            door.state = saved.state;
+            door.openness = saved.openness;
+        }
+        let entities = &mut self.world.entities;
+        for ((_, switch), on) in entities.switches.iter_mut().zip(save.switches) {
+            switch.on = on;
+        }
+        for ((_, wall), solid) in entities.walls.iter_mut().zip(save.walls) {
+            wall.solid = solid;
+        }
+        // Shots in flight aren't saved, so none carry over into the loaded game.
+        let projectiles: Vec<_> = entities
+            .projectiles
+            .iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        for entity in projectiles {
+            entities.despawn(entity);
+        }
+        for (&entity, saved) in self.world.enemy_roster.iter().zip(save.enemies) {
+            match saved {
+                Some(saved) => {
+                    if saved.awake {
+                        entities.wake(entity);
+                    }
+                    entities.positions.insert(entity, saved.position);
+                    if let Some(health) = entities.health.get_mut(entity) {
+                        health.current = saved.health;
+                    }
+                }
+                None => entities.despawn(entity),
+            }
+        }
+        for (&entity, left) in self.world.pickup_roster.iter().zip(save.pickups) {
+            if !left {
+                entities.despawn(entity);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{door::DoorState, map::Map, pickup::KeyColour, testing::scratch, vec2::Vec2};
+
+    const MAP: &str = r##"
+        [spawn]
+        position = [1.0, 1.0]
+
+        [[doors]]
+        start = [0.0, 4.0]
+        end = [2.0, 4.0]
+        colour = "#8b5a2b"
+
+        [[enemies]]
+        position = [5.0, 5.0]
+        colour = "red"
+
+        [[enemies]]
+        position = [6.0, 5.0]
+        colour = "red"
+
+        [[pickups]]
+        position = [2.0, 2.0]
+        type = "ammo"
+
+        [[pickups]]
+        position = [3.0, 2.0]
+        type = "health"
+    "##;
+
+    fn game() -> Game {
+        let map: Map = toml::from_str(MAP).unwrap();
+        Game::new("test".to_string(), map.world().unwrap(), map.camera())
+    }
+
+    #[test]
+    fn saves_restore_the_game_as_it_was() {
+        let mut played = game();
+        played.camera.position = Vec2::new(1.5, 3.0);
+        for (_, door) in played.world.entities.doors.iter_mut() {
+            door.state = DoorState::Open(2.0);
+            door.openness = 1.0;
+        }
+        played.world.health.current = 40.0;
+        played.world.inventory.ammo = 7;
+        played.world.inventory.keys.push(KeyColour::Red);
+        let world = &mut played.world;
+        world.entities.despawn(world.enemy_roster[0]);
+        world
+            .entities
+            .positions
+            .insert(world.enemy_roster[1], Vec2::new(4.0, 4.0));
+        world.entities.despawn(world.pickup_roster[1]);
+
+        // Through a file, the way saves are kept.
+        let path = scratch("save").join("quicksave.ron");
+        played.save().write(&path).unwrap();
+        let save = SaveState::read(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let mut loaded = game();
+        loaded.restore(save).unwrap();
+        assert_eq!(loaded.camera.position, Vec2::new(1.5, 3.0));
+        let (_, door) = loaded.world.entities.doors.iter().next().unwrap();
+        assert!(matches!(door.state, DoorState::Open(_)));
+        assert_eq!(door.openness, 1.0);
+        assert_eq!(loaded.world.health.current, 40.0);
+        assert_eq!(loaded.world.inventory.ammo, 7);
+        assert_eq!(loaded.world.inventory.keys, [KeyColour::Red]);
+        let entities = &loaded.world.entities;
+        let [dead, alive] = loaded.world.enemy_roster[..] else {
+            panic!("expected two enemies");
+        };
+        assert!(!entities.is_alive(dead));
+        assert_eq!(entities.positions.get(alive), Some(&Vec2::new(4.0, 4.0)));
+        let pickups = &loaded.world.pickup_roster;
+        assert!(entities.is_alive(pickups[0]) && !entities.is_alive(pickups[1]));
+    }
+
+    #[test]
+    fn saves_only_restore_into_their_own_map() {
+        let mut save = game().save();
+        save.map = "other".to_string();
+        assert!(game().restore(save).is_err());
+        let mut save = game().save();
+        save.doors.pop();
+        assert!(game().restore(save).is_err());
+        let mut save = game().save();
+        save.enemies.pop();
+        assert!(game().restore(save).is_err());
+        let mut save = game().save();
+        save.pickups.pop();
+        assert!(game().restore(save).is_err());
+    }
+}
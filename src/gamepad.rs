@@ -0,0 +1,58 @@
+use anyhow::{anyhow, Result};
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+
+use crate::input::{Action, Analog};
+
+// Sticks rarely rest exactly at zero, so small deflections are ignored.
+const DEAD_ZONE: f32 = 0.15;
+
+pub struct Gamepads {
+    gilrs: Gilrs,
+    // The gamepad that sent the last event, which is the one whose sticks are read.
+    active: Option<GamepadId>,
+}
+
+impl Gamepads {
+    pub fn new() -> Result<Self> {
+        let gilrs = Gilrs::new().map_err(|e| anyhow!("Could not open gamepads: {e}"))?;
+        Ok(Self {
+            gilrs,
+            active: None,
+        })
+    }
+
+    // Drains pending gamepad events, returning the actions for any buttons pressed and
+    // setting `analog` from the sticks. The left stick moves and the right stick turns.
+    pub fn poll(&mut self, analog: &mut Analog) -> Vec<Action> {
+        let mut actions = Vec::new();
+        while let Some(event) = self.gilrs.next_event() {
+            self.active = Some(event.id);
+            if let EventType::ButtonPressed(button, _) = event.event {
+                actions.push(match button {
+                    Button::South => Action::Use,
+                    Button::RightTrigger2 => Action::Fire,
+                    Button::Select => Action::ToggleMinimap,
+                    Button::Start => Action::Pause,
+                    _ => continue,
+                });
+            }
+        }
+        *analog = match self.active.and_then(|id| self.gilrs.connected_gamepad(id)) {
+            Some(gamepad) => Analog {
+                forward: dead_zone(gamepad.value(Axis::LeftStickY)),
+                strafe: -dead_zone(gamepad.value(Axis::LeftStickX)),
+                turn: -dead_zone(gamepad.value(Axis::RightStickX)),
+            },
+            None => Analog::default(),
+        };
+        actions
+    }
+}
+
+fn dead_zone(value: f32) -> f32 {
+    if value.abs() < DEAD_ZONE {
+        0.0
+    } else {
+        value
+    }
+}
@@ -0,0 +1,329 @@
+use std::f32::consts::TAU;
+
+use crate::{colour::Rgb, vec2::Vec2};
+
+pub const MAX_DISTANCE: f32 = 15.0;
+// How much darker untextured curved walls get towards their edges, where they turn away from
+// the viewer, so they look round.
+const EDGE_SHADE: f32 = 0.5;
+// Most straight pieces a curved wall is drawn with on maps.
+const OUTLINE_PIECES: usize = 32;
+
+// A segment from `start` to `end`, with points along it written as `start + t * (end - start)`
+// for t in [0, 1].
+#[derive(Clone)]
+pub struct LineSegment {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub colour: Rgb,
+    pub texture: Option<usize>,
+    // Below 1.0 the segment is see-through, like a fence or grate, and whatever is behind it
+    // shows through blended with it. Transparent texels show what's behind in full.
+    pub opacity: f32,
+    // Mirrors send rays off again, reflected about the wall.
+    pub mirror: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    pub distance: f32,
+    pub colour: Rgb,
+    pub texture: Option<usize>,
+    pub opacity: f32,
+    pub mirror: bool,
+    // Set on hits where the ray went through a portal rather than hitting a wall.
+    pub portal: bool,
+    // Heights of the floor and ceiling where the wall was hit, which it runs between.
+    pub floor: f32,
+    pub ceiling: f32,
+    // How far along the wall the hit landed, in world units, and where that is.
+    pub u: f32,
+    pub point: Vec2,
+    // Unit vector pointing out of the wall towards the ray's origin.
+    pub normal: Vec2,
+}
+
+impl Hit {
+    pub fn is_see_through(&self) -> bool {
+        self.opacity < 1.0
+    }
+}
+
+impl LineSegment {
+    pub fn from_points(start: Vec2, end: Vec2, colour: Rgb) -> Self {
+        Self {
+            start,
+            end,
+            colour,
+            texture: None,
+            opacity: 1.0,
+            mirror: false,
+        }
+    }
+
+    pub fn ray(start: Vec2, angle: f32, length: f32) -> Self {
+        Self::from_points(start, start + Vec2::from_angle(angle) * length, Rgb::WHITE)
+    }
+
+    pub fn with_texture(mut self, texture: Option<usize>) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn with_mirror(mut self, mirror: bool) -> Self {
+        self.mirror = mirror;
+        self
+    }
+
+    pub fn direction(&self) -> Vec2 {
+        self.end - self.start
+    }
+
+    pub fn length(&self) -> f32 {
+        self.start.distance(self.end)
+    }
+
+    pub fn point_at(&self, t: f32) -> Vec2 {
+        self.start + self.direction() * t
+    }
+
+    // The parameters along this segment and along `other` where the two cross, or `None` if
+    // they don't (including when they're parallel).
+    pub fn intersects(&self, other: &Self) -> Option<(f32, f32)> {
+        let d1 = self.direction();
+        let d2 = other.direction();
+        let denominator = d1.cross(d2);
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+        let offset = other.start - self.start;
+        let t = offset.cross(d2) / denominator;
+        let s = offset.cross(d1) / denominator;
+        ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&s)).then_some((t, s))
+    }
+
+    // Where `ray` crosses this segment, measured from the start of the ray.
+    pub fn hit(&self, ray: &LineSegment) -> Option<Hit> {
+        let (t, s) = self.intersects(ray)?;
+        Some(Hit {
+            distance: s * ray.length(),
+            colour: self.colour,
+            texture: self.texture,
+            opacity: self.opacity,
+            mirror: self.mirror,
+            portal: false,
+            floor: 0.0,
+            ceiling: 1.0,
+            u: t * self.length(),
+            point: ray.point_at(s),
+            normal: self.normal_towards(ray.start),
+        })
+    }
+
+    // The unit normal on the side of the segment facing `point`.
+    pub fn normal_towards(&self, point: Vec2) -> Vec2 {
+        let normal = self.direction().perpendicular().normalize();
+        if (point - self.start).dot(normal) < 0.0 {
+            -normal
+        } else {
+            normal
+        }
+    }
+
+    pub fn distance_to(&self, point: Vec2) -> f32 {
+        let direction = self.direction();
+        let length = direction.dot(direction);
+        if length == 0.0 {
+            return point.distance(self.start);
+        }
+        let t = ((point - self.start).dot(direction) / length).clamp(0.0, 1.0);
+        point.distance(self.point_at(t))
+    }
+}
+
+// A curved wall running anticlockwise around `centre` from the `start` angle through `sweep`
+// radians. A full circle has a sweep of TAU.
+#[derive(Clone)]
+pub struct Arc {
+    pub centre: Vec2,
+    pub radius: f32,
+    pub start: f32,
+    pub sweep: f32,
+    pub colour: Rgb,
+    pub texture: Option<usize>,
+}
+
+impl Arc {
+    pub fn circle(centre: Vec2, radius: f32, colour: Rgb) -> Self {
+        Self {
+            centre,
+            radius,
+            start: 0.0,
+            sweep: TAU,
+            colour,
+            texture: None,
+        }
+    }
+
+    // Limits the arc to run anticlockwise from angle `start` to angle `end`.
+    pub fn with_span(mut self, start: f32, end: f32) -> Self {
+        self.start = start;
+        self.sweep = (end - start).rem_euclid(TAU);
+        if self.sweep == 0.0 {
+            self.sweep = TAU;
+        }
+        self
+    }
+
+    pub fn with_texture(mut self, texture: Option<usize>) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    // How far round from `start` the point at `angle` is, if it's on the arc at all.
+    fn along(&self, angle: f32) -> Option<f32> {
+        let along = (angle - self.start).rem_euclid(TAU);
+        (along <= self.sweep).then_some(along)
+    }
+
+    // Where `ray` first meets the arc, from outside or in, measured from the start of the ray.
+    pub fn hit(&self, ray: &LineSegment) -> Option<Hit> {
+        let length = ray.length();
+        if length <= f32::EPSILON {
+            return None;
+        }
+        let direction = ray.direction() * (1.0 / length);
+        let offset = ray.start - self.centre;
+        let b = offset.dot(direction);
+        let discriminant = b * b - (offset.dot(offset) - self.radius * self.radius);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [-b - root, -b + root]
+            .into_iter()
+            .filter(|t| (0.0..=length).contains(t))
+            .find_map(|distance| {
+                let point = ray.start + direction * distance;
+                let out = (point - self.centre) * (1.0 / self.radius);
+                let along = self.along(out.angle())?;
+                let normal = if out.dot(direction) > 0.0 { -out } else { out };
+                let facing = -normal.dot(direction);
+                Some(Hit {
+                    distance,
+                    colour: self.colour.scale(1.0 - EDGE_SHADE * (1.0 - facing)),
+                    texture: self.texture,
+                    opacity: 1.0,
+                    mirror: false,
+                    portal: false,
+                    floor: 0.0,
+                    ceiling: 1.0,
+                    u: along * self.radius,
+                    point,
+                    normal,
+                })
+            })
+    }
+
+    // Straight pieces following the arc closely enough to draw it from above.
+    pub fn outline(&self) -> impl Iterator<Item = LineSegment> + '_ {
+        let pieces = ((self.sweep / TAU * OUTLINE_PIECES as f32).ceil() as usize).max(1);
+        let point = move |i: usize| {
+            let angle = self.start + self.sweep * i as f32 / pieces as f32;
+            self.centre + Vec2::from_angle(angle) * self.radius
+        };
+        (0..pieces).map(move |i| LineSegment::from_points(point(i), point(i + 1), self.colour))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    use super::*;
+    use crate::testing::close;
+
+    fn wall(start: (f32, f32), end: (f32, f32)) -> LineSegment {
+        LineSegment::from_points(start.into(), end.into(), Rgb::WHITE)
+    }
+
+    #[test]
+    fn rays_hit_walls_in_front() {
+        let wall = wall((2.0, -1.0), (2.0, 3.0));
+        let hit = wall.hit(&LineSegment::ray(Vec2::ZERO, 0.0, 10.0)).unwrap();
+        assert!(close(hit.distance, 2.0));
+        assert!(close(hit.u, 1.0));
+        assert!(close(hit.normal.x, -1.0) && close(hit.normal.y, 0.0));
+    }
+
+    #[test]
+    fn rays_miss_walls_behind_beside_and_beyond() {
+        let wall = wall((2.0, -1.0), (2.0, 1.0));
+        assert!(wall.hit(&LineSegment::ray(Vec2::ZERO, PI, 10.0)).is_none());
+        assert!(wall.hit(&LineSegment::ray(Vec2::ZERO, 0.0, 1.5)).is_none());
+        let beside = LineSegment::ray(Vec2::new(0.0, 2.0), 0.0, 10.0);
+        assert!(wall.hit(&beside).is_none());
+        let parallel = LineSegment::ray(Vec2::new(2.0, -5.0), FRAC_PI_2, 10.0);
+        assert!(wall.intersects(&parallel).is_none());
+    }
+
+    #[test]
+    fn distance_to_walls() {
+        let wall = wall((0.0, 0.0), (4.0, 0.0));
+        assert!(close(wall.distance_to(Vec2::new(2.0, 3.0)), 3.0));
+        assert!(close(wall.distance_to(Vec2::new(7.0, 4.0)), 5.0));
+    }
+
+    #[test]
+    fn rays_hit_circles_from_outside_and_in() {
+        let circle = Arc::circle(Vec2::new(5.0, 0.0), 1.0, Rgb::WHITE);
+        let hit = circle
+            .hit(&LineSegment::ray(Vec2::ZERO, 0.0, 10.0))
+            .unwrap();
+        assert!(close(hit.distance, 4.0));
+        assert!(close(hit.normal.x, -1.0));
+        // Head on, the wall isn't shaded at all.
+        assert_eq!(hit.colour, Rgb::WHITE);
+        let inside = LineSegment::ray(Vec2::new(5.0, 0.0), 0.0, 10.0);
+        let hit = circle.hit(&inside).unwrap();
+        assert!(close(hit.distance, 1.0));
+        assert!(close(hit.normal.x, -1.0));
+        assert!(circle
+            .hit(&LineSegment::ray(Vec2::ZERO, PI, 10.0))
+            .is_none());
+        assert!(circle
+            .hit(&LineSegment::ray(Vec2::ZERO, 0.0, 3.0))
+            .is_none());
+    }
+
+    #[test]
+    fn rays_only_hit_arcs_within_their_span() {
+        // The half of the circle facing the origin, from straight up round to straight down.
+        let arc = Arc::circle(Vec2::new(5.0, 0.0), 1.0, Rgb::WHITE).with_span(FRAC_PI_2, 1.5 * PI);
+        let hit = arc.hit(&LineSegment::ray(Vec2::ZERO, 0.0, 10.0)).unwrap();
+        assert!(close(hit.distance, 4.0));
+        assert!(close(hit.u, FRAC_PI_2));
+        // From the far side the near half is missed, and the ray goes on to the back of it.
+        let from_behind = LineSegment::ray(Vec2::new(10.0, 0.0), PI, 10.0);
+        assert!(close(arc.hit(&from_behind).unwrap().distance, 6.0));
+        let short = LineSegment::ray(Vec2::new(10.0, 0.0), PI, 5.0);
+        assert!(arc.hit(&short).is_none());
+    }
+
+    #[test]
+    fn arc_outlines_follow_the_arc() {
+        let arc = Arc::circle(Vec2::ZERO, 2.0, Rgb::WHITE).with_span(0.0, PI);
+        let pieces: Vec<_> = arc.outline().collect();
+        assert_eq!(pieces.len(), OUTLINE_PIECES / 2);
+        assert!(close(pieces[0].start.x, 2.0));
+        assert!(close(pieces.last().unwrap().end.x, -2.0));
+        for piece in &pieces {
+            assert!(close(piece.start.length(), 2.0));
+        }
+    }
+}
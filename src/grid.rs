@@ -0,0 +1,162 @@
+use crate::{colour::Rgb, geometry::Hit, vec2::Vec2};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Tile {
+    pub colour: Rgb,
+    pub texture: Option<usize>,
+}
+
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<Option<Tile>>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![None; width * height],
+        }
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<Tile> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        self.cells[y as usize * self.width + x as usize]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, cell: Option<Tile>) {
+        self.cells[y * self.width + x] = cell;
+    }
+
+    // Walks the cells along the ray one boundary at a time (DDA) and returns the distance to
+    // the first solid cell.
+    pub fn cast(&self, origin: Vec2, angle: f32, max_distance: f32) -> Option<Hit> {
+        let direction = Vec2::from_angle(angle);
+        let mut cell = (origin.x.floor() as i32, origin.y.floor() as i32);
+        let delta = ((1.0 / direction.x).abs(), (1.0 / direction.y).abs());
+        let step = (direction.x.signum() as i32, direction.y.signum() as i32);
+        let mut side = (
+            if direction.x < 0.0 {
+                (origin.x - cell.0 as f32) * delta.0
+            } else {
+                (cell.0 as f32 + 1.0 - origin.x) * delta.0
+            },
+            if direction.y < 0.0 {
+                (origin.y - cell.1 as f32) * delta.1
+            } else {
+                (cell.1 as f32 + 1.0 - origin.y) * delta.1
+            },
+        );
+        loop {
+            let (distance, u, normal) = if side.0 < side.1 {
+                cell.0 += step.0;
+                side.0 += delta.0;
+                let distance = side.0 - delta.0;
+                (
+                    distance,
+                    origin.y + distance * direction.y,
+                    Vec2::new(-step.0 as f32, 0.0),
+                )
+            } else {
+                cell.1 += step.1;
+                side.1 += delta.1;
+                let distance = side.1 - delta.1;
+                (
+                    distance,
+                    origin.x + distance * direction.x,
+                    Vec2::new(0.0, -step.1 as f32),
+                )
+            };
+            if distance > max_distance {
+                return None;
+            }
+            if let Some(tile) = self.get(cell.0, cell.1) {
+                return Some(Hit {
+                    distance,
+                    colour: tile.colour,
+                    texture: tile.texture,
+                    opacity: 1.0,
+                    mirror: false,
+                    portal: false,
+                    floor: 0.0,
+                    ceiling: 1.0,
+                    u,
+                    point: origin + direction * distance,
+                    normal,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    use super::*;
+    use crate::testing::close;
+
+    // A 5x5 room with a border of walls, and one more wall at (3, 3).
+    fn room() -> Grid {
+        let mut grid = Grid::new(5, 5);
+        let wall = Some(Tile {
+            colour: Rgb::WHITE,
+            texture: None,
+        });
+        for i in 0..5 {
+            grid.set(i, 0, wall);
+            grid.set(i, 4, wall);
+            grid.set(0, i, wall);
+            grid.set(4, i, wall);
+        }
+        grid.set(3, 3, wall);
+        grid
+    }
+
+    #[test]
+    fn get_is_none_outside() {
+        let grid = room();
+        assert!(grid.get(0, 0).is_some());
+        assert!(grid.get(2, 2).is_none());
+        assert!(grid.get(-1, 2).is_none());
+        assert!(grid.get(2, 5).is_none());
+    }
+
+    #[test]
+    fn casts_stop_at_the_first_wall() {
+        let grid = room();
+        let origin = Vec2::new(2.5, 1.5);
+        let east = grid.cast(origin, 0.0, 10.0).unwrap().distance;
+        assert!(close(east, 1.5));
+        let west = grid.cast(origin, PI, 10.0).unwrap().distance;
+        assert!(close(west, 1.5));
+        let north = grid.cast(origin, FRAC_PI_2, 10.0).unwrap().distance;
+        assert!(close(north, 2.5));
+        let south = grid.cast(origin, -FRAC_PI_2, 10.0).unwrap().distance;
+        assert!(close(south, 0.5));
+    }
+
+    #[test]
+    fn diagonal_casts_find_corner_walls() {
+        let grid = room();
+        // Straight to the wall at (3, 3), through its corner.
+        let distance = grid
+            .cast(Vec2::new(1.5, 1.5), FRAC_PI_4, 10.0)
+            .unwrap()
+            .distance;
+        assert!(close(distance, 1.5 * 2.0f32.sqrt()));
+    }
+
+    #[test]
+    fn casts_give_up_past_the_max_distance() {
+        let grid = room();
+        assert!(grid.cast(Vec2::new(2.5, 1.5), FRAC_PI_2, 2.0).is_none());
+        assert!(Grid::new(3, 3)
+            .cast(Vec2::new(1.5, 1.5), 0.3, 20.0)
+            .is_none());
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{
+    colour::Rgb,
+    font::Lettering,
+    framebuffer::{Cell, FrameBuffer},
+    health::Health,
+    inventory::Inventory,
+    pickup::ITEM_COLOUR,
+    theme::HudTheme,
+};
+
+// Below this much health the bar turns red.
+const LOW_HEALTH: f32 = 0.3;
+
+// Where on the screen something is pinned, staying there as the terminal is resized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Centre,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    // Where the top left corner of something `size` big goes to sit at this anchor in `area`.
+    // Works in cells or pixels alike.
+    pub fn place(self, area: (usize, usize), size: (usize, usize)) -> (usize, usize) {
+        let spare = (area.0.saturating_sub(size.0), area.1.saturating_sub(size.1));
+        let x = match self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => 0,
+            Anchor::Top | Anchor::Centre | Anchor::Bottom => spare.0 / 2,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => spare.0,
+        };
+        let y = match self {
+            Anchor::TopLeft | Anchor::Top | Anchor::TopRight => 0,
+            Anchor::Left | Anchor::Centre | Anchor::Right => spare.1 / 2,
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => spare.1,
+        };
+        (x, y)
+    }
+}
+
+// Which side of a column narrower widgets in it line up against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Start,
+    Centre,
+    End,
+}
+
+// A piece of screen-space UI drawn over the finished frame, sized in terminal cells.
+#[derive(Debug, Clone)]
+pub enum Widget {
+    Text(String, Rgb),
+    // A single glyph, like a key's coloured square.
+    Icon(char, Rgb),
+    // Text in the built-in font, each font pixel half a cell tall.
+    Banner(String, Rgb),
+    // `width` cells filled from the left by `fraction` of `fill`, the rest `empty`.
+    Bar {
+        fraction: f32,
+        width: u16,
+        fill: Rgb,
+        empty: Rgb,
+    },
+    // Widgets side by side, a cell apart.
+    Row(Vec<Widget>),
+    // Widgets stacked top to bottom.
+    Column {
+        children: Vec<Widget>,
+        align: Align,
+    },
+    // A box around a widget, `padding` cells across and down between the two.
+    Panel {
+        child: Box<Widget>,
+        background: Option<Rgb>,
+        border: Option<Rgb>,
+        padding: (u16, u16),
+    },
+}
+
+impl Widget {
+    pub fn text(text: impl Into<String>, colour: Rgb) -> Self {
+        Widget::Text(text.into(), colour)
+    }
+
+    pub fn column(children: Vec<Widget>, align: Align) -> Self {
+        Widget::Column { children, align }
+    }
+
+    // Space around a widget with nothing drawn in it.
+    pub fn padded(child: Widget, padding: (u16, u16)) -> Self {
+        Widget::Panel {
+            child: Box::new(child),
+            background: None,
+            border: None,
+            padding,
+        }
+    }
+
+    // Width and height in cells.
+    pub fn size(&self) -> (u16, u16) {
+        match self {
+            Widget::Text(text, _) => (text.chars().count() as u16, 1),
+            Widget::Icon(..) => (1, 1),
+            Widget::Banner(text, _) => {
+                let lettering = Lettering::new(text);
+                (
+                    lettering.width() as u16,
+                    lettering.height().div_ceil(2) as u16,
+                )
+            }
+            Widget::Bar { width, .. } => (*width, 1),
+            Widget::Row(children) => {
+                let sizes = children.iter().map(Widget::size);
+                let width = sizes.clone().map(|(w, _)| w).sum::<u16>();
+                let gaps = children.len().saturating_sub(1) as u16;
+                (width + gaps, sizes.map(|(_, h)| h).max().unwrap_or(0))
+            }
+            Widget::Column { children, .. } => {
+                let sizes = children.iter().map(Widget::size);
+                let width = sizes.clone().map(|(w, _)| w).max().unwrap_or(0);
+                (width, sizes.map(|(_, h)| h).sum())
+            }
+            Widget::Panel {
+                child,
+                border,
+                padding,
+                ..
+            } => {
+                let (width, height) = child.size();
+                let edge = if border.is_some() { 2 } else { 0 };
+                (width + padding.0 * 2 + edge, height + padding.1 * 2 + edge)
+            }
+        }
+    }
+
+    // Draws the widget with its top left corner at `x`, `y`, clipped to the frame.
+    pub fn draw(&self, frame: &mut FrameBuffer, x: u16, y: u16) {
+        match self {
+            Widget::Text(text, colour) => frame.put_str(x, y, text, *colour),
+            Widget::Icon(glyph, colour) => frame.put_str(x, y, &glyph.to_string(), *colour),
+            Widget::Banner(text, colour) => {
+                let lettering = Lettering::new(text);
+                for row in 0..lettering.height().div_ceil(2) {
+                    for column in 0..lettering.width() {
+                        let top = lettering.lit(column, row * 2);
+                        let bottom = lettering.lit(column, row * 2 + 1);
+                        let glyph = match (top, bottom) {
+                            (true, true) => "█",
+                            (true, false) => "▀",
+                            (false, true) => "▄",
+                            (false, false) => continue,
+                        };
+                        frame.put_str(x + column as u16, y + row as u16, glyph, *colour);
+                    }
+                }
+            }
+            Widget::Bar {
+                fraction,
+                width,
+                fill,
+                empty,
+            } => {
+                let filled = (*width as f32 * fraction.clamp(0.0, 1.0)).round() as u16;
+                for i in 0..*width {
+                    let (glyph, colour) = if i < filled {
+                        ('█', *fill)
+                    } else {
+                        ('░', *empty)
+                    };
+                    frame.put_str(x + i, y, &glyph.to_string(), colour);
+                }
+            }
+            Widget::Row(children) => {
+                let mut x = x;
+                for child in children {
+                    child.draw(frame, x, y);
+                    x += child.size().0 + 1;
+                }
+            }
+            Widget::Column { children, align } => {
+                let width = self.size().0;
+                let mut y = y;
+                for child in children {
+                    let (child_width, height) = child.size();
+                    let spare = width - child_width;
+                    let indent = match align {
+                        Align::Start => 0,
+                        Align::Centre => spare / 2,
+                        Align::End => spare,
+                    };
+                    child.draw(frame, x + indent, y);
+                    y += height;
+                }
+            }
+            Widget::Panel {
+                child,
+                background,
+                border,
+                padding,
+            } => {
+                let (width, height) = self.size();
+                if background.is_some() || border.is_some() {
+                    for row in y..y + height {
+                        for column in x..x + width {
+                            let edge = row == y
+                                || row == y + height - 1
+                                || column == x
+                                || column == x + width - 1;
+                            let bg = match border {
+                                Some(border) if edge => Some(*border),
+                                _ => *background,
+                            };
+                            frame.set(
+                                column,
+                                row,
+                                Cell {
+                                    glyph: ' ',
+                                    fg: None,
+                                    bg,
+                                },
+                            );
+                        }
+                    }
+                }
+                let edge = u16::from(border.is_some());
+                child.draw(frame, x + edge + padding.0, y + edge + padding.1);
+            }
+        }
+    }
+}
+
+// The widgets drawn over a frame, each pinned to part of the screen. Later ones are drawn over
+// earlier ones.
+#[derive(Debug, Clone, Default)]
+pub struct Overlay {
+    widgets: Vec<(Anchor, Widget)>,
+}
+
+impl Overlay {
+    pub fn add(&mut self, anchor: Anchor, widget: Widget) {
+        self.widgets.push((anchor, widget));
+    }
+
+    pub fn draw(&self, frame: &mut FrameBuffer) {
+        let area = (frame.width as usize, frame.height as usize);
+        for (anchor, widget) in self.widgets.iter() {
+            let (width, height) = widget.size();
+            let (x, y) = anchor.place(area, (width as usize, height as usize));
+            widget.draw(frame, x as u16, y as u16);
+        }
+    }
+}
+
+// The player's health as a bar a fifth of the screen wide, turning red when it's low.
+pub fn health_bar(health: &Health, screen_width: u16, theme: &HudTheme) -> Widget {
+    let fraction = health.fraction();
+    Widget::Bar {
+        fraction,
+        width: (screen_width / 5).max(1),
+        fill: if fraction < LOW_HEALTH {
+            theme.health_low
+        } else {
+            theme.health
+        },
+        empty: theme.health_empty,
+    }
+}
+
+// The player's keys, each in its colour, and ammo on one line, with any items they're carrying
+// stacked above.
+pub fn inventory(inventory: &Inventory, theme: &HudTheme) -> Widget {
+    let mut lines: Vec<_> = inventory
+        .items
+        .iter()
+        .rev()
+        .map(|item| match item.count {
+            1 => Widget::text(format!("◆ {}", item.name), ITEM_COLOUR),
+            count => Widget::text(format!("◆ {} ×{count}", item.name), ITEM_COLOUR),
+        })
+        .collect();
+    let mut bottom: Vec<_> = inventory
+        .keys
+        .iter()
+        .map(|key| Widget::Icon('■', key.colour()))
+        .collect();
+    bottom.push(Widget::text(format!("{} ammo", inventory.ammo), theme.text));
+    lines.push(Widget::Row(bottom));
+    Widget::column(lines, Align::End)
+}
+
+// `text` in big letters if they'd fit across `width` cells, or as it is if not.
+pub fn banner(text: &str, colour: Rgb, width: u16) -> Widget {
+    let banner = Widget::Banner(text.to_string(), colour);
+    if banner.size().0 <= width {
+        banner
+    } else {
+        Widget::text(text, colour)
+    }
+}
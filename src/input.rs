@@ -0,0 +1,458 @@
+use std::{
+    collections::HashMap,
+    f32::consts::{PI, TAU},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEventKind},
+    cheat::Cheat,
+    collision::slide,
+    controls::{bound, Controls, Key, MAX_SMOOTHING},
+    event::GameEvent,
+    render::{Camera, EYE_HEIGHT},
+    sector::{Reach, CROUCH_HEADROOM, HEADROOM, STEP_HEIGHT},
+    vec2::Vec2,
+    world::World,
+};
+
+// Movement in world units per second and turning in radians per second.
+pub const SPEED: f32 = 4.0;
+pub const TURN_SPEED: f32 = 2.0;
+
+// Most terminals never report key releases, so a key counts as held until it stops repeating.
+// The first press has to last through the terminal's initial repeat delay. Both are in seconds.
+const INITIAL_HOLD: f32 = 0.5;
+const REPEAT_HOLD: f32 = 0.1;
+
+// World units walked between footsteps.
+const STRIDE: f32 = 1.2;
+
+// Jumps start rising at `JUMP_SPEED` world units per second and slow by `GRAVITY` every
+// second, topping out a little over a step above the floor.
+const JUMP_SPEED: f32 = 3.0;
+const GRAVITY: f32 = 12.0;
+// How far above the feet the eye is while crouched, and how fast it moves between that and
+// standing height in world units per second.
+const CROUCH_EYE_HEIGHT: f32 = 0.25;
+const CROUCH_SPEED: f32 = 2.0;
+
+// How fast a head bob grows in when setting off and dies away on stopping, as a fraction of
+// its full height per second.
+const BOB_SETTLE: f32 = 4.0;
+
+const FOV_STEP: f32 = PI / 36.0;
+// Radians turned per column and screen heights the horizon moves per row the mouse is
+// dragged, before scaling by the mouse's sensitivity.
+const YAW_PER_COLUMN: f32 = 0.01;
+const PITCH_PER_ROW: f32 = 0.03;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    None,
+    ToggleMinimap,
+    ToggleStats,
+    ToggleOverhead,
+    Use,
+    Fire,
+    QuickSave,
+    QuickLoad,
+    Screenshot,
+    ExportAnsi,
+    Pause,
+    Quit,
+    OpenConsole,
+    ToggleCheat(Cheat),
+}
+
+// Stick deflection from -1.0 to 1.0 on each axis, added on top of whatever keys are held.
+// Positive values move forwards, strafe left and turn left.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Analog {
+    pub forward: f32,
+    pub strafe: f32,
+    pub turn: f32,
+}
+
+// How fast the player moves: slower with Ctrl held, faster with Shift, or else at `speed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Pace {
+    Walk,
+    Run,
+    Sprint,
+}
+
+// Seconds of sprinting left, for games that want sprinting to run out. It drains while
+// sprinting, refills `recovery` times as fast while not, and stops the sprint when it's empty.
+#[derive(Debug, Clone, Copy)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+    pub recovery: f32,
+}
+
+impl Stamina {
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            current: seconds,
+            max: seconds,
+            recovery: 0.5,
+        }
+    }
+
+    // The pace the player can actually move at, spending or refilling stamina for it.
+    pub fn spend(&mut self, pace: Pace, moving: bool, dt: f32) -> Pace {
+        if pace == Pace::Sprint && moving && self.current > 0.0 {
+            self.current = (self.current - dt).max(0.0);
+            return Pace::Sprint;
+        }
+        self.current = (self.current + self.recovery * dt).min(self.max);
+        pace.min(Pace::Run)
+    }
+}
+
+// The view bobbing up and down `amplitude` world units while moving, `frequency` times a second
+// at `Input::speed`.
+#[derive(Debug, Clone, Copy)]
+pub struct HeadBob {
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+pub struct Input {
+    pub speed: f32,
+    pub turn_speed: f32,
+    pub controls: Controls,
+    pub analog: Analog,
+    // Limits sprinting when set. There's none by default, so sprinting lasts as long as Shift.
+    pub stamina: Option<Stamina>,
+    // Off by default, leaving the view gliding along at a steady height.
+    pub head_bob: Option<HeadBob>,
+    last_mouse_position: Option<(u16, u16)>,
+    // The last drag in columns and rows after smoothing, which the next one carries on from.
+    drag: Vec2,
+    // Held keys, when they stop counting as held and the pace their modifiers asked for. Time
+    // only moves in `update`, so the same events and frame times always play out the same way.
+    held: HashMap<KeyCode, (f32, Pace)>,
+    time: f32,
+    // Distance walked since the last footstep.
+    stride: f32,
+    // Whether the player was pushing into a wall last frame, so a bump only sounds once.
+    bumping: bool,
+    // How far the player's feet are above the floor, how fast they're rising, and how far above
+    // them the eye is.
+    lift: f32,
+    rise: f32,
+    stance: f32,
+    // How far through a bob the view is in radians, and how much of its height it's bobbing by.
+    bob_phase: f32,
+    bob_strength: f32,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self {
+            speed: SPEED,
+            turn_speed: TURN_SPEED,
+            controls: Controls::default(),
+            analog: Analog::default(),
+            stamina: None,
+            head_bob: None,
+            last_mouse_position: None,
+            drag: Vec2::ZERO,
+            held: HashMap::new(),
+            time: 0.0,
+            stride: 0.0,
+            bumping: false,
+            lift: 0.0,
+            rise: 0.0,
+            stance: EYE_HEIGHT,
+            bob_phase: 0.0,
+            bob_strength: 0.0,
+        }
+    }
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A new input for starting over, e.g. in another level, reading the same keys and moving at
+    // the same speeds as this one.
+    pub fn fresh(&self) -> Self {
+        Self {
+            speed: self.speed,
+            turn_speed: self.turn_speed,
+            controls: self.controls.clone(),
+            head_bob: self.head_bob,
+            ..Self::default()
+        }
+    }
+
+    pub fn handle(&mut self, event: Event, camera: &mut Camera) -> Action {
+        match event {
+            Event::Mouse(evt) => match evt.kind {
+                MouseEventKind::Down(_) => {
+                    self.last_mouse_position = Some((evt.column, evt.row));
+                    self.drag = Vec2::ZERO;
+                }
+                MouseEventKind::Drag(_) => {
+                    if let Some((column, row)) = self.last_mouse_position {
+                        let mouse = self.controls.mouse;
+                        let flip = |invert: bool| if invert { -1.0 } else { 1.0 };
+                        let drag = Vec2::new(
+                            (evt.column as i32 - column as i32) as f32 * flip(mouse.invert_x),
+                            (evt.row as i32 - row as i32) as f32 * flip(mouse.invert_y),
+                        );
+                        let smoothing = mouse.smoothing.clamp(0.0, MAX_SMOOTHING);
+                        self.drag = self.drag.lerp(drag, 1.0 - smoothing);
+                        camera.rotate(-self.drag.x * YAW_PER_COLUMN * mouse.sensitivity);
+                        camera.look(-self.drag.y * PITCH_PER_ROW * mouse.sensitivity);
+                    }
+                    self.last_mouse_position = Some((evt.column, evt.row));
+                }
+                _ => {}
+            },
+            Event::Key(mut key) => match key.kind {
+                KeyEventKind::Release => {
+                    pace(&mut key);
+                    self.held.remove(&key.code);
+                }
+                _ if bound(&self.controls.quit, key.code) => return Action::Quit,
+                KeyEventKind::Press if bound(&self.controls.pause, key.code) => {
+                    return Action::Pause
+                }
+                KeyEventKind::Press if bound(&self.controls.minimap, key.code) => {
+                    return Action::ToggleMinimap
+                }
+                KeyEventKind::Press if bound(&self.controls.stats, key.code) => {
+                    return Action::ToggleStats
+                }
+                KeyEventKind::Press if bound(&self.controls.overhead, key.code) => {
+                    return Action::ToggleOverhead
+                }
+                KeyEventKind::Press if bound(&self.controls.use_door, key.code) => {
+                    return Action::Use
+                }
+                KeyEventKind::Press if bound(&self.controls.fire, key.code) => return Action::Fire,
+                KeyEventKind::Press if bound(&self.controls.quick_save, key.code) => {
+                    return Action::QuickSave
+                }
+                KeyEventKind::Press if bound(&self.controls.quick_load, key.code) => {
+                    return Action::QuickLoad
+                }
+                KeyEventKind::Press if bound(&self.controls.screenshot, key.code) => {
+                    return Action::Screenshot
+                }
+                KeyEventKind::Press if bound(&self.controls.export_ansi, key.code) => {
+                    return Action::ExportAnsi
+                }
+                KeyEventKind::Press if bound(&self.controls.console, key.code) => {
+                    return Action::OpenConsole
+                }
+                KeyEventKind::Press if bound(&self.controls.noclip, key.code) => {
+                    return Action::ToggleCheat(Cheat::Noclip)
+                }
+                KeyEventKind::Press if bound(&self.controls.god, key.code) => {
+                    return Action::ToggleCheat(Cheat::God)
+                }
+                KeyEventKind::Press if bound(&self.controls.reveal, key.code) => {
+                    return Action::ToggleCheat(Cheat::Reveal)
+                }
+                _ if bound(&self.controls.widen_fov, key.code) => {
+                    camera.set_fov(camera.fov + FOV_STEP);
+                }
+                _ if bound(&self.controls.narrow_fov, key.code) => {
+                    camera.set_fov(camera.fov - FOV_STEP);
+                }
+                _ => {
+                    let pace = pace(&mut key);
+                    let hold = if self.held.contains_key(&key.code) {
+                        REPEAT_HOLD
+                    } else {
+                        INITIAL_HOLD
+                    };
+                    self.held.insert(key.code, (self.time + hold, pace));
+                }
+            },
+            _ => {}
+        }
+        Action::None
+    }
+
+    pub fn is_held(&self, keys: &[Key]) -> bool {
+        keys.iter().any(|k| self.held.contains_key(&k.0))
+    }
+
+    // The fastest pace any held movement key asks for.
+    pub fn pace(&self) -> Pace {
+        let controls = &self.controls;
+        let movement = [
+            &controls.forward,
+            &controls.back,
+            &controls.strafe_left,
+            &controls.strafe_right,
+        ];
+        self.held
+            .iter()
+            .filter(|(&code, _)| movement.iter().any(|keys| bound(keys, code)))
+            .map(|(_, &(_, pace))| pace)
+            .max()
+            .unwrap_or(Pace::Run)
+    }
+
+    pub fn update(
+        &mut self,
+        camera: &mut Camera,
+        world: &World,
+        dt: f32,
+        events: &mut Vec<GameEvent>,
+    ) {
+        self.time += dt;
+        let now = self.time;
+        self.held.retain(|_, (until, _)| *until > now);
+
+        let forward = axis(
+            self.is_held(&self.controls.forward),
+            self.is_held(&self.controls.back),
+        );
+        let strafe = axis(
+            self.is_held(&self.controls.strafe_left),
+            self.is_held(&self.controls.strafe_right),
+        );
+        let turn = axis(
+            self.is_held(&self.controls.turn_left),
+            self.is_held(&self.controls.turn_right),
+        );
+        let forward = (forward + self.analog.forward).clamp(-1.0, 1.0);
+        let strafe = (strafe + self.analog.strafe).clamp(-1.0, 1.0);
+        let turn = (turn + self.analog.turn).clamp(-1.0, 1.0);
+        let start = camera.position;
+        let feet = world.floor_at(start) + self.lift;
+        // There's no standing up again until there's room to.
+        let heights = world.heights_at(start);
+        let crouching =
+            self.is_held(&self.controls.crouch) || heights.ceiling - heights.floor < HEADROOM;
+        // Off the ground, steps only need to be as high as the feet to be climbed.
+        let reach = Reach {
+            step: STEP_HEIGHT + self.lift,
+            headroom: if crouching { CROUCH_HEADROOM } else { HEADROOM },
+        };
+        // Walking and strafing at once goes along the diagonal no faster than either alone.
+        let mut direction = camera.forward() * forward - camera.right() * strafe;
+        if direction.length() > 1.0 {
+            direction = direction.normalize();
+        }
+        let moving = direction.length() > 0.0;
+        let mut pace = self.pace();
+        if let Some(stamina) = self.stamina.as_mut() {
+            pace = stamina.spend(pace, moving, dt);
+        }
+        let speed = self.speed
+            * match pace {
+                Pace::Walk => self.controls.speeds.walk,
+                Pace::Run => 1.0,
+                Pace::Sprint => self.controls.speeds.sprint,
+            };
+        let blocked = moving && step(camera, world, direction * speed * dt, reach);
+        if blocked && !self.bumping {
+            events.push(GameEvent::Bump);
+        }
+        self.bumping = blocked;
+        // Stepping up onto a sector lifts the feet with the floor, but stepping off one leaves
+        // them in the air to fall.
+        let heights = world.heights_at(camera.position);
+        self.lift = (feet - heights.floor).max(0.0);
+        if self.lift == 0.0 && !crouching && self.is_held(&self.controls.jump) {
+            self.rise = JUMP_SPEED;
+        }
+        if self.lift > 0.0 || self.rise > 0.0 {
+            self.rise -= GRAVITY * dt;
+            self.lift += self.rise * dt;
+            let headroom = if crouching { CROUCH_HEADROOM } else { HEADROOM };
+            let ceiling = (heights.ceiling - heights.floor - headroom).max(0.0);
+            if self.lift >= ceiling {
+                self.lift = ceiling;
+                self.rise = self.rise.min(0.0);
+            }
+            if self.lift <= 0.0 {
+                self.lift = 0.0;
+                self.rise = 0.0;
+                events.push(GameEvent::Footstep);
+            }
+        }
+        let stance = if crouching {
+            CROUCH_EYE_HEIGHT
+        } else {
+            EYE_HEIGHT
+        };
+        let change = (stance - self.stance).clamp(-CROUCH_SPEED * dt, CROUCH_SPEED * dt);
+        self.stance += change;
+        let walked = start.distance(camera.position);
+        let bob = match self.head_bob {
+            // Feet off the ground aren't walking.
+            Some(bob) if self.lift == 0.0 => self.bob(bob, walked, dt),
+            Some(bob) => self.bob(bob, 0.0, dt),
+            None => 0.0,
+        };
+        camera.eye = heights.floor + self.lift + self.stance + bob;
+        self.stride += walked;
+        if self.stride >= STRIDE {
+            self.stride -= STRIDE;
+            events.push(GameEvent::Footstep);
+        }
+        if turn != 0.0 {
+            camera.rotate(turn * self.turn_speed * dt);
+        }
+    }
+
+    // How far above or below its height the view is this frame. The bob keeps time with how
+    // fast the player actually went, so it slows against walls and quickens with a sprint.
+    fn bob(&mut self, bob: HeadBob, walked: f32, dt: f32) -> f32 {
+        if dt <= 0.0 {
+            return 0.0;
+        }
+        let going = walked / dt / self.speed;
+        self.bob_phase = (self.bob_phase + bob.frequency * going.min(2.0) * dt * TAU) % TAU;
+        let strength = going.min(1.0) - self.bob_strength;
+        self.bob_strength += strength.clamp(-BOB_SETTLE * dt, BOB_SETTLE * dt);
+        bob.amplitude * self.bob_strength * self.bob_phase.sin()
+    }
+}
+
+// The pace Shift or Ctrl held with `key` asks for. Most terminals send letters with Shift as
+// capitals, so those are turned back into the letter their key is bound as.
+fn pace(key: &mut KeyEvent) -> Pace {
+    let mut shift = key.modifiers.contains(KeyModifiers::SHIFT);
+    if let KeyCode::Char(c) = key.code {
+        if c.is_ascii_uppercase() {
+            key.code = KeyCode::Char(c.to_ascii_lowercase());
+            shift = true;
+        }
+    }
+    if shift {
+        Pace::Sprint
+    } else if key.modifiers.contains(KeyModifiers::CONTROL) {
+        Pace::Walk
+    } else {
+        Pace::Run
+    }
+}
+
+// 1.0 for the positive key, -1.0 for the negative one and nothing if both or neither are held.
+fn axis(positive: bool, negative: bool) -> f32 {
+    positive as i32 as f32 - negative as i32 as f32
+}
+
+// Returns whether a wall stopped most of the move. Nothing does with noclip on.
+fn step(camera: &mut Camera, world: &World, delta: Vec2, reach: Reach) -> bool {
+    let start = camera.position;
+    if world.cheats.noclip {
+        camera.position = start + delta;
+        return false;
+    }
+    camera.position = slide(world, start, delta, reach);
+    start.distance(camera.position) < delta.length() / 2.0
+}
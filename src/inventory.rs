@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{pickup::KeyColour, projectile::START_AMMO};
+
+// A quest item the player is carrying, and how many of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Item {
+    pub name: String,
+    pub count: u32,
+}
+
+// Everything the player has picked up and is carrying: ammo for the gun, keys for locked doors
+// and any items a map's script asks after.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Inventory {
+    pub ammo: u32,
+    pub keys: Vec<KeyColour>,
+    // In the order they were first picked up.
+    pub items: Vec<Item>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            ammo: START_AMMO,
+            keys: Vec::new(),
+            items: Vec::new(),
+        }
+    }
+}
+
+impl Inventory {
+    pub fn has_key(&self, key: KeyColour) -> bool {
+        self.keys.contains(&key)
+    }
+
+    pub fn add_key(&mut self, key: KeyColour) {
+        if !self.has_key(key) {
+            self.keys.push(key);
+        }
+    }
+
+    pub fn add_ammo(&mut self, amount: u32) {
+        self.ammo = self.ammo.saturating_add(amount);
+    }
+
+    // Takes `amount` ammo if there's that much left.
+    pub fn use_ammo(&mut self, amount: u32) -> bool {
+        let Some(left) = self.ammo.checked_sub(amount) else {
+            return false;
+        };
+        self.ammo = left;
+        true
+    }
+
+    // How many of the item called `name` the player has.
+    pub fn count(&self, name: &str) -> u32 {
+        self.items
+            .iter()
+            .find(|item| item.name == name)
+            .map_or(0, |item| item.count)
+    }
+
+    pub fn add_item(&mut self, name: &str, count: u32) {
+        if count == 0 {
+            return;
+        }
+        match self.items.iter_mut().find(|item| item.name == name) {
+            Some(item) => item.count = item.count.saturating_add(count),
+            None => self.items.push(Item {
+                name: name.to_string(),
+                count,
+            }),
+        }
+    }
+
+    // Takes `count` of the item called `name` if the player has that many, dropping it from
+    // the inventory once none are left.
+    pub fn take_item(&mut self, name: &str, count: u32) -> bool {
+        let Some(i) = self.items.iter().position(|item| item.name == name) else {
+            return count == 0;
+        };
+        let item = &mut self.items[i];
+        let Some(left) = item.count.checked_sub(count) else {
+            return false;
+        };
+        item.count = left;
+        if left == 0 {
+            self.items.remove(i);
+        }
+        true
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+
+use crate::{
+    backend::event::Event,
+    controls::Controls,
+    input::{Action, Input},
+    net::{Client, DEFAULT_PORT},
+    render::Renderer,
+    session::{open_backend, OutputArgs},
+    theme::Theme,
+};
+
+#[derive(Args)]
+pub struct JoinArgs {
+    #[arg(help = "Host to join, as host:port or just host for the default port")]
+    pub address: String,
+    #[command(flatten)]
+    pub output: OutputArgs,
+    #[arg(
+        long,
+        default_value_t = 30.0,
+        help = "Frames per second to draw at most"
+    )]
+    pub fps_cap: f32,
+    #[arg(
+        long,
+        help = "Key bindings to use instead of the ones in the config directory"
+    )]
+    pub controls: Option<PathBuf>,
+    #[arg(long, help = "Watch the game without playing")]
+    pub spectate: bool,
+}
+
+pub fn run(args: JoinArgs) -> Result<()> {
+    if args.fps_cap <= 0.0 {
+        bail!("--fps-cap has to be positive");
+    }
+    let mut input = Input::new();
+    input.controls = match args.controls.or_else(Controls::default_path) {
+        Some(path) => Controls::load_or_create(&path)?,
+        None => Controls::default(),
+    };
+    let address = if args.address.contains(':') {
+        args.address
+    } else {
+        format!("{}:{DEFAULT_PORT}", args.address)
+    };
+    let (mut client, level) = Client::connect(&address, &input.controls, args.spectate)?;
+    let output = &args.output;
+    input.head_bob = output.head_bob();
+    let mut game = level.load()?;
+    output.theme.recolour(&mut game.world, &Theme::classic());
+    let mut renderer = Renderer::new(output.renderer);
+    renderer.theme = output.theme.clone();
+    renderer.narration = output.narrate;
+    let mut backend = open_backend(output)?;
+    let mut size = backend.size()?;
+    // Spectators watch a player by their place in the list, or fly a camera of their own when
+    // there's none.
+    let mut watching = Some(0);
+    let mut free = game.camera;
+
+    let frame_time = Duration::from_secs_f32(1.0 / args.fps_cap);
+    let mut last_frame = Instant::now();
+    loop {
+        let next_frame = last_frame + frame_time;
+        let timeout = || next_frame.saturating_duration_since(Instant::now());
+        let mut events = Vec::new();
+        while let Some(event) = backend.poll_event(timeout())? {
+            match event {
+                Event::Resize(width, height) => size = (width, height),
+                event => events.push(event),
+            }
+        }
+        if backend.closed() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let dt = (now - last_frame).as_secs_f32();
+        last_frame = now;
+        // The host moves the player, so only actions that change this screen are acted on here.
+        // Quitting is still sent on, so the host sees the player go straight away. Spectators
+        // steer their own camera, and use and fire switch between players and flying freely.
+        let mut quit = false;
+        for event in events.iter() {
+            let mut camera = if args.spectate { free } else { game.camera };
+            match input.handle(event.clone(), &mut camera) {
+                Action::Quit => quit = true,
+                Action::ToggleMinimap => renderer.minimap = !renderer.minimap,
+                Action::ToggleStats => renderer.stats = !renderer.stats,
+                Action::ToggleOverhead => renderer.overhead = !renderer.overhead,
+                Action::Use if args.spectate && !client.players.is_empty() => {
+                    let next = watching.map_or(0, |i| (i + 1) % client.players.len());
+                    watching = Some(next);
+                    renderer.notify(format!("Watching player {}", client.players[next].id));
+                }
+                Action::Fire if args.spectate => {
+                    watching = None;
+                    renderer.notify("Flying freely");
+                }
+                _ => {}
+            }
+            if args.spectate {
+                free = camera;
+            }
+        }
+        let updated = if args.spectate {
+            client.update(&mut game)
+        } else {
+            client.send(events).and_then(|()| client.update(&mut game))
+        };
+        if quit {
+            return Ok(());
+        }
+        updated.context("Lost the game")?;
+        if args.spectate {
+            let watched = watching.and_then(|i| client.players.get(i));
+            match watched {
+                Some(player) => free = player.camera,
+                None => {
+                    // Flying goes through walls, and nothing it does is heard.
+                    game.world.cheats.noclip = true;
+                    input.update(&mut free, &game.world, dt, &mut Vec::new());
+                }
+            }
+            game.camera = free;
+        }
+        renderer.render(size, &game.camera, &game.world, backend.as_mut())?;
+    }
+}
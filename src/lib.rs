@@ -0,0 +1,90 @@
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod backend;
+pub mod bench;
+pub mod campaign;
+pub mod canvas;
+pub mod cast;
+pub mod cheat;
+pub mod collision;
+pub mod colour;
+pub mod console;
+pub mod controls;
+pub mod damage;
+pub mod daylight;
+pub mod demo;
+pub mod door;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod edit;
+pub mod editor;
+pub mod enemy;
+pub mod entities;
+pub mod event;
+pub mod font;
+pub mod framebuffer;
+pub mod game;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod geometry;
+pub mod grid;
+pub mod health;
+pub mod hud;
+pub mod input;
+pub mod inventory;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod join;
+pub mod light;
+pub mod map;
+pub mod maze;
+pub mod menu;
+pub mod minimap;
+pub mod narration;
+pub mod net;
+pub mod overhead;
+pub mod particle;
+pub mod pickup;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod play;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod playback;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod portal;
+pub mod projectile;
+#[cfg(feature = "hot-reload")]
+pub mod reload;
+pub mod render;
+pub mod save;
+pub mod screenshot;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod sector;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod session;
+pub mod shade;
+pub mod shake;
+pub mod sketch;
+pub mod sound;
+pub mod spatial;
+pub mod sprite;
+pub mod switch;
+pub mod teleporter;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod terminal;
+#[cfg(test)]
+mod testing;
+pub mod texture;
+pub mod theme;
+pub mod tiled;
+pub mod tilemap;
+pub mod trigger;
+pub mod vec2;
+pub mod video;
+pub mod viewmodel;
+pub mod wad;
+pub mod weather;
+#[cfg(feature = "ratatui")]
+pub mod widget;
+#[cfg(feature = "window")]
+pub mod window;
+pub mod world;
@@ -0,0 +1,152 @@
+use std::f32::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{colour::Rgb, vec2::Vec2};
+
+// How far flickering lights dip, and how dim pulsing ones get at their dimmest, as a fraction
+// of full brightness.
+const FLICKER_DEPTH: f32 = 0.6;
+const PULSE_LOW: f32 = 0.2;
+
+// How a light's brightness changes over time. Each repeats every `period` seconds, apart from
+// flickering, which never quite repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Animation {
+    #[default]
+    Steady,
+    // Wavers unevenly like a torch.
+    Flicker,
+    // Fades smoothly down and back up.
+    Pulse,
+    // Snaps fully on for the first half of each period and off for the second.
+    Strobe,
+}
+
+// A point of light that brightens and tints whatever is within `radius` of it, fading to
+// nothing at the edge. A red light makes white walls near it glow red.
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+    pub position: Vec2,
+    pub colour: Rgb,
+    pub radius: f32,
+    pub intensity: f32,
+    pub animation: Animation,
+    pub period: f32,
+    // How much of its intensity the light is giving off right now, set by `animate`.
+    pub level: f32,
+}
+
+impl Light {
+    pub fn new(position: Vec2, colour: Rgb, radius: f32) -> Self {
+        Self {
+            position,
+            colour,
+            radius,
+            intensity: 1.0,
+            animation: Animation::Steady,
+            period: 1.0,
+            level: 1.0,
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_animation(mut self, animation: Animation, period: f32) -> Self {
+        self.animation = animation;
+        self.period = period;
+        self
+    }
+
+    // Sets how bright the light is `clock` seconds into the level. Lights are put out of step
+    // with each other by where they are, so a row of them doesn't move in unison.
+    pub fn animate(&mut self, clock: f32) {
+        let offset = (self.position.x * 12.9898 + self.position.y * 78.233).sin() * 43.758;
+        let period = self.period.max(f32::EPSILON);
+        let time = clock + offset.rem_euclid(period);
+        let phase = (time / period).fract();
+        self.level = match self.animation {
+            Animation::Steady => 1.0,
+            Animation::Flicker => {
+                // Sines at unrelated rates only rarely line up, which looks random enough.
+                let noise = (time * 11.0).sin() * (time * 17.3 + 1.3).sin() * (time * 5.7).sin();
+                1.0 - FLICKER_DEPTH * noise.abs()
+            }
+            Animation::Pulse => PULSE_LOW + (1.0 - PULSE_LOW) * (0.5 + 0.5 * (phase * TAU).cos()),
+            Animation::Strobe => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        };
+    }
+
+    // How much the light adds at `point`, on a wall facing `normal` or on something that
+    // faces every way, like a floor or a sprite.
+    pub fn brightness(&self, point: Vec2, normal: Option<Vec2>) -> Brightness {
+        let to_light = self.position - point;
+        let distance = to_light.length();
+        if distance >= self.radius {
+            return Brightness::uniform(0.0);
+        }
+        // Walls facing the light get all of it and ones side on get none.
+        let facing = match normal {
+            Some(normal) if distance > f32::EPSILON => {
+                normal.dot(to_light * (1.0 / distance)).max(0.0)
+            }
+            _ => 1.0,
+        };
+        let falloff = (1.0 - distance / self.radius).powi(2);
+        let amount = self.intensity * self.level * falloff * facing / 255.0;
+        Brightness {
+            r: self.colour.r as f32 * amount,
+            g: self.colour.g as f32 * amount,
+            b: self.colour.b as f32 * amount,
+        }
+    }
+}
+
+// How much each of red, green and blue is scaled by where something is lit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Brightness {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl Brightness {
+    pub const fn uniform(level: f32) -> Self {
+        Self {
+            r: level,
+            g: level,
+            b: level,
+        }
+    }
+
+    pub fn apply(self, colour: Rgb) -> Rgb {
+        let channel = |value: u8, scale: f32| (value as f32 * scale.max(0.0)).min(255.0) as u8;
+        Rgb::new(
+            channel(colour.r, self.r),
+            channel(colour.g, self.g),
+            channel(colour.b, self.b),
+        )
+    }
+}
+
+// The light at `point`: `ambient` everywhere, plus whatever nearby lights add.
+pub fn brightness(lights: &[Light], ambient: f32, point: Vec2, normal: Option<Vec2>) -> Brightness {
+    lights
+        .iter()
+        .map(|light| light.brightness(point, normal))
+        .fold(Brightness::uniform(ambient), |total, light| Brightness {
+            r: total.r + light.r,
+            g: total.g + light.g,
+            b: total.b + light.b,
+        })
+}
@@ -1,251 +1,192 @@
 use std::{
-    f32::consts::PI,
-    io::{stdout, Write},
+    fs::{self, File},
+    path::PathBuf,
+    sync::Mutex,
 };
 
-use anyhow::Result;
-use crossterm::{
-    cursor::{Hide, MoveDown, MoveLeft, MoveTo, Show},
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseEventKind},
-    execute, queue,
-    style::{Color, Print, ResetColor, SetBackgroundColor},
-    terminal::{
-        disable_raw_mode, enable_raw_mode, size, Clear, ClearType, DisableLineWrap, EnableLineWrap,
-    },
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use rhywbeth::{
+    bench,
+    edit::{self, EditArgs},
+    game::Level,
+    join::{self, JoinArgs},
+    maze::MazeSize,
+    play::{self, HostArgs, PlayArgs},
+    playback::{self, PlaybackArgs},
+    screenshot::Resolution,
+    sketch,
 };
-
-const SPEED: f32 = 0.25;
-
-pub struct LineSegment {
-    pub slope: f32,
-    pub intercept: f32,
-    pub start: (f32, f32),
-    pub end: (f32, f32),
-    pub colour: Color,
+use tracing_subscriber::fmt::format::FmtSpan;
+
+#[derive(Parser)]
+#[command(version, about = "A raycaster for your terminal")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    // Running without a subcommand is the same as `play`.
+    #[command(flatten)]
+    play: PlayArgs,
+    #[arg(
+        long,
+        global = true,
+        help = "Log file to use instead of the one in the data directory"
+    )]
+    log: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        default_value = "warn",
+        help = "Least severe messages to log: error, warn, info, debug or trace. info and more verbose levels also time every frame"
+    )]
+    log_level: tracing::Level,
 }
 
-impl LineSegment {
-    pub fn from_points(start: (f32, f32), end: (f32, f32), colour: Color) -> Self {
-        let slope = (end.1 - start.1) / (end.0 - start.0);
-        Self {
-            slope,
-            intercept: -slope * start.0 + start.1,
-            start,
-            end,
-            colour,
-        }
-    }
-
-    pub fn ray(start: (f32, f32), angle: f32) -> Self {
-        let slope = angle.tan();
-        let end = (start.0 + 15.0 * angle.cos(), start.1 + 15.0 * angle.sin());
-        Self {
-            slope,
-            intercept: -slope * start.0 + start.1,
-            start,
-            end,
-            colour: Color::White,
-        }
-    }
-
-    pub fn intersects(&self, other: &Self) -> Option<(f32, f32)> {
-        if other.slope.is_infinite() {
-            if self.slope.is_infinite() {
-                if self.start.0 == other.start.0 {
-                    return Some((self.start.0, 0.0)); // same line
-                } else {
-                    return None;
-                }
-            }
-            if between(other.start.0, self.start.0, self.end.0)
-                && between(self.find_y(other.start.0), other.start.1, other.end.1)
-            {
-                return Some((other.start.0, self.find_y(other.start.0)));
-            }
-            return None;
-        }
-        if self.slope.is_infinite() {
-            return other.intersects(self);
-        }
-        let intersection = (other.intercept - self.intercept) / (self.slope - other.slope);
-        return (between(intersection, self.start.0, self.end.0)
-            && between(intersection, other.start.0, other.end.0))
-        .then(|| (intersection, self.find_y(intersection)));
-    }
-
-    pub fn find_y(&self, x: f32) -> f32 {
-        self.slope * x + self.intercept
-    }
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Play a map, or the built-in demo without one")]
+    Play(PlayArgs),
+    #[command(about = "Replay a demo recorded with play --record")]
+    Playback(PlaybackArgs),
+    #[command(about = "Time drawing frames of a large maze or a map")]
+    Bench(BenchArgs),
+    #[command(about = "Play a map that others can join over the network")]
+    Host(HostArgs),
+    #[command(about = "Join a game someone is hosting")]
+    Join(JoinArgs),
+    #[command(about = "Edit a map from above")]
+    Edit(EditArgs),
+    #[command(about = "Turn a picture into a map, with its dark pixels as walls")]
+    FromImage(FromImageArgs),
 }
 
-pub fn between(x: f32, a: f32, b: f32) -> bool {
-    if a < b {
-        (a..=b).contains(&x)
-    } else {
-        (b..=a).contains(&x)
-    }
+#[derive(Args)]
+struct FromImageArgs {
+    #[arg(help = "PNG or GIF to turn into a map, e.g. a sketch of the floor plan")]
+    image: PathBuf,
+    #[arg(
+        short,
+        long,
+        help = "TOML or JSON map to write, named after the image by default"
+    )]
+    output: Option<PathBuf>,
+    #[arg(long, default_value_t = 1, help = "Pixels across each grid cell")]
+    cell: u32,
+    #[arg(
+        long,
+        default_value_t = sketch::DARK,
+        help = "How dark cells have to be to be walls, from 0 for black to 1 for white"
+    )]
+    dark: f32,
+    #[arg(long, help = "Write over the map if it already exists")]
+    force: bool,
 }
 
-pub fn get_distance(point_a: (f32, f32), point_b: (f32, f32)) -> f32 {
-    ((point_b.1 - point_a.1).powf(2.0) + (point_b.0 - point_a.0).powf(2.0)).sqrt()
+#[derive(Args)]
+struct BenchArgs {
+    #[arg(help = "TOML or JSON map to draw instead of a generated maze")]
+    map: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        requires = "map",
+        help = "Draw a level like E1M1 or MAP01 from the Doom WAD given as the map"
+    )]
+    import_wad: Option<String>,
+    #[arg(
+        long,
+        value_name = "WxH",
+        default_value = "80x80",
+        conflicts_with = "map",
+        help = "Size of the generated maze"
+    )]
+    maze: MazeSize,
+    #[arg(long, default_value_t = 500, help = "Frames to draw")]
+    frames: usize,
+    #[arg(
+        long,
+        value_name = "WxH",
+        default_value = "320x180",
+        help = "Pixels to draw per frame"
+    )]
+    resolution: Resolution,
 }
 
-fn exit_raw_mode() -> Result<()> {
-    execute!(
-        stdout(),
-        DisableMouseCapture,
-        ResetColor,
-        Clear(ClearType::All),
-        Show,
-        EnableLineWrap
-    )
-    .unwrap();
-    disable_raw_mode()?;
+// Logs go to a file, since anything written to the terminal would end up on top of the game.
+fn init_logging(path: Option<PathBuf>, level: tracing::Level) -> Result<()> {
+    let path = path
+        .or_else(|| dirs::data_dir().map(|dir| dir.join("rhywbeth").join("rhywbeth.log")))
+        .unwrap_or_else(|| PathBuf::from("rhywbeth.log"));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Could not create {}", parent.display()))?;
+    }
+    let file = File::options()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Could not open log {}", path.display()))?;
+    tracing_subscriber::fmt()
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
     Ok(())
 }
 
-fn render(
-    size: (u16, u16),
-    position: (f32, f32),
-    rotation: &mut f32,
-    segments: &Vec<LineSegment>,
-) -> Result<()> {
-    if *rotation < -PI {
-        *rotation = 2.0 * PI + *rotation;
-    } else if *rotation > PI {
-        *rotation = *rotation - 2.0 * PI;
-    }
-    for y in 0..=size.1 {
-        queue!(stdout(), MoveTo(0, y))?;
-        if y > size.1 / 2 {
-            for _ in 0..size.0 {
-                queue!(stdout(), SetBackgroundColor(Color::Blue), Print(" "))?;
-            }
-        } else {
-            for _ in 0..size.0 {
-                queue!(stdout(), SetBackgroundColor(Color::Red), Print(" "))?;
-            }
-        }
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    init_logging(cli.log, cli.log_level)?;
+    match cli.command {
+        Some(Command::Play(args)) => play::run(args, None),
+        Some(Command::Playback(args)) => playback::run(args),
+        Some(Command::Bench(args)) => bench(args),
+        Some(Command::Host(args)) => play::host(args),
+        Some(Command::Join(args)) => join::run(args),
+        Some(Command::Edit(args)) => edit::run(args),
+        Some(Command::FromImage(args)) => from_image(args),
+        None => play::run(cli.play, None),
     }
-    let d_theta = 0.5 * PI / size.0 as f32;
-    for x in 0..size.0 {
-        let ray = LineSegment::ray(position, *rotation - (x as f32 * d_theta));
-        let mut distance: Option<f32> = None;
-        let mut colour = Color::White;
-        for segment in segments.iter() {
-            if let Some(point) = segment.intersects(&ray) {
-                let new_distance = get_distance(position, point);
-                if distance.is_none() || distance > Some(new_distance) {
-                    distance = Some(new_distance);
-                    colour = segment.colour;
-                }
-            }
-        }
-        if let Some(distance) = distance {
-            let height = if distance > 5.0 {
-                (size.1 as f32 * (1.0 - ((distance - 5.0) * 0.1))).round() as u16
-            } else {
-                size.1
-            };
+}
 
-            let padding = (size.1 - height) / 2;
-            queue!(stdout(), MoveTo(x, padding))?;
-            for _ in 0..height {
-                queue!(
-                    stdout(),
-                    SetBackgroundColor(colour),
-                    Print(" "),
-                    MoveDown(1),
-                    MoveLeft(1),
-                )?;
-            }
-        }
+fn from_image(args: FromImageArgs) -> Result<()> {
+    let output = args
+        .output
+        .unwrap_or_else(|| args.image.with_extension("toml"));
+    if output.exists() && !args.force {
+        bail!(
+            "{} already exists, use --force to write over it",
+            output.display()
+        );
     }
-    queue!(
-        stdout(),
-        MoveTo(0, 0),
-        Print(format!(
-            "x: {}, y: {}, rot: {}",
-            position.0, position.1, rotation
-        ))
-    )?;
-    stdout().flush()?;
+    let image = image::open(&args.image)
+        .with_context(|| format!("Could not read image {}", args.image.display()))?
+        .into_rgba8();
+    let map = sketch::from_image(&image, args.cell, args.dark)?;
+    map.write(&output)?;
+    println!("Wrote {}", output.display());
     Ok(())
 }
 
-fn main() -> Result<()> {
-    let hook = std::panic::take_hook();
-    std::panic::set_hook(Box::new(move |p| {
-        exit_raw_mode().unwrap();
-        hook(p);
-    }));
-    enable_raw_mode().unwrap();
-    execute!(stdout(), EnableMouseCapture, Hide, DisableLineWrap).unwrap();
-
-    let segments = vec![
-        LineSegment::from_points((6.0, 6.0), (4.0, 8.0), Color::Black),
-        LineSegment::from_points((4.0, 8.0), (7.0, 10.0), Color::Magenta),
-        LineSegment::from_points((7.0, 10.0), (6.0, 6.0), Color::Green),
-        LineSegment::from_points((2.0, 6.1), (-2.0, 6.0), Color::White),
-        LineSegment::from_points((-2.0, 6.0), (-2.0, 10.0), Color::Magenta),
-        LineSegment::from_points((-2.0, 10.0), (2.0, 10.0), Color::Green),
-        LineSegment::from_points((2.0, 10.0), (2.0, 6.0), Color::Yellow),
-    ];
-    let mut position = (0.0, 0.0);
-    let mut rotation = 3.0 * PI / 4.0;
-    let mut last_mouse_position = None;
-
-    loop {
-        let size = size()?;
-        match event::read().unwrap() {
-            Event::Mouse(evt) => match evt.kind {
-                MouseEventKind::Down(_) => {
-                    last_mouse_position = Some(evt.column);
-                }
-                MouseEventKind::Drag(_) => {
-                    render(size, position, &mut rotation, &segments)?;
-                    if let Some(pos) = last_mouse_position {
-                        rotation -= (evt.column as i32 - pos as i32) as f32 * 0.01;
-                    }
-                    last_mouse_position = Some(evt.column);
-                }
-                _ => {}
-            },
-            Event::Key(key) => match key.code {
-                KeyCode::Char('q') => break,
-                KeyCode::Char('w') => {
-                    position.0 += (rotation - PI / 4.0).cos() * SPEED;
-                    position.1 += (rotation - PI / 4.0).sin() * SPEED;
-                    render(size, position, &mut rotation, &segments)?;
-                }
-                KeyCode::Char('s') => {
-                    position.0 -= (rotation - PI / 4.0).cos() * SPEED;
-                    position.1 -= (rotation - PI / 4.0).sin() * SPEED;
-                    render(size, position, &mut rotation, &segments)?;
-                }
-                KeyCode::Char('a') => {
-                    position.0 += (rotation + PI / 4.0).cos() * SPEED;
-                    position.1 += (rotation + PI / 4.0).sin() * SPEED;
-                    render(size, position, &mut rotation, &segments)?;
-                }
-                KeyCode::Char('d') => {
-                    position.0 -= (rotation + PI / 4.0).cos() * SPEED;
-                    position.1 -= (rotation + PI / 4.0).sin() * SPEED;
-                    render(size, position, &mut rotation, &segments)?;
-                }
-                KeyCode::Char('h') => {
-                    rotation += 0.05;
-                    render(size, position, &mut rotation, &segments)?;
-                }
-                KeyCode::Char('l') => {
-                    rotation -= 0.05;
-                    render(size, position, &mut rotation, &segments)?;
-                }
-                _ => {}
-            },
-            _ => {}
-        }
+fn bench(args: BenchArgs) -> Result<()> {
+    if args.frames == 0 {
+        bail!("--frames has to be at least 1");
     }
-
-    exit_raw_mode()
+    let level = match (args.map, args.import_wad) {
+        (Some(path), Some(map)) => Level::wad(path, map)?,
+        (Some(path), None) => Level::file(path)?,
+        (None, _) => Level::Maze {
+            size: args.maze,
+            seed: 0,
+        },
+    };
+    let game = level.load()?;
+    println!("{}", level.name());
+    println!(
+        "{}",
+        bench::run(&game.world, game.camera, args.resolution, args.frames)
+    );
+    Ok(())
 }
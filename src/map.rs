@@ -0,0 +1,1020 @@
+use std::{
+    collections::HashMap,
+    f32::consts::{PI, TAU},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use tracing::warn;
+
+use crate::{
+    colour::Rgb,
+    daylight::DayCycle,
+    door::Door,
+    enemy::Enemy,
+    geometry::{Arc, LineSegment},
+    grid::{Grid, Tile},
+    light::{Animation, Light},
+    pickup::{KeyColour, Pickup},
+    render::Camera,
+    sector::Sector,
+    shade::Fog,
+    sound::AmbientSound,
+    sprite::Sprite,
+    switch::{Dormant, Switch, Wall},
+    texture::Texture,
+    tiled, tilemap,
+    trigger::{Exit, Shape, TriggerRegion},
+    vec2::Vec2,
+    weather::Weather,
+    world::{Sky, Surface, World},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Map {
+    #[serde(default)]
+    pub spawn: Spawn,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<Segment>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arcs: Vec<ArcMap>,
+    pub grid: Option<GridMap>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub textures: HashMap<String, TextureMap>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sprites: Vec<SpriteMap>,
+    #[serde(default)]
+    pub fog: Fog,
+    // Rain or snow falling everywhere.
+    pub weather: Option<Weather>,
+    // How bright everything is away from any lights, from 0.0 for pitch black to 1.0.
+    #[serde(default = "default_ambient")]
+    pub ambient: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<LightMap>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub doors: Vec<DoorMap>,
+    #[serde(default = "default_floor")]
+    pub floor: SurfaceMap,
+    #[serde(default = "default_ceiling")]
+    pub ceiling: SurfaceMap,
+    pub sky: Option<SkyMap>,
+    // Turns the ambient light and sky above into how they look at noon, fading to night and
+    // back over the day.
+    pub day: Option<DayMap>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sounds: Vec<AmbientSound>,
+    // An ogg or mp3 file to loop while the map is played, relative to the map file.
+    pub music: Option<PathBuf>,
+    pub exit: Option<Exit>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<TriggerRegion>,
+    // A Lua file with hooks run as the map is played, relative to the map file.
+    pub script: Option<PathBuf>,
+    // WebAssembly modules run as the map is played, relative to the map file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plugins: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enemies: Vec<EnemyMap>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pickups: Vec<PickupMap>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub switches: Vec<SwitchMap>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub teleporters: Vec<TeleporterMap>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub portals: Vec<PortalMap>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sectors: Vec<SectorMap>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spawn {
+    pub position: Vec2,
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+impl Default for Spawn {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            rotation: PI / 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Segment {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub colour: Rgb,
+    pub texture: Option<String>,
+    // How much of what's behind shows through, from 0.0 for nothing to 1.0 for a solid wall.
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    // Mirrors reflect whatever is in front of them.
+    #[serde(default)]
+    pub mirror: bool,
+    // Walls with an ID can be made to appear or disappear by switches. Hidden ones start out
+    // missing.
+    pub id: Option<String>,
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+// A light brightening everything within `radius` of `position`, facing it or not.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightMap {
+    pub position: Vec2,
+    #[serde(default = "default_light_colour")]
+    pub colour: Rgb,
+    #[serde(default = "default_light_radius")]
+    pub radius: f32,
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+    #[serde(default)]
+    pub animation: Animation,
+    // Seconds each pulse or strobe takes.
+    #[serde(default = "default_period")]
+    pub period: f32,
+}
+
+fn default_ambient() -> f32 {
+    1.0
+}
+
+fn default_light_colour() -> Rgb {
+    Rgb::WHITE
+}
+
+fn default_light_radius() -> f32 {
+    4.0
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+fn default_period() -> f32 {
+    1.0
+}
+
+// A curved wall around `centre`, running anticlockwise from the `start` angle to the `end`
+// one in radians, or all the way round without them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArcMap {
+    pub centre: Vec2,
+    pub radius: f32,
+    pub start: Option<f32>,
+    pub end: Option<f32>,
+    pub colour: Rgb,
+    pub texture: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoorMap {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub colour: Rgb,
+    pub texture: Option<String>,
+    // Seconds the door stays open before closing again.
+    #[serde(default = "default_open_time")]
+    pub open_time: f32,
+    // The colour of key the door needs to open.
+    pub lock: Option<KeyColour>,
+    pub id: Option<String>,
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+fn default_open_time() -> f32 {
+    3.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpriteMap {
+    pub position: Vec2,
+    pub colour: Rgb,
+    pub texture: Option<String>,
+    #[serde(default = "default_sprite_size")]
+    pub size: f32,
+}
+
+fn default_sprite_size() -> f32 {
+    0.5
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnemyMap {
+    pub position: Vec2,
+    pub colour: Rgb,
+    pub texture: Option<String>,
+    #[serde(default = "default_enemy_size")]
+    pub size: f32,
+    // Enemies with an ID wait hidden until a switch wakes them.
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub enemy: Enemy,
+}
+
+fn default_enemy_size() -> f32 {
+    0.8
+}
+
+// Pickups are drawn as small blocks coloured by what they are unless given a look of their own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickupMap {
+    pub position: Vec2,
+    #[serde(flatten)]
+    pub pickup: Pickup,
+    pub colour: Option<Rgb>,
+    pub texture: Option<String>,
+    pub size: Option<f32>,
+}
+
+// A switch on the wall from `start` to `end`, setting off everything with the IDs in `targets`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwitchMap {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub colour: Rgb,
+    pub texture: Option<String>,
+    pub targets: Vec<String>,
+    // Whether the switch stays on once used rather than flipping back and forth.
+    #[serde(default)]
+    pub once: bool,
+}
+
+// Two pads that each send the player to the other, drawn as small flat blocks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeleporterMap {
+    pub pads: [PadMap; 2],
+    #[serde(default = "default_teleporter_colour")]
+    pub colour: Rgb,
+    pub texture: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PadMap {
+    pub position: Vec2,
+    // Which way the player faces on arriving at the pad, or unchanged if not given.
+    pub facing: Option<f32>,
+}
+
+const TELEPORTER_SIZE: f32 = 0.3;
+
+fn default_teleporter_colour() -> Rgb {
+    Rgb::named("cyan").unwrap()
+}
+
+// Two segments that look and lead into each other. `colour` is only seen on the minimap, or
+// where a view through too many portals and mirrors gives up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortalMap {
+    pub sides: [SideMap; 2],
+    #[serde(default = "default_portal_colour")]
+    pub colour: Rgb,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SideMap {
+    pub start: Vec2,
+    pub end: Vec2,
+}
+
+fn default_portal_colour() -> Rgb {
+    Rgb::named("magenta").unwrap()
+}
+
+// A polygon of floor with its own heights, where the floor is normally at 0.0 and the ceiling
+// at 1.0. `colour` and `texture` are for the steps up into it and down from its ceiling, and
+// `floor` and `ceiling` replace the map's own inside it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectorMap {
+    pub points: Vec<Vec2>,
+    #[serde(default)]
+    pub floor_height: f32,
+    #[serde(default = "default_ceiling_height")]
+    pub ceiling_height: f32,
+    #[serde(default = "default_sector_colour")]
+    pub colour: Rgb,
+    pub texture: Option<String>,
+    pub floor: Option<SurfaceMap>,
+    pub ceiling: Option<SurfaceMap>,
+}
+
+fn default_ceiling_height() -> f32 {
+    1.0
+}
+
+fn default_sector_colour() -> Rgb {
+    Rgb::named("grey").unwrap()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SurfaceMap {
+    pub colour: Rgb,
+    pub texture: Option<String>,
+}
+
+impl SurfaceMap {
+    pub fn surface(&self, textures: &HashMap<&str, usize>) -> Result<Surface> {
+        let texture = self
+            .texture
+            .as_ref()
+            .map(|name| texture_index(textures, name))
+            .transpose()?;
+        Ok(Surface::new(self.colour).with_texture(texture))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkyMap {
+    pub top: Rgb,
+    pub bottom: Rgb,
+    pub texture: Option<String>,
+}
+
+impl SkyMap {
+    pub fn sky(&self, textures: &HashMap<&str, usize>) -> Result<Sky> {
+        let texture = self
+            .texture
+            .as_ref()
+            .map(|name| texture_index(textures, name))
+            .transpose()?;
+        Ok(Sky {
+            top: self.top,
+            bottom: self.bottom,
+            texture,
+            brightness: 1.0,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayMap {
+    // Seconds a whole day and night takes.
+    #[serde(default = "default_day_length")]
+    pub length: f32,
+    // Hours after midnight the level starts at, from 0.0 up to 24.0.
+    #[serde(default = "default_day_start")]
+    pub start: f32,
+    #[serde(default = "default_night_ambient")]
+    pub night_ambient: f32,
+    #[serde(default = "default_night_top")]
+    pub night_top: Rgb,
+    #[serde(default = "default_night_bottom")]
+    pub night_bottom: Rgb,
+}
+
+impl DayMap {
+    pub fn day_cycle(&self, ambient: f32, sky: Option<Sky>) -> DayCycle {
+        DayCycle {
+            length: self.length,
+            start: self.start,
+            day_ambient: ambient,
+            night_ambient: self.night_ambient,
+            day_sky: sky,
+            night_top: self.night_top,
+            night_bottom: self.night_bottom,
+        }
+    }
+}
+
+fn default_day_length() -> f32 {
+    600.0
+}
+
+fn default_day_start() -> f32 {
+    12.0
+}
+
+fn default_night_ambient() -> f32 {
+    0.2
+}
+
+fn default_night_top() -> Rgb {
+    Rgb::new(0, 0, 16)
+}
+
+fn default_night_bottom() -> Rgb {
+    Rgb::new(16, 16, 48)
+}
+
+fn default_floor() -> SurfaceMap {
+    SurfaceMap {
+        colour: Rgb::BLUE,
+        texture: None,
+    }
+}
+
+fn default_ceiling() -> SurfaceMap {
+    SurfaceMap {
+        colour: Rgb::RED,
+        texture: None,
+    }
+}
+
+impl Default for Map {
+    fn default() -> Self {
+        Self {
+            spawn: Spawn::default(),
+            segments: Vec::new(),
+            arcs: Vec::new(),
+            grid: None,
+            textures: HashMap::new(),
+            sprites: Vec::new(),
+            fog: Fog::default(),
+            weather: None,
+            ambient: default_ambient(),
+            lights: Vec::new(),
+            doors: Vec::new(),
+            floor: default_floor(),
+            ceiling: default_ceiling(),
+            sky: None,
+            day: None,
+            sounds: Vec::new(),
+            music: None,
+            exit: None,
+            triggers: Vec::new(),
+            script: None,
+            plugins: Vec::new(),
+            enemies: Vec::new(),
+            pickups: Vec::new(),
+            switches: Vec::new(),
+            teleporters: Vec::new(),
+            portals: Vec::new(),
+            sectors: Vec::new(),
+        }
+    }
+}
+
+// Rows of tile characters, where every character that appears in the palette is a solid cell
+// and anything else is empty floor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridMap {
+    pub tiles: Vec<String>,
+    pub palette: HashMap<char, Rgb>,
+    // Optional texture names for palette characters, sampled instead of the flat colour.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub textures: HashMap<char, String>,
+}
+
+impl GridMap {
+    pub fn grid(&self, textures: &HashMap<&str, usize>) -> Result<Grid> {
+        let width = self
+            .tiles
+            .iter()
+            .map(|r| r.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut grid = Grid::new(width, self.tiles.len());
+        for (y, row) in self.tiles.iter().enumerate() {
+            for (x, tile) in row.chars().enumerate() {
+                if let Some(&colour) = self.palette.get(&tile) {
+                    let texture = self
+                        .textures
+                        .get(&tile)
+                        .map(|name| texture_index(textures, name))
+                        .transpose()?;
+                    grid.set(x, y, Some(Tile { colour, texture }));
+                }
+            }
+        }
+        Ok(grid)
+    }
+}
+
+// A small image written as rows of characters, each of which is looked up in the palette.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextureMap {
+    #[serde(default)]
+    pub rows: Vec<String>,
+    pub palette: HashMap<char, Texel>,
+    // Animated textures give a set of rows for each frame instead of `rows`, shown in turn for
+    // `frame_time` seconds each.
+    #[serde(default)]
+    pub frames: Vec<Vec<String>>,
+    #[serde(default = "default_frame_time")]
+    pub frame_time: f32,
+}
+
+fn default_frame_time() -> f32 {
+    0.25
+}
+
+// A palette colour, or "transparent" for texels that let whatever is behind show through.
+#[derive(Debug, Clone, Copy)]
+pub struct Texel(pub Option<Rgb>);
+
+impl Serialize for Texel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Some(colour) => colour.serialize(serializer),
+            None => serializer.serialize_str("transparent"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Texel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Text(String),
+            Channels(Rgb),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Text(text) if text == "transparent" => Ok(Self(None)),
+            Repr::Text(text) => text
+                .parse()
+                .map(|c| Self(Some(c)))
+                .map_err(de::Error::custom),
+            Repr::Channels(colour) => Ok(Self(Some(colour))),
+        }
+    }
+}
+
+impl TextureMap {
+    pub fn texture(&self, name: &str) -> Result<Texture> {
+        let frames = match (self.rows.is_empty(), self.frames.is_empty()) {
+            (_, true) => std::slice::from_ref(&self.rows),
+            (true, false) => self.frames.as_slice(),
+            (false, false) => bail!("Texture {name} has both rows and frames"),
+        };
+        let width = frames
+            .iter()
+            .flatten()
+            .map(|r| r.chars().count())
+            .max()
+            .unwrap_or(0);
+        if width == 0 {
+            bail!("Texture {name} is empty");
+        }
+        let height = frames[0].len();
+        if frames.iter().any(|f| f.len() != height) {
+            bail!("Texture {name} has frames of different heights");
+        }
+        let mut texels = Vec::with_capacity(width * height * frames.len());
+        for row in frames.iter().flatten() {
+            for texel in row.chars().chain(std::iter::repeat(' ')).take(width) {
+                texels.push(self.palette.get(&texel).map(|t| t.0).ok_or_else(|| {
+                    anyhow!("Texture {name} uses {texel:?} which is not in its palette")
+                })?);
+            }
+        }
+        Ok(Texture::new(width, height, texels).with_frame_time(self.frame_time))
+    }
+}
+
+fn texture_index(textures: &HashMap<&str, usize>, name: &str) -> Result<usize> {
+    textures
+        .get(name)
+        .copied()
+        .ok_or_else(|| anyhow!("Unknown texture {name}"))
+}
+
+impl Map {
+    // Reads a map to play, with the files it names found relative to it.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut map = Self::read(path)?;
+        if let Some(directory) = path.parent() {
+            map.relative_to(directory);
+        }
+        Ok(map)
+    }
+
+    // Points the files the map names, which are written relative to it, into `directory`.
+    pub fn relative_to(&mut self, directory: &Path) {
+        let next = self.exit.as_mut().and_then(|e| e.next.as_mut());
+        let paths = self.music.iter_mut().chain(self.script.iter_mut());
+        let paths = paths.chain(self.plugins.iter_mut());
+        for path in paths.chain(next) {
+            *path = directory.join(&*path);
+        }
+    }
+
+    // Reads a map just as it's written, with the files it names left relative to it.
+    pub fn read(path: &Path) -> Result<Self> {
+        let extension = path.extension().and_then(|e| e.to_str());
+        // Maps made for other things are read by their own importers.
+        match extension {
+            Some("tmx" | "tmj") => return tiled::import(path),
+            Some("csv" | "bin") => return tilemap::import(path),
+            _ => {}
+        }
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Could not read map {}", path.display()))?;
+        match extension {
+            Some("toml") => toml::from_str(&source)
+                .with_context(|| format!("Could not parse map {}", path.display())),
+            Some("json") => serde_json::from_str(&source)
+                .with_context(|| format!("Could not parse map {}", path.display())),
+            _ => bail!("Unsupported map format {}", path.display()),
+        }
+    }
+
+    // Writes the map back out in the format its extension asks for.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let source = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::to_string(self)?,
+            Some("json") => serde_json::to_string_pretty(self)?,
+            _ => bail!("Unsupported map format {}", path.display()),
+        };
+        fs::write(path, source).with_context(|| format!("Could not write map {}", path.display()))
+    }
+
+    pub fn camera(&self) -> Camera {
+        Camera::new(self.spawn.position, self.spawn.rotation)
+    }
+
+    pub fn world(&self) -> Result<World> {
+        let ends = self.segments.iter().map(|s| (s.start, s.end));
+        let doors = self.doors.iter().map(|d| (d.start, d.end));
+        let switches = self.switches.iter().map(|s| (s.start, s.end));
+        for (start, end) in ends.chain(doors).chain(switches) {
+            if start.distance(end) <= f32::EPSILON {
+                warn!("Wall at {start:?} has no length and can't be seen");
+            }
+        }
+        for arc in self.arcs.iter() {
+            if arc.radius <= 0.0 {
+                warn!("Arc at {:?} has no radius and can't be seen", arc.centre);
+            }
+        }
+        for [a, b] in self.portals.iter().map(|p| &p.sides) {
+            if (a.start.distance(a.end) - b.start.distance(b.end)).abs() > 1e-3 {
+                warn!(
+                    "Portal at {:?} is a different length to its partner",
+                    a.start
+                );
+            }
+        }
+        for (i, sector) in self.sectors.iter().enumerate() {
+            if sector.points.len() < 3 {
+                bail!("Sector {} needs at least three points", i + 1);
+            }
+            if sector.ceiling_height <= sector.floor_height {
+                warn!(
+                    "Sector at {:?} has its ceiling at or below its floor",
+                    sector.points[0]
+                );
+            }
+        }
+        for (i, trigger) in self.triggers.iter().enumerate() {
+            if let Shape::Polygon { points } = &trigger.shape {
+                if points.len() < 3 {
+                    bail!("Trigger region {} needs at least three points", i + 1);
+                }
+            }
+            if trigger.interval <= 0.0 {
+                bail!("Trigger region {} needs an interval above zero", i + 1);
+            }
+        }
+        if let Some(day) = &self.day {
+            if day.length <= 0.0 {
+                bail!("A day needs to last longer than {} seconds", day.length);
+            }
+        }
+        let mut indices = HashMap::new();
+        let mut textures = Vec::new();
+        for (name, texture) in self.textures.iter() {
+            indices.insert(name.as_str(), textures.len());
+            textures.push(texture.texture(name)?);
+        }
+        let mut segments = Vec::new();
+        let mut walls = Vec::new();
+        for s in self.segments.iter() {
+            let texture = s
+                .texture
+                .as_ref()
+                .map(|name| texture_index(&indices, name))
+                .transpose()?;
+            let segment = LineSegment::from_points(s.start, s.end, s.colour)
+                .with_texture(texture)
+                .with_opacity(s.opacity)
+                .with_mirror(s.mirror);
+            match &s.id {
+                Some(id) => walls.push(Wall {
+                    id: id.clone(),
+                    segment,
+                    solid: !s.hidden,
+                }),
+                None => segments.push(segment),
+            }
+        }
+        let arcs = self
+            .arcs
+            .iter()
+            .map(|a| {
+                let texture = a
+                    .texture
+                    .as_ref()
+                    .map(|name| texture_index(&indices, name))
+                    .transpose()?;
+                let arc = Arc::circle(a.centre, a.radius, a.colour).with_texture(texture);
+                Ok(match (a.start, a.end) {
+                    (None, None) => arc,
+                    (start, end) => arc.with_span(start.unwrap_or(0.0), end.unwrap_or(TAU)),
+                })
+            })
+            .collect::<Result<_>>()?;
+        let lights = self
+            .lights
+            .iter()
+            .map(|l| {
+                Light::new(l.position, l.colour, l.radius)
+                    .with_intensity(l.intensity)
+                    .with_animation(l.animation, l.period)
+            })
+            .collect();
+        let sprites = self
+            .sprites
+            .iter()
+            .map(|s| {
+                let texture = s
+                    .texture
+                    .as_ref()
+                    .map(|name| texture_index(&indices, name))
+                    .transpose()?;
+                Ok((
+                    s.position,
+                    Sprite::new(s.colour, s.size).with_texture(texture),
+                ))
+            })
+            .collect::<Result<_>>()?;
+        let doors = self
+            .doors
+            .iter()
+            .map(|d| {
+                let texture = d
+                    .texture
+                    .as_ref()
+                    .map(|name| texture_index(&indices, name))
+                    .transpose()?;
+                Ok(Door::new(d.start, d.end, d.colour, d.open_time)
+                    .with_texture(texture)
+                    .with_lock(d.lock)
+                    .with_id(d.id.clone()))
+            })
+            .collect::<Result<_>>()?;
+        let mut enemies = Vec::new();
+        let mut dormant = Vec::new();
+        for e in self.enemies.iter() {
+            let texture = e
+                .texture
+                .as_ref()
+                .map(|name| texture_index(&indices, name))
+                .transpose()?;
+            let sprite = Sprite::new(e.colour, e.size).with_texture(texture);
+            match &e.id {
+                Some(id) => dormant.push((
+                    e.position,
+                    Dormant {
+                        id: id.clone(),
+                        sprite,
+                        enemy: e.enemy,
+                    },
+                )),
+                None => enemies.push((e.position, sprite, e.enemy)),
+            }
+        }
+        let switches = self
+            .switches
+            .iter()
+            .map(|s| {
+                let texture = s
+                    .texture
+                    .as_ref()
+                    .map(|name| texture_index(&indices, name))
+                    .transpose()?;
+                Ok(Switch::new(s.start, s.end, s.colour, s.targets.clone())
+                    .with_texture(texture)
+                    .with_once(s.once))
+            })
+            .collect::<Result<_>>()?;
+        let pickups = self
+            .pickups
+            .iter()
+            .map(|p| {
+                let texture = p
+                    .texture
+                    .as_ref()
+                    .map(|name| texture_index(&indices, name))
+                    .transpose()?;
+                let mut sprite = p.pickup.sprite().with_texture(texture);
+                sprite.colour = p.colour.unwrap_or(sprite.colour);
+                sprite.size = p.size.unwrap_or(sprite.size);
+                Ok((p.position, sprite, p.pickup.clone()))
+            })
+            .collect::<Result<_>>()?;
+        let teleporters = self
+            .teleporters
+            .iter()
+            .map(|t| {
+                let texture = t
+                    .texture
+                    .as_ref()
+                    .map(|name| texture_index(&indices, name))
+                    .transpose()?;
+                Ok(t.pads.clone().map(|pad| {
+                    let sprite = Sprite::new(t.colour, TELEPORTER_SIZE).with_texture(texture);
+                    (pad.position, sprite, pad.facing)
+                }))
+            })
+            .collect::<Result<_>>()?;
+        let portals = self
+            .portals
+            .iter()
+            .map(|p| {
+                p.sides
+                    .clone()
+                    .map(|side| LineSegment::from_points(side.start, side.end, p.colour))
+            })
+            .collect();
+        let sectors = self
+            .sectors
+            .iter()
+            .map(|s| {
+                let texture = s
+                    .texture
+                    .as_ref()
+                    .map(|name| texture_index(&indices, name))
+                    .transpose()?;
+                let surface = |surface: &Option<SurfaceMap>| {
+                    surface.as_ref().map(|s| s.surface(&indices)).transpose()
+                };
+                Ok(Sector {
+                    points: s.points.clone(),
+                    floor_height: s.floor_height,
+                    ceiling_height: s.ceiling_height,
+                    colour: s.colour,
+                    texture,
+                    floor: surface(&s.floor)?,
+                    ceiling: surface(&s.ceiling)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let sky = self.sky.as_ref().map(|s| s.sky(&indices)).transpose()?;
+        let world = World::new(segments)
+            .with_arcs(arcs)
+            .with_doors(doors)
+            .with_textures(textures)
+            .with_sprites(sprites)
+            .with_enemies(enemies)
+            .with_dormant(dormant)
+            .with_walls(walls)
+            .with_switches(switches)
+            .with_teleporters(teleporters)
+            .with_portals(portals)
+            .with_sectors(sectors)
+            .with_pickups(pickups)
+            .with_fog(self.fog)
+            .with_weather(self.weather)
+            .with_lights(self.ambient, lights)
+            .with_surfaces(
+                self.floor.surface(&indices)?,
+                self.ceiling.surface(&indices)?,
+            )
+            .with_sky(sky)
+            .with_day_cycle(self.day.as_ref().map(|d| d.day_cycle(self.ambient, sky)))
+            .with_sounds(self.sounds.clone())
+            .with_music(self.music.clone())
+            .with_exit(self.exit.clone())
+            .with_triggers(self.triggers.clone())
+            .with_script(self.script.clone())
+            .with_plugins(self.plugins.clone());
+        Ok(match &self.grid {
+            Some(grid) => world.with_grid(grid.grid(&indices)?),
+            None => world,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+
+    fn shipped(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("maps")
+            .join(name)
+    }
+
+    #[test]
+    fn parses_toml_maps() {
+        let source = r#"
+            [spawn]
+            position = [1.5, 2.5]
+
+            [[segments]]
+            start = [0.0, 0.0]
+            end = [4.0, 0.0]
+            colour = "red"
+        "#;
+        let map: Map = toml::from_str(source).unwrap();
+        assert_eq!(map.spawn.position, Vec2::new(1.5, 2.5));
+        assert_eq!(map.spawn.rotation, 0.0);
+        assert_eq!(map.segments.len(), 1);
+        assert_eq!(map.segments[0].end, Vec2::new(4.0, 0.0));
+        assert_eq!(map.segments[0].colour, Rgb::RED);
+        assert_eq!(map.world().unwrap().segments.len(), 1);
+    }
+
+    #[test]
+    fn parses_json_maps() {
+        let source = r#"{
+            "segments": [{ "start": [0.0, 0.0], "end": [0.0, 3.0], "colour": "blue" }]
+        }"#;
+        let map: Map = serde_json::from_str(source).unwrap();
+        // Maps without a spawn start where the demo does.
+        assert_eq!(map.spawn.position, Vec2::ZERO);
+        assert_eq!(map.spawn.rotation, Spawn::default().rotation);
+        assert_eq!(map.segments[0].colour, Rgb::BLUE);
+    }
+
+    #[test]
+    fn reads_shipped_maps() {
+        assert_eq!(
+            Map::from_path(shipped("box.json")).unwrap().segments.len(),
+            4
+        );
+        assert!(!Map::from_path(shipped("demo.toml"))
+            .unwrap()
+            .segments
+            .is_empty());
+        assert!(Map::from_path(shipped("missing.toml")).is_err());
+        assert!(Map::from_path(shipped("box.txt")).is_err());
+        assert!(toml::from_str::<Map>("[spawn]\nposition = \"here\"").is_err());
+    }
+
+    #[test]
+    fn parses_grids() {
+        let source = r##"
+            [grid]
+            tiles = ["#.", ".#", "#"]
+
+            [grid.palette]
+            "#" = "blue"
+        "##;
+        let map: Map = toml::from_str(source).unwrap();
+        let grid = map.world().unwrap().grid.unwrap();
+        assert_eq!((grid.width, grid.height), (2, 3));
+        // Each row written is the one at the next y up.
+        assert_eq!(grid.get(0, 0).map(|tile| tile.colour), Some(Rgb::BLUE));
+        assert_eq!(grid.get(1, 1).map(|tile| tile.colour), Some(Rgb::BLUE));
+        assert!(grid.get(1, 0).is_none());
+        assert!(grid.get(1, 2).is_none());
+    }
+
+    fn value(map: &Map) -> serde_json::Value {
+        serde_json::to_value(map).unwrap()
+    }
+
+    #[test]
+    fn maps_round_trip_through_toml_and_json() {
+        let directory = scratch("round-trip");
+        for name in ["demo.toml", "grid.toml", "box.json"] {
+            let map = Map::read(&shipped(name)).unwrap();
+            for extension in ["toml", "json"] {
+                let path = directory.join(format!("copy.{extension}"));
+                map.write(&path).unwrap();
+                let copy = Map::read(&path).unwrap();
+                assert_eq!(value(&copy), value(&map), "{name} as {extension}");
+            }
+        }
+        fs::remove_dir_all(directory).unwrap();
+        assert!(Map::default().write(Path::new("map.txt")).is_err());
+    }
+
+    #[test]
+    fn files_are_found_beside_the_map() {
+        let map = Map::read(&shipped("grid.toml")).unwrap();
+        assert_eq!(map.script, Some(PathBuf::from("grid.lua")));
+        let map = Map::from_path(shipped("grid.toml")).unwrap();
+        assert_eq!(map.script, Some(shipped("grid.lua")));
+        assert_eq!(map.plugins, [shipped("grid.wat")]);
+        assert_eq!(map.exit.unwrap().next, Some(shipped("box.json")));
+    }
+
+    #[test]
+    fn shipped_maps_build_worlds() {
+        for name in [
+            "demo.toml",
+            "grid.toml",
+            "box.json",
+            "tiled.tmx",
+            "wolf.csv",
+        ] {
+            let map = Map::from_path(shipped(name)).unwrap();
+            assert!(map.world().is_ok(), "{name}");
+        }
+    }
+}
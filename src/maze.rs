@@ -0,0 +1,151 @@
+use std::{f32::consts::PI, str::FromStr};
+
+use anyhow::{anyhow, Error, Result};
+use rand::{rngs::StdRng, seq::IndexedRandom, RngExt, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    colour::Rgb,
+    map::{Map, Segment, Spawn},
+    vec2::Vec2,
+};
+
+// World units per maze cell.
+const CELL: f32 = 2.0;
+
+const PALETTE: [&str; 6] = [
+    "white",
+    "grey",
+    "dark_cyan",
+    "dark_green",
+    "dark_yellow",
+    "magenta",
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MazeSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FromStr for MazeSize {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (width, height) = s
+            .split_once('x')
+            .ok_or_else(|| anyhow!("Invalid maze size {s}, expected WIDTHxHEIGHT"))?;
+        let size = Self {
+            width: width.parse()?,
+            height: height.parse()?,
+        };
+        if size.width == 0 || size.height == 0 {
+            return Err(anyhow!("Maze size {s} must be at least 1x1"));
+        }
+        Ok(size)
+    }
+}
+
+// Carves a maze with a recursive backtracker and turns its remaining walls into segments,
+// merging runs of wall along the same line into one segment each.
+pub fn generate(size: MazeSize, seed: u64) -> Map {
+    let MazeSize { width, height } = size;
+    let mut rng = StdRng::seed_from_u64(seed);
+    // horizontal[y * width + x] is the wall above cell (x, y), vertical[y * (width + 1) + x]
+    // the wall to its left.
+    let mut horizontal = vec![true; width * (height + 1)];
+    let mut vertical = vec![true; (width + 1) * height];
+    let mut visited = vec![false; width * height];
+
+    let start = (rng.random_range(0..width), rng.random_range(0..height));
+    let mut stack = vec![start];
+    visited[start.1 * width + start.0] = true;
+    while let Some(&(x, y)) = stack.last() {
+        let mut neighbours = Vec::with_capacity(4);
+        if x > 0 && !visited[y * width + x - 1] {
+            neighbours.push((x - 1, y));
+        }
+        if x + 1 < width && !visited[y * width + x + 1] {
+            neighbours.push((x + 1, y));
+        }
+        if y > 0 && !visited[(y - 1) * width + x] {
+            neighbours.push((x, y - 1));
+        }
+        if y + 1 < height && !visited[(y + 1) * width + x] {
+            neighbours.push((x, y + 1));
+        }
+        let Some(&(nx, ny)) = neighbours.choose(&mut rng) else {
+            stack.pop();
+            continue;
+        };
+        if nx != x {
+            vertical[y * (width + 1) + x.max(nx)] = false;
+        } else {
+            horizontal[y.max(ny) * width + x] = false;
+        }
+        visited[ny * width + nx] = true;
+        stack.push((nx, ny));
+    }
+
+    let mut segments = Vec::new();
+    let mut colour = || Rgb::named(PALETTE.choose(&mut rng).unwrap()).unwrap();
+    for y in 0..=height {
+        for (from, to) in runs(&horizontal[y * width..(y + 1) * width]) {
+            segments.push(Segment {
+                start: Vec2::new(from as f32 * CELL, y as f32 * CELL),
+                end: Vec2::new(to as f32 * CELL, y as f32 * CELL),
+                colour: colour(),
+                texture: None,
+                opacity: 1.0,
+                mirror: false,
+                id: None,
+                hidden: false,
+            });
+        }
+    }
+    for x in 0..=width {
+        let column: Vec<bool> = (0..height).map(|y| vertical[y * (width + 1) + x]).collect();
+        for (from, to) in runs(&column) {
+            segments.push(Segment {
+                start: Vec2::new(x as f32 * CELL, from as f32 * CELL),
+                end: Vec2::new(x as f32 * CELL, to as f32 * CELL),
+                colour: colour(),
+                texture: None,
+                opacity: 1.0,
+                mirror: false,
+                id: None,
+                hidden: false,
+            });
+        }
+    }
+
+    let spawn = Spawn {
+        position: Vec2::new(
+            (rng.random_range(0..width) as f32 + 0.5) * CELL,
+            (rng.random_range(0..height) as f32 + 0.5) * CELL,
+        ),
+        rotation: rng.random_range(-PI..PI),
+    };
+    Map {
+        spawn,
+        segments,
+        ..Map::default()
+    }
+}
+
+// Start and end indices of every run of consecutive walls.
+fn runs(walls: &[bool]) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (i, &wall) in walls.iter().chain(std::iter::once(&false)).enumerate() {
+        match (wall, start) {
+            (true, None) => start = Some(i),
+            (false, Some(from)) => {
+                runs.push((from, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    runs
+}
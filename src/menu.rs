@@ -0,0 +1,97 @@
+use crate::{
+    backend::event::{Event, KeyCode, KeyEventKind},
+    hud::{self, Align, Widget},
+    theme::MenuTheme,
+};
+
+// A list of options drawn in a box over the game, moved through with the arrow keys.
+#[derive(Debug, Clone)]
+pub struct Menu {
+    // Shown in big letters above the title, e.g. the game's name.
+    pub banner: Option<String>,
+    pub title: String,
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+// What a key press in a menu asked for. Item choices carry the index of the selected item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuInput {
+    None,
+    Select(usize),
+    // Left and right change the value of a setting.
+    Decrease(usize),
+    Increase(usize),
+    Back,
+}
+
+impl Menu {
+    pub fn new(title: impl Into<String>, items: Vec<String>) -> Self {
+        Self {
+            banner: None,
+            title: title.into(),
+            items,
+            selected: 0,
+        }
+    }
+
+    pub fn with_banner(mut self, banner: impl Into<String>) -> Self {
+        self.banner = Some(banner.into());
+        self
+    }
+
+    pub fn handle(&mut self, event: &Event) -> MenuInput {
+        let Event::Key(key) = event else {
+            return MenuInput::None;
+        };
+        if key.kind == KeyEventKind::Release || self.items.is_empty() {
+            return MenuInput::None;
+        }
+        match key.code {
+            KeyCode::Up => {
+                self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+            }
+            KeyCode::Down => self.selected = (self.selected + 1) % self.items.len(),
+            KeyCode::Left => return MenuInput::Decrease(self.selected),
+            KeyCode::Right => return MenuInput::Increase(self.selected),
+            KeyCode::Enter | KeyCode::Char(' ') => return MenuInput::Select(self.selected),
+            KeyCode::Esc => return MenuInput::Back,
+            _ => {}
+        }
+        MenuInput::None
+    }
+
+    // The menu as a box to draw over a frame `width` cells across. Titles can span several
+    // lines.
+    pub fn widget(&self, width: u16, theme: &MenuTheme) -> Widget {
+        let mut lines: Vec<_> = self
+            .title
+            .lines()
+            .map(|line| Widget::text(line, theme.text))
+            .collect();
+        if !lines.is_empty() {
+            lines.push(Widget::text("", theme.text));
+        }
+        for (i, item) in self.items.iter().enumerate() {
+            let (marker, colour) = if i == self.selected {
+                ("> ", theme.selected)
+            } else {
+                ("  ", theme.text)
+            };
+            lines.push(Widget::text(format!("{marker}{item}"), colour));
+        }
+        let mut body = Widget::column(lines, Align::Start);
+        if let Some(banner) = &self.banner {
+            // Leaves room for the box around it.
+            let banner = hud::banner(banner, theme.banner, width.saturating_sub(4));
+            let blank = Widget::text("", theme.text);
+            body = Widget::column(vec![banner, blank, body], Align::Centre);
+        }
+        Widget::Panel {
+            child: Box::new(body),
+            background: Some(theme.background),
+            border: Some(theme.border),
+            padding: (1, 0),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use crate::{
+    canvas::Canvas,
+    colour::Rgb,
+    geometry::Arc,
+    hud::Anchor,
+    overhead::bounds,
+    render::Camera,
+    sector::Sector,
+    theme::HudTheme,
+    trigger::{Region, Shape},
+    vec2::Vec2,
+    world::World,
+};
+
+// World units visible from the player to the edge of the minimap.
+const RADIUS: f32 = 8.0;
+
+// Draws a top-down view centred on the player into the top right corner of the canvas. With the
+// reveal cheat on it fits in the whole map instead, along with enemies that haven't woken up yet
+// and the outlines of trigger regions.
+pub fn draw_minimap(canvas: &mut Canvas, camera: &Camera, world: &World, theme: &HudTheme) {
+    let width = canvas.width / 4;
+    let height = ((width as f32 / canvas.aspect) as usize).min(canvas.height / 2);
+    if width < 8 || height < 4 {
+        return;
+    }
+    let (left, top) = Anchor::TopRight.place((canvas.width, canvas.height), (width, height));
+    let (right, bottom, aspect) = (left + width, top + height, canvas.aspect);
+    let (focus, scale) = if world.cheats.reveal {
+        let (min, max) = bounds(camera, world);
+        let size = max - min;
+        let scale = ((width - 2) as f32 / size.x.max(1.0))
+            .min((height - 2) as f32 * aspect / size.y.max(1.0));
+        (min.lerp(max, 0.5), scale)
+    } else {
+        (camera.position, width as f32 / (2.0 * RADIUS))
+    };
+    let centre = Vec2::new(
+        left as f32 + width as f32 / 2.0,
+        top as f32 + height as f32 / 2.0,
+    );
+    let to_screen = |point: Vec2| {
+        let offset = point - focus;
+        centre + Vec2::new(offset.x * scale, -offset.y * scale / aspect)
+    };
+    let inside = |p: Vec2| {
+        p.x >= left as f32 && p.x < right as f32 && p.y >= top as f32 && p.y < bottom as f32
+    };
+
+    for y in top..bottom {
+        for x in left..right {
+            let edge = y == top || y == bottom - 1 || x == left || x == right - 1;
+            canvas.set(
+                x,
+                y,
+                if edge {
+                    theme.minimap_border
+                } else {
+                    theme.minimap_background
+                },
+            );
+        }
+    }
+
+    if let Some(grid) = &world.grid {
+        for y in top..bottom {
+            for x in left..right {
+                let world_x = focus.x + (x as f32 + 0.5 - centre.x) / scale;
+                let world_y = focus.y - (y as f32 + 0.5 - centre.y) * aspect / scale;
+                if let Some(tile) = grid.get(world_x.floor() as i32, world_y.floor() as i32) {
+                    canvas.set(x, y, tile.colour);
+                }
+            }
+        }
+    }
+
+    if let Some(exit) = &world.exit {
+        draw_region(canvas, exit.region, theme.exit, &to_screen, &inside);
+    }
+
+    if world.cheats.reveal {
+        for trigger in world.triggers.iter() {
+            match &trigger.shape {
+                Shape::Box(region) => {
+                    draw_region(canvas, *region, theme.trigger, &to_screen, &inside)
+                }
+                Shape::Polygon { points } => {
+                    for (i, &point) in points.iter().enumerate() {
+                        let next = points[(i + 1) % points.len()];
+                        draw_line(
+                            canvas,
+                            to_screen(point),
+                            to_screen(next),
+                            theme.trigger,
+                            &inside,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Sector outlines go under the walls, since they often share edges.
+    for segment in world.sectors.iter().flat_map(Sector::edges) {
+        draw_line(
+            canvas,
+            to_screen(segment.start),
+            to_screen(segment.end),
+            segment.colour,
+            &inside,
+        );
+    }
+
+    for segment in world.segments.iter() {
+        draw_line(
+            canvas,
+            to_screen(segment.start),
+            to_screen(segment.end),
+            segment.colour,
+            &inside,
+        );
+    }
+
+    for segment in world.arcs.iter().flat_map(Arc::outline) {
+        draw_line(
+            canvas,
+            to_screen(segment.start),
+            to_screen(segment.end),
+            segment.colour,
+            &inside,
+        );
+    }
+
+    for segment in world.entity_segments() {
+        draw_line(
+            canvas,
+            to_screen(segment.start),
+            to_screen(segment.end),
+            segment.colour,
+            &inside,
+        );
+    }
+
+    for (_, portal) in world.entities.portals.iter() {
+        let segment = &portal.segment;
+        draw_line(
+            canvas,
+            to_screen(segment.start),
+            to_screen(segment.end),
+            segment.colour,
+            &inside,
+        );
+    }
+
+    // Locked doors show the colour of their key.
+    for (_, door) in world.entities.doors.iter() {
+        let (Some(segment), Some(lock)) = (door.segment(), door.lock) else {
+            continue;
+        };
+        draw_line(
+            canvas,
+            to_screen(segment.start),
+            to_screen(segment.end),
+            lock.colour(),
+            &inside,
+        );
+    }
+
+    for (position, sprite) in world.entities.visible() {
+        let point = to_screen(position);
+        if inside(point) {
+            canvas.set(point.x as usize, point.y as usize, sprite.colour);
+        }
+    }
+
+    if world.cheats.reveal {
+        for (entity, dormant) in world.entities.dormant.iter() {
+            let Some(&position) = world.entities.positions.get(entity) else {
+                continue;
+            };
+            let point = to_screen(position);
+            if inside(point) {
+                canvas.set(point.x as usize, point.y as usize, dormant.sprite.colour);
+            }
+        }
+    }
+
+    let player = to_screen(camera.position);
+    let facing = camera.position + Vec2::from_angle(camera.rotation) * 1.5;
+    draw_line(canvas, player, to_screen(facing), theme.facing, &inside);
+    if inside(player) {
+        canvas.set(player.x as usize, player.y as usize, theme.player);
+    }
+}
+
+// Outlines a region of the floor.
+pub fn draw_region(
+    canvas: &mut Canvas,
+    region: Region,
+    colour: Rgb,
+    to_screen: &impl Fn(Vec2) -> Vec2,
+    inside: &impl Fn(Vec2) -> bool,
+) {
+    let corners = [
+        region.min,
+        Vec2::new(region.max.x, region.min.y),
+        region.max,
+        Vec2::new(region.min.x, region.max.y),
+    ];
+    for (i, &corner) in corners.iter().enumerate() {
+        let next = corners[(i + 1) % corners.len()];
+        draw_line(canvas, to_screen(corner), to_screen(next), colour, inside);
+    }
+}
+
+pub fn draw_line(
+    canvas: &mut Canvas,
+    from: Vec2,
+    to: Vec2,
+    colour: Rgb,
+    inside: &impl Fn(Vec2) -> bool,
+) {
+    let steps = (to.x - from.x)
+        .abs()
+        .max((to.y - from.y).abs())
+        .ceil()
+        .max(1.0) as usize;
+    for i in 0..=steps {
+        let point = from.lerp(to, i as f32 / steps as f32);
+        if inside(point) {
+            canvas.set(point.x as usize, point.y as usize, colour);
+        }
+    }
+}
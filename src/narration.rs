@@ -0,0 +1,158 @@
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_4},
+    fmt,
+    str::FromStr,
+};
+
+use anyhow::{bail, Error, Result};
+
+use crate::{
+    geometry::{Hit, LineSegment},
+    render::Camera,
+    vec2::Vec2,
+    world::World,
+};
+
+// How far ahead walls are described, and how close one has to be at either side to be.
+const AHEAD_RANGE: f32 = 20.0;
+const SIDE_RANGE: f32 = 1.5;
+// Enemies and the exit further away than this aren't mentioned.
+const NOTICE_RANGE: f32 = 10.0;
+
+// Going anticlockwise from east, the way angles do.
+const COMPASS: [&str; 8] = [
+    "east",
+    "northeast",
+    "north",
+    "northwest",
+    "west",
+    "southwest",
+    "south",
+    "southeast",
+];
+const RELATIVE: [&str; 8] = [
+    "ahead",
+    "ahead on the left",
+    "to the left",
+    "behind on the left",
+    "behind",
+    "behind on the right",
+    "to the right",
+    "ahead on the right",
+];
+
+// Whether a line describing the player's surroundings is shown over the picture, or shown
+// instead of it so there's nothing else on screen for a screen reader to read out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Narration {
+    Alongside,
+    Only,
+}
+
+impl FromStr for Narration {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "alongside" => Ok(Narration::Alongside),
+            "only" => Ok(Narration::Only),
+            _ => bail!("Unknown narration {s}, expected alongside or only"),
+        }
+    }
+}
+
+impl fmt::Display for Narration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Narration::Alongside => "alongside",
+            Narration::Only => "only",
+        })
+    }
+}
+
+// What's around the player as one line of plain text, like "facing north, wall 3m ahead, door to
+// the left". Directions and distances are rounded, so the line only changes when the player has
+// moved or turned enough to notice, rather than being read out again every frame.
+pub fn describe(camera: &Camera, world: &World) -> String {
+    let (position, facing) = (camera.position, camera.rotation);
+    let mut parts = vec![format!("facing {}", eighth(&COMPASS, facing))];
+    match world.cast(position, facing, AHEAD_RANGE) {
+        Some(hit) => {
+            let thing = what_was_hit(world, position, facing, &hit);
+            parts.push(format!("{thing} {:.0}m ahead", hit.distance));
+        }
+        None => parts.push("nothing ahead".to_string()),
+    }
+    for turn in [FRAC_PI_2, -FRAC_PI_2] {
+        if let Some(hit) = world.cast(position, facing + turn, SIDE_RANGE) {
+            let thing = what_was_hit(world, position, facing + turn, &hit);
+            parts.push(format!("{thing} {}", eighth(&RELATIVE, turn)));
+        }
+    }
+    let entities = &world.entities;
+    let enemy = entities
+        .enemies
+        .iter()
+        .filter_map(|(entity, _)| entities.positions.get(entity))
+        .min_by(|a, b| a.distance(position).total_cmp(&b.distance(position)));
+    if let Some(&enemy) = enemy {
+        parts.extend(notice("enemy", position, facing, enemy));
+    }
+    if let Some(exit) = &world.exit {
+        if exit.region.contains(position) {
+            parts.push("at the exit".to_string());
+        } else {
+            let centre = exit.region.min.lerp(exit.region.max, 0.5);
+            parts.extend(notice("exit", position, facing, centre));
+        }
+    }
+    parts.join(", ")
+}
+
+// Where `thing` is from the player, if it's near enough to matter.
+fn notice(thing: &str, position: Vec2, facing: f32, at: Vec2) -> Option<String> {
+    let distance = at.distance(position);
+    if distance > NOTICE_RANGE {
+        return None;
+    }
+    let direction = eighth(&RELATIVE, (at - position).angle() - facing);
+    Some(format!("{thing} {distance:.0}m {direction}"))
+}
+
+// The name for whichever eighth of a turn `angle` is closest to.
+fn eighth(names: &[&'static str; 8], angle: f32) -> &'static str {
+    names[((angle / FRAC_PI_4).round() as i32).rem_euclid(8) as usize]
+}
+
+// Whether the wall a ray hit is a door or switch, which are worth knowing about, or anything
+// else.
+fn what_was_hit(world: &World, origin: Vec2, angle: f32, hit: &Hit) -> &'static str {
+    let ray = LineSegment::ray(origin, angle, hit.distance + 0.01);
+    let reached = |segment: &LineSegment| segment.hit(&ray).is_some();
+    let entities = &world.entities;
+    let door = entities
+        .doors
+        .iter()
+        .find(|(_, door)| door.segment().is_some_and(|s| reached(&s)));
+    if let Some((_, door)) = door {
+        return if door.lock.is_some() {
+            "locked door"
+        } else {
+            "door"
+        };
+    }
+    if entities
+        .switches
+        .iter()
+        .any(|(_, switch)| reached(&switch.segment()))
+    {
+        return "switch";
+    }
+    if hit.mirror {
+        "mirror"
+    } else if hit.portal {
+        "portal"
+    } else {
+        "wall"
+    }
+}
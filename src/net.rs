@@ -0,0 +1,538 @@
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    backend::event::Event,
+    colour::Rgb,
+    controls::Controls,
+    entities::Entity,
+    event::GameEvent,
+    game::{Game, Level},
+    input::{Action, Input},
+    map::Map,
+    render::Camera,
+    save::SaveState,
+    sprite::Sprite,
+    world::World,
+};
+
+pub const DEFAULT_PORT: u16 = 7777;
+// The longest message either side will read, which is plenty for the biggest map.
+const MAX_LINE: u64 = 16 * 1024 * 1024;
+// Seconds of game time between snapshots sent to everyone playing.
+pub const TICK: f32 = 1.0 / 20.0;
+// How long joining waits to hear back from the host, and how long a send can take before the
+// other side is given up on.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+// Each player's colour, starting with the host's and going round again after the last.
+const PLAYER_COLOURS: [Rgb; 4] = [
+    Rgb::new(255, 255, 0),
+    Rgb::new(0, 200, 255),
+    Rgb::new(255, 96, 200),
+    Rgb::new(128, 255, 64),
+];
+const PLAYER_SIZE: f32 = 0.7;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClientMessage {
+    // Sent once on joining, so the host reads the keys the way the player bound them. Spectators
+    // only watch, and send nothing after this.
+    Hello {
+        controls: Box<Controls>,
+        #[serde(default)]
+        spectator: bool,
+    },
+    // Every terminal event since the last message.
+    Input(Vec<Event>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ServerMessage {
+    // Sent on joining and whenever the host moves on to another level, which the player loads
+    // for themselves.
+    Welcome { id: u32, level: Box<SharedLevel> },
+    Snapshot(Box<Snapshot>),
+}
+
+// Where everyone is and the state of the world at the end of a tick.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub players: Vec<PlayerState>,
+    pub world: SaveState,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub id: u32,
+    pub camera: Camera,
+}
+
+// A level as guests are sent it: the map itself, so they needn't have a copy, without the files
+// it names on the host's machine. Scripts and plugins only run on the host, and guests see what
+// they do in each snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedLevel {
+    pub name: String,
+    // `None` for the demo, which guests build for themselves.
+    pub map: Option<Map>,
+}
+
+impl SharedLevel {
+    pub fn new(level: &Level) -> Result<Self> {
+        let mut map = level.map()?;
+        if let Some(map) = map.as_mut() {
+            map.music = None;
+            map.script = None;
+            map.plugins.clear();
+            if let Some(exit) = map.exit.as_mut() {
+                exit.next = None;
+            }
+        }
+        Ok(Self {
+            name: shared_name(level),
+            map,
+        })
+    }
+
+    pub fn load(&self) -> Result<Game> {
+        Game::from_map(self.name.clone(), self.map.as_ref())
+    }
+}
+
+// The level's name with only the last part of any path in it, so guests never learn where the
+// host keeps their files.
+fn shared_name(level: &Level) -> String {
+    let file_name = |path: &Path| {
+        path.file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+    };
+    match level {
+        Level::Demo | Level::Maze { .. } => level.name(),
+        Level::File(path) => file_name(path),
+        Level::Campaign { dir, index } => format!("{} level {}", file_name(dir), index + 1),
+        Level::Wad { path, map } => format!("{} {map}", file_name(path)),
+    }
+}
+
+// How another player is drawn.
+pub fn player_sprite(id: u32) -> Sprite {
+    Sprite::new(
+        PLAYER_COLOURS[id as usize % PLAYER_COLOURS.len()],
+        PLAYER_SIZE,
+    )
+}
+
+// A TCP stream carrying a line of JSON per message each way. Reading happens on a thread of its
+// own, so checking for messages never blocks the game.
+pub struct Connection<T> {
+    stream: TcpStream,
+    incoming: Receiver<Result<T>>,
+}
+
+impl<T: DeserializeOwned + Send + 'static> Connection<T> {
+    pub fn new(stream: TcpStream) -> Result<Self> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || loop {
+            let message = match read_line(&mut reader) {
+                Ok(None) => return,
+                Ok(Some(line)) => serde_json::from_str(&line).context("Could not read a message"),
+                Err(e) => Err(e),
+            };
+            let failed = message.is_err();
+            if sender.send(message).is_err() || failed {
+                return;
+            }
+        });
+        Ok(Self { stream, incoming })
+    }
+
+    pub fn send(&mut self, message: &impl Serialize) -> Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.stream
+            .write_all(line.as_bytes())
+            .context("Lost the connection")
+    }
+
+    // Every message that's arrived since the last call. Once the other side has gone, this
+    // fails after the last of what they sent has been read.
+    pub fn receive(&self) -> Result<Vec<T>> {
+        let mut messages = Vec::new();
+        loop {
+            match self.incoming.try_recv() {
+                Ok(message) => messages.push(message?),
+                Err(TryRecvError::Empty) => return Ok(messages),
+                Err(TryRecvError::Disconnected) if messages.is_empty() => bail!("Disconnected"),
+                Err(TryRecvError::Disconnected) => return Ok(messages),
+            }
+        }
+    }
+}
+
+// The next line the other side sent, or `None` once they've closed the connection. A line that
+// goes on past `MAX_LINE` is refused rather than read for as long as they keep sending.
+fn read_line(reader: &mut impl BufRead) -> Result<Option<String>> {
+    let mut line = String::new();
+    let read = reader
+        .take(MAX_LINE)
+        .read_line(&mut line)
+        .context("Lost the connection")?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if read as u64 == MAX_LINE && !line.ends_with('\n') {
+        bail!("A message was too long");
+    }
+    Ok(Some(line))
+}
+
+impl<T> Drop for Connection<T> {
+    // Closing both ways also ends the reading thread.
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+// Someone playing in or watching a hosted game from another terminal. The host moves players by
+// the input they send, the same way its own player is moved.
+struct Guest {
+    id: u32,
+    connection: Connection<ClientMessage>,
+    input: Input,
+    camera: Camera,
+    // The sprite the host and everyone else sees them as, once they've said hello as a player.
+    // Spectators never get one.
+    entity: Option<Entity>,
+}
+
+impl Guest {
+    // Acts on what the guest sent and moves them on a tick, returning false if they quit.
+    fn step(&mut self, world: &mut World, events: &mut Vec<GameEvent>) -> Result<bool> {
+        for message in self.connection.receive()? {
+            match message {
+                ClientMessage::Hello {
+                    controls,
+                    spectator,
+                } => {
+                    self.input.controls = *controls;
+                    if spectator {
+                        world
+                            .messages
+                            .push(format!("Player {} is watching", self.id));
+                    } else {
+                        let sprite = player_sprite(self.id);
+                        self.entity =
+                            Some(world.entities.spawn_sprite(self.camera.position, sprite));
+                        world.messages.push(format!("Player {} joined", self.id));
+                    }
+                }
+                ClientMessage::Input(_) if self.entity.is_none() => {}
+                ClientMessage::Input(input) => {
+                    for event in input {
+                        let camera = self.camera;
+                        match self.input.handle(event, &mut self.camera) {
+                            Action::Quit => return Ok(false),
+                            Action::Fire => world.fire(camera.position, camera.rotation, events),
+                            Action::Use => {
+                                world.use_in_front(camera.position, camera.rotation, events)
+                            }
+                            // Everything else only changes how their own screen looks.
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        let Some(entity) = self.entity else {
+            return Ok(true);
+        };
+        // Footsteps and bumps are only heard by whoever made them.
+        self.input
+            .update(&mut self.camera, world, TICK, &mut Vec::new());
+        if let Some(position) = world.entities.positions.get_mut(entity) {
+            *position = self.camera.position;
+        }
+        Ok(true)
+    }
+}
+
+// Listens for players joining the host's game, and keeps the world they see in step with the
+// host's. The host's own player is always player 0.
+pub struct Server {
+    listener: TcpListener,
+    level: SharedLevel,
+    // Where guests appear, which is where the host started.
+    spawn: Camera,
+    guests: Vec<Guest>,
+    next_id: u32,
+    tick: u64,
+    // Seconds played since the last tick.
+    elapsed: f32,
+}
+
+impl Server {
+    pub fn bind(address: SocketAddr, game: &Game, level: &Level) -> Result<Self> {
+        let listener =
+            TcpListener::bind(address).with_context(|| format!("Could not listen on {address}"))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            level: SharedLevel::new(level)?,
+            spawn: game.camera,
+            guests: Vec::new(),
+            next_id: 1,
+            tick: 0,
+            elapsed: 0.0,
+        })
+    }
+
+    // Sends everyone to a new level, starting where the host does.
+    pub fn change_level(&mut self, game: &mut Game, level: &Level) -> Result<()> {
+        self.level = SharedLevel::new(level)?;
+        self.spawn = game.camera;
+        for guest in self.guests.iter_mut() {
+            guest.camera = self.spawn;
+            guest.input = guest.input.fresh();
+        }
+        self.guests.retain_mut(|guest| {
+            let welcome = ServerMessage::Welcome {
+                id: guest.id,
+                level: Box::new(self.level.clone()),
+            };
+            match guest.connection.send(&welcome) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Player {} left: {e:#}", guest.id);
+                    false
+                }
+            }
+        });
+        self.spawn_guests(&mut game.world);
+        Ok(())
+    }
+
+    // Puts every playing guest's sprite into a world that's just been loaded, e.g. from a quick
+    // save.
+    pub fn spawn_guests(&mut self, world: &mut World) {
+        for guest in self
+            .guests
+            .iter_mut()
+            .filter(|guest| guest.entity.is_some())
+        {
+            let sprite = player_sprite(guest.id);
+            guest.entity = Some(world.entities.spawn_sprite(guest.camera.position, sprite));
+        }
+    }
+
+    // Lets in anyone who's connected and runs as many ticks as `dt` covers, sending everyone
+    // a snapshot after each.
+    pub fn update(&mut self, game: &mut Game, dt: f32, events: &mut Vec<GameEvent>) {
+        if let Err(e) = self.accept() {
+            warn!("Could not accept a player: {e:#}");
+        }
+        self.elapsed += dt;
+        while self.elapsed >= TICK {
+            self.elapsed -= TICK;
+            self.tick += 1;
+            self.step(game, events);
+        }
+    }
+
+    fn accept(&mut self) -> Result<()> {
+        loop {
+            let (stream, address) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+            let id = self.next_id;
+            self.next_id += 1;
+            let mut connection = Connection::new(stream)?;
+            connection.send(&ServerMessage::Welcome {
+                id,
+                level: Box::new(self.level.clone()),
+            })?;
+            info!("Player {id} connected from {address}");
+            self.guests.push(Guest {
+                id,
+                connection,
+                input: Input::new(),
+                camera: self.spawn,
+                entity: None,
+            });
+        }
+    }
+
+    fn step(&mut self, game: &mut Game, events: &mut Vec<GameEvent>) {
+        let world = &mut game.world;
+        self.guests.retain_mut(|guest| {
+            let left = match guest.step(world, events) {
+                Ok(true) => return true,
+                Ok(false) => format!("Player {} quit", guest.id),
+                Err(e) => {
+                    warn!("Player {} left: {e:#}", guest.id);
+                    format!("Player {} left", guest.id)
+                }
+            };
+            if let Some(entity) = guest.entity {
+                world.entities.despawn(entity);
+            }
+            world.messages.push(left);
+            false
+        });
+        let mut players = vec![PlayerState {
+            id: 0,
+            camera: game.camera,
+        }];
+        players.extend(
+            self.guests
+                .iter()
+                .filter(|guest| guest.entity.is_some())
+                .map(|guest| PlayerState {
+                    id: guest.id,
+                    camera: guest.camera,
+                }),
+        );
+        let mut world = game.save();
+        world.map = self.level.name.clone();
+        let snapshot = ServerMessage::Snapshot(Box::new(Snapshot {
+            tick: self.tick,
+            players,
+            world,
+        }));
+        // A guest that can't keep up is dropped, and goes when their next tick fails.
+        for guest in self.guests.iter_mut() {
+            if let Err(e) = guest.connection.send(&snapshot) {
+                warn!("Could not send player {} a snapshot: {e:#}", guest.id);
+                let _ = guest.connection.stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+// A player in someone else's game, or a spectator watching it. Nothing is simulated here: the
+// world shown is whatever the host last sent.
+pub struct Client {
+    connection: Connection<ServerMessage>,
+    pub id: u32,
+    // Everyone playing as of the last snapshot, starting with the host.
+    pub players: Vec<PlayerState>,
+    // The sprites of everyone else, replaced with each snapshot.
+    sprites: Vec<Entity>,
+}
+
+impl Client {
+    // Joins the game at `address`, returning the level being played there.
+    pub fn connect(
+        address: &str,
+        controls: &Controls,
+        spectator: bool,
+    ) -> Result<(Self, SharedLevel)> {
+        let stream = TcpStream::connect(address)
+            .with_context(|| format!("Could not connect to {address}"))?;
+        let mut connection = Connection::new(stream)?;
+        connection.send(&ClientMessage::Hello {
+            controls: Box::new(controls.clone()),
+            spectator,
+        })?;
+        let welcome = connection
+            .incoming
+            .recv_timeout(CONNECT_TIMEOUT)
+            .with_context(|| format!("{address} didn't answer"))??;
+        let ServerMessage::Welcome { id, level } = welcome else {
+            bail!("{address} didn't welcome us in");
+        };
+        let client = Self {
+            connection,
+            id,
+            players: Vec::new(),
+            sprites: Vec::new(),
+        };
+        Ok((client, *level))
+    }
+
+    pub fn send(&mut self, events: Vec<Event>) -> Result<()> {
+        self.connection.send(&ClientMessage::Input(events))
+    }
+
+    // Catches `game` up with everything the host has sent, loading the next level if they've
+    // moved on.
+    pub fn update(&mut self, game: &mut Game) -> Result<()> {
+        for message in self.connection.receive()? {
+            match message {
+                ServerMessage::Welcome { id, level } => {
+                    *game = level.load()?;
+                    self.id = id;
+                    self.players.clear();
+                    self.sprites.clear();
+                }
+                ServerMessage::Snapshot(snapshot) => self.apply(game, *snapshot)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, game: &mut Game, snapshot: Snapshot) -> Result<()> {
+        game.restore(snapshot.world)?;
+        let entities = &mut game.world.entities;
+        for entity in self.sprites.drain(..) {
+            entities.despawn(entity);
+        }
+        for &player in snapshot.players.iter() {
+            if player.id == self.id {
+                game.camera = player.camera;
+            } else {
+                let sprite = player_sprite(player.id);
+                self.sprites
+                    .push(entities.spawn_sprite(player.camera.position, sprite));
+            }
+        }
+        self.players = snapshot.players;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, Cursor};
+
+    #[test]
+    fn guests_are_sent_the_map_not_where_it_is() {
+        let maps = Path::new(env!("CARGO_MANIFEST_DIR")).join("maps");
+        let level = Level::file(maps.join("grid.toml")).unwrap();
+        let shared = SharedLevel::new(&level).unwrap();
+        let sent = serde_json::to_string(&shared).unwrap();
+        assert!(!sent.contains(env!("CARGO_MANIFEST_DIR")), "{sent}");
+        assert_eq!(shared.name, "grid.toml");
+        let game = shared.load().unwrap();
+        assert_eq!(game.world.script, None);
+        assert!(game.world.plugins.is_empty());
+    }
+
+    #[test]
+    fn messages_are_read_a_line_at_a_time() {
+        let mut reader = Cursor::new("{}\n[]");
+        assert_eq!(read_line(&mut reader).unwrap().as_deref(), Some("{}\n"));
+        assert_eq!(read_line(&mut reader).unwrap().as_deref(), Some("[]"));
+        assert_eq!(read_line(&mut reader).unwrap(), None);
+        let mut endless = BufReader::new(io::repeat(b'x'));
+        assert!(read_line(&mut endless).is_err());
+    }
+}
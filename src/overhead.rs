@@ -0,0 +1,157 @@
+use crate::{
+    canvas::Canvas,
+    colour::Rgb,
+    geometry::{Arc, MAX_DISTANCE},
+    minimap::{draw_line, draw_region},
+    render::{Camera, Projection, SceneStats},
+    sector::Sector,
+    vec2::Vec2,
+    world::World,
+};
+
+// How many rays the view fans out across the field of view.
+const RAYS: usize = 32;
+// Pixels left empty around the world.
+const MARGIN: f32 = 2.0;
+const BACKGROUND: Rgb = Rgb::new(16, 16, 16);
+const RAY: Rgb = Rgb::new(96, 96, 0);
+const HIT: Rgb = Rgb::new(255, 64, 64);
+const PLAYER: Rgb = Rgb::new(255, 255, 0);
+const EXIT: Rgb = Rgb::new(0, 255, 128);
+
+// Draws the whole world from above in place of the scene: every wall, door and sprite, the
+// player, and a fan of the rays they're casting with the points they hit.
+pub fn draw_overhead(canvas: &mut Canvas, camera: &Camera, world: &World) -> SceneStats {
+    canvas.pixels.fill(BACKGROUND);
+    let (min, max) = bounds(camera, world);
+    let size = max - min;
+    let scale = ((canvas.width as f32 - 2.0 * MARGIN) / size.x.max(1.0))
+        .min((canvas.height as f32 - 2.0 * MARGIN) * canvas.aspect / size.y.max(1.0));
+    if scale <= 0.0 {
+        return SceneStats::default();
+    }
+    // Centre the world on the canvas, with y pointing up.
+    let offset = Vec2::new(
+        (canvas.width as f32 - size.x * scale) / 2.0,
+        (canvas.height as f32 - size.y * scale / canvas.aspect) / 2.0,
+    );
+    let (width, height, aspect) = (canvas.width as f32, canvas.height as f32, canvas.aspect);
+    let to_screen = |point: Vec2| {
+        let point = point - min;
+        Vec2::new(
+            offset.x + point.x * scale,
+            height - offset.y - point.y * scale / aspect,
+        )
+    };
+    let inside = |p: Vec2| p.x >= 0.0 && p.x < width && p.y >= 0.0 && p.y < height;
+
+    if let Some(grid) = &world.grid {
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                let world_x = min.x + (x as f32 + 0.5 - offset.x) / scale;
+                let world_y = min.y + (height - y as f32 - 0.5 - offset.y) * aspect / scale;
+                if let Some(tile) = grid.get(world_x.floor() as i32, world_y.floor() as i32) {
+                    canvas.set(x, y, tile.colour);
+                }
+            }
+        }
+    }
+
+    let projection = Projection::new(camera, RAYS, 1, 1.0);
+    let mut stats = SceneStats::default();
+    let mut hits = Vec::new();
+    for x in 0..RAYS {
+        let angle = camera.rotation + projection.ray_offset(x);
+        let hit = world.cast_counting(
+            camera.position,
+            angle,
+            MAX_DISTANCE,
+            &mut stats.segment_tests,
+        );
+        let distance = hit.map_or(MAX_DISTANCE, |h| h.distance);
+        let end = camera.position + Vec2::from_angle(angle) * distance;
+        draw_line(
+            canvas,
+            to_screen(camera.position),
+            to_screen(end),
+            RAY,
+            &inside,
+        );
+        if hit.is_some() {
+            hits.push(end);
+        }
+        stats.rays += 1;
+    }
+
+    if let Some(exit) = &world.exit {
+        draw_region(canvas, exit.region, EXIT, &to_screen, &inside);
+    }
+    // Sector outlines go under the walls, since they often share edges.
+    let sectors = world.sectors.iter().flat_map(Sector::edges);
+    let walls = sectors
+        .chain(world.segments.iter().cloned())
+        .chain(world.arcs.iter().flat_map(Arc::outline))
+        .map(|s| (s.start, s.end, s.colour));
+    let entities = world.entity_segments().map(|s| (s.start, s.end, s.colour));
+    let portals = world.entities.portals.iter().map(|(_, p)| {
+        let s = &p.segment;
+        (s.start, s.end, s.colour)
+    });
+    for (start, end, colour) in walls.chain(entities).chain(portals) {
+        draw_line(canvas, to_screen(start), to_screen(end), colour, &inside);
+    }
+    for (position, sprite) in world.entities.visible() {
+        let point = to_screen(position);
+        if inside(point) {
+            canvas.set(point.x as usize, point.y as usize, sprite.colour);
+        }
+    }
+    // Hits go over the walls so they can be told apart from them.
+    for point in hits.into_iter().map(to_screen).filter(|&p| inside(p)) {
+        canvas.set(point.x as usize, point.y as usize, HIT);
+    }
+    let player = to_screen(camera.position);
+    if inside(player) {
+        canvas.set(player.x as usize, player.y as usize, PLAYER);
+    }
+    stats
+}
+
+// The corners of a box around every wall, door and sprite, and the player.
+pub fn bounds(camera: &Camera, world: &World) -> (Vec2, Vec2) {
+    let mut points = vec![camera.position];
+    for segment in world.segments.iter() {
+        points.extend([segment.start, segment.end]);
+    }
+    for (_, door) in world.entities.doors.iter() {
+        points.extend([door.start, door.end]);
+    }
+    for (_, wall) in world.entities.walls.iter() {
+        points.extend([wall.segment.start, wall.segment.end]);
+    }
+    for (_, switch) in world.entities.switches.iter() {
+        points.extend([switch.start, switch.end]);
+    }
+    for (_, portal) in world.entities.portals.iter() {
+        points.extend([portal.segment.start, portal.segment.end]);
+    }
+    for arc in world.arcs.iter() {
+        let reach = Vec2::new(arc.radius, arc.radius);
+        points.extend([arc.centre - reach, arc.centre + reach]);
+    }
+    for sector in world.sectors.iter() {
+        points.extend(sector.points.iter().copied());
+    }
+    points.extend(world.entities.visible().map(|(position, _)| position));
+    if let Some(grid) = &world.grid {
+        points.extend([Vec2::ZERO, Vec2::new(grid.width as f32, grid.height as f32)]);
+    }
+    points
+        .iter()
+        .fold((camera.position, camera.position), |(min, max), p| {
+            (
+                Vec2::new(min.x.min(p.x), min.y.min(p.y)),
+                Vec2::new(max.x.max(p.x), max.y.max(p.y)),
+            )
+        })
+}
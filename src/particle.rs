@@ -0,0 +1,241 @@
+use std::f32::consts::{PI, TAU};
+
+use rand::{rngs::StdRng, RngExt, SeedableRng};
+
+use crate::{
+    canvas::Canvas,
+    colour::Rgb,
+    render::{Camera, Projection},
+    vec2::Vec2,
+    world::World,
+};
+
+// The most particles alive at once. The pool is allocated at this size up front, and new
+// particles are dropped while it's full rather than growing it.
+const CAPACITY: usize = 1024;
+// How wide a particle is in world units, though it's always at least a pixel.
+const SIZE: f32 = 0.04;
+// How far either side of the direction a burst is aimed in its particles can fly off.
+const SPREAD: f32 = PI / 3.0;
+
+// What a particle is, which decides how it moves and looks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    // Grit knocked off a wall by a shot, falling back down.
+    Dust,
+    // Motes drifting up off a teleporter pad.
+    Sparkle,
+    // Hot flecks thrown off a door grinding along its frame.
+    Spark,
+}
+
+struct Style {
+    // The colour it starts out, fading to `to` by the end of its life.
+    from: Rgb,
+    to: Rgb,
+    // World units a second it's thrown sideways and upwards at.
+    speed: f32,
+    rise: f32,
+    gravity: f32,
+    lifetime: f32,
+    // Whether it gives off its own light rather than being lit by the world.
+    glows: bool,
+}
+
+impl Effect {
+    fn style(self) -> Style {
+        match self {
+            Effect::Dust => Style {
+                from: Rgb::new(170, 160, 140),
+                to: Rgb::new(70, 64, 56),
+                speed: 1.5,
+                rise: 1.0,
+                gravity: 6.0,
+                lifetime: 0.6,
+                glows: false,
+            },
+            Effect::Sparkle => Style {
+                from: Rgb::new(220, 255, 255),
+                to: Rgb::new(40, 80, 255),
+                speed: 0.1,
+                rise: 0.6,
+                gravity: 0.0,
+                lifetime: 1.2,
+                glows: true,
+            },
+            Effect::Spark => Style {
+                from: Rgb::new(255, 240, 160),
+                to: Rgb::new(200, 60, 0),
+                speed: 1.2,
+                rise: 1.5,
+                gravity: 8.0,
+                lifetime: 0.4,
+                glows: true,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    pub effect: Effect,
+    pub position: Vec2,
+    pub height: f32,
+    pub velocity: Vec2,
+    // Upward speed.
+    pub rise: f32,
+    // The floor where it was spawned, which it comes to rest on.
+    pub ground: f32,
+    // Seconds since it was spawned.
+    pub age: f32,
+}
+
+impl Particle {
+    pub fn colour(&self) -> Rgb {
+        let style = self.effect.style();
+        style.from.lerp(style.to, self.age / style.lifetime)
+    }
+}
+
+// Every live particle, kept in one buffer whose slots are reused as particles die out.
+pub struct Particles {
+    pool: Vec<Particle>,
+    rng: StdRng,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Self {
+            pool: Vec::with_capacity(CAPACITY),
+            // Seeded so that demos play back the same way.
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+}
+
+impl Particles {
+    pub fn iter(&self) -> impl Iterator<Item = &Particle> {
+        self.pool.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+
+    // Throws `count` particles out from `position`, `height` above the floor at `ground`,
+    // fanned around `direction` or scattered every way if there isn't one.
+    pub fn burst(
+        &mut self,
+        effect: Effect,
+        position: Vec2,
+        height: f32,
+        ground: f32,
+        count: usize,
+        direction: Option<Vec2>,
+    ) {
+        let style = effect.style();
+        for _ in 0..count.min(CAPACITY - self.pool.len()) {
+            let angle = match direction {
+                Some(direction) => direction.angle() + self.rng.random_range(-SPREAD..SPREAD),
+                None => self.rng.random_range(0.0..TAU),
+            };
+            let speed = style.speed * self.rng.random_range(0.5..1.0);
+            self.pool.push(Particle {
+                effect,
+                position,
+                height: ground + height,
+                velocity: Vec2::from_angle(angle) * speed,
+                rise: style.rise * self.rng.random_range(0.5..1.0),
+                ground,
+                age: 0.0,
+            });
+        }
+    }
+
+    // Spawns about `rate` particles a second off the floor at `ground` somewhere within
+    // `radius` of `position`, for something giving them off steadily.
+    pub fn trickle(
+        &mut self,
+        effect: Effect,
+        position: Vec2,
+        radius: f32,
+        ground: f32,
+        rate: f32,
+        dt: f32,
+    ) {
+        let expected = rate * dt;
+        let mut count = expected as usize;
+        if self.rng.random_range(0.0..1.0) < expected.fract() {
+            count += 1;
+        }
+        for _ in 0..count {
+            let offset = Vec2::from_angle(self.rng.random_range(0.0..TAU))
+                * radius
+                * self.rng.random_range(0.0f32..1.0).sqrt();
+            self.burst(effect, position + offset, 0.0, ground, 1, None);
+        }
+    }
+
+    // Moves every particle along for `dt` seconds and clears out the ones that have died.
+    pub fn update(&mut self, dt: f32) {
+        for particle in self.pool.iter_mut() {
+            let style = particle.effect.style();
+            particle.age += dt;
+            particle.position += particle.velocity * dt;
+            particle.rise -= style.gravity * dt;
+            particle.height += particle.rise * dt;
+            if particle.height < particle.ground {
+                particle.height = particle.ground;
+                particle.rise = 0.0;
+                particle.velocity = Vec2::ZERO;
+            }
+        }
+        self.pool.retain(|p| p.age < p.effect.style().lifetime);
+    }
+}
+
+// Draws every particle as a tiny square facing the camera, skipping any column where a wall
+// is in front of it.
+pub fn draw_particles(
+    canvas: &mut Canvas,
+    camera: &Camera,
+    world: &World,
+    projection: &Projection,
+    depth: &[f32],
+) {
+    for particle in world.particles.iter() {
+        let to_particle = particle.position - camera.position;
+        let offset = (to_particle.angle() - camera.rotation + PI).rem_euclid(2.0 * PI) - PI;
+        let distance = to_particle.length() * offset.cos();
+        if distance <= 0.1 {
+            continue;
+        }
+        let mut colour = particle.colour();
+        if !particle.effect.style().glows {
+            colour = world.brightness_at(particle.position, None).apply(colour);
+        }
+        let colour = world.fog.apply(colour, distance);
+        let half_width = projection.width_of(SIZE, distance).max(1.0) / 2.0;
+        let half_height = (projection.wall_height(distance) * SIZE).max(1.0) / 2.0;
+        let (x, y) = (
+            projection.column(offset),
+            projection.row(particle.height, distance),
+        );
+        let columns = (x - half_width).round().max(0.0) as usize
+            ..((x + half_width).round().max(0.0) as usize).min(canvas.width);
+        let rows = (y - half_height).round().max(0.0) as usize
+            ..((y + half_height).round().max(0.0) as usize).min(canvas.height);
+        for column in columns {
+            if depth[column] < distance {
+                continue;
+            }
+            for row in rows.clone() {
+                canvas.set(column, row, colour);
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{colour::Rgb, sprite::Sprite};
+
+// How close the player has to walk to a pickup to collect it.
+pub const PICKUP_RADIUS: f32 = 0.5;
+// What items look like when the map doesn't say, and how they're shown on the HUD.
+pub const ITEM_COLOUR: Rgb = Rgb::new(200, 96, 255);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyColour {
+    Red,
+    Yellow,
+    Blue,
+}
+
+impl KeyColour {
+    pub fn colour(self) -> Rgb {
+        match self {
+            KeyColour::Red => Rgb::new(255, 48, 48),
+            KeyColour::Yellow => Rgb::new(255, 224, 32),
+            KeyColour::Blue => Rgb::new(48, 96, 255),
+        }
+    }
+}
+
+impl fmt::Display for KeyColour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            KeyColour::Red => "red",
+            KeyColour::Yellow => "yellow",
+            KeyColour::Blue => "blue",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// Something lying on the floor that the player collects by walking over it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Pickup {
+    Health {
+        #[serde(default = "default_health")]
+        amount: f32,
+    },
+    Ammo {
+        #[serde(default = "default_ammo")]
+        amount: u32,
+    },
+    Key {
+        key: KeyColour,
+    },
+    // Anything else a map wants the player to find, which scripts can check for and take.
+    Item {
+        name: String,
+        #[serde(default = "default_count")]
+        count: u32,
+    },
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+fn default_health() -> f32 {
+    25.0
+}
+
+fn default_ammo() -> u32 {
+    10
+}
+
+impl Pickup {
+    // How the pickup looks when the map doesn't say.
+    pub fn sprite(&self) -> Sprite {
+        let colour = match self {
+            Pickup::Health { .. } => Rgb::new(240, 240, 240),
+            Pickup::Ammo { .. } => Rgb::new(200, 160, 48),
+            Pickup::Key { key } => key.colour(),
+            Pickup::Item { .. } => ITEM_COLOUR,
+        };
+        Sprite::new(colour, 0.25)
+    }
+}
+
+impl fmt::Display for Pickup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pickup::Health { amount } => write!(f, "{amount:.0} health"),
+            Pickup::Ammo { amount } => write!(f, "{amount} ammo"),
+            Pickup::Key { key } => write!(f, "the {key} key"),
+            Pickup::Item { name, count: 1 } => write!(f, "the {name}"),
+            Pickup::Item { name, count } => write!(f, "{count} {name}"),
+        }
+    }
+}
@@ -0,0 +1,500 @@
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use clap::Args;
+use tracing::warn;
+
+#[cfg(feature = "gamepad")]
+use crate::gamepad::Gamepads;
+use crate::{
+    campaign::{Campaign, Progress},
+    controls::Controls,
+    demo::{Demo, Frame},
+    game::Level,
+    input::{Action, Input, SPEED, TURN_SPEED},
+    maze::MazeSize,
+    menu::{Menu, MenuInput},
+    net::{Server, DEFAULT_PORT},
+    session::{settings, Flow, OutputArgs, SecondPlayer, Session, Setting},
+    video::{VideoFormat, VideoRecorder},
+};
+
+#[derive(Args)]
+pub struct PlayArgs {
+    #[arg(help = "TOML or JSON map to load, or a Doom WAD with --import-wad")]
+    pub map: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        requires = "map",
+        help = "Play a level like E1M1 or MAP01 from the Doom WAD given as the map"
+    )]
+    pub import_wad: Option<String>,
+    #[arg(
+        long,
+        value_name = "WxH",
+        conflicts_with = "map",
+        help = "Play a random maze"
+    )]
+    pub generate: Option<MazeSize>,
+    #[arg(long, requires = "generate", help = "Seed for --generate")]
+    pub seed: Option<u64>,
+    #[arg(
+        long,
+        value_name = "DIR|ZIP",
+        conflicts_with_all = ["map", "generate"],
+        help = "Campaign to pick a level from on the title screen"
+    )]
+    pub campaign: Option<PathBuf>,
+    #[command(flatten)]
+    pub output: OutputArgs,
+    #[arg(long, help = "Field of view in degrees")]
+    pub fov: Option<f32>,
+    #[arg(long, default_value_t = SPEED, help = "Movement speed in units per second")]
+    pub speed: f32,
+    #[arg(long, default_value_t = TURN_SPEED, help = "Turning speed in radians per second")]
+    pub turn_speed: f32,
+    #[arg(
+        long,
+        help = "Mouse look multiplier, instead of the one in the key bindings"
+    )]
+    pub sensitivity: Option<f32>,
+    #[arg(
+        long,
+        default_value_t = 30.0,
+        help = "Frames per second to draw at most"
+    )]
+    pub fps_cap: f32,
+    #[arg(
+        long,
+        help = "Key bindings to use instead of the ones in the config directory"
+    )]
+    pub controls: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Quick save file to use instead of the one in the data directory"
+    )]
+    pub save: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value = "maps",
+        help = "Directory of maps to list on the title screen"
+    )]
+    pub maps: PathBuf,
+    #[arg(
+        long,
+        value_name = "DEMO|GIF|PNG",
+        help = "Record the game to a demo file, or to an animation if it ends in .gif or .png"
+    )]
+    pub record: Option<PathBuf>,
+    #[arg(long, help = "Share the screen with a second player on the arrow keys")]
+    pub split: bool,
+    #[cfg(feature = "gamepad")]
+    #[arg(long, help = "Ignore connected gamepads")]
+    pub no_gamepad: bool,
+}
+
+#[derive(Args)]
+pub struct HostArgs {
+    #[command(flatten)]
+    pub play: PlayArgs,
+    #[arg(long, default_value_t = DEFAULT_PORT, help = "Port to listen for players on")]
+    pub port: u16,
+    #[arg(
+        long,
+        default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST),
+        help = "Address to listen on, e.g. 0.0.0.0 to let in players on other machines"
+    )]
+    pub bind: IpAddr,
+}
+
+// Where the player is: on the title screen, in the game, or in one of the menus over it. The
+// game stands still while a menu is open.
+enum State {
+    Title(Menu, Vec<TitleItem>),
+    Playing,
+    Paused(Menu),
+    // Dead, offered a restart.
+    Dead(Menu),
+    // Settings remember the menu they were opened from, to go back to.
+    Settings(Menu, Box<State>),
+    // Fading out after reaching the exit, for `time` seconds so far.
+    Leaving { next: Option<Level>, time: f32 },
+}
+
+#[derive(Clone)]
+enum TitleItem {
+    Play(Level),
+    // A campaign level that hasn't been reached yet.
+    Locked,
+    Options,
+    Quit,
+}
+
+const TITLE: &str = "RHYWBETH";
+// Shown over the screen as it fades out after reaching a level's exit.
+const LEVEL_CLEAR: &str = "LEVEL CLEAR";
+
+// Seconds the screen takes to fade out after reaching a level's exit.
+const EXIT_FADE: f32 = 1.0;
+
+// Radians per second the view turns behind the title screen.
+const TITLE_TURN_SPEED: f32 = 0.2;
+
+// Size of the maze the title screen offers.
+const TITLE_MAZE: MazeSize = MazeSize {
+    width: 12,
+    height: 12,
+};
+
+// Offers the demo, every map in `maps` and a fresh maze.
+fn title_menu(maps: &Path) -> (Menu, Vec<TitleItem>) {
+    let mut files: Vec<_> = fs::read_dir(maps)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let palette = path.to_string_lossy().ends_with(".palette.toml");
+            let map = matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("toml" | "json" | "tmx" | "tmj" | "csv" | "bin")
+            );
+            map && !palette
+        })
+        .collect();
+    files.sort();
+    let mut items = vec![("Demo".to_string(), TitleItem::Play(Level::Demo))];
+    for path in files {
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        match Level::file(path) {
+            Ok(level) => items.push((name, TitleItem::Play(level))),
+            Err(e) => warn!("{e:#}"),
+        }
+    }
+    let maze = Level::Maze {
+        size: TITLE_MAZE,
+        seed: rand::random(),
+    };
+    items.push(("Random maze".to_string(), TitleItem::Play(maze)));
+    items.push(("Options".to_string(), TitleItem::Options));
+    items.push(("Quit".to_string(), TitleItem::Quit));
+    let (names, items) = items.into_iter().unzip();
+    (Menu::new("", names).with_banner(TITLE), items)
+}
+
+// Offers every level of a campaign up to the furthest one reached.
+fn campaign_menu(dir: &Path, campaign: &Campaign, progress: &Progress) -> (Menu, Vec<TitleItem>) {
+    let unlocked = progress.unlocked(&campaign.name);
+    let mut items = Vec::new();
+    for (index, entry) in campaign.levels.iter().enumerate() {
+        items.push(if index < unlocked {
+            let level = Level::Campaign {
+                dir: dir.to_path_buf(),
+                index,
+            };
+            (entry.name.clone(), TitleItem::Play(level))
+        } else {
+            (format!("{} (locked)", entry.name), TitleItem::Locked)
+        });
+    }
+    items.push(("Options".to_string(), TitleItem::Options));
+    items.push(("Quit".to_string(), TitleItem::Quit));
+    let (names, items) = items.into_iter().unzip();
+    let menu = Menu::new(campaign.name.clone(), names).with_banner(TITLE);
+    (menu, items)
+}
+
+fn pause_menu() -> Menu {
+    let items = ["Resume", "Settings", "Quit"];
+    Menu::new("Paused", items.map(String::from).to_vec())
+}
+
+fn death_menu() -> Menu {
+    let items = ["Restart", "Quit"];
+    Menu::new("You died", items.map(String::from).to_vec())
+}
+
+impl State {
+    // Acts on a key pressed in a menu, returning where that leaves the player or `None` if
+    // they chose to quit.
+    fn navigate(self, input: MenuInput, session: &mut Session) -> Option<State> {
+        match (self, input) {
+            (State::Title(menu, items), MenuInput::Select(i)) => match items[i].clone() {
+                TitleItem::Play(level) => match session.start(level) {
+                    Ok(()) => Some(State::Playing),
+                    Err(e) => {
+                        session.fail(e);
+                        Some(State::Title(menu, items))
+                    }
+                },
+                TitleItem::Options => {
+                    let parent = Box::new(State::Title(menu, items));
+                    Some(State::Settings(session.settings_menu(), parent))
+                }
+                TitleItem::Locked => Some(State::Title(menu, items)),
+                TitleItem::Quit => None,
+            },
+            (State::Paused(_), MenuInput::Back) => Some(State::Playing),
+            (State::Paused(_), MenuInput::Select(0)) => Some(State::Playing),
+            (State::Paused(menu), MenuInput::Select(1)) => Some(State::Settings(
+                session.settings_menu(),
+                Box::new(State::Paused(menu)),
+            )),
+            (State::Paused(_), MenuInput::Select(_)) => None,
+            (State::Dead(menu), MenuInput::Select(0)) => match session.start(session.level.clone())
+            {
+                Ok(()) => Some(State::Playing),
+                Err(e) => {
+                    session.fail(e);
+                    Some(State::Dead(menu))
+                }
+            },
+            (State::Dead(_), MenuInput::Select(_)) => None,
+            (State::Settings(mut menu, parent), input) => {
+                let setting = settings()[menu.selected];
+                let step = match (input, setting) {
+                    (MenuInput::Back, _) | (MenuInput::Select(_), Setting::Back) => {
+                        return Some(*parent)
+                    }
+                    (MenuInput::Select(_) | MenuInput::Increase(_), _) => 1,
+                    (MenuInput::Decrease(_), _) => -1,
+                    (MenuInput::None, _) => 0,
+                };
+                if step != 0 {
+                    session.change_setting(setting, step);
+                    menu.items = session.settings_menu().items;
+                }
+                Some(State::Settings(menu, parent))
+            }
+            (state, _) => Some(state),
+        }
+    }
+
+    fn menu_mut(&mut self) -> Option<&mut Menu> {
+        match self {
+            State::Title(menu, _)
+            | State::Paused(menu)
+            | State::Dead(menu)
+            | State::Settings(menu, _) => Some(menu),
+            State::Playing | State::Leaving { .. } => None,
+        }
+    }
+}
+
+// Plays a game that others can join.
+pub fn host(args: HostArgs) -> Result<()> {
+    run(args.play, Some(SocketAddr::new(args.bind, args.port)))
+}
+
+// Plays a game, listening for other players on `address` if there is one.
+pub fn run(args: PlayArgs, address: Option<SocketAddr>) -> Result<()> {
+    if args.fps_cap <= 0.0 {
+        bail!("--fps-cap has to be positive");
+    }
+    // Recording an animation instead of a demo is picked by the file's extension.
+    let (demo_path, video_path) = match args.record {
+        Some(path) if VideoFormat::from_path(&path).is_some() => (None, Some(path)),
+        path => (path, None),
+    };
+    if address.is_some() && demo_path.is_some() {
+        bail!("Hosted games can't be recorded to a demo, since other players' input isn't kept");
+    }
+    if address.is_some() && args.split {
+        bail!("Hosted games can't be split-screen");
+    }
+    // Without a map to play, the game starts on the title screen.
+    let level = match (args.generate, args.map) {
+        (Some(size), _) => Some(Level::Maze {
+            size,
+            seed: args.seed.unwrap_or_else(rand::random),
+        }),
+        (None, Some(path)) => match args.import_wad {
+            Some(map) => Some(Level::wad(path, map)?),
+            None => Some(Level::file(path)?),
+        },
+        (None, None) => None,
+    };
+    let campaign = match &args.campaign {
+        Some(path) => {
+            let dir = Campaign::locate(path)?;
+            let campaign = Campaign::read(&dir)?;
+            Some((dir, campaign))
+        }
+        None => None,
+    };
+    let progress_path = Progress::default_path().unwrap_or_else(|| PathBuf::from("progress.ron"));
+    let mut progress = Progress::read(&progress_path)?;
+
+    let save_path = args
+        .save
+        .or_else(|| dirs::data_dir().map(|dir| dir.join("rhywbeth").join("quicksave.ron")))
+        .unwrap_or_else(|| PathBuf::from("quicksave.ron"));
+
+    let controls_path = args.controls.or_else(Controls::default_path);
+    let mut controls = match &controls_path {
+        Some(path) => Controls::load_or_create(path)?,
+        None => Controls::default(),
+    };
+    if let Some(sensitivity) = args.sensitivity {
+        controls.mouse.sensitivity = sensitivity;
+    }
+
+    let mut input = Input::new();
+    input.speed = args.speed;
+    input.turn_speed = args.turn_speed;
+    input.controls = controls;
+    #[cfg(feature = "gamepad")]
+    let mut gamepads = if args.no_gamepad {
+        None
+    } else {
+        Some(Gamepads::new()?)
+    };
+    let mut session = Session::new(Level::Demo, input, &args.output)?;
+    session.save_path = Some(save_path);
+    session.controls_path = controls_path;
+    session.fov = args.fov.map(f32::to_radians);
+    if args.split {
+        session.second = Some(SecondPlayer::new(&mut session.game, &session.input));
+    }
+    if let Some(path) = &video_path {
+        let output = &args.output;
+        session.video = Some(VideoRecorder::create(
+            path,
+            output.video_size,
+            output.video_fps,
+        )?);
+    }
+    if let Some(address) = address {
+        session.server = Some(Server::bind(address, &session.game, &session.level)?);
+    }
+    let mut state = match level {
+        Some(level) => {
+            session.start(level)?;
+            State::Playing
+        }
+        None => {
+            let (menu, items) = match &campaign {
+                Some((dir, campaign)) => campaign_menu(dir, campaign, &progress),
+                None => title_menu(&args.maps),
+            };
+            State::Title(menu, items)
+        }
+    };
+    // Made when the game starts, which is after the title screen if there is one.
+    let mut demo = None;
+
+    let frame_time = Duration::from_secs_f32(1.0 / args.fps_cap);
+    let mut last_frame = Instant::now();
+
+    'game: loop {
+        let next_frame = last_frame + frame_time;
+        let mut frame = Frame {
+            analog: session.input.analog,
+            ..Frame::default()
+        };
+        frame.events = session.poll(next_frame)?;
+        if session.closed() {
+            break;
+        }
+        #[cfg(feature = "hot-reload")]
+        session.reload_if_changed();
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepads) = gamepads.as_mut() {
+            frame.gamepad = gamepads.poll(&mut frame.analog);
+        }
+
+        let now = Instant::now();
+        frame.dt = (now - last_frame).as_secs_f32();
+        last_frame = now;
+        if let State::Playing = state {
+            let demo = demo.get_or_insert_with(|| Demo {
+                level: session.level.clone(),
+                camera: session.game.camera,
+                speed: session.input.speed,
+                turn_speed: session.input.turn_speed,
+                sensitivity: session.input.controls.mouse.sensitivity,
+                controls: session.input.controls.clone(),
+                split: session.second.is_some(),
+                frames: Vec::new(),
+            });
+            let flow = session.step(&mut frame)?;
+            if demo_path.is_some() {
+                demo.frames.push(frame);
+            }
+            match flow {
+                Flow::Continue => {}
+                Flow::Pause => state = State::Paused(pause_menu()),
+                Flow::Died => state = State::Dead(death_menu()),
+                Flow::Exit(next) => {
+                    if let Err(e) = session.complete_level(&mut progress, &progress_path) {
+                        session.fail(e);
+                    }
+                    session.renderer.banner = Some(LEVEL_CLEAR.to_string());
+                    state = State::Leaving { next, time: 0.0 };
+                }
+                Flow::Quit => break,
+            }
+            continue;
+        }
+
+        // The game stands still while the screen fades out, so none of it is recorded.
+        if let State::Leaving { next, time } = &mut state {
+            *time += frame.dt;
+            session.renderer.fade = (1.0 - *time / EXIT_FADE).max(0.0);
+            session.render()?;
+            if *time >= EXIT_FADE {
+                session.renderer.fade = 1.0;
+                session.renderer.banner = None;
+                match next.take() {
+                    Some(next) => session.start(next)?,
+                    None => break,
+                }
+                state = State::Playing;
+            }
+            continue;
+        }
+
+        // Nothing that happens in a menu is recorded, since the game doesn't move.
+        for event in frame.events.iter() {
+            let Some(menu) = state.menu_mut() else {
+                break;
+            };
+            let input = menu.handle(event);
+            let Some(next) = state.navigate(input, &mut session) else {
+                break 'game;
+            };
+            state = next;
+        }
+        if let State::Paused(_) = state {
+            if frame.gamepad.contains(&Action::Pause) {
+                state = State::Playing;
+            }
+        }
+        if let State::Title(..) = state {
+            // The demo slowly turns behind the title.
+            session.game.camera.rotate(frame.dt * TITLE_TURN_SPEED);
+        }
+        if let Some(menu) = state.menu_mut() {
+            let menu = menu.clone();
+            session.render_menu(&menu)?;
+        }
+    }
+
+    // The terminal is handed back before waiting on anything else.
+    let video = session.video.take();
+    drop(session);
+    if let Some(video) = video {
+        video.finish()?;
+    }
+    match (&demo_path, demo) {
+        (Some(path), Some(demo)) => demo.write(path),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use clap::Args;
+
+use crate::{
+    backend::event::Event,
+    controls::bound,
+    demo::Demo,
+    input::Input,
+    session::{Flow, OutputArgs, SecondPlayer, Session},
+    video::VideoRecorder,
+};
+
+#[derive(Args)]
+pub struct PlaybackArgs {
+    #[arg(help = "Demo file to replay")]
+    pub demo: PathBuf,
+    #[arg(
+        long,
+        value_name = "GIF|PNG",
+        help = "Record the replay to an animated GIF or PNG"
+    )]
+    pub record: Option<PathBuf>,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+pub fn run(args: PlaybackArgs) -> Result<()> {
+    let demo = Demo::read(&args.demo)?;
+    let mut input = Input::new();
+    input.speed = demo.speed;
+    input.turn_speed = demo.turn_speed;
+    input.controls = demo.controls;
+    // Demos from before the controls had mouse settings only have this.
+    input.controls.mouse.sensitivity = demo.sensitivity;
+    let mut session = Session::new(demo.level, input, &args.output)?;
+    session.game.camera = demo.camera;
+    if demo.split {
+        session.second = Some(SecondPlayer::new(&mut session.game, &session.input));
+    }
+    if let Some(path) = &args.record {
+        let output = &args.output;
+        session.video = Some(VideoRecorder::create(
+            path,
+            output.video_size,
+            output.video_fps,
+        )?);
+    }
+
+    let mut last_frame = Instant::now();
+    for mut frame in demo.frames {
+        // Frames are shown for as long as they took to play, and the quit key stops early.
+        let next_frame = last_frame + Duration::from_secs_f32(frame.dt);
+        let quit = session.poll(next_frame)?.iter().any(|event| {
+            matches!(event, Event::Key(key) if bound(&session.input.controls.quit, key.code))
+        });
+        if quit || session.closed() {
+            break;
+        }
+        thread::sleep(next_frame.saturating_duration_since(Instant::now()));
+        last_frame = next_frame;
+        // Pausing and fading between levels are left out of playback, and dying can only have
+        // been followed by a restart since the recording went on.
+        match session.step(&mut frame)? {
+            Flow::Continue | Flow::Pause => {}
+            Flow::Died => session.start(session.level.clone())?,
+            Flow::Exit(Some(next)) => session.start(next)?,
+            Flow::Exit(None) | Flow::Quit => break,
+        }
+    }
+
+    let video = session.video.take();
+    drop(session);
+    match video {
+        Some(video) => video.finish(),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,371 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Error, Result};
+use wasmtime::{
+    Caller, Config, Engine, Extern, Instance, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder, WasmParams,
+};
+
+use crate::{
+    colour::Rgb,
+    enemy::Enemy,
+    event::GameEvent,
+    pickup::{KeyColour, Pickup},
+    sprite::Sprite,
+    vec2::Vec2,
+    world::World,
+};
+
+// The version of the host interface below. A plugin can export `api_version` returning the
+// version it was written against, and is refused if that isn't this one.
+pub const API_VERSION: i32 = 1;
+// The module plugins import the host functions from.
+pub const HOST_MODULE: &str = "rhywbeth";
+// How much work a plugin can do in one hook before it's stopped, so one stuck in a loop can't
+// hang the game.
+const FUEL: u64 = 10_000_000;
+// The most memory a plugin can have, so one can't take everything the machine has.
+const MEMORY: usize = 64 * 1024 * 1024;
+// What enemies spawned by plugins look like.
+const ENEMY_COLOUR: Rgb = Rgb::RED;
+const ENEMY_SIZE: f32 = 0.8;
+
+// A WebAssembly module with logic for a map, written in any language that compiles to it and
+// only able to touch the game through the host interface. Version 1 of the interface has
+// plugins import these from the `rhywbeth` module:
+//
+//   player_x() -> f32, player_y() -> f32         where the player is
+//   clock() -> f32                               seconds since the level started
+//   spawn_enemy(x: f32, y: f32)                  add an enemy
+//   spawn_pickup(kind: i32, x: f32, y: f32, amount: f32)
+//                                                add health (0), ammo (1) or a red (2), yellow
+//                                                (3) or blue (4) key, `amount` being ignored
+//                                                for keys
+//   set_light(index: i32, colour: i32, intensity: f32)
+//                                                change the map's light at `index`, `colour`
+//                                                being 0xRRGGBB
+//   trigger(id: i32, len: i32)                   set off everything with an ID, like a switch
+//   message(text: i32, len: i32)                 show a line along the bottom of the screen
+//
+// Strings are passed as a pointer to and length of UTF-8 in the plugin's exported `memory`.
+// Plugins can export any of these to be called as the level is played:
+//
+//   api_version() -> i32
+//   on_load()             once the level has loaded, before the first frame
+//   on_tick(dt: f32)      every frame, with the seconds since the last one
+//   on_enter(region: i32) when the player walks into the map's trigger region at that index
+//   on_exit(region: i32)  when they walk out of it
+pub struct Plugin {
+    store: Store<Host>,
+    instance: Instance,
+    // Lines from `message` waiting to be shown.
+    pub messages: Vec<String>,
+}
+
+// What the host functions can see of the game, set before each hook is called, and what
+// they've asked to change, applied to the world afterwards.
+#[derive(Default)]
+struct Host {
+    player: Vec2,
+    clock: f32,
+    changes: Vec<Change>,
+    limits: StoreLimits,
+}
+
+enum Change {
+    SpawnEnemy(Vec2),
+    SpawnPickup(Vec2, Pickup),
+    SetLight {
+        index: usize,
+        colour: Rgb,
+        intensity: f32,
+    },
+    Trigger(String),
+    Message(String),
+}
+
+// Reads a UTF-8 string from the calling plugin's memory.
+fn read_string(caller: &mut Caller<'_, Host>, pointer: i32, len: i32) -> wasmtime::Result<String> {
+    let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+        wasmtime::bail!("Plugin has no memory to pass strings in");
+    };
+    // Checked against the memory before anything is copied, so a bad length can't ask for more
+    // than the plugin has.
+    let start = usize::try_from(pointer).ok();
+    let len = usize::try_from(len).ok();
+    let end = start
+        .zip(len)
+        .and_then(|(start, len)| start.checked_add(len));
+    let (Some(start), Some(end)) = (start, end) else {
+        wasmtime::bail!("Plugin passed a string outside its memory");
+    };
+    if end > memory.data_size(&caller) {
+        wasmtime::bail!("Plugin passed a string outside its memory");
+    }
+    Ok(String::from_utf8(
+        memory.data(&caller)[start..end].to_vec(),
+    )?)
+}
+
+fn link(linker: &mut Linker<Host>) -> wasmtime::Result<()> {
+    linker.func_wrap(HOST_MODULE, "player_x", |caller: Caller<'_, Host>| {
+        caller.data().player.x
+    })?;
+    linker.func_wrap(HOST_MODULE, "player_y", |caller: Caller<'_, Host>| {
+        caller.data().player.y
+    })?;
+    linker.func_wrap(HOST_MODULE, "clock", |caller: Caller<'_, Host>| {
+        caller.data().clock
+    })?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "spawn_enemy",
+        |mut caller: Caller<'_, Host>, x: f32, y: f32| {
+            let change = Change::SpawnEnemy(Vec2::new(x, y));
+            caller.data_mut().changes.push(change);
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "spawn_pickup",
+        |mut caller: Caller<'_, Host>, kind: i32, x: f32, y: f32, amount: f32| {
+            let pickup = match kind {
+                0 => Pickup::Health { amount },
+                1 => Pickup::Ammo {
+                    amount: amount.max(0.0) as u32,
+                },
+                2 => Pickup::Key {
+                    key: KeyColour::Red,
+                },
+                3 => Pickup::Key {
+                    key: KeyColour::Yellow,
+                },
+                4 => Pickup::Key {
+                    key: KeyColour::Blue,
+                },
+                _ => wasmtime::bail!("No kind of pickup {kind}"),
+            };
+            let change = Change::SpawnPickup(Vec2::new(x, y), pickup);
+            caller.data_mut().changes.push(change);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "set_light",
+        |mut caller: Caller<'_, Host>, index: i32, colour: i32, intensity: f32| {
+            let Ok(index) = usize::try_from(index) else {
+                wasmtime::bail!("Plugin set light {index}, but there are no negative lights");
+            };
+            let [_, r, g, b] = colour.to_be_bytes();
+            let change = Change::SetLight {
+                index,
+                colour: Rgb::new(r, g, b),
+                intensity,
+            };
+            caller.data_mut().changes.push(change);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "trigger",
+        |mut caller: Caller<'_, Host>, pointer: i32, len: i32| {
+            let id = read_string(&mut caller, pointer, len)?;
+            caller.data_mut().changes.push(Change::Trigger(id));
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "message",
+        |mut caller: Caller<'_, Host>, pointer: i32, len: i32| {
+            let text = read_string(&mut caller, pointer, len)?;
+            caller.data_mut().changes.push(Change::Message(text));
+            Ok(())
+        },
+    )?;
+    Ok(())
+}
+
+impl Plugin {
+    // Loads a compiled `.wasm` module, or one in the `.wat` text format.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, path)
+            .map_err(Error::from)
+            .with_context(|| format!("Could not load plugin {}", path.display()))?;
+        let mut linker = Linker::new(&engine);
+        link(&mut linker)?;
+        let host = Host {
+            limits: StoreLimitsBuilder::new().memory_size(MEMORY).build(),
+            ..Host::default()
+        };
+        let mut store = Store::new(&engine, host);
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(FUEL)?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(Error::from)
+            .with_context(|| format!("Could not start plugin {}", path.display()))?;
+        if let Ok(version) = instance.get_typed_func::<(), i32>(&mut store, "api_version") {
+            let version = version.call(&mut store, ())?;
+            if version != API_VERSION {
+                bail!(
+                    "Plugin {} needs version {version} of the host interface, this is version {API_VERSION}",
+                    path.display()
+                );
+            }
+        }
+        Ok(Self {
+            store,
+            instance,
+            messages: Vec::new(),
+        })
+    }
+
+    pub fn on_load(&mut self, world: &mut World, player: Vec2) -> Result<()> {
+        // Nothing is listening for events before the first frame.
+        self.call("on_load", world, player, &mut Vec::new(), ())
+    }
+
+    // Runs `on_tick`, then `on_enter` and `on_exit` for every trigger region entered or left in
+    // `events`.
+    pub fn update(
+        &mut self,
+        dt: f32,
+        world: &mut World,
+        player: Vec2,
+        events: &mut Vec<GameEvent>,
+    ) -> Result<()> {
+        self.call("on_tick", world, player, events, dt)?;
+        let crossed: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                GameEvent::Entered(i) => Some(("on_enter", *i as i32)),
+                GameEvent::Left(i) => Some(("on_exit", *i as i32)),
+                _ => None,
+            })
+            .collect();
+        for (hook, i) in crossed {
+            self.call(hook, world, player, events, i)?;
+        }
+        Ok(())
+    }
+
+    // Calls the exported function `hook` if the plugin has one, then makes the changes it
+    // asked for to `world`.
+    fn call<P: WasmParams>(
+        &mut self,
+        hook: &str,
+        world: &mut World,
+        player: Vec2,
+        events: &mut Vec<GameEvent>,
+        params: P,
+    ) -> Result<()> {
+        let Ok(function) = self.instance.get_typed_func::<P, ()>(&mut self.store, hook) else {
+            return Ok(());
+        };
+        let host = self.store.data_mut();
+        host.player = player;
+        host.clock = world.clock;
+        self.store.set_fuel(FUEL)?;
+        let result = function.call(&mut self.store, params);
+        let changes = std::mem::take(&mut self.store.data_mut().changes);
+        result
+            .map_err(Error::from)
+            .with_context(|| format!("Plugin failed in {hook}"))?;
+        for change in changes {
+            match change {
+                Change::SpawnEnemy(position) => {
+                    let sprite = Sprite::new(ENEMY_COLOUR, ENEMY_SIZE);
+                    world
+                        .entities
+                        .spawn_enemy(position, sprite, Enemy::default());
+                }
+                Change::SpawnPickup(position, pickup) => {
+                    world
+                        .entities
+                        .spawn_pickup(position, pickup.sprite(), pickup);
+                }
+                Change::SetLight {
+                    index,
+                    colour,
+                    intensity,
+                } => {
+                    let Some(light) = world.lights.get_mut(index) else {
+                        bail!("Plugin set light {index}, but the map has no such light");
+                    };
+                    light.colour = colour;
+                    light.intensity = intensity;
+                }
+                Change::Trigger(id) => world.trigger(&id, events),
+                Change::Message(text) => self.messages.push(text),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::fs;
+
+    fn plugin(test: &str, source: &str) -> Result<Plugin> {
+        let directory = scratch(test);
+        let path = directory.join("plugin.wat");
+        fs::write(&path, source).unwrap();
+        let plugin = Plugin::load(&path);
+        fs::remove_dir_all(directory).unwrap();
+        plugin
+    }
+
+    // A plugin that sends `message` a string at `pointer` and `len` when it loads.
+    fn message(test: &str, pointer: i32, len: i32) -> Result<Vec<String>> {
+        let source = format!(
+            r#"(module
+                (import "rhywbeth" "message" (func $message (param i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "hello")
+                (func (export "on_load") (call $message (i32.const {pointer}) (i32.const {len}))))"#
+        );
+        let mut plugin = plugin(test, &source)?;
+        plugin.on_load(&mut World::demo(), Vec2::ZERO)?;
+        Ok(plugin.messages)
+    }
+
+    #[test]
+    fn strings_must_be_in_the_plugins_memory() {
+        assert_eq!(message("string", 0, 5).unwrap(), ["hello"]);
+        assert!(message("string-past-end", 65_530, 100).is_err());
+        assert!(message("string-huge", 0, i32::MAX).is_err());
+        assert!(message("string-negative-length", 0, -1).is_err());
+        assert!(message("string-negative-pointer", -1, 5).is_err());
+    }
+
+    #[test]
+    fn negative_lights_are_refused() {
+        let source = r#"(module
+            (import "rhywbeth" "set_light" (func $set_light (param i32 i32 f32)))
+            (func (export "on_load")
+              (call $set_light (i32.const -1) (i32.const 0xffffff) (f32.const 1.0))))"#;
+        let mut plugin = plugin("negative-light", source).unwrap();
+        assert!(plugin.on_load(&mut World::demo(), Vec2::ZERO).is_err());
+    }
+
+    #[test]
+    fn plugins_cant_take_too_much_memory() {
+        assert!(plugin("memory-start", "(module (memory 2000))").is_err());
+        // Growing past the limit fails the way running out of memory would.
+        let source = r#"(module
+            (memory 1)
+            (func (export "on_load")
+              (if (i32.ne (memory.grow (i32.const 2000)) (i32.const -1)) (then unreachable))))"#;
+        let mut plugin = plugin("memory-grow", source).unwrap();
+        plugin.on_load(&mut World::demo(), Vec2::ZERO).unwrap();
+    }
+}
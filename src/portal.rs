@@ -0,0 +1,29 @@
+use crate::{entities::Entity, geometry::LineSegment, vec2::Vec2};
+
+// A doorway into somewhere else: looking or walking through it comes out of its partner, as if
+// the two were the same segment. Each end goes to the opposite end of the partner, so walking
+// into one side comes out of the partner's other side.
+pub struct Portal {
+    pub segment: LineSegment,
+    pub partner: Entity,
+}
+
+// How much anything passing from `from` to `to` is turned.
+pub fn turn(from: &LineSegment, to: &LineSegment) -> f32 {
+    (to.start - to.end).angle() - from.direction().angle()
+}
+
+// Where `point`, near `from`, ends up after passing through to `to`.
+pub fn carry(from: &LineSegment, to: &LineSegment, point: Vec2) -> Vec2 {
+    to.end + (point - from.start).rotate(turn(from, to))
+}
+
+// Whether moving from `start` to `end` goes through the segment. Starting on it doesn't count,
+// so whatever comes out of a portal isn't sent straight back.
+pub fn crossed(segment: &LineSegment, start: Vec2, end: Vec2) -> bool {
+    let side = segment.direction().cross(start - segment.start);
+    side != 0.0
+        && segment
+            .intersects(&LineSegment::from_points(start, end, segment.colour))
+            .is_some()
+}
@@ -0,0 +1,47 @@
+use crate::{colour::Rgb, sprite::Sprite};
+
+// World units per second.
+pub const SPEED: f32 = 10.0;
+// How close a projectile has to pass to something to hit it.
+pub const RADIUS: f32 = 0.3;
+// Damage done by the player's shots.
+pub const PLAYER_DAMAGE: f32 = 25.0;
+// Shots the player starts every level with.
+pub const START_AMMO: u32 = 20;
+// Seconds between the player's shots.
+pub const PLAYER_RELOAD: f32 = 0.4;
+// Seconds before a projectile that hasn't hit anything is cleared away.
+const LIFETIME: f32 = 3.0;
+
+// Who fired a projectile, which it can't hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Owner {
+    Player,
+    Enemy,
+}
+
+pub struct Projectile {
+    pub owner: Owner,
+    pub damage: f32,
+    // Seconds left before it's cleared away.
+    pub lifetime: f32,
+}
+
+impl Projectile {
+    pub fn new(owner: Owner, damage: f32) -> Self {
+        Self {
+            owner,
+            damage,
+            lifetime: LIFETIME,
+        }
+    }
+
+    // A small bright ball floating around chest height.
+    pub fn sprite(&self) -> Sprite {
+        let colour = match self.owner {
+            Owner::Player => Rgb::new(255, 255, 128),
+            Owner::Enemy => Rgb::new(255, 96, 32),
+        };
+        Sprite::new(colour, 0.15).with_elevation(0.45)
+    }
+}
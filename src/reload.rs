@@ -0,0 +1,74 @@
+use std::{
+    ffi::OsString,
+    path::Path,
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+// How long a map has to go without changing before it's read again. Editors often save in a
+// few writes, and reading in between could catch it half written.
+const SETTLE: Duration = Duration::from_millis(100);
+
+// Notices a map file being saved, e.g. from an editor alongside the game. The directory is
+// watched rather than the file, since editors that save by writing a new file and renaming it
+// over the old one would otherwise leave the watch on a file that's gone.
+pub struct MapWatcher {
+    // Kept so the watch lasts as long as this does.
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    name: OsString,
+    // When the file last changed, if it hasn't been read since.
+    changed: Option<Instant>,
+}
+
+impl MapWatcher {
+    pub fn new(path: &Path) -> Result<Self> {
+        let name = path
+            .file_name()
+            .with_context(|| format!("{} is not a file", path.display()))?
+            .to_os_string();
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let (sender, events) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(sender).context("Could not watch for changes to maps")?;
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Could not watch {}", directory.display()))?;
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            name,
+            changed: None,
+        })
+    }
+
+    // Whether the map has been saved since this last said so, and has settled.
+    pub fn changed(&mut self) -> bool {
+        for event in self.events.try_iter().flatten() {
+            let written = matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any
+            );
+            let ours = event
+                .paths
+                .iter()
+                .any(|path| path.file_name() == Some(self.name.as_os_str()));
+            if written && ours {
+                self.changed = Some(Instant::now());
+            }
+        }
+        match self.changed {
+            Some(at) if at.elapsed() >= SETTLE => {
+                self.changed = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
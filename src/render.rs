@@ -0,0 +1,1079 @@
+use std::{f32::consts::PI, fmt, io::Write, str::FromStr, time::Duration};
+
+use anyhow::{bail, Error, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use web_time::Instant;
+
+use crate::{
+    backend::{Backend, Headless},
+    canvas::Canvas,
+    colour::{ColourMode, Rgb},
+    damage::draw_damage_numbers,
+    framebuffer::{Cell, FrameBuffer},
+    geometry::{Hit, MAX_DISTANCE},
+    hud::{self, health_bar, Align, Anchor, Overlay, Widget},
+    menu::Menu,
+    minimap::draw_minimap,
+    narration::{describe, Narration},
+    overhead::draw_overhead,
+    particle::draw_particles,
+    sector::{Heights, Sector},
+    shake::CameraShake,
+    sprite::Sprite,
+    theme::Theme,
+    vec2::Vec2,
+    viewmodel::draw_viewmodel,
+    weather::draw_weather,
+    world::{Sky, Surface, World},
+};
+
+pub const MIN_FOV: f32 = PI / 6.0;
+// How far the horizon can move from the middle of the screen, in screen heights.
+pub const MAX_PITCH: f32 = 0.5;
+pub const MAX_FOV: f32 = 5.0 * PI / 6.0;
+
+// Terminal cells are roughly twice as tall as they are wide.
+pub const CELL_ASPECT: f32 = 2.0;
+
+// How far the eye is above whatever floor the camera stands on, halfway up a standard wall.
+pub const EYE_HEIGHT: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Camera {
+    pub position: Vec2,
+    // The angle the camera looks along, down the middle of the view.
+    pub rotation: f32,
+    // Horizontal field of view in radians.
+    pub fov: f32,
+    // How far the horizon is pushed down the screen, in screen heights. Positive looks up.
+    pub pitch: f32,
+    // Height of the eye above the ground, where standard walls run from 0.0 to 1.0.
+    #[serde(default = "default_eye")]
+    pub eye: f32,
+}
+
+fn default_eye() -> f32 {
+    EYE_HEIGHT
+}
+
+impl Camera {
+    pub fn new(position: Vec2, rotation: f32) -> Self {
+        Self {
+            position,
+            rotation,
+            fov: PI / 2.0,
+            pitch: 0.0,
+            eye: EYE_HEIGHT,
+        }
+    }
+
+    pub fn look(&mut self, delta: f32) {
+        self.pitch = (self.pitch + delta).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(MIN_FOV, MAX_FOV);
+    }
+
+    pub fn rotate(&mut self, delta: f32) {
+        self.rotation += delta;
+        if self.rotation < -PI {
+            self.rotation += 2.0 * PI;
+        } else if self.rotation > PI {
+            self.rotation -= 2.0 * PI;
+        }
+    }
+
+    // Unit vectors along the way the camera looks and out to its right, along the floor.
+    pub fn forward(&self) -> Vec2 {
+        Vec2::from_angle(self.rotation)
+    }
+
+    pub fn right(&self) -> Vec2 {
+        Vec2::from_angle(self.rotation - PI / 2.0)
+    }
+}
+
+// Maps between world angles/distances and canvas pixels for a flat camera plane.
+pub struct Projection {
+    pub width: usize,
+    pub height: usize,
+    half_tan: f32,
+    focal: f32,
+    focal_y: f32,
+    pitch: f32,
+    eye: f32,
+}
+
+impl Projection {
+    // `aspect` is the height of a canvas pixel relative to its width.
+    pub fn new(camera: &Camera, width: usize, height: usize, aspect: f32) -> Self {
+        let half_tan = (camera.fov / 2.0).tan();
+        let focal = width as f32 / 2.0 / half_tan;
+        Self {
+            width,
+            height,
+            half_tan,
+            focal,
+            focal_y: focal / aspect,
+            pitch: camera.pitch,
+            eye: camera.eye,
+        }
+    }
+
+    // How far left of the view direction the ray through column `x` points.
+    pub fn ray_offset(&self, x: usize) -> f32 {
+        ((1.0 - (2.0 * x as f32 + 1.0) / self.width as f32) * self.half_tan).atan()
+    }
+
+    // The (fractional) column something `offset` radians left of the view direction lands on.
+    pub fn column(&self, offset: f32) -> f32 {
+        self.width as f32 / 2.0 - offset.tan() * self.focal
+    }
+
+    // Screen-space width of something `size` world units wide.
+    pub fn width_of(&self, size: f32, distance: f32) -> f32 {
+        self.focal * size / distance
+    }
+
+    // How many pixels a full-height wall `distance` away covers.
+    pub fn wall_height(&self, distance: f32) -> f32 {
+        self.focal_y / distance
+    }
+
+    pub fn horizon(&self) -> f32 {
+        self.height as f32 * (0.5 + self.pitch)
+    }
+
+    // The (fractional) row something at `height` and `distance` away lands on.
+    pub fn row(&self, height: f32, distance: f32) -> f32 {
+        self.horizon() + (self.eye - height) * self.wall_height(distance)
+    }
+
+    // How far along the view direction a floor or ceiling at `height` seen through row `y` is.
+    pub fn surface_distance(&self, y: usize, height: f32) -> f32 {
+        (self.eye - height) * self.focal_y / (y as f32 + 0.5 - self.horizon())
+    }
+}
+
+// The part of the terminal a camera's view is drawn into, in cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Viewport {
+    // The whole of a terminal `size` cells big.
+    pub fn full(size: (u16, u16)) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: size.0,
+            height: size.1,
+        }
+    }
+
+    // The left and right halves, the right one a column wider if the width is odd.
+    pub fn split(self) -> (Self, Self) {
+        let half = self.width / 2;
+        let left = Self {
+            width: half,
+            ..self
+        };
+        let right = Self {
+            x: self.x + half,
+            width: self.width - half,
+            ..self
+        };
+        (left, right)
+    }
+}
+
+// How a canvas is turned into terminal cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    // One pixel per cell, drawn as a coloured space.
+    Blocks,
+    // Two pixels per cell stacked vertically, drawn as an upper half block.
+    HalfBlocks,
+    // A 2x4 grid of dots per cell, dithered by brightness and tinted with the cell's average
+    // colour. Works without background colours.
+    Braille,
+    // One pixel per cell, drawn as a character from a ramp of ever denser ones by brightness,
+    // tinted with the pixel's colour. Works without background colours.
+    Ascii,
+}
+
+impl FromStr for Output {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "blocks" => Ok(Output::Blocks),
+            "half-blocks" => Ok(Output::HalfBlocks),
+            "braille" => Ok(Output::Braille),
+            "ascii" => Ok(Output::Ascii),
+            _ => bail!("Unknown output {s}, expected blocks, half-blocks, braille or ascii"),
+        }
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Output::Blocks => "blocks",
+            Output::HalfBlocks => "half-blocks",
+            Output::Braille => "braille",
+            Output::Ascii => "ascii",
+        })
+    }
+}
+
+impl Output {
+    pub const ALL: [Output; 4] = [
+        Output::Blocks,
+        Output::HalfBlocks,
+        Output::Braille,
+        Output::Ascii,
+    ];
+
+    pub fn pixels_per_cell(self) -> (usize, usize) {
+        match self {
+            Output::Blocks | Output::Ascii => (1, 1),
+            Output::HalfBlocks => (1, 2),
+            Output::Braille => (2, 4),
+        }
+    }
+
+    // The height of one canvas pixel relative to its width.
+    pub fn pixel_aspect(self) -> f32 {
+        let (px, py) = self.pixels_per_cell();
+        CELL_ASPECT * px as f32 / py as f32
+    }
+}
+
+// How long a message from `notify` stays on screen.
+const MESSAGE_TIME: Duration = Duration::from_secs(2);
+// How bright the game stays behind a menu.
+const MENU_DIM: f32 = 0.35;
+// How long a flash from `flash` takes to fade, and how strongly it tints the screen at first.
+const FLASH_TIME: Duration = Duration::from_millis(250);
+const FLASH_STRENGTH: f32 = 0.4;
+// How much of a mirror's own colour shows over what it reflects.
+const MIRROR_TINT: f32 = 0.2;
+// How much each new frame moves the averaged frame rate.
+const FRAME_SMOOTHING: f32 = 0.1;
+
+pub struct Renderer {
+    pub output: Output,
+    pub theme: Theme,
+    pub minimap: bool,
+    // Whether to show the frame rate and how much work each frame took along the top.
+    pub stats: bool,
+    // Whether to draw the world from above with the rays being cast, instead of the scene.
+    pub overhead: bool,
+    // Whether to draw the player's gun over the scene.
+    pub viewmodel: bool,
+    // How bright the frame is drawn, from 0.0 (black) to 1.0, for fading in and out.
+    pub fade: f32,
+    // Shown in big letters in the middle of the screen, over the fade.
+    pub banner: Option<String>,
+    // The line being typed into the console while it's open.
+    pub console: Option<String>,
+    // Whether to describe what's around each player in words, with or instead of the scene.
+    pub narration: Option<Narration>,
+    // Knocks every view about for a moment after a hit, before its rays are cast.
+    pub shake: CameraShake,
+    // How many pixels the backend shows each cell with, if it draws the scene pixel for pixel
+    // instead of as cells.
+    cell_pixels: Option<(usize, usize)>,
+    // The whole screen's pixels, which each view is copied into when drawing pixel for pixel.
+    screen: Canvas,
+    message: Option<(String, Instant)>,
+    flash: Option<(Rgb, Instant)>,
+    last_draw: Option<Instant>,
+    // Seconds between frames, smoothed so the frame rate is readable.
+    frame_interval: f32,
+    canvas: Canvas,
+    // One view's cells, before they're copied into `frame`.
+    view: FrameBuffer,
+    frame: FrameBuffer,
+}
+
+impl Renderer {
+    pub fn new(output: Output) -> Self {
+        Self {
+            output,
+            theme: Theme::classic(),
+            minimap: true,
+            stats: false,
+            overhead: false,
+            viewmodel: true,
+            fade: 1.0,
+            banner: None,
+            console: None,
+            narration: None,
+            shake: CameraShake::default(),
+            cell_pixels: None,
+            screen: Canvas::new(0, 0),
+            message: None,
+            flash: None,
+            last_draw: None,
+            frame_interval: 0.0,
+            canvas: Canvas::new(0, 0),
+            view: FrameBuffer::new(0, 0),
+            frame: FrameBuffer::new(0, 0),
+        }
+    }
+
+    pub fn render(
+        &mut self,
+        size: (u16, u16),
+        camera: &Camera,
+        world: &World,
+        backend: &mut dyn Backend,
+    ) -> Result<()> {
+        self.render_views(
+            size,
+            &[(Viewport::full(size), *camera)],
+            world,
+            None,
+            backend,
+        )
+    }
+
+    // Draws each camera into its own part of the screen, e.g. side by side for split-screen,
+    // with the HUD over the lot, and shows it on `backend`. The game is dimmed behind `menu` if
+    // there is one.
+    pub fn render_views(
+        &mut self,
+        size: (u16, u16),
+        views: &[(Viewport, Camera)],
+        world: &World,
+        menu: Option<&Menu>,
+        backend: &mut dyn Backend,
+    ) -> Result<()> {
+        self.cell_pixels = backend.cell_pixels();
+        self.draw_views(size, views, world, menu);
+        if self.cell_pixels.is_some() {
+            backend.present_pixels(&mut self.screen, &self.frame)
+        } else {
+            backend.present(&self.frame)
+        }
+    }
+
+    // How many pixels each cell is drawn with. A backend drawing pixel for pixel has as many as
+    // it shows, with the HUD's cells drawn over them.
+    fn pixels_per_cell(&self) -> (usize, usize) {
+        self.cell_pixels
+            .unwrap_or_else(|| self.output.pixels_per_cell())
+    }
+
+    // Pixels shown pixel for pixel are square.
+    fn pixel_aspect(&self) -> f32 {
+        if self.cell_pixels.is_some() {
+            return 1.0;
+        }
+        self.output.pixel_aspect()
+    }
+
+    fn draw_views(
+        &mut self,
+        size: (u16, u16),
+        views: &[(Viewport, Camera)],
+        world: &World,
+        menu: Option<&Menu>,
+    ) {
+        self.frame.resize(size.0, size.1);
+        let narrated_only = self.narration == Some(Narration::Only);
+        if self.cell_pixels.is_some() || narrated_only {
+            // Only the HUD goes into the cells, and anything left empty shows the scene.
+            self.frame.cells.fill(Cell::default());
+            let (px, py) = self.pixels_per_cell();
+            self.screen
+                .resize(size.0 as usize * px, size.1 as usize * py);
+        }
+        let start = Instant::now();
+        let brightness = self.fade * if menu.is_some() { MENU_DIM } else { 1.0 };
+        let mut tint = None;
+        if let Some((colour, start)) = self.flash {
+            let left = 1.0 - start.elapsed().as_secs_f32() / FLASH_TIME.as_secs_f32();
+            if left > 0.0 {
+                tint = Some((colour, left * FLASH_STRENGTH));
+            } else {
+                self.flash = None;
+            }
+        }
+        let mut scene = SceneStats::default();
+        for (viewport, camera) in views.iter().filter(|_| !narrated_only) {
+            let camera = self.shake.apply(camera);
+            let view = self.draw_view(*viewport, &camera, world, brightness, tint);
+            scene.rays += view.rays;
+            scene.segment_tests += view.segment_tests;
+        }
+        let draw_time = start.elapsed();
+        if let Some(last) = self.last_draw {
+            let interval = (start - last).as_secs_f32();
+            self.frame_interval = if self.frame_interval == 0.0 {
+                interval
+            } else {
+                self.frame_interval * (1.0 - FRAME_SMOOTHING) + interval * FRAME_SMOOTHING
+            };
+        }
+        self.last_draw = Some(start);
+        let narration = match self.narration {
+            Some(_) => views
+                .iter()
+                .map(|(_, camera)| describe(camera, world))
+                .collect(),
+            None => Vec::new(),
+        };
+        self.draw_hud(world, menu, &narration, scene, draw_time);
+    }
+
+    // Draws what `camera` sees into `viewport`, dimmed to `brightness` and tinted by a flash.
+    fn draw_view(
+        &mut self,
+        viewport: Viewport,
+        camera: &Camera,
+        world: &World,
+        brightness: f32,
+        tint: Option<(Rgb, f32)>,
+    ) -> SceneStats {
+        let (px, py) = self.pixels_per_cell();
+        self.canvas
+            .resize(viewport.width as usize * px, viewport.height as usize * py);
+        self.canvas.aspect = self.pixel_aspect();
+        let scene = if self.overhead {
+            draw_overhead(&mut self.canvas, camera, world)
+        } else {
+            let scene = draw_scene(&mut self.canvas, camera, world);
+            // Screen-space overlays go on top of the finished scene.
+            if self.viewmodel {
+                draw_viewmodel(&mut self.canvas, world.recoil());
+            }
+            if self.minimap {
+                draw_minimap(&mut self.canvas, camera, world, &self.theme.hud);
+            }
+            scene
+        };
+        if brightness < 1.0 {
+            for pixel in self.canvas.pixels.iter_mut() {
+                *pixel = pixel.scale(brightness);
+            }
+        }
+        if let Some((colour, amount)) = tint {
+            for pixel in self.canvas.pixels.iter_mut() {
+                *pixel = pixel.lerp(colour, amount);
+            }
+        }
+        if self.cell_pixels.is_some() {
+            self.screen.blit(
+                &self.canvas,
+                viewport.x as usize * px,
+                viewport.y as usize * py,
+            );
+            return scene;
+        }
+        self.view.resize(viewport.width, viewport.height);
+        encode(&self.canvas, &mut self.view, self.output);
+        self.frame.blit(&self.view, viewport.x, viewport.y);
+        scene
+    }
+
+    // Screen-space overlays go on top of every view.
+    fn draw_hud(
+        &mut self,
+        world: &World,
+        menu: Option<&Menu>,
+        narration: &[String],
+        scene: SceneStats,
+        draw_time: Duration,
+    ) {
+        let mut overlay = Overlay::default();
+        let mut top_left = Vec::new();
+        for line in narration {
+            top_left.push(Widget::text(line, self.theme.hud.text));
+        }
+        if self.stats {
+            let fps = if self.frame_interval > 0.0 {
+                1.0 / self.frame_interval
+            } else {
+                0.0
+            };
+            let line = format!(
+                "{fps:.1} fps, {:.2?} drawing, {} rays, {} segment tests",
+                draw_time, scene.rays, scene.segment_tests
+            );
+            top_left.push(Widget::text(line, self.theme.hud.text));
+        }
+        if let Some(line) = &self.console {
+            top_left.push(Widget::text(format!("> {line}_"), self.theme.hud.text));
+        }
+        overlay.add(Anchor::TopLeft, Widget::column(top_left, Align::Start));
+        self.message = self
+            .message
+            .take()
+            .filter(|(_, until)| *until > Instant::now());
+        let message = self.message.as_ref().map_or("", |(message, _)| message);
+        let mut bottom_left = Vec::new();
+        if !self.overhead {
+            // A bar means nothing read out, so it's spelled out when there's nothing else.
+            let bar = if self.narration == Some(Narration::Only) {
+                let percent = world.health.fraction() * 100.0;
+                Widget::text(format!("health {percent:.0}%"), self.theme.hud.text)
+            } else {
+                health_bar(&world.health, self.frame.width, &self.theme.hud)
+            };
+            bottom_left.push(Widget::padded(bar, (1, 0)));
+            let inventory =
+                Widget::padded(hud::inventory(&world.inventory, &self.theme.hud), (1, 0));
+            overlay.add(Anchor::BottomRight, inventory);
+        }
+        bottom_left.push(Widget::text(message, self.theme.hud.text));
+        overlay.add(
+            Anchor::BottomLeft,
+            Widget::column(bottom_left, Align::Start),
+        );
+        if let Some(banner) = &self.banner {
+            let banner = hud::banner(banner, self.theme.hud.banner, self.frame.width);
+            overlay.add(Anchor::Centre, banner);
+        }
+        if let Some(menu) = menu {
+            overlay.add(
+                Anchor::Centre,
+                menu.widget(self.frame.width, &self.theme.menu),
+            );
+        }
+        overlay.draw(&mut self.frame);
+    }
+
+    // Tints the whole screen briefly, e.g. when picking something up.
+    pub fn flash(&mut self, colour: Rgb) {
+        self.flash = Some((colour, Instant::now()));
+    }
+
+    // Shows a line of text along the bottom of the screen for a couple of seconds.
+    pub fn notify(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), Instant::now() + MESSAGE_TIME));
+    }
+
+    // Writes the last frame drawn as plain ANSI text, e.g. for a .ans file.
+    pub fn write_ansi(&self, colours: ColourMode, out: &mut impl Write) -> Result<()> {
+        self.frame.write_ansi(colours, out)
+    }
+}
+
+// Draws a single frame as ANSI text in true colour with half blocks, without a terminal, e.g.
+// to be shown somewhere else.
+pub fn render_to_string(size: (u16, u16), camera: &Camera, world: &World) -> String {
+    let mut renderer = Renderer::new(Output::HalfBlocks);
+    let mut backend = Headless::new(size);
+    // Drawing without a screen can't fail.
+    renderer.render(size, camera, world, &mut backend).unwrap();
+    let mut out = Vec::new();
+    renderer
+        .write_ansi(ColourMode::TrueColour, &mut out)
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+// Draws a single frame as plain text, shaded with ASCII characters, a line per row and no
+// colour escapes, without a terminal. The same camera and world always give the same string,
+// so frames can be snapshotted and compared.
+pub fn render_to_plain_string(size: (u16, u16), camera: &Camera, world: &World) -> String {
+    let mut renderer = Renderer::new(Output::Blocks);
+    let mut backend = Headless::new(size);
+    // Drawing without a screen can't fail.
+    renderer.render(size, camera, world, &mut backend).unwrap();
+    let frame = &renderer.frame;
+    let mut out = String::with_capacity(frame.cells.len() + frame.height as usize);
+    for row in frame.cells.chunks(frame.width.max(1) as usize) {
+        // The scene is drawn as blank cells in the background colour, while text keeps its own.
+        out.extend(row.iter().map(|cell| match (cell.glyph, cell.fg, cell.bg) {
+            (' ', None, Some(bg)) => ramp_glyph(bg),
+            (glyph, _, _) => glyph,
+        }));
+        out.push('\n');
+    }
+    out
+}
+
+// Turns canvas pixels into terminal cells according to `output`.
+pub fn encode(canvas: &Canvas, frame: &mut FrameBuffer, output: Output) {
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let (cx, cy) = (x as usize, y as usize);
+            let cell = match output {
+                Output::Blocks => Cell {
+                    glyph: ' ',
+                    fg: None,
+                    bg: Some(canvas.get(cx, cy)),
+                },
+                Output::HalfBlocks => Cell {
+                    glyph: '▀',
+                    fg: Some(canvas.get(cx, cy * 2)),
+                    bg: Some(canvas.get(cx, cy * 2 + 1)),
+                },
+                Output::Braille => {
+                    let (glyph, colour) = braille_cell(canvas, cx, cy);
+                    Cell {
+                        glyph,
+                        fg: Some(colour),
+                        bg: None,
+                    }
+                }
+                Output::Ascii => {
+                    let (glyph, colour) = ascii_cell(canvas.get(cx, cy));
+                    Cell {
+                        glyph,
+                        fg: Some(colour),
+                        bg: None,
+                    }
+                }
+            };
+            frame.set(x, y, cell);
+        }
+    }
+}
+
+// An ASCII character about as bright as `pixel`, from a space for black up to `@` for white.
+fn ramp_glyph(pixel: Rgb) -> char {
+    const RAMP: &[u8] = b" .:-=+*#%@";
+    // Green looks far brighter than blue at the same strength.
+    let luma = 0.299 * pixel.r as f32 + 0.587 * pixel.g as f32 + 0.114 * pixel.b as f32;
+    RAMP[(luma / 256.0 * RAMP.len() as f32) as usize] as char
+}
+
+// Packs the 2x4 block of pixels behind a cell into a braille pattern, lighting each dot whose
+// brightness beats its ordered-dither threshold.
+fn braille_cell(canvas: &Canvas, x: usize, y: usize) -> (char, Rgb) {
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+    const THRESHOLDS: [[f32; 2]; 4] = [[0.0, 4.0], [6.0, 2.0], [1.0, 5.0], [7.0, 3.0]];
+    let mut pattern = 0;
+    let mut total = (0u32, 0u32, 0u32);
+    for dy in 0..4 {
+        for dx in 0..2 {
+            let pixel = canvas.get(x * 2 + dx, y * 4 + dy);
+            total.0 += pixel.r as u32;
+            total.1 += pixel.g as u32;
+            total.2 += pixel.b as u32;
+            let brightness = pixel.r.max(pixel.g).max(pixel.b) as f32 / 255.0;
+            if brightness > (THRESHOLDS[dy][dx] + 0.5) / 8.0 {
+                pattern |= DOTS[dy][dx];
+            }
+        }
+    }
+    let colour = Rgb::new(
+        (total.0 / 8) as u8,
+        (total.1 / 8) as u8,
+        (total.2 / 8) as u8,
+    );
+    let colour = colour.scale(255.0 / colour.r.max(colour.g).max(colour.b).max(1) as f32);
+    (char::from_u32(0x2800 + pattern).unwrap(), colour)
+}
+
+// Picks the character as dense as the pixel looks bright, in the pixel's colour at full
+// brightness so that the character alone shows how lit it is.
+fn ascii_cell(pixel: Rgb) -> (char, Rgb) {
+    let peak = pixel.r.max(pixel.g).max(pixel.b).max(1);
+    (ramp_glyph(pixel), pixel.scale(255.0 / peak as f32))
+}
+
+// How much work drawing a scene took.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SceneStats {
+    pub rays: usize,
+    pub segment_tests: usize,
+}
+
+pub fn draw_scene(canvas: &mut Canvas, camera: &Camera, world: &World) -> SceneStats {
+    let projection = Projection::new(camera, canvas.width, canvas.height, canvas.aspect);
+    draw_surfaces(canvas, camera, world, &projection);
+    // Every column's ray is independent, so they're cast in parallel and drawn afterwards.
+    let hits: Vec<_> = (0..canvas.width)
+        .into_par_iter()
+        .map(|x| {
+            let offset = projection.ray_offset(x);
+            let mut tests = 0;
+            let mut hits = world.cast_layers(
+                camera.position,
+                camera.rotation + offset,
+                MAX_DISTANCE,
+                &mut tests,
+            );
+            for hit in hits.iter_mut() {
+                // Measure along the view direction rather than along the ray, otherwise flat
+                // walls bulge towards the middle of the screen.
+                hit.distance *= offset.cos();
+            }
+            // Sprites can't be seen past the first solid wall, even a mirror.
+            let depth = hits
+                .iter()
+                .find(|h| !h.is_see_through())
+                .map(|h| h.distance);
+            // See-through walls, mirrors and sectors are drawn over the scene later, furthest
+            // first.
+            let hit = hits.pop_if(|h| !h.is_see_through());
+            let mut layers = sector_layers(camera, world, offset, depth.unwrap_or(MAX_DISTANCE));
+            layers.extend(hits.into_iter().map(Layer::Wall));
+            layers.sort_by(|a, b| a.distance().total_cmp(&b.distance()));
+            (hit, layers, depth, tests)
+        })
+        .collect();
+    let nan = hits
+        .iter()
+        .filter(|(hit, ..)| hit.as_ref().is_some_and(|h| h.distance.is_nan()))
+        .count();
+    if nan > 0 {
+        warn!(
+            "{nan} rays hit walls at a NaN distance from {:?}",
+            camera.position
+        );
+    }
+    let stats = SceneStats {
+        rays: hits.len(),
+        segment_tests: hits.iter().map(|(.., tests)| tests).sum(),
+    };
+    let mut depth = vec![f32::INFINITY; canvas.width];
+    let mut layers = Vec::with_capacity(canvas.width);
+    for (x, (hit, see_through, nearest, _)) in hits.into_iter().enumerate() {
+        if let Some(hit) = hit {
+            draw_wall(canvas, world, &projection, x, &hit);
+        }
+        depth[x] = nearest.unwrap_or(f32::INFINITY);
+        layers.push(see_through);
+    }
+    draw_sprites(canvas, camera, world, &projection, &depth, &mut layers);
+    draw_layers(canvas, camera, world, &projection, &mut layers, 0.0);
+    draw_particles(canvas, camera, world, &projection, &depth);
+    draw_damage_numbers(canvas, camera, &world.damage_numbers, &projection, &depth);
+    draw_weather(canvas, camera, world, &projection, &depth);
+    stats
+}
+
+// Something drawn over the walls in order of distance, interleaved with the sprites.
+enum Layer {
+    Wall(Hit),
+    // The stretch of a floor or ceiling at `height` between `near` and `far` along a column's
+    // ray, which is scaled so that a distance along the view direction maps to a world offset.
+    Surface {
+        near: f32,
+        far: f32,
+        height: f32,
+        surface: Surface,
+        ray: Vec2,
+    },
+}
+
+impl Layer {
+    fn distance(&self) -> f32 {
+        match self {
+            Layer::Wall(hit) => hit.distance,
+            Layer::Surface { far, .. } => *far,
+        }
+    }
+}
+
+// The floors and ceilings of each sector a column's ray passes through before `limit`, and
+// the steps between them. Columns that don't pass into any sector are left to `draw_surfaces`.
+fn sector_layers(camera: &Camera, world: &World, offset: f32, limit: f32) -> Vec<Layer> {
+    let angle = camera.rotation + offset;
+    let crossings = world.crossings(camera.position, angle, limit / offset.cos());
+    if crossings.is_empty() {
+        return Vec::new();
+    }
+    let ray = Vec2::from_angle(angle) * (1.0 / offset.cos());
+    let heights = |sector: Option<&Sector>| sector.map_or(Heights::DEFAULT, Sector::heights);
+    let mut layers = Vec::new();
+    let mut sector = world.sector_at(camera.position);
+    let mut near = 0.0;
+    for crossing in crossings.into_iter().map(Some).chain([None]) {
+        let far = crossing
+            .as_ref()
+            .map_or(limit, |c| c.hit.distance * offset.cos());
+        let here = heights(sector);
+        // Floors above the eye and ceilings below it are only seen edge on, from their steps.
+        if here.floor < camera.eye {
+            let floor = sector.and_then(|s| s.floor).unwrap_or(world.floor);
+            layers.push(Layer::Surface {
+                near,
+                far,
+                height: here.floor,
+                surface: floor,
+                ray,
+            });
+        }
+        // Under an open sky, only sectors with a ceiling of their own have one.
+        let ceiling = match (sector.and_then(|s| s.ceiling), world.sky) {
+            (Some(ceiling), _) => Some(ceiling),
+            (None, Some(_)) => None,
+            (None, None) => Some(world.ceiling),
+        };
+        if let Some(ceiling) = ceiling.filter(|_| here.ceiling > camera.eye) {
+            layers.push(Layer::Surface {
+                near,
+                far,
+                height: here.ceiling,
+                surface: ceiling,
+                ray,
+            });
+        }
+        let Some(crossing) = crossing else {
+            break;
+        };
+        let next = heights(crossing.sector);
+        // Steps are drawn in the colour of whichever side they belong to.
+        let owner = crossing.sector.or(sector);
+        let step = Hit {
+            distance: far,
+            colour: owner.map_or(crossing.hit.colour, |s| s.colour),
+            texture: owner.map_or(crossing.hit.texture, |s| s.texture),
+            ..crossing.hit
+        };
+        if next.floor > here.floor {
+            layers.push(Layer::Wall(Hit {
+                floor: here.floor,
+                ceiling: next.floor,
+                ..step
+            }));
+        }
+        if next.ceiling < here.ceiling {
+            layers.push(Layer::Wall(Hit {
+                floor: next.ceiling,
+                ceiling: here.ceiling,
+                ..step
+            }));
+        }
+        sector = crossing.sector;
+        near = far;
+    }
+    layers
+}
+
+// Draws one column of wall. See-through walls and mirrors are blended over what's already
+// there, and portals aren't drawn at all since what's through them already has been.
+fn draw_wall(canvas: &mut Canvas, world: &World, projection: &Projection, x: usize, hit: &Hit) {
+    if hit.portal {
+        return;
+    }
+    let blend = if hit.mirror {
+        Some(MIRROR_TINT)
+    } else {
+        hit.is_see_through().then_some(hit.opacity)
+    };
+    let top = projection.row(hit.ceiling, hit.distance);
+    let height = (hit.ceiling - hit.floor) * projection.wall_height(hit.distance);
+    let texture = hit.texture.and_then(|t| world.textures.get(t));
+    // Lights are only worked out once per column, where the ray hit.
+    let brightness = world.brightness_at(hit.point, Some(hit.normal));
+    for y in visible_rows(top, top + height, canvas.height) {
+        let v = (y as f32 + 0.5 - top) / height;
+        let colour = match texture.map(|t| t.sample(hit.u, v)) {
+            Some(Some(colour)) => colour,
+            Some(None) if blend.is_some() => continue,
+            _ => hit.colour,
+        };
+        let colour = world.fog.apply(brightness.apply(colour), hit.distance);
+        match blend {
+            Some(amount) => canvas.set(x, y, canvas.get(x, y).lerp(colour, amount)),
+            None => canvas.set(x, y, colour),
+        }
+    }
+}
+
+// Draws every layer further away than `distance`, furthest first, taking them off each
+// column's list nearest first.
+fn draw_layers(
+    canvas: &mut Canvas,
+    camera: &Camera,
+    world: &World,
+    projection: &Projection,
+    layers: &mut [Vec<Layer>],
+    distance: f32,
+) {
+    for (x, column) in layers.iter_mut().enumerate() {
+        while let Some(layer) = column.pop_if(|l| l.distance() > distance) {
+            match layer {
+                Layer::Wall(hit) => draw_wall(canvas, world, projection, x, &hit),
+                Layer::Surface {
+                    near,
+                    far,
+                    height,
+                    surface,
+                    ray,
+                } => {
+                    let (a, b) = (projection.row(height, far), projection.row(height, near));
+                    for y in visible_rows(a.min(b), a.max(b), canvas.height) {
+                        let distance = projection.surface_distance(y, height);
+                        let point = camera.position + ray * distance;
+                        canvas.set(x, y, surface_colour(world, &surface, point, distance));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Fills the floor and ceiling row by row, projecting each pixel back onto the plane it shows.
+fn draw_surfaces(canvas: &mut Canvas, camera: &Camera, world: &World, projection: &Projection) {
+    // Per column, the ray direction scaled so that a perpendicular distance maps to a world
+    // offset.
+    let rays: Vec<_> = (0..canvas.width)
+        .map(|x| {
+            let offset = projection.ray_offset(x);
+            Vec2::from_angle(camera.rotation + offset) * (1.0 / offset.cos())
+        })
+        .collect();
+    let horizon = projection.horizon();
+    canvas
+        .pixels
+        .par_chunks_mut(projection.width.max(1))
+        .enumerate()
+        .for_each(|(y, row)| {
+            let floor = y as f32 + 0.5 >= horizon;
+            if let (false, Some(sky)) = (floor, world.sky) {
+                draw_sky_row(row, &sky, world, &rays, projection, y);
+                return;
+            }
+            let surface = if floor { world.floor } else { world.ceiling };
+            let distance = projection.surface_distance(y, if floor { 0.0 } else { 1.0 });
+            for (pixel, &ray) in row.iter_mut().zip(&rays) {
+                let point = camera.position + ray * distance;
+                *pixel = surface_colour(world, &surface, point, distance);
+            }
+        });
+}
+
+// The colour of a floor or ceiling at `point`, seen from `distance` away.
+fn surface_colour(world: &World, surface: &Surface, point: Vec2, distance: f32) -> Rgb {
+    let colour = surface
+        .texture
+        .and_then(|t| world.textures.get(t))
+        .and_then(|t| t.sample(point.x, point.y.rem_euclid(1.0)))
+        .unwrap_or(surface.colour);
+    let colour = world.brightness_at(point, None).apply(colour);
+    world.fog.apply(colour, distance)
+}
+
+// The sky is far enough away that only the view angle matters, so it pans as the camera turns
+// and stretches from the horizon up to half a screen above it.
+fn draw_sky_row(
+    row: &mut [Rgb],
+    sky: &Sky,
+    world: &World,
+    rays: &[Vec2],
+    projection: &Projection,
+    y: usize,
+) {
+    let v = 1.0 - (projection.horizon() - y as f32 - 0.5) / (projection.height as f32 / 2.0);
+    let v = v.clamp(0.0, 1.0);
+    let fallback = sky.top.lerp(sky.bottom, v);
+    let texture = sky.texture.and_then(|t| world.textures.get(t));
+    for (pixel, ray) in row.iter_mut().zip(rays) {
+        *pixel = texture
+            .and_then(|t| t.sample(-ray.angle() / (2.0 * PI), v))
+            .map(|colour| colour.scale(sky.brightness))
+            .unwrap_or(fallback);
+    }
+}
+
+// The canvas rows whose centres fall between `top` and `bottom`.
+fn visible_rows(top: f32, bottom: f32, height: usize) -> std::ops::Range<usize> {
+    let first = (top - 0.5).ceil().clamp(0.0, height as f32) as usize;
+    let last = (bottom - 0.5).ceil().clamp(0.0, height as f32) as usize;
+    first..last
+}
+
+// Draws sprites back to front as camera-facing billboards, skipping any column where the wall
+// pass found something closer. See-through walls behind each sprite are drawn just before it,
+// so the sprite covers them and the ones in front cover it.
+fn draw_sprites(
+    canvas: &mut Canvas,
+    camera: &Camera,
+    world: &World,
+    projection: &Projection,
+    depth: &[f32],
+    layers: &mut [Vec<Layer>],
+) {
+    let mut sprites: Vec<(f32, f32, Vec2, &Sprite)> = world
+        .entities
+        .visible()
+        .map(|(position, s)| {
+            let to_sprite = position - camera.position;
+            let offset = (to_sprite.angle() - camera.rotation + PI).rem_euclid(2.0 * PI) - PI;
+            let distance = to_sprite.length();
+            (distance * offset.cos(), offset, position, s)
+        })
+        .filter(|(d, ..)| *d > 0.1 && *d < MAX_DISTANCE)
+        .collect();
+    sprites.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    for (distance, offset, position, sprite) in sprites {
+        draw_layers(canvas, camera, world, projection, layers, distance);
+        let floor = world.floor_at(position);
+        let brightness = world.brightness_at(position, None);
+        let centre = projection.column(offset);
+        let half_width = projection.width_of(sprite.size, distance) / 2.0;
+        let height = projection.wall_height(distance) * sprite.size;
+        let bottom = projection.row(floor + sprite.elevation, distance);
+        let top = bottom - height;
+        let left = centre - half_width;
+        let texture = sprite.texture.and_then(|t| world.textures.get(t));
+
+        let first = left.round().clamp(0.0, canvas.width as f32) as usize;
+        let last = (centre + half_width)
+            .round()
+            .clamp(0.0, canvas.width as f32) as usize;
+        for (x, &column_depth) in depth.iter().enumerate().take(last).skip(first) {
+            if column_depth < distance {
+                continue;
+            }
+            let u = (x as f32 + 0.5 - left) / (2.0 * half_width);
+            for y in visible_rows(top, bottom, canvas.height) {
+                let colour = match texture {
+                    Some(texture) => {
+                        match texture.sample(u.min(0.999), (y as f32 + 0.5 - top) / height) {
+                            Some(colour) => colour,
+                            None => continue,
+                        }
+                    }
+                    None => sprite.colour,
+                };
+                canvas.set(x, y, world.fog.apply(brightness.apply(colour), distance));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demo_snapshot() {
+        let camera = Camera::new(Vec2::ZERO, PI / 2.0);
+        let frame = render_to_plain_string((40, 12), &camera, &World::demo());
+        let expected = [
+            "::::::::::::::::::::::::::::::---=@@%=++",
+            "::::::::::::::::::::::::::::::-        -",
+            "::::::::::::::::::::::::::::::-    %   -",
+            ":::::::::::::::::::..:::::::::-        -",
+            "::::::::::::::::::.**.::::::::----------",
+            ".............%%%%%.--.%%%%%...          ",
+            ":::::::::::::%%%%%.--.%%%%%:::          ",
+            "=================.----.=================",
+            "================.--**--.================",
+            "================.------.================",
+            "=████████======..------..===============",
+            "===============.--------.=======20 ammo=",
+        ];
+        assert_eq!(frame.lines().collect::<Vec<_>>(), expected);
+        assert!(!frame.contains('\x1b'));
+    }
+}
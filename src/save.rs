@@ -0,0 +1,82 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    door::DoorState, health::PLAYER_HEALTH, inventory::Item, pickup::KeyColour,
+    projectile::START_AMMO, render::Camera, vec2::Vec2,
+};
+
+// Everything about a game in progress that isn't already in the map it was loaded from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveState {
+    // Which map the save belongs to, so it can't be loaded over a different one.
+    pub map: String,
+    pub camera: Camera,
+    pub doors: Vec<DoorSave>,
+    #[serde(default = "full_health")]
+    pub health: f32,
+    #[serde(default = "start_ammo")]
+    pub ammo: u32,
+    #[serde(default)]
+    pub keys: Vec<KeyColour>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+    // Every enemy in the order the map lists them, or `None` for ones that have been killed.
+    #[serde(default)]
+    pub enemies: Vec<Option<EnemySave>>,
+    // Whether each pickup the map lists is still there to be picked up.
+    #[serde(default)]
+    pub pickups: Vec<bool>,
+    // Whether each switch is on and each wall with an ID is solid, in map order.
+    #[serde(default)]
+    pub switches: Vec<bool>,
+    #[serde(default)]
+    pub walls: Vec<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct EnemySave {
+    pub position: Vec2,
+    pub health: f32,
+    // False for enemies still waiting for a switch to wake them.
+    #[serde(default = "awake")]
+    pub awake: bool,
+}
+
+fn awake() -> bool {
+    true
+}
+
+fn full_health() -> f32 {
+    PLAYER_HEALTH
+}
+
+fn start_ammo() -> u32 {
+    START_AMMO
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DoorSave {
+    pub state: DoorState,
+    pub openness: f32,
+}
+
+impl SaveState {
+    pub fn read(path: &Path) -> Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Could not read save {}", path.display()))?;
+        ron::from_str(&source).with_context(|| format!("Could not parse save {}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Could not create {}", parent.display()))?;
+        }
+        let source = ron::ser::to_string_pretty(self, PrettyConfig::default())?;
+        fs::write(path, source).with_context(|| format!("Could not write save {}", path.display()))
+    }
+}
@@ -0,0 +1,50 @@
+use std::{path::Path, str::FromStr};
+
+use anyhow::{anyhow, Context, Error, Result};
+use image::RgbImage;
+
+use crate::{
+    canvas::Canvas,
+    render::{draw_scene, Camera},
+    world::World,
+};
+
+// The size of a screenshot in pixels, written like 1280x720.
+#[derive(Debug, Clone, Copy)]
+pub struct Resolution {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FromStr for Resolution {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (width, height) = s
+            .split_once('x')
+            .ok_or_else(|| anyhow!("Invalid resolution {s}, expected WIDTHxHEIGHT"))?;
+        let resolution = Self {
+            width: width.parse()?,
+            height: height.parse()?,
+        };
+        if resolution.width == 0 || resolution.height == 0 {
+            return Err(anyhow!("Resolution {s} must be at least 1x1"));
+        }
+        Ok(resolution)
+    }
+}
+
+// Draws the view from `camera` into an image with square pixels, whatever size the terminal
+// happens to be. Every column of pixels casts its own ray and every row is shaded on its own, so
+// it can be as much finer than the terminal as asked for.
+pub fn capture(camera: &Camera, world: &World, resolution: Resolution) -> RgbImage {
+    let mut canvas = Canvas::new(resolution.width, resolution.height);
+    draw_scene(&mut canvas, camera, world);
+    canvas.to_image()
+}
+
+pub fn write_png(image: &RgbImage, path: &Path) -> Result<()> {
+    image
+        .save(path)
+        .with_context(|| format!("Could not write screenshot {}", path.display()))
+}
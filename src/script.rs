@@ -0,0 +1,325 @@
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    path::Path,
+    rc::Rc,
+};
+
+use anyhow::{Context, Result};
+use mlua::{
+    Function, HookTriggers, IntoLuaMulti, Lua, LuaOptions, LuaSerdeExt, StdLib, Table, Value,
+    VmState,
+};
+
+use crate::{
+    enemy::EnemySpawn,
+    entities::Entity,
+    event::GameEvent,
+    pickup::{KeyColour, Pickup},
+    vec2::Vec2,
+    world::World,
+};
+
+// Lua instructions a script gets each time it's run, so one stuck in a loop stops with an error
+// instead of hanging the game.
+const INSTRUCTION_BUDGET: u32 = 1_000_000;
+// How many instructions run between checks of the budget.
+const BUDGET_STEP: u32 = 1000;
+
+// A map's Lua script. Any of these global functions it defines are called as the level is
+// played:
+//
+//   on_load()         once the level has loaded, before the first frame
+//   on_tick(dt)       every frame, with the seconds since the last one
+//   on_use(entity)    when the player uses a door or switch, with a table of its `kind`,
+//                     `id` (if it has one) and `x` and `y`
+//   on_enter(region)  when the player walks into a trigger region, with its name
+//   on_exit(region)   when they walk out of one
+//
+// While a hook runs the script can call:
+//
+//   trigger(id)                   set off everything with the ID, like a switch does
+//   set_wall(id, solid)           make the walls with the ID appear or disappear
+//   spawn_enemy(x, y, enemy)      add an enemy, `enemy` taking the same fields as in maps
+//   spawn_pickup(x, y, pickup)    add a pickup, e.g. `{ type = "health", amount = 10 }`
+//   message(text)                 show a line of text along the bottom of the screen
+//   shake(magnitude)              shake the view, from 0 for nothing to 1 for as hard as it
+//                                 goes
+//   count(item)                   how many of the named item the player is carrying
+//   give(item, count)             add to them, `count` being 1 if left out
+//   take(item, count)             take them away if the player has enough, returning whether
+//                                 they did
+//   has_key(colour)               whether the player has the "red", "yellow" or "blue" key
+//   player()                      the player's x and y
+//   clock()                       seconds since the level started
+//
+// Scripts only get Lua's table, string and math libraries, without the base library's file
+// loading, so a map can't reach outside the game.
+pub struct Script {
+    lua: Lua,
+    // Instructions left for whatever the script is running now.
+    budget: Rc<Cell<u32>>,
+    // Lines from `message` waiting to be shown.
+    pub messages: Vec<String>,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Could not read script {}", path.display()))?;
+        let lua = Lua::new_with(
+            StdLib::TABLE | StdLib::STRING | StdLib::MATH,
+            LuaOptions::default(),
+        )?;
+        let globals = lua.globals();
+        for name in ["dofile", "loadfile", "load"] {
+            globals.raw_remove(name)?;
+        }
+        let budget = Rc::new(Cell::new(INSTRUCTION_BUDGET));
+        let left = budget.clone();
+        let every = HookTriggers::new().every_nth_instruction(BUDGET_STEP);
+        lua.set_hook(every, move |_, _| {
+            match left.get().checked_sub(BUDGET_STEP) {
+                Some(rest) => {
+                    left.set(rest);
+                    Ok(VmState::Continue)
+                }
+                None => Err(mlua::Error::runtime("Script ran for too long")),
+            }
+        })?;
+        lua.load(source)
+            .set_name(format!("@{}", path.display()))
+            .exec()
+            .with_context(|| format!("Could not run script {}", path.display()))?;
+        Ok(Self {
+            lua,
+            budget,
+            messages: Vec::new(),
+        })
+    }
+
+    pub fn on_load(&mut self, world: &mut World, player: Vec2) -> Result<()> {
+        // Nothing is listening for events before the first frame.
+        self.call("on_load", world, player, &mut Vec::new(), ())
+    }
+
+    // Runs `on_tick`, then `on_use`, `on_enter` and `on_exit` for anything in `events` they're
+    // for.
+    pub fn update(
+        &mut self,
+        dt: f32,
+        world: &mut World,
+        player: Vec2,
+        events: &mut Vec<GameEvent>,
+    ) -> Result<()> {
+        self.call("on_tick", world, player, events, dt)?;
+        let hooks: Vec<_> = events
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event,
+                    GameEvent::Used(_) | GameEvent::Entered(_) | GameEvent::Left(_)
+                )
+            })
+            .cloned()
+            .collect();
+        for event in hooks {
+            match event {
+                GameEvent::Used(entity) => {
+                    let used = self.used(world, entity)?;
+                    self.call("on_use", world, player, events, used)?;
+                }
+                GameEvent::Entered(i) => {
+                    let name = world.triggers[i].name.clone();
+                    self.call("on_enter", world, player, events, name)?;
+                }
+                GameEvent::Left(i) => {
+                    let name = world.triggers[i].name.clone();
+                    self.call("on_exit", world, player, events, name)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // The table `on_use` is given for `entity`.
+    fn used(&self, world: &World, entity: Entity) -> Result<Table> {
+        let table = self.lua.create_table()?;
+        let entities = &world.entities;
+        let (kind, id, position) = if let Some(door) = entities.doors.get(entity) {
+            ("door", door.id.clone(), door.start.lerp(door.end, 0.5))
+        } else if let Some(switch) = entities.switches.get(entity) {
+            ("switch", None, switch.start.lerp(switch.end, 0.5))
+        } else {
+            ("unknown", None, Vec2::ZERO)
+        };
+        table.set("kind", kind)?;
+        table.set("id", id)?;
+        table.set("x", position.x)?;
+        table.set("y", position.y)?;
+        Ok(table)
+    }
+
+    // Calls the global function `hook` if the script defines one, with the functions it can
+    // call working on `world` for as long as it runs.
+    fn call(
+        &mut self,
+        hook: &str,
+        world: &mut World,
+        player: Vec2,
+        events: &mut Vec<GameEvent>,
+        args: impl IntoLuaMulti,
+    ) -> Result<()> {
+        let Ok(function) = self.lua.globals().get::<Function>(hook) else {
+            return Ok(());
+        };
+        self.budget.set(INSTRUCTION_BUDGET);
+        let world = RefCell::new(world);
+        let events = RefCell::new(events);
+        let messages = RefCell::new(&mut self.messages);
+        let globals = self.lua.globals();
+        self.lua
+            .scope(|scope| {
+                globals.set(
+                    "trigger",
+                    scope.create_function(|_, id: String| {
+                        world.borrow_mut().trigger(&id, &mut events.borrow_mut());
+                        Ok(())
+                    })?,
+                )?;
+                globals.set(
+                    "set_wall",
+                    scope.create_function(|_, (id, solid): (String, bool)| {
+                        for (_, wall) in world.borrow_mut().entities.walls.iter_mut() {
+                            if wall.id == id {
+                                wall.solid = solid;
+                            }
+                        }
+                        Ok(())
+                    })?,
+                )?;
+                globals.set(
+                    "spawn_enemy",
+                    scope.create_function(|lua, (x, y, enemy): (f32, f32, Option<Table>)| {
+                        let table = enemy.unwrap_or(lua.create_table()?);
+                        let spawn: EnemySpawn = lua.from_value(Value::Table(table))?;
+                        world.borrow_mut().entities.spawn_enemy(
+                            Vec2::new(x, y),
+                            spawn.sprite(),
+                            spawn.enemy,
+                        );
+                        Ok(())
+                    })?,
+                )?;
+                globals.set(
+                    "spawn_pickup",
+                    scope.create_function(|lua, (x, y, pickup): (f32, f32, Value)| {
+                        let pickup: Pickup = lua.from_value(pickup)?;
+                        world.borrow_mut().entities.spawn_pickup(
+                            Vec2::new(x, y),
+                            pickup.sprite(),
+                            pickup,
+                        );
+                        Ok(())
+                    })?,
+                )?;
+                globals.set(
+                    "message",
+                    scope.create_function(|_, text: String| {
+                        messages.borrow_mut().push(text);
+                        Ok(())
+                    })?,
+                )?;
+                globals.set(
+                    "shake",
+                    scope.create_function(|_, magnitude: f32| {
+                        events.borrow_mut().push(GameEvent::Shake(magnitude));
+                        Ok(())
+                    })?,
+                )?;
+                globals.set(
+                    "count",
+                    scope.create_function(|_, name: String| {
+                        Ok(world.borrow().inventory.count(&name))
+                    })?,
+                )?;
+                globals.set(
+                    "give",
+                    scope.create_function(|_, (name, count): (String, Option<u32>)| {
+                        let inventory = &mut world.borrow_mut().inventory;
+                        inventory.add_item(&name, count.unwrap_or(1));
+                        Ok(())
+                    })?,
+                )?;
+                globals.set(
+                    "take",
+                    scope.create_function(|_, (name, count): (String, Option<u32>)| {
+                        let inventory = &mut world.borrow_mut().inventory;
+                        Ok(inventory.take_item(&name, count.unwrap_or(1)))
+                    })?,
+                )?;
+                globals.set(
+                    "has_key",
+                    scope.create_function(|lua, key: Value| {
+                        let key: KeyColour = lua.from_value(key)?;
+                        Ok(world.borrow().inventory.has_key(key))
+                    })?,
+                )?;
+                globals.set(
+                    "player",
+                    scope.create_function(move |_, ()| Ok((player.x, player.y)))?,
+                )?;
+                globals.set(
+                    "clock",
+                    scope.create_function(|_, ()| Ok(world.borrow().clock))?,
+                )?;
+                function.call::<()>(args)
+            })
+            .with_context(|| format!("Script failed in {hook}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+
+    fn script(test: &str, source: &str) -> Result<Script> {
+        let directory = scratch(test);
+        let path = directory.join("script.lua");
+        fs::write(&path, source).unwrap();
+        let script = Script::load(&path);
+        fs::remove_dir_all(directory).unwrap();
+        script
+    }
+
+    #[test]
+    fn scripts_cant_reach_outside_the_game() {
+        let source = r#"
+            assert(io == nil and os == nil and require == nil and debug == nil)
+            assert(dofile == nil and loadfile == nil and load == nil)
+            assert(string.upper("a") == "A" and math.floor(1.5) == 1 and table.concat({}) == "")
+        "#;
+        script("sandbox", source).unwrap();
+    }
+
+    #[test]
+    fn scripts_that_never_finish_are_stopped() {
+        assert!(script("loop-on-load", "while true do end").is_err());
+        let mut script = script(
+            "loop-on-tick",
+            "ticks = 0\nfunction on_tick() ticks = ticks + 1 while ticks == 1 do end end",
+        )
+        .unwrap();
+        let mut world = World::demo();
+        let mut events = Vec::new();
+        assert!(script
+            .update(0.1, &mut world, Vec2::ZERO, &mut events)
+            .is_err());
+        // The next call gets a fresh budget.
+        script
+            .update(0.1, &mut world, Vec2::ZERO, &mut events)
+            .unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{
+    colour::Rgb,
+    geometry::{Hit, LineSegment},
+    vec2::Vec2,
+    world::Surface,
+};
+
+// A step up or down, or a dip in the ceiling, that the player can't get past without
+// climbing further than this.
+pub const STEP_HEIGHT: f32 = 0.3;
+// The least room the player needs between floor and ceiling to fit, standing up and crouched.
+pub const HEADROOM: f32 = 0.7;
+pub const CROUCH_HEADROOM: f32 = 0.4;
+
+// An area of floor with its own floor and ceiling heights, outlined by a polygon. Anywhere
+// outside every sector has its floor at 0.0 and ceiling at 1.0.
+pub struct Sector {
+    pub points: Vec<Vec2>,
+    pub floor_height: f32,
+    pub ceiling_height: f32,
+    // How the steps up into the sector and down from its ceiling are drawn.
+    pub colour: Rgb,
+    pub texture: Option<usize>,
+    // Drawn instead of the world's floor and ceiling when set.
+    pub floor: Option<Surface>,
+    pub ceiling: Option<Surface>,
+}
+
+impl Sector {
+    pub fn contains(&self, point: Vec2) -> bool {
+        polygon_contains(&self.points, point)
+    }
+
+    pub fn heights(&self) -> Heights {
+        Heights {
+            floor: self.floor_height,
+            ceiling: self.ceiling_height,
+        }
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = LineSegment> + '_ {
+        self.corners()
+            .map(|(a, b)| LineSegment::from_points(a, b, self.colour).with_texture(self.texture))
+    }
+
+    fn corners(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let next = self.points.iter().cycle().skip(1);
+        self.points.iter().copied().zip(next.copied())
+    }
+}
+
+// Whether `point` is inside the polygon with corners `points`, in either winding.
+pub fn polygon_contains(points: &[Vec2], point: Vec2) -> bool {
+    // Counts crossings of a ray running along +x from the point.
+    let mut inside = false;
+    let next = points.iter().cycle().skip(1);
+    for (&a, &b) in points.iter().zip(next) {
+        if (a.y > point.y) != (b.y > point.y) {
+            let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+// The floor and ceiling heights somewhere.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Heights {
+    pub floor: f32,
+    pub ceiling: f32,
+}
+
+impl Heights {
+    pub const DEFAULT: Self = Self {
+        floor: 0.0,
+        ceiling: 1.0,
+    };
+
+    // Whether something here can move on to `next`.
+    pub fn can_reach(self, next: Heights, reach: Reach) -> bool {
+        next.floor - self.floor <= reach.step && next.ceiling - next.floor >= reach.headroom
+    }
+}
+
+// How high something can step up and how little room it can squeeze through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reach {
+    pub step: f32,
+    pub headroom: f32,
+}
+
+impl Reach {
+    pub const WALKING: Self = Self {
+        step: STEP_HEIGHT,
+        headroom: HEADROOM,
+    };
+}
+
+// Where a ray passes from one sector, or the space outside them, into another.
+pub struct Crossing<'a> {
+    pub hit: Hit,
+    pub sector: Option<&'a Sector>,
+}
@@ -0,0 +1,862 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "window")]
+use anyhow::bail;
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use tracing::{error, info_span, warn};
+
+#[cfg(feature = "audio")]
+use crate::audio::Audio;
+#[cfg(feature = "hot-reload")]
+use crate::reload::MapWatcher;
+#[cfg(feature = "window")]
+use crate::window::Window;
+use crate::{
+    backend::{
+        event::{Event, KeyEventKind},
+        Backend,
+    },
+    campaign::{Campaign, Progress},
+    cast::Cast,
+    cheat::Cheat,
+    colour::{ColourMode, Rgb},
+    console::{Console, ConsoleInput},
+    controls::{bound, Controls, MAX_SMOOTHING},
+    demo::Frame,
+    entities::Entity,
+    event::GameEvent,
+    game::{Game, Level},
+    input::{Action, HeadBob, Input},
+    menu::Menu,
+    narration::Narration,
+    net::{player_sprite, Server},
+    render::{Camera, Output, Renderer, Viewport},
+    save::SaveState,
+    screenshot::{capture, write_png, Resolution},
+    shake,
+    terminal::Terminal,
+    theme::Theme,
+    vec2::Vec2,
+    video::VideoRecorder,
+    world::World,
+};
+
+// How a game is drawn and heard, shared by every command that shows one.
+#[derive(Args)]
+pub struct OutputArgs {
+    #[arg(
+        long,
+        visible_alias = "output",
+        default_value = "half-blocks",
+        help = "blocks, half-blocks, braille or ascii"
+    )]
+    pub renderer: Output,
+    #[arg(
+        long,
+        default_value = "classic",
+        help = "classic, gruvbox, monochrome or a theme file"
+    )]
+    pub theme: Theme,
+    #[arg(
+        long,
+        value_name = "MODE",
+        help = "Describe the surroundings in words, alongside or only"
+    )]
+    pub narrate: Option<Narration>,
+    #[arg(
+        long,
+        value_name = "UNITS",
+        help = "Bob the view up and down this far while moving, e.g. 0.03"
+    )]
+    pub head_bob: Option<f32>,
+    #[arg(
+        long,
+        value_name = "HZ",
+        default_value_t = 1.7,
+        help = "Bobs per second with --head-bob"
+    )]
+    pub head_bob_rate: f32,
+    #[arg(
+        long,
+        value_name = "WxH",
+        default_value = "1280x720",
+        help = "Size of screenshots taken with F12"
+    )]
+    pub screenshot_size: Resolution,
+    #[arg(
+        long,
+        value_name = "CAST",
+        help = "Record what's drawn to an asciinema cast file"
+    )]
+    pub cast: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "WxH",
+        default_value = "480x270",
+        help = "Size of animations recorded with --record"
+    )]
+    pub video_size: Resolution,
+    #[arg(
+        long,
+        default_value_t = 15,
+        help = "Frames per second of animations recorded with --record"
+    )]
+    pub video_fps: u16,
+    #[cfg(feature = "window")]
+    #[arg(
+        long,
+        value_name = "WxH",
+        help = "Play in a window this size instead of the terminal"
+    )]
+    pub window: Option<Resolution>,
+    #[cfg(feature = "audio")]
+    #[arg(long, help = "Play without sound")]
+    pub mute: bool,
+    #[cfg(feature = "audio")]
+    #[arg(long, default_value_t = 0.5, help = "Music volume from 0 to 1")]
+    pub music_volume: f32,
+}
+
+impl OutputArgs {
+    // The bob asked for with --head-bob, if any.
+    pub fn head_bob(&self) -> Option<HeadBob> {
+        self.head_bob.map(|amplitude| HeadBob {
+            amplitude,
+            frequency: self.head_bob_rate,
+        })
+    }
+}
+
+// A window if one was asked for, or else the terminal, recording to a cast if asked to.
+pub fn open_backend(output: &OutputArgs) -> Result<Box<dyn Backend>> {
+    #[cfg(feature = "window")]
+    if let Some(resolution) = output.window {
+        if output.cast.is_some() {
+            bail!("Casts record the terminal, so --cast can't be used with --window");
+        }
+        let window = Window::open("rhywbeth", resolution.width, resolution.height)?;
+        return Ok(Box::new(window));
+    }
+    let mut terminal = Terminal::open(ColourMode::detect())?;
+    if let Some(path) = &output.cast {
+        terminal.cast = Some(Cast::create(path, terminal.size()?)?);
+    }
+    Ok(Box::new(terminal))
+}
+
+// What the game loop should do after a frame.
+pub enum Flow {
+    Continue,
+    Pause,
+    // The player reached the exit, carrying the level to go on to if there is one.
+    Exit(Option<Level>),
+    // The player ran out of health.
+    Died,
+    Quit,
+}
+
+// Most settings only change how the game looks and sounds, so they can't knock a recording out
+// of step with its playback. The mouse ones are recorded in the frame after they change.
+#[derive(Clone, Copy)]
+pub enum Setting {
+    Minimap,
+    Stats,
+    Overhead,
+    Viewmodel,
+    Renderer,
+    Theme,
+    Sensitivity,
+    InvertMouse,
+    Smoothing,
+    #[cfg(feature = "audio")]
+    MusicVolume,
+    Back,
+}
+
+pub fn settings() -> Vec<Setting> {
+    let mut settings = vec![
+        Setting::Minimap,
+        Setting::Stats,
+        Setting::Overhead,
+        Setting::Viewmodel,
+        Setting::Renderer,
+        Setting::Theme,
+        Setting::Sensitivity,
+        Setting::InvertMouse,
+        Setting::Smoothing,
+    ];
+    #[cfg(feature = "audio")]
+    settings.push(Setting::MusicVolume);
+    settings.push(Setting::Back);
+    settings
+}
+
+#[cfg(feature = "audio")]
+fn open_audio(world: &World, music_volume: f32) -> Result<Audio> {
+    let mut audio = Audio::new(&world.sounds)?;
+    audio.set_music_volume(music_volume);
+    audio.play_music(world.music.as_deref())?;
+    Ok(audio)
+}
+
+// The second player in split-screen, moved by their own keys on the same keyboard and drawn in
+// the right half of the screen.
+pub struct SecondPlayer {
+    input: Input,
+    camera: Camera,
+    // Both players' sprites, so each sees the other. Neither is drawn in its own view, being
+    // right at the camera.
+    sprites: [Entity; 2],
+}
+
+impl SecondPlayer {
+    pub fn new(game: &mut Game, first: &Input) -> Self {
+        let mut input = first.fresh();
+        input.controls = Controls::second_player();
+        Self {
+            input,
+            camera: game.camera,
+            sprites: player_sprites(&mut game.world, game.camera.position),
+        }
+    }
+
+    // Starts alongside the first player in a newly loaded level.
+    fn start(&mut self, game: &mut Game) {
+        self.input = self.input.fresh();
+        self.camera = game.camera;
+        self.spawn(&mut game.world);
+    }
+
+    // Puts both players' sprites into a world that's just been loaded.
+    fn spawn(&mut self, world: &mut World) {
+        self.sprites = player_sprites(world, self.camera.position);
+    }
+
+    // Moves the sprites to where the players are now.
+    fn follow(&self, game: &mut Game) {
+        let positions = [game.camera.position, self.camera.position];
+        for (&entity, position) in self.sprites.iter().zip(positions) {
+            if let Some(at) = game.world.entities.positions.get_mut(entity) {
+                *at = position;
+            }
+        }
+    }
+}
+
+fn player_sprites(world: &mut World, position: Vec2) -> [Entity; 2] {
+    [0, 1].map(|id| world.entities.spawn_sprite(position, player_sprite(id)))
+}
+
+// Frames longer than this many seconds are logged as warnings.
+const SLOW_FRAME: f32 = 0.1;
+// Highest mouse sensitivity the settings menu goes up to.
+const MAX_SENSITIVITY: f32 = 5.0;
+
+// A game being played or replayed. Both go through `step` a frame at a time, so a demo plays
+// out exactly as it was recorded.
+pub struct Session {
+    pub game: Game,
+    // Where `game` came from, so a recording can load it again.
+    pub level: Level,
+    // Field of view in radians for every game started, instead of the default.
+    pub fov: Option<f32>,
+    pub input: Input,
+    // Listening for other players when hosting.
+    pub server: Option<Server>,
+    pub second: Option<SecondPlayer>,
+    // Drawing the game into an animation as it's played.
+    pub video: Option<VideoRecorder>,
+    // Open while a command is being typed, taking the keys instead of `input`.
+    pub console: Option<Console>,
+    // Where frames are shown and keys come from: the terminal, or a window.
+    pub backend: Box<dyn Backend>,
+    pub renderer: Renderer,
+    // What the theme setting goes through: the built-in ones and any picked with --theme.
+    pub themes: Vec<Theme>,
+    pub events: Vec<GameEvent>,
+    // Where quick saves go. Playback has none, and loads the saves recorded in the demo.
+    pub save_path: Option<PathBuf>,
+    // Where mouse settings changed in the menu are kept. Playback has none, and takes them from
+    // the demo as they were changed.
+    pub controls_path: Option<PathBuf>,
+    // Whether they've changed since the last frame, to record them in the next.
+    pub mouse_changed: bool,
+    pub screenshot_size: Resolution,
+    // The terminal's size in cells, kept up to date by resize events.
+    pub size: (u16, u16),
+    // How many frames have been stepped, to tell them apart in the log.
+    pub frames: u64,
+    // Watching the level's map file, to load it again when it's saved.
+    #[cfg(feature = "hot-reload")]
+    pub watcher: Option<MapWatcher>,
+    // Seconds played of the current level, to compare against its par time.
+    pub level_time: f32,
+    #[cfg(feature = "audio")]
+    pub music_volume: f32,
+    #[cfg(feature = "audio")]
+    pub audio: Option<Audio>,
+}
+
+impl Session {
+    pub fn new(level: Level, mut input: Input, output: &OutputArgs) -> Result<Self> {
+        input.head_bob = output.head_bob();
+        let mut game = level.load()?;
+        output.theme.recolour(&mut game.world, &Theme::classic());
+        let mut themes = Theme::builtin();
+        if !themes.contains(&output.theme) {
+            themes.push(output.theme.clone());
+        }
+        let mut renderer = Renderer::new(output.renderer);
+        renderer.theme = output.theme.clone();
+        renderer.narration = output.narrate;
+        #[cfg(feature = "audio")]
+        let audio = if output.mute {
+            None
+        } else {
+            Some(open_audio(&game.world, output.music_volume)?)
+        };
+        let backend = open_backend(output)?;
+        let size = backend.size()?;
+        Ok(Self {
+            game,
+            level,
+            fov: None,
+            input,
+            server: None,
+            second: None,
+            video: None,
+            console: None,
+            backend,
+            renderer,
+            themes,
+            events: Vec::new(),
+            save_path: None,
+            controls_path: None,
+            mouse_changed: false,
+            screenshot_size: output.screenshot_size,
+            size,
+            frames: 0,
+            #[cfg(feature = "hot-reload")]
+            watcher: None,
+            level_time: 0.0,
+            #[cfg(feature = "audio")]
+            music_volume: output.music_volume,
+            #[cfg(feature = "audio")]
+            audio,
+        })
+    }
+
+    // Loads `level` in the theme's colours.
+    fn load(&self, level: &Level) -> Result<Game> {
+        let mut game = level.load()?;
+        self.renderer
+            .theme
+            .recolour(&mut game.world, &Theme::classic());
+        Ok(game)
+    }
+
+    // Swaps in a new game, e.g. one picked on the title screen.
+    pub fn start(&mut self, level: Level) -> Result<()> {
+        let cheats = self.game.world.cheats;
+        self.game = self.load(&level)?;
+        self.game.world.cheats = cheats;
+        self.level = level;
+        self.level_time = 0.0;
+        #[cfg(feature = "hot-reload")]
+        {
+            self.watcher = self
+                .level
+                .path()
+                .and_then(|path| MapWatcher::new(&path).inspect_err(|e| warn!("{e:#}")).ok());
+        }
+        if let Some(fov) = self.fov {
+            self.game.camera.set_fov(fov);
+        }
+        if let Some(server) = self.server.as_mut() {
+            server.change_level(&mut self.game, &self.level)?;
+        }
+        if let Some(second) = self.second.as_mut() {
+            second.start(&mut self.game);
+        }
+        #[cfg(feature = "audio")]
+        if self.audio.is_some() {
+            // Drop the old output first so the two don't briefly play over each other.
+            self.audio = None;
+            self.audio = Some(open_audio(&self.game.world, self.music_volume)?);
+        }
+        Ok(())
+    }
+
+    // Loads the level's map again if it's been saved since, keeping the player where they are
+    // with what they're carrying. Everything else starts over as the map now has it.
+    #[cfg(feature = "hot-reload")]
+    pub fn reload_if_changed(&mut self) {
+        if !self.watcher.as_mut().is_some_and(MapWatcher::changed) {
+            return;
+        }
+        let mut game = match self.load(&self.level) {
+            Ok(game) => game,
+            Err(e) => {
+                warn!("Could not reload the map: {e:#}");
+                self.renderer.notify(format!("Could not reload: {e:#}"));
+                return;
+            }
+        };
+        let world = &mut self.game.world;
+        game.world.health = world.health;
+        game.world.inventory = world.inventory.clone();
+        game.world.cheats = world.cheats;
+        game.world.clock = world.clock;
+        game.camera = self.game.camera;
+        self.game = game;
+        // Guests are sent the map again, and start over next to the host.
+        if let Some(server) = self.server.as_mut() {
+            if let Err(e) = server.change_level(&mut self.game, &self.level) {
+                warn!("Could not send players the reloaded map: {e:#}");
+            }
+        }
+        if let Some(second) = self.second.as_mut() {
+            second.spawn(&mut self.game.world);
+        }
+        self.renderer.notify("Map reloaded");
+    }
+
+    // Runs one frame of input through the game and draws it.
+    pub fn step(&mut self, frame: &mut Frame) -> Result<Flow> {
+        let _span = info_span!("frame", number = self.frames, dt = frame.dt).entered();
+        self.frames += 1;
+        self.level_time += frame.dt;
+        if frame.dt > SLOW_FRAME {
+            warn!("Frame {} took {:.3}s", self.frames, frame.dt);
+        }
+        if std::mem::take(&mut self.mouse_changed) {
+            frame.mouse = Some(self.input.controls.mouse);
+        }
+        if let Some(mouse) = frame.mouse {
+            self.input.controls.mouse = mouse;
+        }
+        let mut actions = Vec::new();
+        for event in frame.events.iter() {
+            let Some(console) = self.console.as_mut() else {
+                actions.push(self.input.handle(event.clone(), &mut self.game.camera));
+                // The mouse belongs to the first player.
+                if let (Some(second), Event::Key(_)) = (self.second.as_mut(), event) {
+                    let camera = second.camera;
+                    match second.input.handle(event.clone(), &mut second.camera) {
+                        Action::Fire => {
+                            self.game
+                                .world
+                                .fire(camera.position, camera.rotation, &mut self.events)
+                        }
+                        Action::Use => self.game.world.use_in_front(
+                            camera.position,
+                            camera.rotation,
+                            &mut self.events,
+                        ),
+                        _ => {}
+                    }
+                }
+                continue;
+            };
+            // The key that opened the console closes it again rather than being typed.
+            let input = match event {
+                Event::Key(key)
+                    if key.kind == KeyEventKind::Press
+                        && bound(&self.input.controls.console, key.code) =>
+                {
+                    ConsoleInput::Close
+                }
+                _ => console.handle(event),
+            };
+            match input {
+                ConsoleInput::None => {}
+                ConsoleInput::Run(line) => self.run_command(line.trim()),
+                ConsoleInput::Close => self.console = None,
+            }
+        }
+        actions.extend(frame.gamepad.iter().copied());
+        self.input.analog = frame.analog;
+        let mut flow = Flow::Continue;
+        for action in actions {
+            match action {
+                Action::Quit => return Ok(Flow::Quit),
+                // The rest of the frame still plays out, so pausing doesn't drop any input.
+                Action::Pause => flow = Flow::Pause,
+                Action::ToggleMinimap => self.renderer.minimap = !self.renderer.minimap,
+                Action::ToggleStats => self.renderer.stats = !self.renderer.stats,
+                Action::ToggleOverhead => self.renderer.overhead = !self.renderer.overhead,
+                Action::Use => {
+                    let camera = self.game.camera;
+                    self.game.world.use_in_front(
+                        camera.position,
+                        camera.rotation,
+                        &mut self.events,
+                    );
+                }
+                Action::Fire => {
+                    let camera = self.game.camera;
+                    self.game
+                        .world
+                        .fire(camera.position, camera.rotation, &mut self.events);
+                }
+                Action::QuickSave => {
+                    if let Some(path) = &self.save_path {
+                        match self.game.save().write(path) {
+                            Ok(()) => self.renderer.notify("Saved"),
+                            Err(e) => self.fail(e),
+                        }
+                    }
+                }
+                Action::QuickLoad => {
+                    let save = match &self.save_path {
+                        Some(path) => SaveState::read(path).inspect(|save| {
+                            frame.loaded = Some(save.clone());
+                        }),
+                        None => frame
+                            .loaded
+                            .clone()
+                            .ok_or_else(|| anyhow!("Nothing was loaded while recording")),
+                    };
+                    match save.and_then(|save| self.restore(save)) {
+                        Ok(()) => self.renderer.notify("Loaded"),
+                        Err(e) => self.fail(e),
+                    }
+                }
+                Action::Screenshot => {
+                    let image = capture(&self.game.camera, &self.game.world, self.screenshot_size);
+                    let path = timestamped("screenshot", "png");
+                    match write_png(&image, &path) {
+                        Ok(()) => self.renderer.notify(format!("Saved {}", path.display())),
+                        Err(e) => self.fail(e),
+                    }
+                }
+                Action::ExportAnsi => {
+                    let path = timestamped("frame", "ans");
+                    let mut ansi = Vec::new();
+                    let written = self
+                        .renderer
+                        .write_ansi(ColourMode::detect(), &mut ansi)
+                        .and_then(|()| {
+                            fs::write(&path, ansi)
+                                .with_context(|| format!("Could not write {}", path.display()))
+                        });
+                    match written {
+                        Ok(()) => self.renderer.notify(format!("Saved {}", path.display())),
+                        Err(e) => self.fail(e),
+                    }
+                }
+                Action::OpenConsole => self.console = Some(Console::default()),
+                Action::ToggleCheat(cheat) => self.toggle_cheat(cheat),
+                Action::None => {}
+            }
+        }
+
+        let (game, events) = (&mut self.game, &mut self.events);
+        self.input
+            .update(&mut game.camera, &game.world, frame.dt, events);
+        if let Some(second) = self.second.as_mut() {
+            second
+                .input
+                .update(&mut second.camera, &game.world, frame.dt, events);
+        }
+        game.world.update(frame.dt, game.camera.position, events);
+        if let Some(server) = self.server.as_mut() {
+            server.update(game, frame.dt, events);
+        }
+        for message in game.world.messages.drain(..) {
+            self.renderer.notify(message);
+        }
+        #[cfg(feature = "scripting")]
+        show_messages(&mut self.renderer, game.run_script(frame.dt, events));
+        #[cfg(feature = "plugins")]
+        show_messages(&mut self.renderer, game.run_plugins(frame.dt, events));
+        #[cfg(feature = "audio")]
+        if let Some(audio) = self.audio.as_mut() {
+            audio.listen(game.camera.position, game.camera.rotation);
+        }
+        for event in events.drain(..) {
+            if let Some(magnitude) = shake::strength(&event, game.camera.position) {
+                self.renderer.shake.add(magnitude);
+            }
+            match event {
+                // Dying takes over from anything else that happened in the same frame.
+                GameEvent::Died => flow = Flow::Died,
+                GameEvent::Teleported { position, facing } => {
+                    game.camera.position = position;
+                    game.camera.rotation = facing.unwrap_or(game.camera.rotation);
+                    self.renderer.flash(Rgb::WHITE);
+                }
+                GameEvent::Portalled { position, turn } => {
+                    game.camera.position = position;
+                    game.camera.rotate(turn);
+                }
+                GameEvent::PickedUp(ref pickup) => {
+                    self.renderer.notify(format!("Picked up {pickup}"));
+                    self.renderer.flash(pickup.sprite().colour);
+                }
+                GameEvent::Locked(lock) => self.renderer.notify(format!("Requires the {lock} key")),
+                GameEvent::OutOfAmmo => self.renderer.notify("Out of ammo"),
+                GameEvent::ExitReached if !matches!(flow, Flow::Died) => {
+                    let next = game.world.exit.as_ref().and_then(|e| e.next.clone());
+                    flow = Flow::Exit(self.level.next(next)?);
+                }
+                _ => {}
+            }
+            #[cfg(feature = "audio")]
+            if let Some(audio) = &self.audio {
+                audio.play(event);
+            }
+        }
+        if let Some(second) = &self.second {
+            second.follow(&mut self.game);
+        }
+        if let Some(video) = self.video.as_mut() {
+            if let Err(e) = video.frame(frame.dt, &self.game.camera, &self.game.world) {
+                self.video = None;
+                self.fail(e);
+            }
+        }
+        self.renderer.console = self.console.as_ref().map(|console| console.line.clone());
+        self.render()?;
+        Ok(flow)
+    }
+
+    // Runs a line typed into the console. Every command so far is the name of a cheat to toggle.
+    fn run_command(&mut self, line: &str) {
+        if line.is_empty() {
+            return;
+        }
+        match line.parse::<Cheat>() {
+            Ok(cheat) => self.toggle_cheat(cheat),
+            Err(e) => self.renderer.notify(e.to_string()),
+        }
+    }
+
+    fn toggle_cheat(&mut self, cheat: Cheat) {
+        let on = self.game.world.cheats.toggle(cheat);
+        let state = if on { "on" } else { "off" };
+        self.renderer.notify(format!("Cheat {cheat} {state}"));
+    }
+
+    // Loads a quick save into a fresh copy of the level, so enemies killed since come back.
+    fn restore(&mut self, save: SaveState) -> Result<()> {
+        let mut game = self.load(&self.level)?;
+        game.restore(save)?;
+        game.world.cheats = self.game.world.cheats;
+        self.game = game;
+        if let Some(server) = self.server.as_mut() {
+            server.spawn_guests(&mut self.game.world);
+        }
+        if let Some(second) = self.second.as_mut() {
+            second.spawn(&mut self.game.world);
+        }
+        Ok(())
+    }
+
+    pub fn render(&mut self) -> Result<()> {
+        let views = self.views();
+        let backend = self.backend.as_mut();
+        let result = self
+            .renderer
+            .render_views(self.size, &views, &self.game.world, None, backend);
+        if let Err(e) = &result {
+            error!("Could not draw frame: {e:#}");
+        }
+        result
+    }
+
+    // Draws the game, stood still, behind a menu.
+    pub fn render_menu(&mut self, menu: &Menu) -> Result<()> {
+        let views = self.views();
+        let backend = self.backend.as_mut();
+        self.renderer
+            .render_views(self.size, &views, &self.game.world, Some(menu), backend)
+    }
+
+    // The whole screen for the player, or its left half with the second player on the right.
+    fn views(&self) -> Vec<(Viewport, Camera)> {
+        let screen = Viewport::full(self.size);
+        match &self.second {
+            Some(second) => {
+                let (left, right) = screen.split();
+                vec![(left, self.game.camera), (right, second.camera)]
+            }
+            None => vec![(screen, self.game.camera)],
+        }
+    }
+
+    pub fn settings_menu(&self) -> Menu {
+        let items = settings().iter().map(|&s| self.setting_label(s)).collect();
+        Menu::new("Settings", items)
+    }
+
+    fn setting_label(&self, setting: Setting) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let mouse = &self.input.controls.mouse;
+        match setting {
+            Setting::Minimap => format!("Minimap: {}", on_off(self.renderer.minimap)),
+            Setting::Stats => format!("Frame stats: {}", on_off(self.renderer.stats)),
+            Setting::Overhead => format!("Top-down view: {}", on_off(self.renderer.overhead)),
+            Setting::Viewmodel => format!("Gun: {}", on_off(self.renderer.viewmodel)),
+            Setting::Renderer => format!("Renderer: {}", self.renderer.output),
+            Setting::Theme => format!("Theme: {}", self.renderer.theme.name),
+            Setting::Sensitivity => format!("Mouse sensitivity: {:.1}", mouse.sensitivity),
+            Setting::InvertMouse => {
+                let inverted = match (mouse.invert_x, mouse.invert_y) {
+                    (false, false) => "off",
+                    (false, true) => "up and down",
+                    (true, false) => "left and right",
+                    (true, true) => "both",
+                };
+                format!("Invert mouse: {inverted}")
+            }
+            Setting::Smoothing => format!("Mouse smoothing: {:.1}", mouse.smoothing),
+            #[cfg(feature = "audio")]
+            Setting::MusicVolume => format!("Music volume: {:.0}%", self.music_volume * 100.0),
+            Setting::Back => "Back".to_string(),
+        }
+    }
+
+    // Moves a setting one step either way. Toggles flip whichever way they're pushed.
+    pub fn change_setting(&mut self, setting: Setting, step: i32) {
+        match setting {
+            Setting::Minimap => self.renderer.minimap = !self.renderer.minimap,
+            Setting::Stats => self.renderer.stats = !self.renderer.stats,
+            Setting::Overhead => self.renderer.overhead = !self.renderer.overhead,
+            Setting::Viewmodel => self.renderer.viewmodel = !self.renderer.viewmodel,
+            Setting::Renderer => {
+                let outputs = Output::ALL;
+                let current = outputs
+                    .iter()
+                    .position(|&o| o == self.renderer.output)
+                    .unwrap_or(0);
+                let next = (current as i32 + step).rem_euclid(outputs.len() as i32);
+                self.renderer.output = outputs[next as usize];
+            }
+            Setting::Theme => {
+                let current = self
+                    .themes
+                    .iter()
+                    .position(|t| *t == self.renderer.theme)
+                    .unwrap_or(0);
+                let next = (current as i32 + step).rem_euclid(self.themes.len() as i32);
+                let theme = self.themes[next as usize].clone();
+                theme.recolour(&mut self.game.world, &self.renderer.theme);
+                self.renderer.theme = theme;
+            }
+            Setting::Sensitivity | Setting::InvertMouse | Setting::Smoothing => {
+                self.change_mouse(setting, step)
+            }
+            #[cfg(feature = "audio")]
+            Setting::MusicVolume => {
+                self.music_volume = (self.music_volume + step as f32 * 0.1).clamp(0.0, 1.0);
+                if let Some(audio) = &self.audio {
+                    audio.set_music_volume(self.music_volume);
+                }
+            }
+            Setting::Back => {}
+        }
+    }
+
+    // Mouse settings stay changed for next time, in the controls file.
+    fn change_mouse(&mut self, setting: Setting, step: i32) {
+        let mouse = &mut self.input.controls.mouse;
+        let step = step as f32 * 0.1;
+        match setting {
+            Setting::Sensitivity => {
+                mouse.sensitivity = (mouse.sensitivity + step).clamp(0.1, MAX_SENSITIVITY);
+            }
+            // Off, up and down, left and right, then both, and round again.
+            Setting::InvertMouse => {
+                let index = mouse.invert_x as i32 * 2 + mouse.invert_y as i32;
+                let next = (index + step.signum() as i32).rem_euclid(4);
+                mouse.invert_x = next & 2 != 0;
+                mouse.invert_y = next & 1 != 0;
+            }
+            Setting::Smoothing => {
+                mouse.smoothing = (mouse.smoothing + step).clamp(0.0, MAX_SMOOTHING);
+            }
+            _ => return,
+        }
+        self.mouse_changed = true;
+        if let Some(path) = &self.controls_path {
+            if let Err(e) = self.input.controls.write(path) {
+                self.fail(e);
+            }
+        }
+    }
+
+    // Unlocks the campaign level after the one just finished and shows how long it took.
+    pub fn complete_level(&mut self, progress: &mut Progress, path: &Path) -> Result<()> {
+        let Level::Campaign { dir, index } = &self.level else {
+            return Ok(());
+        };
+        let campaign = Campaign::read(dir)?;
+        let level = &campaign.levels[*index];
+        let mut message = format!("Finished {} in {:.1}s", level.name, self.level_time);
+        if let Some(par) = level.par {
+            message += &format!(", par {par:.0}s");
+        }
+        self.renderer.notify(message);
+        progress.complete(&campaign.name, *index);
+        progress.write(path)
+    }
+
+    // Waits until `until` for keys and mouse drags, keeping the screen size up to date. Size
+    // changes aren't part of a demo, since playback draws to whatever screen it's on, so they're
+    // handled here rather than in `step`.
+    pub fn poll(&mut self, until: Instant) -> Result<Vec<Event>> {
+        let mut events = Vec::new();
+        let timeout = || until.saturating_duration_since(Instant::now());
+        while let Some(event) = self.backend.poll_event(timeout())? {
+            match event {
+                Event::Resize(width, height) => self.size = (width, height),
+                event => events.push(event),
+            }
+        }
+        Ok(events)
+    }
+
+    // Whether the window the game is drawn in has been closed.
+    pub fn closed(&self) -> bool {
+        self.backend.closed()
+    }
+
+    // Shows a failure that the game can carry on from and keeps it in the log.
+    pub fn fail(&mut self, e: anyhow::Error) {
+        warn!("{e:#}");
+        self.renderer.notify(format!("{e:#}"));
+    }
+}
+
+// Shows what a map's script or plugins had to say this frame, or why they stopped.
+#[cfg(any(feature = "scripting", feature = "plugins"))]
+pub fn show_messages(renderer: &mut Renderer, messages: Result<Vec<String>>) {
+    match messages {
+        Ok(messages) => {
+            for message in messages {
+                renderer.notify(message);
+            }
+        }
+        Err(e) => {
+            // Lua errors come with a traceback, which is only worth having in the log.
+            warn!("{e:#}");
+            let error = format!("{e:#}");
+            renderer.notify(error.lines().next().unwrap_or_default());
+        }
+    }
+}
+
+// A file name in the current directory that won't overwrite an earlier one.
+pub fn timestamped(prefix: &str, extension: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    PathBuf::from(format!("{prefix}-{millis}.{extension}"))
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{colour::Rgb, geometry::MAX_DISTANCE};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Fog {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            start: 5.0,
+            end: MAX_DISTANCE,
+        }
+    }
+}
+
+impl Fog {
+    // 0.0 before the fog starts, rising to 1.0 where it becomes opaque.
+    pub fn density(&self, distance: f32) -> f32 {
+        if self.end <= self.start {
+            return if distance >= self.end { 1.0 } else { 0.0 };
+        }
+        ((distance - self.start) / (self.end - self.start)).clamp(0.0, 1.0)
+    }
+
+    pub fn apply(&self, colour: Rgb, distance: f32) -> Rgb {
+        colour.scale(1.0 - self.density(distance))
+    }
+}
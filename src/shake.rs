@@ -0,0 +1,100 @@
+use web_time::Instant;
+
+use crate::{
+    event::{GameEvent, Target},
+    render::Camera,
+    vec2::Vec2,
+};
+
+// How far a full-strength shake knocks the view: radians of turn, screen heights of looking
+// up and down, and world units of eye height.
+const MAX_YAW: f32 = 0.05;
+const MAX_PITCH: f32 = 0.08;
+const MAX_EYE: f32 = 0.05;
+// Anything weaker than this is too small to see, so the view is left alone.
+const STILL: f32 = 0.01;
+// How much of itself a shake loses every second by default.
+const DECAY: f32 = 6.0;
+
+// Hits on the player shake the view this much per point of damage, and projectiles landing
+// this close shake it more the closer they are.
+const SHAKE_PER_DAMAGE: f32 = 0.04;
+const BLAST_RADIUS: f32 = 3.0;
+const BLAST_SHAKE: f32 = 0.3;
+
+// The view jolting about after a hit or a blast nearby. `magnitude` goes from 0.0 (still) to
+// 1.0 and dies away by `decay` of itself a second from when it last got stronger.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraShake {
+    pub magnitude: f32,
+    pub decay: f32,
+    start: Instant,
+}
+
+impl CameraShake {
+    pub fn new(decay: f32) -> Self {
+        Self {
+            magnitude: 0.0,
+            decay,
+            start: Instant::now(),
+        }
+    }
+
+    // Adds to what's left of the shake, up to full strength.
+    pub fn add(&mut self, magnitude: f32) {
+        self.magnitude = (self.current() + magnitude).min(1.0);
+        self.start = Instant::now();
+    }
+
+    pub fn current(&self) -> f32 {
+        let magnitude = self.magnitude * (-self.decay * self.start.elapsed().as_secs_f32()).exp();
+        if magnitude < STILL {
+            0.0
+        } else {
+            magnitude
+        }
+    }
+
+    // `camera` knocked about by the shake. The jitter is a few sine waves rather than random
+    // numbers, so it moves smoothly from frame to frame instead of flickering.
+    pub fn apply(&self, camera: &Camera) -> Camera {
+        let magnitude = self.current();
+        let mut shaken = *camera;
+        if magnitude == 0.0 {
+            return shaken;
+        }
+        let t = self.start.elapsed().as_secs_f32();
+        shaken.rotate(magnitude * MAX_YAW * wobble(t, 31.0, 0.0));
+        shaken.look(magnitude * MAX_PITCH * wobble(t, 37.0, 1.0));
+        shaken.eye += magnitude * MAX_EYE * wobble(t, 29.0, 2.0);
+        shaken
+    }
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self::new(DECAY)
+    }
+}
+
+// How hard `event` shakes the view of a player standing at `player`, if at all.
+pub fn strength(event: &GameEvent, player: Vec2) -> Option<f32> {
+    match *event {
+        GameEvent::Hit {
+            target: Target::Player,
+            damage,
+            ..
+        } => Some(damage * SHAKE_PER_DAMAGE),
+        GameEvent::Impact(position) => {
+            let near = 1.0 - position.distance(player) / BLAST_RADIUS;
+            (near > 0.0).then_some(near * BLAST_SHAKE)
+        }
+        GameEvent::Shake(magnitude) => Some(magnitude),
+        _ => None,
+    }
+}
+
+// Two sine waves out of step, so the jitter doesn't look like a steady wave, from -1.0 to 1.0.
+fn wobble(t: f32, frequency: f32, phase: f32) -> f32 {
+    ((t * frequency + phase).sin() + (t * frequency * 1.7 + phase).sin()) / 2.0
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use image::{Rgba, RgbaImage};
+use tracing::warn;
+
+use crate::{
+    colour::Rgb,
+    map::{GridMap, Map, Spawn},
+    tiled::tile_char,
+    vec2::Vec2,
+};
+
+// How dark a cell has to be to be a wall by default, from 0.0 for black to 1.0 for white.
+pub const DARK: f32 = 0.5;
+// Walls sketched in black would be lost against the dark, so anything with every channel below
+// this is drawn in `PEN` instead.
+const BLACK: u8 = 48;
+const PEN: Rgb = Rgb::new(160, 160, 160);
+// Wall colours closer than this to one already used are drawn in that one, so the blurry edges
+// of lines don't each get a colour of their own.
+const SAME_COLOUR: f32 = 48.0;
+// Pixels more see-through than this are left out, as if they weren't drawn on.
+const OPAQUE: u8 = 128;
+const FLOOR: char = '.';
+
+// Turns a picture, like a sketch of a floor plan, into a grid map with a cell for every
+// `cell` by `cell` pixels. Cells with any pixel darker than `dark` are walls, in the average
+// colour of their dark pixels, so thin lines aren't lost when cells are bigger than them. The
+// player starts on the first cell with bright green in it, or else the first floor.
+pub fn from_image(image: &RgbaImage, cell: u32, dark: f32) -> Result<Map> {
+    if cell == 0 {
+        bail!("Cells have to be at least a pixel across");
+    }
+    let width = image.width().div_ceil(cell);
+    let height = image.height().div_ceil(cell);
+    let mut palette: Vec<(char, Rgb)> = Vec::new();
+    let mut tiles = Vec::with_capacity(height as usize);
+    let mut spawn = None;
+    let mut floor = None;
+    // Pictures count rows down, and maps count y up, so the rows are turned upside down.
+    for row in (0..height).rev() {
+        let mut line = String::with_capacity(width as usize);
+        for column in 0..width {
+            let centre = Vec2::new(column as f32 + 0.5, (height - 1 - row) as f32 + 0.5);
+            let pixels = (0..cell * cell).filter_map(|i| {
+                image.get_pixel_checked(column * cell + i % cell, row * cell + i / cell)
+            });
+            let mut total = [0u32; 4];
+            for &Rgba([r, g, b, a]) in pixels {
+                let colour = Rgb::new(r, g, b);
+                if a < OPAQUE {
+                    continue;
+                }
+                // The marker is drawn on floor, so it's never part of a wall however dark it is.
+                if is_marker(colour) {
+                    spawn.get_or_insert(centre);
+                    continue;
+                }
+                if brightness(colour) < dark {
+                    total[0] += r as u32;
+                    total[1] += g as u32;
+                    total[2] += b as u32;
+                    total[3] += 1;
+                }
+            }
+            let [r, g, b, count] = total;
+            let average = |total: u32| total.checked_div(count).map(|c| c as u8);
+            let colour = match (average(r), average(g), average(b)) {
+                (Some(r), Some(g), Some(b)) if r.max(g).max(b) < BLACK => PEN,
+                (Some(r), Some(g), Some(b)) => Rgb::new(r, g, b),
+                _ => {
+                    floor.get_or_insert(centre);
+                    line.push(FLOOR);
+                    continue;
+                }
+            };
+            let same = palette
+                .iter()
+                .find(|(_, used)| difference(*used, colour) < SAME_COLOUR);
+            let c = match same {
+                Some(&(c, _)) => c,
+                None => {
+                    let c = tile_char(palette.len());
+                    palette.push((c, colour));
+                    c
+                }
+            };
+            line.push(c);
+        }
+        tiles.push(line);
+    }
+    let position = match (spawn, floor) {
+        (Some(spawn), _) => spawn,
+        (None, Some(floor)) => {
+            warn!("There's no green in the picture to start on, so it starts on the first floor");
+            floor
+        }
+        (None, None) => bail!("There's nowhere to stand, the whole picture is dark"),
+    };
+    Ok(Map {
+        spawn: Spawn {
+            position,
+            ..Spawn::default()
+        },
+        grid: Some(GridMap {
+            tiles,
+            palette: palette.into_iter().collect(),
+            textures: HashMap::new(),
+        }),
+        ..Map::default()
+    })
+}
+
+// Bright green marks where to start.
+fn is_marker(colour: Rgb) -> bool {
+    colour.g >= 200 && colour.r <= 80 && colour.b <= 80
+}
+
+fn brightness(colour: Rgb) -> f32 {
+    (0.299 * colour.r as f32 + 0.587 * colour.g as f32 + 0.114 * colour.b as f32) / 255.0
+}
+
+fn difference(a: Rgb, b: Rgb) -> f32 {
+    let channel = |a: u8, b: u8| (a as f32 - b as f32).powi(2);
+    (channel(a.r, b.r) + channel(a.g, b.g) + channel(a.b, b.b)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A white square with a black border, and `centre` in its middle.
+    fn room(centre: [u8; 3]) -> RgbaImage {
+        RgbaImage::from_fn(5, 5, |x, y| match (x, y) {
+            (2, 2) => Rgba([centre[0], centre[1], centre[2], 255]),
+            (0 | 4, _) | (_, 0 | 4) => Rgba([0, 0, 0, 255]),
+            _ => Rgba([255, 255, 255, 255]),
+        })
+    }
+
+    #[test]
+    fn walls_are_dark_cells() {
+        let map = from_image(&room([255, 255, 255]), 1, DARK).unwrap();
+        let grid = map.grid.unwrap();
+        assert_eq!(grid.tiles, ["#####", "#...#", "#...#", "#...#", "#####"]);
+        assert_eq!(grid.palette[&'#'], PEN);
+        assert_eq!(map.spawn.position, Vec2::new(1.5, 1.5));
+    }
+
+    #[test]
+    fn markers_are_floor_however_dark() {
+        for dark in [DARK, 0.9] {
+            let map = from_image(&room([0, 200, 0]), 1, dark).unwrap();
+            let grid = map.grid.unwrap();
+            assert_eq!(grid.tiles, ["#####", "#...#", "#...#", "#...#", "#####"]);
+            assert_eq!(map.spawn.position, Vec2::new(2.5, 2.5));
+        }
+    }
+
+    #[test]
+    fn cells_with_any_dark_pixel_are_walls() {
+        let image = RgbaImage::from_fn(4, 4, |x, _| match x {
+            0 => Rgba([0, 0, 200, 255]),
+            _ => Rgba([255, 255, 255, 255]),
+        });
+        let grid = from_image(&image, 2, DARK).unwrap().grid.unwrap();
+        assert_eq!(grid.tiles, ["#.", "#."]);
+        assert_eq!(grid.palette[&'#'], Rgb::new(0, 0, 200));
+    }
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+use crate::vec2::Vec2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ambience {
+    Hum,
+    Buzz,
+}
+
+// A sound that loops forever at a fixed spot, getting quieter with distance until it can't be
+// heard past `radius`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AmbientSound {
+    pub position: Vec2,
+    pub sound: Ambience,
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    #[serde(default = "default_radius")]
+    pub radius: f32,
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+fn default_radius() -> f32 {
+    8.0
+}
+
+// A sound played once where something happened, e.g. by a trigger region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cue {
+    Chime,
+    Rumble,
+    Alarm,
+}
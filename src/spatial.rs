@@ -0,0 +1,297 @@
+use crate::{
+    geometry::{Hit, LineSegment},
+    vec2::Vec2,
+};
+
+// World units along each side of a cell, before the index grows them to stay under
+// `MAX_CELLS`.
+const CELL_SIZE: f32 = 1.0;
+const MAX_CELLS: usize = 1 << 16;
+// How far past the line a segment is counted as being in a cell, so walls lying right on a cell
+// boundary are in the cells on both sides.
+const MARGIN: f32 = 1e-3;
+
+// Buckets segments into a uniform grid of cells so a ray only has to be tested against the
+// segments in the cells it passes through, rather than every segment in the map.
+pub struct SegmentIndex {
+    origin: Vec2,
+    cell_size: f32,
+    width: usize,
+    height: usize,
+    // The indices of the segments overlapping each cell, row by row.
+    cells: Vec<Vec<usize>>,
+}
+
+impl SegmentIndex {
+    pub fn new(segments: &[LineSegment]) -> Self {
+        let points = segments.iter().flat_map(|s| [s.start, s.end]);
+        let Some((min, max)) = points.fold(None, |bounds: Option<(Vec2, Vec2)>, p| {
+            Some(match bounds {
+                Some((min, max)) => (
+                    Vec2::new(min.x.min(p.x), min.y.min(p.y)),
+                    Vec2::new(max.x.max(p.x), max.y.max(p.y)),
+                ),
+                None => (p, p),
+            })
+        }) else {
+            return Self {
+                origin: Vec2::ZERO,
+                cell_size: CELL_SIZE,
+                width: 0,
+                height: 0,
+                cells: Vec::new(),
+            };
+        };
+        let origin = min - Vec2::new(MARGIN, MARGIN);
+        let size = max - min + Vec2::new(2.0 * MARGIN, 2.0 * MARGIN);
+        let cell_size = CELL_SIZE.max((size.x * size.y / MAX_CELLS as f32).sqrt());
+        let width = (size.x / cell_size).ceil().max(1.0) as usize;
+        let height = (size.y / cell_size).ceil().max(1.0) as usize;
+        let mut index = Self {
+            origin,
+            cell_size,
+            width,
+            height,
+            cells: vec![Vec::new(); width * height],
+        };
+        // Only the cells each segment crosses are walked, and the box around the part in each
+        // one is grown by the margin, so a long diagonal doesn't fill its whole bounding box.
+        for (i, segment) in segments.iter().enumerate() {
+            let direction = (segment.end - segment.start).normalize();
+            let mut entered = 0.0;
+            for (_, left) in index.walk(segment.start, segment.end) {
+                let a = segment.start + direction * entered;
+                let b = segment.start + direction * left;
+                entered = left;
+                let (left, bottom) =
+                    index.cell_of(Vec2::new(a.x.min(b.x) - MARGIN, a.y.min(b.y) - MARGIN));
+                let (right, top) =
+                    index.cell_of(Vec2::new(a.x.max(b.x) + MARGIN, a.y.max(b.y) + MARGIN));
+                for y in bottom..=top {
+                    for x in left..=right {
+                        let cell = &mut index.cells[y * width + x];
+                        // The boxes of neighbouring parts overlap, and nothing else is added
+                        // to a cell in between.
+                        if cell.last() != Some(&i) {
+                            cell.push(i);
+                        }
+                    }
+                }
+            }
+        }
+        index
+    }
+
+    // The cell `point` is in, clamped to the grid.
+    fn cell_of(&self, point: Vec2) -> (usize, usize) {
+        let cell = (point - self.origin) * (1.0 / self.cell_size);
+        (
+            (cell.x.floor().max(0.0) as usize).min(self.width - 1),
+            (cell.y.floor().max(0.0) as usize).min(self.height - 1),
+        )
+    }
+
+    // Every hit between `ray` and the segments in the cells it passes through, up to the cell
+    // holding the nearest one that can't be seen through. Adds how many segments it tested
+    // to `tests`.
+    pub fn hits(&self, segments: &[LineSegment], ray: &LineSegment, tests: &mut usize) -> Vec<Hit> {
+        let mut hits = Vec::new();
+        if ray.length() <= f32::EPSILON {
+            return hits;
+        }
+        // A bit for each segment already tested, since one can be in many of the cells.
+        let mut tested = vec![0u64; segments.len().div_ceil(64)];
+        let mut nearest = f32::INFINITY;
+        for (cell, left) in self.walk(ray.start, ray.end) {
+            for &i in self.cells[cell].iter() {
+                *tests += 1;
+                let (word, bit) = (i / 64, 1 << (i % 64));
+                if tested[word] & bit != 0 {
+                    continue;
+                }
+                tested[word] |= bit;
+                if let Some(hit) = segments[i].hit(ray) {
+                    if !hit.is_see_through() {
+                        nearest = nearest.min(hit.distance);
+                    }
+                    hits.push(hit);
+                }
+            }
+            if nearest <= left {
+                break;
+            }
+        }
+        hits
+    }
+
+    // The cells the line from `start` to `end` passes through, walked one boundary at a time
+    // (DDA) like `Grid::cast`.
+    fn walk(&self, start: Vec2, end: Vec2) -> Walk {
+        let length = start.distance(end);
+        let direction = (end - start).normalize();
+        let mut walk = Walk {
+            width: self.width,
+            height: self.height,
+            cell: (-1, -1),
+            step: (direction.x.signum() as i32, direction.y.signum() as i32),
+            delta: (
+                (self.cell_size / direction.x).abs(),
+                (self.cell_size / direction.y).abs(),
+            ),
+            side: (f32::INFINITY, f32::INFINITY),
+            exit: 0.0,
+        };
+        if self.cells.is_empty() {
+            return walk;
+        }
+        let Some((enter, exit)) = self.clip(start, direction, length) else {
+            return walk;
+        };
+        let start = start + direction * enter;
+        let (x, y) = self.cell_of(start);
+        let corner = self.origin + Vec2::new(x as f32, y as f32) * self.cell_size;
+        walk.cell = (x as i32, y as i32);
+        // How far it is to the first boundary on an axis, which is never reached on one the line
+        // runs along.
+        let boundary = |start: f32, corner: f32, direction: f32| {
+            if direction < 0.0 {
+                enter + (start - corner) / -direction
+            } else if direction > 0.0 {
+                enter + (corner + self.cell_size - start) / direction
+            } else {
+                f32::INFINITY
+            }
+        };
+        walk.side = (
+            boundary(start.x, corner.x, direction.x),
+            boundary(start.y, corner.y, direction.y),
+        );
+        walk.exit = exit;
+        walk
+    }
+
+    // How far along the ray it enters and leaves the grid, if it does at all.
+    fn clip(&self, start: Vec2, direction: Vec2, length: f32) -> Option<(f32, f32)> {
+        let max = self.origin + Vec2::new(self.width as f32, self.height as f32) * self.cell_size;
+        let mut enter: f32 = 0.0;
+        let mut exit = length;
+        for (start, direction, low, high) in [
+            (start.x, direction.x, self.origin.x, max.x),
+            (start.y, direction.y, self.origin.y, max.y),
+        ] {
+            if direction == 0.0 {
+                if start < low || start > high {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((low - start) / direction, (high - start) / direction);
+            enter = enter.max(a.min(b));
+            exit = exit.min(a.max(b));
+        }
+        (enter <= exit).then_some((enter, exit))
+    }
+}
+
+// The cells along a line, each with how far along the line it's left, ending with the one the
+// line leaves the grid or stops in.
+struct Walk {
+    width: usize,
+    height: usize,
+    cell: (i32, i32),
+    step: (i32, i32),
+    // How far along the line it is between boundaries, and to the next boundary, on each axis.
+    delta: (f32, f32),
+    side: (f32, f32),
+    exit: f32,
+}
+
+impl Iterator for Walk {
+    type Item = (usize, f32);
+
+    fn next(&mut self) -> Option<(usize, f32)> {
+        let (x, y) = self.cell;
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        let left = self.side.0.min(self.side.1);
+        if left > self.exit {
+            // Nothing more after this one.
+            self.cell = (-1, -1);
+        } else if self.side.0 < self.side.1 {
+            self.cell.0 += self.step.0;
+            self.side.0 += self.delta.0;
+        } else {
+            self.cell.1 += self.step.1;
+            self.side.1 += self.delta.1;
+        }
+        Some((y as usize * self.width + x as usize, left.min(self.exit)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Rgb;
+    use rand::{rngs::StdRng, RngExt, SeedableRng};
+
+    // Every hit up to and including the nearest one that can't be seen through, nearest first.
+    fn visible(mut hits: Vec<Hit>) -> Vec<f32> {
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        let nearest = hits
+            .iter()
+            .find(|hit| !hit.is_see_through())
+            .map_or(f32::INFINITY, |hit| hit.distance);
+        hits.iter()
+            .map(|hit| hit.distance)
+            .filter(|&distance| distance <= nearest)
+            .collect()
+    }
+
+    #[test]
+    fn indexed_casts_match_testing_every_segment() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let point = |rng: &mut StdRng| {
+            Vec2::new(rng.random_range(-20.0..20.0), rng.random_range(-20.0..20.0))
+        };
+        let mut segments: Vec<_> = (0..300)
+            .map(|i| {
+                let start = point(&mut rng);
+                let (start, end) = match i % 3 {
+                    0 => (start, point(&mut rng)),
+                    1 => {
+                        let offset =
+                            Vec2::new(rng.random_range(-2.0..2.0), rng.random_range(-2.0..2.0));
+                        (start, start + offset)
+                    }
+                    // Lying along a cell boundary.
+                    _ => {
+                        let x = start.x.round();
+                        (Vec2::new(x, start.y), Vec2::new(x, start.y + 3.0))
+                    }
+                };
+                let opacity = if i % 4 == 0 { 0.5 } else { 1.0 };
+                LineSegment::from_points(start, end, Rgb::WHITE).with_opacity(opacity)
+            })
+            .collect();
+        let dot = Vec2::new(3.0, 3.0);
+        segments.push(LineSegment::from_points(dot, dot, Rgb::WHITE));
+        let index = SegmentIndex::new(&segments);
+        for _ in 0..2000 {
+            let ray = LineSegment::ray(
+                point(&mut rng) * 1.5,
+                rng.random_range(0.0..std::f32::consts::TAU),
+                rng.random_range(0.0..60.0),
+            );
+            let all = segments.iter().filter_map(|s| s.hit(&ray)).collect();
+            let indexed = index.hits(&segments, &ray, &mut 0);
+            assert_eq!(
+                visible(indexed),
+                visible(all),
+                "{:?} to {:?}",
+                ray.start,
+                ray.end
+            );
+        }
+    }
+}
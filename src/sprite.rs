@@ -0,0 +1,32 @@
+use crate::colour::Rgb;
+
+// How an entity is drawn, as a billboard at its position.
+pub struct Sprite {
+    pub colour: Rgb,
+    pub texture: Option<usize>,
+    // Width and height in world units, where walls are 1.0 tall. Sprites stand on the floor.
+    pub size: f32,
+    // Height of the sprite's bottom above the floor, on the same scale.
+    pub elevation: f32,
+}
+
+impl Sprite {
+    pub fn new(colour: Rgb, size: f32) -> Self {
+        Self {
+            colour,
+            texture: None,
+            size,
+            elevation: 0.0,
+        }
+    }
+
+    pub fn with_texture(mut self, texture: Option<usize>) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    pub fn with_elevation(mut self, elevation: f32) -> Self {
+        self.elevation = elevation;
+        self
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{colour::Rgb, enemy::Enemy, geometry::LineSegment, sprite::Sprite, vec2::Vec2};
+
+// How much darker a switch is drawn while it's off.
+const OFF_SHADE: f32 = 0.5;
+
+// A short stretch of wall the player can use to set off everything in `targets`, which name
+// doors, walls and enemies by their map IDs.
+pub struct Switch {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub colour: Rgb,
+    pub texture: Option<usize>,
+    pub targets: Vec<String>,
+    // Switches that only work once stay on after the first use.
+    pub once: bool,
+    pub on: bool,
+}
+
+impl Switch {
+    pub fn new(start: Vec2, end: Vec2, colour: Rgb, targets: Vec<String>) -> Self {
+        Self {
+            start,
+            end,
+            colour,
+            texture: None,
+            targets,
+            once: false,
+            on: false,
+        }
+    }
+
+    pub fn with_texture(mut self, texture: Option<usize>) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    pub fn with_once(mut self, once: bool) -> Self {
+        self.once = once;
+        self
+    }
+
+    pub fn segment(&self) -> LineSegment {
+        let colour = if self.on {
+            self.colour
+        } else {
+            self.colour.scale(OFF_SHADE)
+        };
+        LineSegment::from_points(self.start, self.end, colour).with_texture(self.texture)
+    }
+
+    // Flips the switch, returning whether it did anything.
+    pub fn toggle(&mut self) -> bool {
+        if self.once && self.on {
+            return false;
+        }
+        self.on = !self.on;
+        true
+    }
+}
+
+// A wall that switches can make appear or disappear.
+pub struct Wall {
+    pub id: String,
+    pub segment: LineSegment,
+    pub solid: bool,
+}
+
+impl Wall {
+    pub fn segment(&self) -> Option<LineSegment> {
+        self.solid.then(|| self.segment.clone())
+    }
+}
+
+// An enemy waiting out of sight until a switch wakes it.
+pub struct Dormant {
+    pub id: String,
+    pub sprite: Sprite,
+    pub enemy: Enemy,
+}
@@ -0,0 +1,25 @@
+use crate::entities::Entity;
+
+// How close the player has to step to the middle of a pad to be sent off.
+pub const TELEPORT_RADIUS: f32 = 0.4;
+
+// One of a pair of pads on the floor, each sending the player to the other.
+pub struct Teleporter {
+    pub partner: Entity,
+    // Which way the player ends up looking when they arrive on this pad, or `None` to keep
+    // their facing.
+    pub facing: Option<f32>,
+    // Pads start disarmed, and the one the player arrives on is disarmed too, until the player
+    // steps off it. Otherwise they'd be sent straight back.
+    pub armed: bool,
+}
+
+impl Teleporter {
+    pub fn new(partner: Entity, facing: Option<f32>) -> Self {
+        Self {
+            partner,
+            facing,
+            armed: false,
+        }
+    }
+}
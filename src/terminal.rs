@@ -0,0 +1,121 @@
+use std::{
+    io::{stdout, Stdout, Write},
+    time::Duration,
+};
+
+use anyhow::Result;
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute,
+    style::ResetColor,
+    terminal::{
+        disable_raw_mode, enable_raw_mode, size, supports_keyboard_enhancement, Clear, ClearType,
+        DisableLineWrap, EnableLineWrap, EnterAlternateScreen, LeaveAlternateScreen,
+    },
+};
+use tracing::warn;
+
+use crate::{
+    backend::{AnsiWriter, Backend},
+    cast::Cast,
+    colour::ColourMode,
+    framebuffer::FrameBuffer,
+};
+
+// The terminal the game was started in, through crossterm. It's taken over with raw mode and
+// the alternate screen while this is open, and handed back as it was when it's dropped.
+pub struct Terminal {
+    stdout: Stdout,
+    ansi: AnsiWriter,
+    // Where everything drawn is recorded too, if anywhere.
+    pub cast: Option<Cast>,
+}
+
+impl Terminal {
+    pub fn open(colours: ColourMode) -> Result<Self> {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |p| {
+            leave().unwrap();
+            hook(p);
+        }));
+        enable_raw_mode()?;
+        execute!(
+            stdout(),
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            Hide,
+            DisableLineWrap
+        )?;
+        if supports_keyboard_enhancement()? {
+            execute!(
+                stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        Ok(Self {
+            stdout: stdout(),
+            ansi: AnsiWriter::new(colours),
+            cast: None,
+        })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if let Err(e) = leave() {
+            warn!("Could not restore the terminal: {e:#}");
+        }
+    }
+}
+
+impl Backend for Terminal {
+    fn size(&self) -> Result<(u16, u16)> {
+        Ok(size()?)
+    }
+
+    fn present(&mut self, frame: &FrameBuffer) -> Result<()> {
+        let mut changes = Vec::new();
+        self.ansi.write(frame, &mut changes)?;
+        self.stdout.write_all(&changes)?;
+        self.stdout.flush()?;
+        if let Some(cast) = self.cast.as_mut() {
+            let size = (frame.width, frame.height);
+            // Losing the recording isn't worth stopping the game for.
+            if let Err(e) = cast.frame(size, &changes) {
+                warn!("{e:#}");
+                self.cast = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_event(&mut self, timeout: Duration) -> Result<Option<Event>> {
+        if !event::poll(timeout)? {
+            return Ok(None);
+        }
+        let event = event::read()?;
+        if let Event::Resize(..) = event {
+            self.ansi.invalidate();
+        }
+        Ok(Some(event))
+    }
+}
+
+fn leave() -> Result<()> {
+    execute!(
+        stdout(),
+        PopKeyboardEnhancementFlags,
+        DisableMouseCapture,
+        ResetColor,
+        Clear(ClearType::All),
+        Show,
+        EnableLineWrap,
+        LeaveAlternateScreen
+    )?;
+    disable_raw_mode()?;
+    Ok(())
+}
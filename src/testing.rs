@@ -0,0 +1,15 @@
+// Helpers shared by the unit tests.
+
+use std::{env, fs, path::PathBuf, process};
+
+// Whether two distances or angles are the same, give or take rounding.
+pub fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-4
+}
+
+// A fresh directory for a test to write files in, kept apart from other tests and runs.
+pub fn scratch(test: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("rhywbeth-{}-{test}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,47 @@
+use crate::colour::Rgb;
+
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    // Every frame's texels one after another. Transparent texels are `None`.
+    pub texels: Vec<Option<Rgb>>,
+    pub frames: usize,
+    // Seconds each frame is shown for when there's more than one.
+    pub frame_time: f32,
+    // The frame being shown, moved on by `animate`.
+    pub frame: usize,
+}
+
+impl Texture {
+    pub fn new(width: usize, height: usize, texels: Vec<Option<Rgb>>) -> Self {
+        Self {
+            width,
+            height,
+            frames: (texels.len() / (width * height).max(1)).max(1),
+            texels,
+            frame_time: 0.0,
+            frame: 0,
+        }
+    }
+
+    pub fn with_frame_time(mut self, frame_time: f32) -> Self {
+        self.frame_time = frame_time;
+        self
+    }
+
+    // Shows whichever frame is due `clock` seconds into the level.
+    pub fn animate(&mut self, clock: f32) {
+        if self.frames > 1 && self.frame_time > 0.0 {
+            self.frame = (clock / self.frame_time) as usize % self.frames;
+        }
+    }
+
+    // u wraps every world unit along the wall while v runs from the top of the wall (0.0) to
+    // the bottom (1.0).
+    pub fn sample(&self, u: f32, v: f32) -> Option<Rgb> {
+        let x = (u.rem_euclid(1.0) * self.width as f32) as usize;
+        let y = (v.clamp(0.0, 1.0) * self.height as f32) as usize;
+        let start = self.frame * self.width * self.height;
+        self.texels[start + y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+    }
+}
@@ -0,0 +1,230 @@
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{bail, Context, Error, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{colour::Rgb, world::World};
+
+// The colours of everything that hasn't got any of its own: the world where a map leaves them
+// out, the HUD and menus. Anything left out of a theme file is the classic theme's.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    pub ceiling: Rgb,
+    pub floor: Rgb,
+    // Stand in for the classic theme's wall colours in the same order, e.g. in generated mazes.
+    pub walls: Vec<Rgb>,
+    pub hud: HudTheme,
+    pub menu: MenuTheme,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HudTheme {
+    pub text: Rgb,
+    pub banner: Rgb,
+    pub health: Rgb,
+    pub health_low: Rgb,
+    pub health_empty: Rgb,
+    pub minimap_background: Rgb,
+    pub minimap_border: Rgb,
+    pub player: Rgb,
+    pub facing: Rgb,
+    pub exit: Rgb,
+    pub trigger: Rgb,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MenuTheme {
+    pub background: Rgb,
+    pub border: Rgb,
+    pub text: Rgb,
+    pub selected: Rgb,
+    pub banner: Rgb,
+}
+
+impl Theme {
+    pub fn classic() -> Self {
+        Self {
+            name: "classic".to_string(),
+            ceiling: Rgb::RED,
+            floor: Rgb::BLUE,
+            walls: [
+                "white",
+                "grey",
+                "dark_cyan",
+                "dark_green",
+                "dark_yellow",
+                "magenta",
+                "black",
+                "green",
+                "yellow",
+            ]
+            .iter()
+            .map(|name| Rgb::named(name).unwrap())
+            .collect(),
+            hud: HudTheme::default(),
+            menu: MenuTheme::default(),
+        }
+    }
+
+    pub fn gruvbox() -> Self {
+        let hex = |s: &str| s.parse().unwrap();
+        Self {
+            name: "gruvbox".to_string(),
+            ceiling: hex("#504945"),
+            floor: hex("#7c6f64"),
+            walls: [
+                "#ebdbb2", "#a89984", "#689d6a", "#98971a", "#d79921", "#b16286", "#3c3836",
+                "#b8bb26", "#fabd2f",
+            ]
+            .iter()
+            .map(|s| hex(s))
+            .collect(),
+            hud: HudTheme {
+                text: hex("#ebdbb2"),
+                banner: hex("#fe8019"),
+                health: hex("#b8bb26"),
+                health_low: hex("#fb4934"),
+                health_empty: hex("#3c3836"),
+                minimap_background: hex("#282828"),
+                minimap_border: hex("#665c54"),
+                player: hex("#fabd2f"),
+                facing: hex("#fe8019"),
+                exit: hex("#8ec07c"),
+                trigger: hex("#83a598"),
+            },
+            menu: MenuTheme {
+                background: hex("#282828"),
+                border: hex("#665c54"),
+                text: hex("#ebdbb2"),
+                selected: hex("#fabd2f"),
+                banner: hex("#fe8019"),
+            },
+        }
+    }
+
+    pub fn monochrome() -> Self {
+        let grey = |v: u8| Rgb::new(v, v, v);
+        Self {
+            name: "monochrome".to_string(),
+            ceiling: grey(48),
+            floor: grey(96),
+            walls: [255, 192, 144, 112, 160, 208, 32, 176, 224]
+                .into_iter()
+                .map(grey)
+                .collect(),
+            hud: HudTheme {
+                text: grey(255),
+                banner: grey(255),
+                health: grey(224),
+                health_low: grey(128),
+                health_empty: grey(48),
+                minimap_background: grey(16),
+                minimap_border: grey(96),
+                player: grey(255),
+                facing: grey(192),
+                exit: grey(160),
+                trigger: grey(112),
+            },
+            menu: MenuTheme {
+                background: grey(16),
+                border: grey(96),
+                text: grey(192),
+                selected: grey(255),
+                banner: grey(255),
+            },
+        }
+    }
+
+    pub fn builtin() -> Vec<Self> {
+        vec![Self::classic(), Self::gruvbox(), Self::monochrome()]
+    }
+
+    // A built-in theme by name, or else a theme file.
+    pub fn find(name: &str) -> Result<Self> {
+        if let Some(theme) = Self::builtin().into_iter().find(|theme| theme.name == name) {
+            return Ok(theme);
+        }
+        let path = Path::new(name);
+        if !path.exists() {
+            bail!("Unknown theme {name}, expected classic, gruvbox, monochrome or a theme file");
+        }
+        Self::load(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Could not read theme {}", path.display()))?;
+        let mut theme: Self = toml::from_str(&source)
+            .with_context(|| format!("Could not parse theme {}", path.display()))?;
+        if theme.name == Self::classic().name {
+            theme.name = path.display().to_string();
+        }
+        Ok(theme)
+    }
+
+    // Swaps the colours `world` got from `previous` for this theme's. Worlds are loaded in the
+    // classic theme's colours, so that's what to pass for one that's just been loaded. Colours
+    // that aren't the previous theme's, like most of a map's own, are kept.
+    pub fn recolour(&self, world: &mut World, previous: &Theme) {
+        if world.ceiling.colour == previous.ceiling {
+            world.ceiling.colour = self.ceiling;
+        }
+        if world.floor.colour == previous.floor {
+            world.floor.colour = self.floor;
+        }
+        for segment in &mut world.segments {
+            let wall = previous.walls.iter().position(|&c| c == segment.colour);
+            if let Some(&colour) = wall.and_then(|i| self.walls.get(i)) {
+                segment.colour = colour;
+            }
+        }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::find(s)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+impl Default for HudTheme {
+    fn default() -> Self {
+        Self {
+            text: Rgb::WHITE,
+            banner: Rgb::new(255, 200, 0),
+            health: Rgb::new(0, 200, 64),
+            health_low: Rgb::new(220, 32, 32),
+            health_empty: Rgb::new(64, 0, 0),
+            minimap_background: Rgb::new(16, 16, 16),
+            minimap_border: Rgb::new(96, 96, 96),
+            player: Rgb::new(255, 255, 0),
+            facing: Rgb::new(255, 160, 0),
+            exit: Rgb::new(0, 255, 128),
+            trigger: Rgb::new(64, 128, 255),
+        }
+    }
+}
+
+impl Default for MenuTheme {
+    fn default() -> Self {
+        Self {
+            background: Rgb::new(16, 16, 16),
+            border: Rgb::new(96, 96, 96),
+            text: Rgb::WHITE,
+            selected: Rgb::new(255, 255, 0),
+            banner: Rgb::new(255, 200, 0),
+        }
+    }
+}
@@ -0,0 +1,1014 @@
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use image::RgbaImage;
+use roxmltree::{Document, Node};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::{
+    colour::Rgb,
+    map::{GridMap, Map, Segment, Spawn},
+    theme::Theme,
+    trigger::Exit,
+    vec2::Vec2,
+};
+
+// Whatever's being imported, as the fields a map file would give it.
+type Fields = serde_json::Map<String, Value>;
+
+// Tiled keeps whether a tile is flipped in the top bits of its ID.
+const FLIP_FLAGS: u32 = 0xf000_0000;
+// Grid cells are written with these, one for each kind of tile, and then any character past
+// the end of them.
+const TILE_CHARS: &str = "#ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const FLOOR: char = '.';
+// What imported things without a colour of their own are drawn in.
+const WALL: Rgb = Rgb::WHITE;
+const SPRITE: Rgb = Rgb::new(192, 192, 192);
+const ENEMY: Rgb = Rgb::new(200, 0, 0);
+const DOOR: Rgb = Rgb::new(160, 96, 32);
+const SWITCH: Rgb = Rgb::new(255, 160, 0);
+
+// A map as Tiled writes it to JSON. TMX files are turned into the same shape first.
+#[derive(Debug, Deserialize)]
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    orientation: String,
+    #[serde(default)]
+    layers: Vec<Layer>,
+    #[serde(default)]
+    tilesets: Vec<TilesetRef>,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Layer {
+    Tilelayer {
+        #[serde(default)]
+        data: Option<Data>,
+        encoding: Option<String>,
+        compression: Option<String>,
+        #[serde(default = "visible")]
+        visible: bool,
+        #[serde(default)]
+        properties: Vec<Property>,
+    },
+    Objectgroup {
+        #[serde(default)]
+        objects: Vec<Object>,
+        #[serde(default = "visible")]
+        visible: bool,
+    },
+    Group {
+        #[serde(default)]
+        layers: Vec<Layer>,
+        #[serde(default = "visible")]
+        visible: bool,
+    },
+    // Image layers, which are only for show.
+    #[serde(other)]
+    Other,
+}
+
+fn visible() -> bool {
+    true
+}
+
+// Tile IDs as a list, or packed into base64 when the map was saved that way.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Data {
+    Tiles(Vec<u32>),
+    Encoded(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct TilesetRef {
+    firstgid: u32,
+    // Tilesets kept in a file of their own, relative to the map.
+    source: Option<PathBuf>,
+    #[serde(flatten)]
+    tileset: Tileset,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Tileset {
+    columns: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    margin: u32,
+    spacing: u32,
+    image: Option<PathBuf>,
+    tiles: Vec<TileInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TileInfo {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<Property>,
+    // Tiles of a collection of images each have their own.
+    image: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Object {
+    #[serde(default)]
+    id: u32,
+    // Called the type before Tiled 1.9, and the class after.
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    #[serde(default)]
+    x: f32,
+    #[serde(default)]
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    // Objects drawn as a tile hang up from their position rather than down.
+    gid: Option<u32>,
+    polygon: Option<Vec<Point>>,
+    polyline: Option<Vec<Point>>,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Property {
+    name: String,
+    value: Value,
+}
+
+// A tileset along with where its images are found and, once needed, the image itself.
+struct LoadedTileset {
+    firstgid: u32,
+    tileset: Tileset,
+    dir: PathBuf,
+    image: Option<RgbaImage>,
+}
+
+// Reads a Tiled map, in TMX or JSON by its extension, as a map of this game's own. Tile layers
+// become a grid, with every tile a solid cell, and objects become whatever their class names:
+// a spawn, wall, door, switch, light, sprite, enemy, pickup or exit.
+pub fn import(path: &Path) -> Result<Map> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Could not read map {}", path.display()))?;
+    let tiled: TiledMap = if is_xml(path) {
+        let document = Document::parse(&source)
+            .with_context(|| format!("Could not parse map {}", path.display()))?;
+        serde_json::from_value(xml_map(document.root_element())?)
+    } else {
+        serde_json::from_str(&source)
+    }
+    .with_context(|| format!("Could not parse map {}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    convert(tiled, dir).with_context(|| format!("Could not import {}", path.display()))
+}
+
+fn is_xml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("tmx" | "tsx")
+    )
+}
+
+fn convert(tiled: TiledMap, dir: &Path) -> Result<Map> {
+    if tiled.infinite {
+        bail!("Infinite maps can't be imported, turn off Infinite in the map's properties");
+    }
+    if !matches!(tiled.orientation.as_str(), "" | "orthogonal") {
+        bail!("Only orthogonal maps can be imported");
+    }
+    let mut map: Map = serde_json::from_value(Value::Object(fields(&tiled.properties)))
+        .context("Could not read the map's properties")?;
+    let mut tilesets = Vec::new();
+    for reference in tiled.tilesets {
+        tilesets.push(load_tileset(reference, dir)?);
+    }
+
+    // Tiled counts rows down the screen, and maps count y up it, so everything is turned upside
+    // down to look the same way round in the game as in Tiled.
+    let (width, height) = (tiled.width, tiled.height);
+    let (tile_width, tile_height) = (tiled.tilewidth, tiled.tileheight);
+    let to_map = |x: f32, y: f32| Vec2::new(x / tile_width, height as f32 - y / tile_height);
+
+    let mut layers = Vec::new();
+    flatten(&tiled.layers, &mut layers);
+    let mut cells = vec![0; width * height];
+    let mut objects = Vec::new();
+    for layer in layers {
+        match layer {
+            Layer::Tilelayer {
+                data,
+                encoding,
+                compression,
+                properties,
+                ..
+            } => {
+                // Floors and anything else to walk over can be drawn on layers of their own.
+                let walls = properties.iter().find(|p| p.name == "walls");
+                if walls.is_some_and(|p| p.value == Value::Bool(false)) {
+                    continue;
+                }
+                let Some(data) = data else {
+                    continue;
+                };
+                let tiles = decode(data, encoding.as_deref(), compression.as_deref())?;
+                for (cell, &tile) in cells.iter_mut().zip(tiles.iter()) {
+                    if tile != 0 {
+                        *cell = tile & !FLIP_FLAGS;
+                    }
+                }
+            }
+            Layer::Objectgroup { objects: found, .. } => objects.extend(found),
+            Layer::Group { .. } | Layer::Other => {}
+        }
+    }
+
+    if cells.iter().any(|&tile| tile != 0) {
+        let mut chars = HashMap::new();
+        let mut palette = HashMap::new();
+        let mut tiles = Vec::with_capacity(height);
+        for row in (0..height).rev() {
+            let mut line = String::with_capacity(width);
+            for &tile in cells[row * width..(row + 1) * width].iter() {
+                if tile == 0 {
+                    line.push(FLOOR);
+                    continue;
+                }
+                let count = chars.len();
+                let c = *chars.entry(tile).or_insert_with(|| {
+                    let colour =
+                        tile_colour(&mut tilesets, tile).unwrap_or_else(|| fallback_colour(count));
+                    palette.insert(tile_char(count), colour);
+                    tile_char(count)
+                });
+                line.push(c);
+            }
+            tiles.push(line);
+        }
+        map.grid = Some(GridMap {
+            tiles,
+            palette,
+            textures: HashMap::new(),
+        });
+    }
+
+    for object in objects {
+        let class = if object.class.is_empty() {
+            &object.kind
+        } else {
+            &object.class
+        };
+        let class = class.to_lowercase();
+        add_object(&mut map, object, &class, &to_map)
+            .with_context(|| format!("Could not import object {} ({class})", object.id))?;
+    }
+    Ok(map)
+}
+
+// Every visible layer, with the ones in groups taken out of them.
+fn flatten<'a>(layers: &'a [Layer], out: &mut Vec<&'a Layer>) {
+    for layer in layers {
+        match layer {
+            Layer::Tilelayer { visible, .. } | Layer::Objectgroup { visible, .. } => {
+                if *visible {
+                    out.push(layer);
+                }
+            }
+            Layer::Group { layers, visible } => {
+                if *visible {
+                    flatten(layers, out);
+                }
+            }
+            Layer::Other => {}
+        }
+    }
+}
+
+fn add_object(
+    map: &mut Map,
+    object: &Object,
+    class: &str,
+    to_map: &impl Fn(f32, f32) -> Vec2,
+) -> Result<()> {
+    let mut fields = fields(&object.properties);
+    // Points are where they are, tiles hang up from the bottom left and anything else is
+    // measured from its middle.
+    let (x, y) = match object.gid {
+        Some(_) => (
+            object.x + object.width / 2.0,
+            object.y - object.height / 2.0,
+        ),
+        None => (
+            object.x + object.width / 2.0,
+            object.y + object.height / 2.0,
+        ),
+    };
+    let position = to_map(x, y);
+    let outline = outline(object, to_map);
+    let line = || -> Result<(Vec2, Vec2)> {
+        match outline.as_slice() {
+            [start, end] => Ok((*start, *end)),
+            // A box is the line along the middle of it the long way.
+            [a, b, c, _] if object.polygon.is_none() => {
+                if a.distance(*b) >= b.distance(*c) {
+                    let half = (*c - *b) * 0.5;
+                    Ok((*a + half, *b + half))
+                } else {
+                    let half = (*b - *a) * 0.5;
+                    Ok((*b + half, *c + half))
+                }
+            }
+            [start, .., end] if object.polyline.is_some() => Ok((*start, *end)),
+            _ => bail!("Expected a line or a box"),
+        }
+    };
+    let with_line = |fields: &mut Fields, default: Rgb| -> Result<()> {
+        let (start, end) = line()?;
+        fields.insert("start".to_string(), json!(start));
+        fields.insert("end".to_string(), json!(end));
+        fields.entry("colour").or_insert(json!(default));
+        Ok(())
+    };
+    match class {
+        "spawn" | "player" => {
+            let facing = fields.get("facing").and_then(Value::as_f64);
+            map.spawn = Spawn {
+                position,
+                rotation: facing.map_or(PI / 2.0, |degrees| (degrees as f32).to_radians()),
+            };
+        }
+        "wall" => {
+            let colour = match fields.get("colour") {
+                Some(colour) => serde_json::from_value(colour.clone())?,
+                None => WALL,
+            };
+            let texture = fields
+                .get("texture")
+                .and_then(Value::as_str)
+                .map(String::from);
+            let closed = object.polyline.is_none() && outline.len() > 2;
+            let ends = outline.iter().zip(outline.iter().skip(1));
+            let ends = ends.chain(closed.then(|| (&outline[outline.len() - 1], &outline[0])));
+            for (&start, &end) in ends {
+                map.segments.push(Segment {
+                    start,
+                    end,
+                    colour,
+                    texture: texture.clone(),
+                    opacity: 1.0,
+                    mirror: false,
+                    id: None,
+                    hidden: false,
+                });
+            }
+        }
+        "door" => {
+            with_line(&mut fields, DOOR)?;
+            map.doors.push(entity(fields)?);
+        }
+        "switch" => {
+            with_line(&mut fields, SWITCH)?;
+            // Tiled has no lists, so targets are written with commas between them.
+            if let Some(Value::String(targets)) = fields.get("targets") {
+                let targets = targets.split(',').map(str::trim).collect::<Vec<_>>();
+                fields.insert("targets".to_string(), json!(targets));
+            }
+            fields.entry("targets").or_insert(json!([]));
+            map.switches.push(entity(fields)?);
+        }
+        "light" => {
+            fields.insert("position".to_string(), json!(position));
+            // Ellipses and boxes light as far as they reach, unless given a radius.
+            if object.width > 0.0 && !fields.contains_key("radius") {
+                let radius = to_map(object.width, 0.0).x / 2.0;
+                fields.insert("radius".to_string(), json!(radius));
+            }
+            map.lights.push(entity(fields)?);
+        }
+        "sprite" => {
+            fields.insert("position".to_string(), json!(position));
+            fields.entry("colour").or_insert(json!(SPRITE));
+            map.sprites.push(entity(fields)?);
+        }
+        "enemy" => {
+            fields.insert("position".to_string(), json!(position));
+            fields.entry("colour").or_insert(json!(ENEMY));
+            map.enemies.push(entity(fields)?);
+        }
+        "pickup" => {
+            fields.insert("position".to_string(), json!(position));
+            map.pickups.push(entity(fields)?);
+        }
+        "exit" => {
+            let (min, max) = bounds(&outline).context("Expected a box")?;
+            fields.insert("min".to_string(), json!(min));
+            fields.insert("max".to_string(), json!(max));
+            let exit: Exit = entity(fields)?;
+            map.exit = Some(exit);
+        }
+        "" => {}
+        _ => warn!("Object {} has unknown class {class}, skipping", object.id),
+    }
+    Ok(())
+}
+
+// The corners of a box, polygon or line in map units, or the one point of anything else.
+fn outline(object: &Object, to_map: &impl Fn(f32, f32) -> Vec2) -> Vec<Vec2> {
+    let (x, y) = (object.x, object.y);
+    if let Some(points) = object.polygon.as_ref().or(object.polyline.as_ref()) {
+        return points.iter().map(|p| to_map(x + p.x, y + p.y)).collect();
+    }
+    if object.width > 0.0 || object.height > 0.0 {
+        let (width, height) = (object.width, object.height);
+        return vec![
+            to_map(x, y),
+            to_map(x + width, y),
+            to_map(x + width, y + height),
+            to_map(x, y + height),
+        ];
+    }
+    vec![to_map(x, y)]
+}
+
+fn bounds(points: &[Vec2]) -> Option<(Vec2, Vec2)> {
+    let first = *points.first()?;
+    Some(points.iter().fold((first, first), |(min, max), p| {
+        (
+            Vec2::new(min.x.min(p.x), min.y.min(p.y)),
+            Vec2::new(max.x.max(p.x), max.y.max(p.y)),
+        )
+    }))
+}
+
+// Anything imported is read the same way as a map file, so it has the same defaults.
+fn entity<T: DeserializeOwned>(fields: Fields) -> Result<T> {
+    Ok(serde_json::from_value(Value::Object(fields))?)
+}
+
+// Custom properties as the fields of whatever they're on, spelled the game's way.
+fn fields(properties: &[Property]) -> Fields {
+    properties
+        .iter()
+        .map(|p| {
+            let name = if p.name == "color" { "colour" } else { &p.name };
+            (name.to_string(), without_alpha(p.value.clone()))
+        })
+        .collect()
+}
+
+// Tiled writes colours as #aarrggbb, where the game has no use for the alpha.
+fn without_alpha(value: Value) -> Value {
+    match value {
+        Value::String(s)
+            if s.len() == 9
+                && s.starts_with('#')
+                && s[1..].chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            Value::String(format!("#{}", &s[3..]))
+        }
+        Value::Object(members) => Value::Object(
+            members
+                .into_iter()
+                .map(|(name, value)| (name, without_alpha(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+fn decode(data: &Data, encoding: Option<&str>, compression: Option<&str>) -> Result<Vec<u32>> {
+    let text = match data {
+        Data::Tiles(tiles) => return Ok(tiles.clone()),
+        Data::Encoded(text) => text,
+    };
+    match encoding {
+        Some("csv") => text
+            .split(',')
+            .map(|s| s.trim().parse().context("Invalid tile"))
+            .collect(),
+        Some("base64") => {
+            let packed = STANDARD
+                .decode(text.trim())
+                .context("Invalid base64 tile data")?;
+            let mut bytes = Vec::new();
+            match compression.unwrap_or("") {
+                "" => bytes = packed,
+                "zlib" => {
+                    ZlibDecoder::new(packed.as_slice()).read_to_end(&mut bytes)?;
+                }
+                "gzip" => {
+                    GzDecoder::new(packed.as_slice()).read_to_end(&mut bytes)?;
+                }
+                other => bail!("Unsupported tile layer compression {other}"),
+            }
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect())
+        }
+        _ => bail!("Unsupported tile layer encoding"),
+    }
+}
+
+fn load_tileset(reference: TilesetRef, dir: &Path) -> Result<LoadedTileset> {
+    let (tileset, dir) = match &reference.source {
+        Some(source) => {
+            let path = dir.join(source);
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Could not read tileset {}", path.display()))?;
+            let tileset = if is_xml(&path) {
+                let document = Document::parse(&text)
+                    .with_context(|| format!("Could not parse tileset {}", path.display()))?;
+                serde_json::from_value(xml_tileset(document.root_element())?)
+            } else {
+                serde_json::from_str(&text)
+            }
+            .with_context(|| format!("Could not parse tileset {}", path.display()))?;
+            let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+            (tileset, dir)
+        }
+        None => (reference.tileset, dir.to_path_buf()),
+    };
+    Ok(LoadedTileset {
+        firstgid: reference.firstgid,
+        tileset,
+        dir,
+        image: None,
+    })
+}
+
+// The colour a tile's `colour` property gives it, or else the average of its image.
+fn tile_colour(tilesets: &mut [LoadedTileset], tile: u32) -> Option<Rgb> {
+    let loaded = tilesets
+        .iter_mut()
+        .filter(|t| t.firstgid <= tile)
+        .max_by_key(|t| t.firstgid)?;
+    let id = tile - loaded.firstgid;
+    let info = loaded.tileset.tiles.iter().find(|t| t.id == id);
+    let properties = info
+        .map(|info| fields(&info.properties))
+        .unwrap_or_default();
+    if let Some(colour) = properties.get("colour") {
+        return serde_json::from_value(colour.clone()).ok();
+    }
+    if let Some(image) = info.and_then(|info| info.image.as_ref()) {
+        let image = read_image(&loaded.dir.join(image))?;
+        return average(&image, 0, 0, image.width(), image.height());
+    }
+    let tileset = &loaded.tileset;
+    if loaded.image.is_none() {
+        loaded.image = read_image(&loaded.dir.join(tileset.image.as_ref()?));
+    }
+    let image = loaded.image.as_ref()?;
+    let columns = tileset.columns.max(1);
+    let (column, row) = (id % columns, id / columns);
+    let x = tileset.margin + column * (tileset.tilewidth + tileset.spacing);
+    let y = tileset.margin + row * (tileset.tileheight + tileset.spacing);
+    average(image, x, y, tileset.tilewidth, tileset.tileheight)
+}
+
+fn read_image(path: &Path) -> Option<RgbaImage> {
+    match image::open(path) {
+        Ok(image) => Some(image.to_rgba8()),
+        Err(e) => {
+            warn!("Could not read tile image {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+// The average of the pixels in a part of an image that aren't see-through.
+fn average(image: &RgbaImage, x: u32, y: u32, width: u32, height: u32) -> Option<Rgb> {
+    let mut sum = [0u64; 3];
+    let mut count = 0;
+    for py in y..(y + height).min(image.height()) {
+        for px in x..(x + width).min(image.width()) {
+            let pixel = image.get_pixel(px, py);
+            if pixel[3] > 0 {
+                for (total, &channel) in sum.iter_mut().zip(pixel.0.iter()) {
+                    *total += channel as u64;
+                }
+                count += 1;
+            }
+        }
+    }
+    let channel = |total: u64| (total / count) as u8;
+    (count > 0).then(|| Rgb::new(channel(sum[0]), channel(sum[1]), channel(sum[2])))
+}
+
+pub fn tile_char(index: usize) -> char {
+    TILE_CHARS
+        .chars()
+        .nth(index)
+        .or_else(|| char::from_u32(0x100 + index as u32))
+        .unwrap_or(FLOOR)
+}
+
+pub fn fallback_colour(index: usize) -> Rgb {
+    let walls = Theme::classic().walls;
+    walls[index % walls.len()]
+}
+
+// The TMX format turned into what Tiled would have written to JSON, attribute by attribute.
+fn xml_map(node: Node) -> Result<Value> {
+    let mut map = attributes(node, &["width", "height", "tilewidth", "tileheight"]);
+    map.insert(
+        "infinite".to_string(),
+        json!(node.attribute("infinite") == Some("1")),
+    );
+    if let Some(orientation) = node.attribute("orientation") {
+        map.insert("orientation".to_string(), json!(orientation));
+    }
+    map.insert("properties".to_string(), xml_properties(node));
+    let mut tilesets = Vec::new();
+    for child in node.children().filter(Node::is_element) {
+        if child.has_tag_name("tileset") {
+            tilesets.push(xml_tileset(child)?);
+        }
+    }
+    map.insert("tilesets".to_string(), Value::Array(tilesets));
+    map.insert("layers".to_string(), Value::Array(xml_layers(node)?));
+    Ok(Value::Object(map))
+}
+
+fn xml_layers(node: Node) -> Result<Vec<Value>> {
+    let mut layers = Vec::new();
+    for child in node.children().filter(Node::is_element) {
+        let visible = json!(child.attribute("visible") != Some("0"));
+        let mut layer = match child.tag_name().name() {
+            "layer" => {
+                let mut layer = Fields::new();
+                layer.insert("type".to_string(), json!("tilelayer"));
+                if let Some(data) = child.children().find(|c| c.has_tag_name("data")) {
+                    if data.children().any(|c| c.has_tag_name("chunk")) {
+                        bail!("Infinite maps can't be imported, turn off Infinite in the map's properties");
+                    }
+                    for name in ["encoding", "compression"] {
+                        if let Some(value) = data.attribute(name) {
+                            layer.insert(name.to_string(), json!(value));
+                        }
+                    }
+                    // Without an encoding each tile is an element of its own.
+                    let value = match data.attribute("encoding") {
+                        Some(_) => json!(data.text().unwrap_or("")),
+                        None => data
+                            .children()
+                            .filter(|c| c.has_tag_name("tile"))
+                            .map(|tile| number(tile, "gid"))
+                            .collect(),
+                    };
+                    layer.insert("data".to_string(), value);
+                }
+                layer
+            }
+            "objectgroup" => {
+                let mut layer = Fields::new();
+                layer.insert("type".to_string(), json!("objectgroup"));
+                let objects = child
+                    .children()
+                    .filter(|c| c.has_tag_name("object"))
+                    .map(xml_object)
+                    .collect();
+                layer.insert("objects".to_string(), Value::Array(objects));
+                layer
+            }
+            "group" => {
+                let mut layer = Fields::new();
+                layer.insert("type".to_string(), json!("group"));
+                layer.insert("layers".to_string(), Value::Array(xml_layers(child)?));
+                layer
+            }
+            "imagelayer" => {
+                let mut layer = Fields::new();
+                layer.insert("type".to_string(), json!("imagelayer"));
+                layer
+            }
+            _ => continue,
+        };
+        layer.insert("visible".to_string(), visible);
+        layer.insert("properties".to_string(), xml_properties(child));
+        layers.push(Value::Object(layer));
+    }
+    Ok(layers)
+}
+
+fn xml_object(node: Node) -> Value {
+    let mut object = attributes(node, &["id", "x", "y", "width", "height", "gid"]);
+    for name in ["type", "class"] {
+        if let Some(value) = node.attribute(name) {
+            object.insert(name.to_string(), json!(value));
+        }
+    }
+    for child in node.children().filter(Node::is_element) {
+        let name = child.tag_name().name();
+        if let ("polygon" | "polyline", Some(points)) = (name, child.attribute("points")) {
+            let points = points
+                .split_whitespace()
+                .filter_map(|pair| pair.split_once(','))
+                .map(|(x, y)| {
+                    json!({
+                        "x": x.parse::<f32>().unwrap_or(0.0),
+                        "y": y.parse::<f32>().unwrap_or(0.0),
+                    })
+                })
+                .collect();
+            object.insert(name.to_string(), Value::Array(points));
+        }
+    }
+    object.insert("properties".to_string(), xml_properties(node));
+    Value::Object(object)
+}
+
+fn xml_tileset(node: Node) -> Result<Value> {
+    let mut tileset = attributes(
+        node,
+        &[
+            "firstgid",
+            "columns",
+            "tilewidth",
+            "tileheight",
+            "margin",
+            "spacing",
+        ],
+    );
+    if let Some(source) = node.attribute("source") {
+        tileset.insert("source".to_string(), json!(source));
+    }
+    let image = |node: Node| {
+        node.children()
+            .find(|c| c.has_tag_name("image"))
+            .and_then(|image| image.attribute("source"))
+            .map(|source| json!(source))
+    };
+    if let Some(source) = image(node) {
+        tileset.insert("image".to_string(), source);
+    }
+    let mut tiles = Vec::new();
+    for tile in node.children().filter(|c| c.has_tag_name("tile")) {
+        let mut info = attributes(tile, &["id"]);
+        info.insert("properties".to_string(), xml_properties(tile));
+        if let Some(source) = image(tile) {
+            info.insert("image".to_string(), source);
+        }
+        tiles.push(Value::Object(info));
+    }
+    tileset.insert("tiles".to_string(), Value::Array(tiles));
+    Ok(Value::Object(tileset))
+}
+
+// A node's `<properties>`, each with its value turned into the type it's marked as.
+fn xml_properties(node: Node) -> Value {
+    let Some(properties) = node.children().find(|c| c.has_tag_name("properties")) else {
+        return json!([]);
+    };
+    properties
+        .children()
+        .filter(|c| c.has_tag_name("property"))
+        .map(|property| {
+            let text = property
+                .attribute("value")
+                .or_else(|| property.text())
+                .unwrap_or("");
+            let value = match property.attribute("type").unwrap_or("string") {
+                "int" | "object" => text.parse::<i64>().map(|n| json!(n)).unwrap_or(Value::Null),
+                "float" => text.parse::<f64>().map(|n| json!(n)).unwrap_or(Value::Null),
+                "bool" => json!(text == "true"),
+                // Class properties are a set of properties of their own.
+                "class" => {
+                    let members = xml_properties(property);
+                    let members = members.as_array().into_iter().flatten();
+                    Value::Object(
+                        members
+                            .filter_map(|m| {
+                                Some((m["name"].as_str()?.to_string(), m["value"].clone()))
+                            })
+                            .collect(),
+                    )
+                }
+                _ => json!(text),
+            };
+            json!({ "name": property.attribute("name").unwrap_or(""), "value": value })
+        })
+        .collect()
+}
+
+// Numeric attributes as JSON numbers, leaving out any that are missing.
+fn attributes(node: Node, names: &[&str]) -> Fields {
+    names
+        .iter()
+        .filter(|&&name| node.attribute(name).is_some())
+        .map(|&name| (name.to_string(), number(node, name)))
+        .collect()
+}
+
+fn number(node: Node, name: &str) -> Value {
+    let text = node.attribute(name).unwrap_or("0");
+    match text.parse::<u64>() {
+        Ok(n) => json!(n),
+        Err(_) => json!(text.parse::<f64>().unwrap_or(0.0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::*;
+
+    // A 4x3 room with its walls in tile 1, which is coloured red, and a pillar in tile 2.
+    const TMX: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map orientation="orthogonal" width="4" height="3" tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="ambient" type="float" value="0.5"/>
+ </properties>
+ <tileset firstgid="1" name="tiles" tilewidth="16" tileheight="16" columns="2">
+  <tile id="0">
+   <properties>
+    <property name="color" type="color" value="#ffff0000"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer id="1" name="walls" width="4" height="3">
+  <data encoding="csv">
+1,1,1,1,
+1,0,2,1,
+1,1,1,1
+</data>
+ </layer>
+ <objectgroup id="2" name="things">
+  <object id="1" type="spawn" x="24" y="24">
+   <properties>
+    <property name="facing" type="float" value="0"/>
+   </properties>
+   <point/>
+  </object>
+  <object id="2" type="door" x="16" y="8" width="32" height="4"/>
+  <object id="3" type="enemy" x="16" y="16" width="16" height="16">
+   <properties>
+    <property name="health" type="float" value="20"/>
+   </properties>
+  </object>
+  <object id="4" type="pickup" x="40" y="24">
+   <properties>
+    <property name="type" value="key"/>
+    <property name="key" value="blue"/>
+   </properties>
+   <point/>
+  </object>
+  <object id="5" class="exit" x="32" y="16" width="16" height="16"/>
+ </objectgroup>
+</map>
+"##;
+
+    // The same map as Tiled writes it to JSON.
+    const TMJ: &str = r##"{
+        "orientation": "orthogonal", "width": 4, "height": 3,
+        "tilewidth": 16, "tileheight": 16, "infinite": false,
+        "properties": [{ "name": "ambient", "type": "float", "value": 0.5 }],
+        "tilesets": [{
+            "firstgid": 1, "name": "tiles", "tilewidth": 16, "tileheight": 16, "columns": 2,
+            "tiles": [{
+                "id": 0,
+                "properties": [{ "name": "color", "type": "color", "value": "#ffff0000" }]
+            }]
+        }],
+        "layers": [
+            {
+                "type": "tilelayer", "name": "walls", "width": 4, "height": 3,
+                "data": [1, 1, 1, 1, 1, 0, 2, 1, 1, 1, 1, 1]
+            },
+            {
+                "type": "objectgroup", "name": "things",
+                "objects": [
+                    {
+                        "id": 1, "type": "spawn", "x": 24, "y": 24, "point": true,
+                        "properties": [{ "name": "facing", "type": "float", "value": 0 }]
+                    },
+                    { "id": 2, "type": "door", "x": 16, "y": 8, "width": 32, "height": 4 },
+                    {
+                        "id": 3, "type": "enemy", "x": 16, "y": 16, "width": 16, "height": 16,
+                        "properties": [{ "name": "health", "type": "float", "value": 20 }]
+                    },
+                    {
+                        "id": 4, "type": "pickup", "x": 40, "y": 24, "point": true,
+                        "properties": [
+                            { "name": "type", "type": "string", "value": "key" },
+                            { "name": "key", "type": "string", "value": "blue" }
+                        ]
+                    },
+                    { "id": 5, "class": "exit", "x": 32, "y": 16, "width": 16, "height": 16 }
+                ]
+            }
+        ]
+    }"##;
+
+    fn from_tmx(source: &str) -> Result<Map> {
+        let document = Document::parse(source)?;
+        convert(
+            serde_json::from_value(xml_map(document.root_element())?)?,
+            Path::new(""),
+        )
+    }
+
+    fn from_tmj(source: &str) -> Result<Map> {
+        convert(serde_json::from_str(source)?, Path::new(""))
+    }
+
+    #[test]
+    fn imports_tiles_and_objects() {
+        let map = from_tmx(TMX).unwrap();
+        assert_eq!(map.ambient, 0.5);
+        let grid = map.grid.as_ref().unwrap();
+        // The bottom row in Tiled is the first in the map.
+        assert_eq!(grid.tiles, ["####", "#.A#", "####"]);
+        assert_eq!(grid.palette[&'#'], Rgb::new(255, 0, 0));
+        assert_eq!(grid.palette[&'A'], fallback_colour(1));
+        assert_eq!(map.spawn.position, Vec2::new(1.5, 1.5));
+        assert_eq!(map.spawn.rotation, 0.0);
+        // The door runs along the middle of its box the long way.
+        let door = &map.doors[0];
+        assert_eq!(
+            (door.start, door.end),
+            (Vec2::new(1.0, 2.375), Vec2::new(3.0, 2.375))
+        );
+        assert_eq!(door.colour, DOOR);
+        assert_eq!(map.enemies[0].position, Vec2::new(1.5, 1.5));
+        assert_eq!(map.enemies[0].enemy.health, 20.0);
+        assert_eq!(map.enemies[0].colour, ENEMY);
+        assert_eq!(map.pickups[0].position, Vec2::new(2.5, 1.5));
+        assert!(matches!(
+            map.pickups[0].pickup,
+            crate::pickup::Pickup::Key {
+                key: crate::pickup::KeyColour::Blue
+            }
+        ));
+        let exit = map.exit.as_ref().unwrap();
+        assert_eq!(
+            (exit.region.min, exit.region.max),
+            (Vec2::new(2.0, 1.0), Vec2::new(3.0, 2.0))
+        );
+        assert!(map.world().is_ok());
+    }
+
+    #[test]
+    fn tmx_and_tmj_import_the_same() {
+        let tmx = serde_json::to_value(from_tmx(TMX).unwrap()).unwrap();
+        let tmj = serde_json::to_value(from_tmj(TMJ).unwrap()).unwrap();
+        assert_eq!(tmx, tmj);
+    }
+
+    #[test]
+    fn decodes_packed_tiles() {
+        let tiles = [1u32, 0, 2, 0x8000_0003];
+        let bytes: Vec<u8> = tiles.iter().flat_map(|t| t.to_le_bytes()).collect();
+        let plain = Data::Encoded(STANDARD.encode(&bytes));
+        assert_eq!(decode(&plain, Some("base64"), None).unwrap(), tiles);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes).unwrap();
+        let zlib = Data::Encoded(STANDARD.encode(encoder.finish().unwrap()));
+        assert_eq!(decode(&zlib, Some("base64"), Some("zlib")).unwrap(), tiles);
+        let csv = Data::Encoded("1, 0,\n2".to_string());
+        assert_eq!(decode(&csv, Some("csv"), None).unwrap(), [1, 0, 2]);
+        assert!(decode(&plain, Some("base64"), Some("zstd")).is_err());
+    }
+
+    #[test]
+    fn rejects_maps_it_cant_import() {
+        let infinite = TMJ.replace(r#""infinite": false"#, r#""infinite": true"#);
+        assert!(from_tmj(&infinite).is_err());
+        let isometric = TMJ.replace("orthogonal", "isometric");
+        assert!(from_tmj(&isometric).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{
+    colour::Rgb,
+    map::{GridMap, Map, Spawn, TextureMap},
+    tiled::{fallback_colour, tile_char},
+    vec2::Vec2,
+};
+
+// Wolfenstein 3D and Rise of the Triad maps are 64 by 64 tiles, with each plane of them one
+// 16-bit number per tile.
+const DUMP_SIZE: usize = 64;
+const PLANE_BYTES: usize = DUMP_SIZE * DUMP_SIZE * 2;
+// Without a palette, tiles numbered below this are walls, as they are in both games, and the
+// rest are floor.
+const FIRST_FLOOR: u16 = 64;
+// The player starts on one of these in the objects plane, facing north, east, south or west.
+const PLAYER_STARTS: [u16; 4] = [19, 20, 21, 22];
+const FLOOR: char = '.';
+
+// Which tile IDs are walls and how they look, read from a TOML file beside the grid.
+#[derive(Debug, Default, Deserialize)]
+struct Palette {
+    // The tile the player starts on, for grids without an objects plane to find it in.
+    spawn: Option<u16>,
+    // Walls by tile ID. Any ID not in here is floor.
+    #[serde(default)]
+    tiles: HashMap<String, PaletteTile>,
+    // Textures for the walls to name, just as they're written in maps.
+    #[serde(default)]
+    textures: HashMap<String, TextureMap>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PaletteTile {
+    colour: Option<Rgb>,
+    texture: Option<String>,
+}
+
+// A grid of tile IDs, rows from the top down, with the objects on it if it came with them.
+struct Planes {
+    width: usize,
+    height: usize,
+    walls: Vec<u16>,
+    objects: Option<Vec<u16>>,
+}
+
+// Reads a grid of tile IDs as a grid map. CSV files are rows of IDs separated by commas, and
+// `.bin` files are dumps of a Wolfenstein 3D or Rise of the Triad map: its walls plane, then
+// optionally its objects plane and any others, which are ignored. Walls are given their colours
+// and textures by `<name>.palette.toml` or `palette.toml` beside the grid, if there is one.
+pub fn import(path: &Path) -> Result<Map> {
+    let planes = match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => read_csv(path)?,
+        _ => read_dump(path)?,
+    };
+    let palette = match palette_path(path) {
+        Some(palette) => {
+            let source = fs::read_to_string(&palette)
+                .with_context(|| format!("Could not read palette {}", palette.display()))?;
+            toml::from_str(&source)
+                .with_context(|| format!("Could not parse palette {}", palette.display()))?
+        }
+        None => Palette::default(),
+    };
+    convert(planes, palette).with_context(|| format!("Could not import {}", path.display()))
+}
+
+fn palette_path(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let own = path.with_file_name(format!("{stem}.palette.toml"));
+    let shared = path.with_file_name("palette.toml");
+    [own, shared].into_iter().find(|palette| palette.is_file())
+}
+
+fn read_csv(path: &Path) -> Result<Planes> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Could not read map {}", path.display()))?;
+    let rows = source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .enumerate()
+        .map(|(y, line)| {
+            line.split(',')
+                .map(str::trim)
+                .map(|id| match id {
+                    "" => Ok(0),
+                    id => id
+                        .parse()
+                        .with_context(|| format!("Tile {id:?} on row {} is not a tile ID", y + 1)),
+                })
+                .collect::<Result<Vec<u16>>>()
+        })
+        .collect::<Result<Vec<_>>>()
+        .with_context(|| format!("Could not parse map {}", path.display()))?;
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let walls = rows
+        .iter()
+        .flat_map(|row| row.iter().copied().chain(std::iter::repeat(0)).take(width))
+        .collect();
+    Ok(Planes {
+        width,
+        height: rows.len(),
+        walls,
+        objects: None,
+    })
+}
+
+fn read_dump(path: &Path) -> Result<Planes> {
+    let data = fs::read(path).with_context(|| format!("Could not read map {}", path.display()))?;
+    if data.is_empty() || data.len() % PLANE_BYTES != 0 {
+        bail!(
+            "{} is not a map dump, which should be {DUMP_SIZE}x{DUMP_SIZE} planes of 16-bit tile \
+             IDs",
+            path.display()
+        );
+    }
+    let mut planes = data.chunks_exact(PLANE_BYTES).map(|plane| {
+        plane
+            .chunks_exact(2)
+            .map(|id| u16::from_le_bytes([id[0], id[1]]))
+            .collect()
+    });
+    Ok(Planes {
+        width: DUMP_SIZE,
+        height: DUMP_SIZE,
+        walls: planes.next().unwrap_or_default(),
+        objects: planes.next(),
+    })
+}
+
+fn convert(planes: Planes, palette: Palette) -> Result<Map> {
+    let Planes {
+        width,
+        height,
+        walls,
+        objects,
+    } = planes;
+    let tiles = palette
+        .tiles
+        .iter()
+        .map(|(id, tile)| {
+            let id = id
+                .parse::<u16>()
+                .with_context(|| format!("Palette tile {id:?} is not a tile ID"))?;
+            Ok((id, tile))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+    let is_wall = |id: u16| {
+        if tiles.is_empty() {
+            id != 0 && id < FIRST_FLOOR
+        } else {
+            tiles.contains_key(&id)
+        }
+    };
+    // The middle of the tile at `index`, with rows turned upside down since maps count y up.
+    let centre = |index: usize| {
+        let (x, y) = (index % width, index / width);
+        Vec2::new(x as f32 + 0.5, (height - 1 - y) as f32 + 0.5)
+    };
+
+    let mut chars = HashMap::new();
+    let mut grid = GridMap {
+        tiles: Vec::with_capacity(height),
+        palette: HashMap::new(),
+        textures: HashMap::new(),
+    };
+    for row in walls.chunks(width.max(1)).rev() {
+        let mut line = String::with_capacity(width);
+        for &id in row {
+            if !is_wall(id) {
+                line.push(FLOOR);
+                continue;
+            }
+            let count = chars.len();
+            let c = *chars.entry(id).or_insert_with(|| {
+                let tile = tiles.get(&id);
+                let colour = tile.and_then(|t| t.colour);
+                let c = tile_char(count);
+                grid.palette
+                    .insert(c, colour.unwrap_or_else(|| fallback_colour(count)));
+                if let Some(texture) = tile.and_then(|t| t.texture.clone()) {
+                    grid.textures.insert(c, texture);
+                }
+                c
+            });
+            line.push(c);
+        }
+        grid.tiles.push(line);
+    }
+
+    let start = match (&objects, palette.spawn) {
+        (_, Some(spawn)) => walls
+            .iter()
+            .position(|&id| id == spawn)
+            .map(|index| (index, PI / 2.0)),
+        (Some(objects), None) => objects.iter().enumerate().find_map(|(index, id)| {
+            let facing = PLAYER_STARTS.iter().position(|start| start == id)?;
+            Some((index, PI / 2.0 - facing as f32 * PI / 2.0))
+        }),
+        (None, None) => None,
+    };
+    let spawn = match start {
+        Some((index, rotation)) => Spawn {
+            position: centre(index),
+            rotation,
+        },
+        None => {
+            warn!("The map has no player start, so it starts on the first floor tile");
+            let index = walls
+                .iter()
+                .position(|&id| !is_wall(id))
+                .context("There's nowhere to stand, every tile is a wall")?;
+            Spawn {
+                position: centre(index),
+                ..Spawn::default()
+            }
+        }
+    };
+    Ok(Map {
+        spawn,
+        grid: Some(grid),
+        textures: palette.textures,
+        ..Map::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::testing::scratch;
+
+    #[test]
+    fn imports_csv_with_a_palette() {
+        let directory = scratch("csv");
+        let path = directory.join("room.csv");
+        fs::write(&path, "1,1,1,1\n1,0,9,2\n1,1,1\n").unwrap();
+        fs::write(
+            directory.join("room.palette.toml"),
+            r##"
+                spawn = 9
+
+                [tiles.1]
+                colour = "#808080"
+                texture = "stone"
+
+                [tiles.2]
+                colour = "blue"
+            "##,
+        )
+        .unwrap();
+        let map = import(&path).unwrap();
+        fs::remove_dir_all(directory).unwrap();
+        let grid = map.grid.unwrap();
+        // Short rows are filled out with floor, and the last row read is the first in the map.
+        assert_eq!(grid.tiles, ["###.", "#..A", "####"]);
+        assert_eq!(grid.palette[&'#'], Rgb::new(128, 128, 128));
+        assert_eq!(grid.textures[&'#'], "stone");
+        assert!(!grid.textures.contains_key(&'A'));
+        assert_eq!(map.spawn.position, Vec2::new(2.5, 1.5));
+    }
+
+    #[test]
+    fn imports_dumps_with_an_objects_plane() {
+        let directory = scratch("dump");
+        let path = directory.join("level.bin");
+        let mut walls = vec![0u16; DUMP_SIZE * DUMP_SIZE];
+        let mut objects = vec![0u16; DUMP_SIZE * DUMP_SIZE];
+        for i in 0..DUMP_SIZE {
+            walls[i] = 1;
+            walls[(DUMP_SIZE - 1) * DUMP_SIZE + i] = 2;
+        }
+        walls[DUMP_SIZE + 4] = FIRST_FLOOR - 1;
+        walls[DUMP_SIZE + 5] = FIRST_FLOOR;
+        // Facing east, two rows down from the top and three across.
+        objects[2 * DUMP_SIZE + 3] = PLAYER_STARTS[1];
+        let bytes: Vec<u8> = walls
+            .iter()
+            .chain(objects.iter())
+            .flat_map(|id| id.to_le_bytes())
+            .collect();
+        fs::write(&path, bytes).unwrap();
+        let map = import(&path).unwrap();
+        fs::write(&path, [0u8; 10]).unwrap();
+        assert!(import(&path).is_err());
+        fs::remove_dir_all(directory).unwrap();
+
+        let grid = map.grid.unwrap();
+        assert_eq!(grid.tiles.len(), DUMP_SIZE);
+        // The top row of the dump is the last in the map, so its walls are the last lettered.
+        assert!(grid.tiles[0].chars().all(|c| c == '#'));
+        assert!(grid.tiles[DUMP_SIZE - 1].chars().all(|c| c == 'B'));
+        // IDs from the first floor up aren't walls.
+        let row: Vec<char> = grid.tiles[DUMP_SIZE - 2].chars().collect();
+        assert_eq!(row[4..6], ['A', FLOOR]);
+        assert_eq!(grid.palette[&'#'], fallback_colour(0));
+        let position = Vec2::new(3.5, (DUMP_SIZE - 3) as f32 + 0.5);
+        assert_eq!(map.spawn.position, position);
+        assert_eq!(map.spawn.rotation, 0.0);
+    }
+
+    #[test]
+    fn starts_on_the_first_floor_without_a_player_start() {
+        let planes = Planes {
+            width: 3,
+            height: 2,
+            walls: vec![1, 0, 1, 1, 1, 1],
+            objects: None,
+        };
+        let map = convert(planes, Palette::default()).unwrap();
+        assert_eq!(map.spawn.position, Vec2::new(1.5, 1.5));
+        assert_eq!(map.spawn.rotation, FRAC_PI_2);
+        let walled_in = Planes {
+            width: 1,
+            height: 1,
+            walls: vec![1],
+            objects: None,
+        };
+        assert!(convert(walled_in, Palette::default()).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_tile_ids() {
+        let directory = scratch("bad-csv");
+        let path = directory.join("bad.csv");
+        fs::write(&path, "1,x,1\n").unwrap();
+        let result = import(&path);
+        fs::remove_dir_all(directory).unwrap();
+        assert!(result.is_err());
+    }
+}
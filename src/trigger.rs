@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{enemy::EnemySpawn, pickup::Pickup, sector, sound::Cue, vec2::Vec2};
+
+// An axis-aligned box on the floor that something happens in when the player walks into it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Region {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Region {
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+}
+
+// The floor a trigger region covers: a box given by `min` and `max`, or a polygon of `points`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Shape {
+    Box(Region),
+    Polygon { points: Vec<Vec2> },
+}
+
+impl Shape {
+    pub fn contains(&self, point: Vec2) -> bool {
+        match self {
+            Shape::Box(region) => region.contains(point),
+            Shape::Polygon { points } => sector::polygon_contains(points, point),
+        }
+    }
+
+    // Roughly the middle, where sounds it plays come from.
+    pub fn centre(&self) -> Vec2 {
+        match self {
+            Shape::Box(region) => region.min.lerp(region.max, 0.5),
+            Shape::Polygon { points } => {
+                let sum = points.iter().fold(Vec2::ZERO, |sum, &p| sum + p);
+                sum * (1.0 / points.len().max(1) as f32)
+            }
+        }
+    }
+}
+
+// Something a trigger region does when the player walks into, stays in or leaves it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    // Shows a line of text along the bottom of the screen.
+    Message {
+        text: String,
+    },
+    // Sets off everything with the ID, like a switch: doors open or close, walls appear or
+    // disappear and dormant enemies wake up.
+    Trigger {
+        id: String,
+    },
+    Enemy {
+        position: Vec2,
+        #[serde(flatten)]
+        enemy: EnemySpawn,
+    },
+    Pickup {
+        position: Vec2,
+        pickup: Pickup,
+    },
+    Sound {
+        sound: Cue,
+    },
+}
+
+// An area of floor that runs actions as the player walks into, around and out of it, and that
+// the map's script and plugins are told about, by name and by index respectively.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerRegion {
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub shape: Shape,
+    #[serde(default)]
+    pub on_enter: Vec<Action>,
+    #[serde(default)]
+    pub on_exit: Vec<Action>,
+    // Run every `interval` seconds the player spends inside.
+    #[serde(default)]
+    pub on_stay: Vec<Action>,
+    #[serde(default = "default_interval")]
+    pub interval: f32,
+    // Whether it stops doing anything once the player has been through it.
+    #[serde(default)]
+    pub once: bool,
+    // Whether the player was inside as of the last update.
+    #[serde(skip)]
+    pub occupied: bool,
+    // Seconds since `on_stay` last ran, or since the player walked in.
+    #[serde(skip)]
+    pub stayed: f32,
+    // Set when a region that only goes off once has.
+    #[serde(skip)]
+    pub spent: bool,
+}
+
+fn default_interval() -> f32 {
+    1.0
+}
+
+// Where a level ends. `next` is the map to go on to, relative to the map file, and the game
+// is over when there isn't one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exit {
+    #[serde(flatten)]
+    pub region: Region,
+    pub next: Option<PathBuf>,
+}
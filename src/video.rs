@@ -0,0 +1,145 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::Path,
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, DynamicImage, Frame, RgbImage,
+};
+
+use crate::{
+    render::Camera,
+    screenshot::{capture, Resolution},
+    world::World,
+};
+
+// How hard the GIF encoder works at picking each frame's colours, from 1 (best) to 30 (fastest).
+const GIF_SPEED: i32 = 10;
+// Frames captured but not yet encoded. The game waits for the encoder past this many.
+const QUEUED_FRAMES: usize = 8;
+
+// What a recording is written as, going by its file's extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    Gif,
+    Apng,
+}
+
+impl VideoFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "gif" => Some(VideoFormat::Gif),
+            "png" | "apng" => Some(VideoFormat::Apng),
+            _ => None,
+        }
+    }
+}
+
+// Draws the game at a fixed resolution and frame rate alongside the terminal, and encodes the
+// frames into an animation on a thread of its own.
+pub struct VideoRecorder {
+    resolution: Resolution,
+    fps: u16,
+    // Seconds played since the last frame was captured.
+    elapsed: f32,
+    frames: SyncSender<(RgbImage, u32)>,
+    encoder: JoinHandle<Result<()>>,
+}
+
+impl VideoRecorder {
+    pub fn create(path: &Path, resolution: Resolution, fps: u16) -> Result<Self> {
+        let Some(format) = VideoFormat::from_path(path) else {
+            bail!("{} isn't a .gif or .png file", path.display());
+        };
+        if fps == 0 {
+            bail!("Recordings need at least 1 frame per second");
+        }
+        let file =
+            File::create(path).with_context(|| format!("Could not create {}", path.display()))?;
+        let (frames, received) = mpsc::sync_channel(QUEUED_FRAMES);
+        let path = path.to_path_buf();
+        let encoder = thread::spawn(move || {
+            let out = BufWriter::new(file);
+            match format {
+                VideoFormat::Gif => write_gif(out, received, fps),
+                VideoFormat::Apng => write_apng(out, received, resolution, fps),
+            }
+            .with_context(|| format!("Could not write {}", path.display()))
+        });
+        Ok(Self {
+            resolution,
+            fps,
+            elapsed: 0.0,
+            frames,
+            encoder,
+        })
+    }
+
+    // Captures the view if a frame is due after `dt` more seconds of play. A frame that comes
+    // late is shown for as many frames' time as it's covering.
+    pub fn frame(&mut self, dt: f32, camera: &Camera, world: &World) -> Result<()> {
+        self.elapsed += dt;
+        let interval = 1.0 / self.fps as f32;
+        let mut covered = 0;
+        while self.elapsed >= interval {
+            self.elapsed -= interval;
+            covered += 1;
+        }
+        if covered == 0 {
+            return Ok(());
+        }
+        let image = capture(camera, world, self.resolution);
+        self.frames
+            .send((image, covered))
+            .map_err(|_| anyhow!("The recording stopped early"))
+    }
+
+    // Waits for every frame to be encoded and the file to be finished.
+    pub fn finish(self) -> Result<()> {
+        drop(self.frames);
+        self.encoder
+            .join()
+            .map_err(|_| anyhow!("The recording's encoder crashed"))?
+    }
+}
+
+// GIFs are written a frame at a time as they come in.
+fn write_gif(out: BufWriter<File>, frames: Receiver<(RgbImage, u32)>, fps: u16) -> Result<()> {
+    let mut encoder = GifEncoder::new_with_speed(out, GIF_SPEED);
+    encoder.set_repeat(Repeat::Infinite)?;
+    for (image, covered) in frames {
+        let delay = Delay::from_numer_denom_ms(covered * 1000, fps as u32);
+        let rgba = DynamicImage::ImageRgb8(image).into_rgba8();
+        encoder.encode_frame(Frame::from_parts(rgba, 0, 0, delay))?;
+    }
+    Ok(())
+}
+
+// APNGs give their frame count up front, so every frame is kept until the recording ends.
+fn write_apng(
+    out: BufWriter<File>,
+    frames: Receiver<(RgbImage, u32)>,
+    resolution: Resolution,
+    fps: u16,
+) -> Result<()> {
+    let frames: Vec<_> = frames.into_iter().collect();
+    if frames.is_empty() {
+        bail!("Nothing was recorded");
+    }
+    let mut encoder = png::Encoder::new(out, resolution.width as u32, resolution.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0)?;
+    let mut writer = encoder.write_header()?;
+    for (image, covered) in frames {
+        writer.set_frame_delay(covered.min(u16::MAX as u32) as u16, fps)?;
+        writer.write_image_data(image.as_raw())?;
+    }
+    writer.finish()?;
+    Ok(())
+}
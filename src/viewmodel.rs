@@ -0,0 +1,71 @@
+use crate::{canvas::Canvas, colour::Rgb};
+
+// The player's gun, drawn over the bottom middle of the scene. Like textures, each character
+// is a pixel and spaces show the scene behind.
+const GUN: &[&str] = &[
+    "     ##     ",
+    "    #oo#    ",
+    "    #==#    ",
+    "    #==#    ",
+    "   #====#   ",
+    "  #==oo==#  ",
+    "  #======#  ",
+    " ##======## ",
+    " #========# ",
+];
+const FLASH: &[&str] = &[
+    "   *    *   ",
+    "    *++*    ",
+    "   *++++*   ",
+    "    *++*    ",
+];
+
+// How much of the screen's width the gun takes up.
+const WIDTH: f32 = 0.18;
+// Pixels of the gun's art it drops by at the start of a shot, easing back up as it reloads.
+const KICK: f32 = 3.0;
+// How far through reloading the muzzle flash disappears.
+const FLASH_TIME: f32 = 0.7;
+
+fn palette(c: char) -> Option<Rgb> {
+    match c {
+        '#' => Some(Rgb::new(40, 40, 44)),
+        '=' => Some(Rgb::new(96, 96, 104)),
+        'o' => Some(Rgb::new(160, 160, 172)),
+        '*' => Some(Rgb::new(255, 176, 48)),
+        '+' => Some(Rgb::new(255, 248, 200)),
+        _ => None,
+    }
+}
+
+// Draws the gun, `recoil` being how much of the last shot's reload is left, from 1.0 just
+// after firing down to 0.0 when it's ready again. The recoil steps a whole art pixel at a
+// time so it reads as frames of animation rather than a smear.
+pub fn draw_viewmodel(canvas: &mut Canvas, recoil: f32) {
+    let columns = GUN[0].chars().count();
+    let scale_x = ((canvas.width as f32 * WIDTH) / columns as f32).max(1.0) as usize;
+    let scale_y = (scale_x as f32 / canvas.aspect).round().max(1.0) as usize;
+    let kick = (recoil * KICK).round() as usize;
+    let left = (canvas.width / 2).saturating_sub(columns * scale_x / 2);
+    let top = (canvas.height + kick * scale_y).saturating_sub(GUN.len() * scale_y);
+    draw_art(canvas, GUN, left, top, scale_x, scale_y);
+    if recoil > FLASH_TIME {
+        let above = top.saturating_sub(FLASH.len() * scale_y);
+        draw_art(canvas, FLASH, left, above, scale_x, scale_y);
+    }
+}
+
+fn draw_art(canvas: &mut Canvas, art: &[&str], left: usize, top: usize, sx: usize, sy: usize) {
+    for (row, line) in art.iter().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            let Some(colour) = palette(c) else {
+                continue;
+            };
+            for y in 0..sy {
+                for x in 0..sx {
+                    canvas.set(left + column * sx + x, top + row * sy + y, colour);
+                }
+            }
+        }
+    }
+}
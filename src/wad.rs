@@ -0,0 +1,975 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use tracing::warn;
+
+use crate::{
+    colour::Rgb,
+    enemy::Enemy,
+    map::{DoorMap, EnemyMap, Map, PickupMap, SectorMap, Segment, Spawn, SpriteMap, SurfaceMap},
+    pickup::{KeyColour, Pickup},
+    theme::Theme,
+    trigger::{Exit, Region},
+    vec2::Vec2,
+};
+
+// Doom units to a map unit. At this scale Doom's player fits the engine's: its 24 unit steps
+// are `STEP_HEIGHT`, its 56 units of height `HEADROOM` and its 16 unit radius `PLAYER_RADIUS`.
+const SCALE: f32 = 80.0;
+// The lumps that can follow a level's marker, in any order.
+const LEVEL_LUMPS: [&str; 11] = [
+    "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SEGS", "SSECTORS", "NODES", "SECTORS", "REJECT",
+    "BLOCKMAP", "BEHAVIOR",
+];
+// Sidedefs name this as their texture when there isn't one.
+const NO_TEXTURE: &str = "-";
+// Linedefs that nothing can pass, and sidedefs or linedefs that aren't there.
+const BLOCKING: u16 = 0x0001;
+const NONE: u16 = 0xffff;
+// Things only placed on medium skill or higher, and only in multiplayer.
+const MEDIUM_SKILL: u16 = 0x0002;
+const MULTIPLAYER: u16 = 0x0010;
+const PLAYER_START: u16 = 1;
+// How far above the floor a door opens, at least: enough for the player to fit under.
+const DOOR_HEIGHT: f32 = 56.0;
+// Doors open to just under the lowest ceiling beside them, for about as long as Doom's do.
+const DOOR_LIP: f32 = 4.0;
+const DOOR_OPEN_TIME: f32 = 4.0;
+// How much of what's behind shows through the bars and grates on two-sided lines.
+const GRATE_OPACITY: f32 = 0.5;
+// How far from an exit line the player has to be to leave.
+const EXIT_REACH: f32 = 0.5;
+const SKY: Rgb = Rgb::new(96, 128, 176);
+const CORRUPT: &str = "The WAD is corrupt";
+
+// A Doom level from the WAD at `path`, e.g. "E1M1" or "MAP01", as a map with its sectors,
+// walls, doors and things. It's only the level's shape: lifts, crushers, switches and the rest
+// of what its linedefs do are left out.
+pub fn import(path: &Path, level: &str) -> Result<Map> {
+    let wad = Wad::read(path)?;
+    let lumps = wad
+        .level(level)
+        .with_context(|| format!("Could not read {level} from {}", path.display()))?;
+    lumps
+        .convert(&mut Colours::new(&wad))
+        .with_context(|| format!("Could not import {level} from {}", path.display()))
+}
+
+struct Lump {
+    name: String,
+    start: usize,
+    size: usize,
+}
+
+struct Wad {
+    data: Vec<u8>,
+    lumps: Vec<Lump>,
+}
+
+impl Wad {
+    fn read(path: &Path) -> Result<Self> {
+        let data =
+            fs::read(path).with_context(|| format!("Could not read WAD {}", path.display()))?;
+        if !matches!(data.get(..4), Some(b"IWAD" | b"PWAD")) {
+            bail!("{} is not a WAD", path.display());
+        }
+        let count = read_size(&data, 4)?;
+        let directory = read_size(&data, 8)?;
+        let lumps = (0..count)
+            .map(|i| {
+                let entry = i
+                    .checked_mul(16)
+                    .and_then(|offset| directory.checked_add(offset))
+                    .filter(|&entry| entry < data.len())
+                    .context(CORRUPT)?;
+                let lump = Lump {
+                    start: read_size(&data, entry)?,
+                    size: read_size(&data, entry + 4)?,
+                    name: read_name(&data, entry + 8)?,
+                };
+                let end = lump.start.checked_add(lump.size);
+                if end.is_none_or(|end| end > data.len()) {
+                    bail!("Lump {} runs past the end of the file", lump.name);
+                }
+                Ok(lump)
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { data, lumps })
+    }
+
+    // The last lump with the name, since later ones replace earlier ones.
+    fn lump(&self, name: &str) -> Option<&[u8]> {
+        self.lumps
+            .iter()
+            .rfind(|lump| lump.name == name)
+            .map(|lump| &self.data[lump.start..lump.start + lump.size])
+    }
+
+    fn level(&self, name: &str) -> Result<LevelLumps<'_>> {
+        let name = name.to_uppercase();
+        let marker = self
+            .lumps
+            .iter()
+            .rposition(|lump| lump.name == name)
+            .with_context(|| format!("There's no level {name}"))?;
+        let lumps: HashMap<_, _> = self.lumps[marker + 1..]
+            .iter()
+            .take_while(|lump| LEVEL_LUMPS.contains(&lump.name.as_str()))
+            .map(|lump| {
+                let data = &self.data[lump.start..lump.start + lump.size];
+                (lump.name.as_str(), data)
+            })
+            .collect();
+        if lumps.contains_key("BEHAVIOR") {
+            bail!("Hexen format levels aren't supported");
+        }
+        let lump = |name| {
+            lumps
+                .get(name)
+                .copied()
+                .with_context(|| format!("No {name}"))
+        };
+        Ok(LevelLumps {
+            things: lump("THINGS")?,
+            linedefs: lump("LINEDEFS")?,
+            sidedefs: lump("SIDEDEFS")?,
+            vertexes: lump("VERTEXES")?,
+            sectors: lump("SECTORS")?,
+        })
+    }
+}
+
+struct LevelLumps<'a> {
+    things: &'a [u8],
+    linedefs: &'a [u8],
+    sidedefs: &'a [u8],
+    vertexes: &'a [u8],
+    sectors: &'a [u8],
+}
+
+struct Linedef {
+    start: usize,
+    end: usize,
+    flags: u16,
+    special: u16,
+    right: Option<usize>,
+    left: Option<usize>,
+}
+
+struct Sidedef {
+    upper: String,
+    lower: String,
+    middle: String,
+    sector: usize,
+}
+
+struct Sector {
+    floor: f32,
+    ceiling: f32,
+    floor_flat: String,
+    ceiling_flat: String,
+    light: u8,
+}
+
+impl LevelLumps<'_> {
+    fn convert(&self, colours: &mut Colours) -> Result<Map> {
+        let vertices: Vec<_> = self
+            .vertexes
+            .chunks_exact(4)
+            .map(|v| point(read_i16(v, 0), read_i16(v, 2)))
+            .collect::<Result<_>>()?;
+        let mut sectors: Vec<_> = self
+            .sectors
+            .chunks_exact(26)
+            .map(|s| {
+                Ok(Sector {
+                    floor: read_i16(s, 0)? as f32,
+                    ceiling: read_i16(s, 2)? as f32,
+                    floor_flat: read_name(s, 4)?,
+                    ceiling_flat: read_name(s, 12)?,
+                    light: read_i16(s, 20)?.clamp(0, 255) as u8,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let sidedefs: Vec<_> = self
+            .sidedefs
+            .chunks_exact(30)
+            .enumerate()
+            .map(|(i, s)| {
+                let sector = read_u16(s, 28)? as usize;
+                if sector >= sectors.len() {
+                    bail!("Sidedef {i} is in sector {sector}, which doesn't exist");
+                }
+                Ok(Sidedef {
+                    upper: read_name(s, 4)?,
+                    lower: read_name(s, 12)?,
+                    middle: read_name(s, 20)?,
+                    sector,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let side = |index: u16| (index != NONE).then_some(index as usize);
+        let linedefs: Vec<_> = self
+            .linedefs
+            .chunks_exact(14)
+            .enumerate()
+            .map(|(i, l)| {
+                let linedef = Linedef {
+                    start: read_u16(l, 0)? as usize,
+                    end: read_u16(l, 2)? as usize,
+                    flags: read_u16(l, 4)?,
+                    special: read_u16(l, 6)?,
+                    right: side(read_u16(l, 10)?),
+                    left: side(read_u16(l, 12)?),
+                };
+                let mut sides = linedef.right.into_iter().chain(linedef.left);
+                if linedef.start.max(linedef.end) >= vertices.len()
+                    || sides.any(|s| s >= sidedefs.len())
+                {
+                    bail!("Linedef {i} joins vertices or sidedefs that don't exist");
+                }
+                Ok(linedef)
+            })
+            .collect::<Result<_>>()?;
+        let sector_of = |side: Option<usize>| side.map(|s| sidedefs[s].sector);
+
+        let mut map = Map::default();
+        open_doors(&linedefs, &mut sectors, sector_of);
+
+        // Each sector's edges run with it on their right, so walking them goes clockwise around
+        // the sector and anticlockwise around any holes in it.
+        let mut edges = vec![Vec::new(); sectors.len()];
+        // The texture a sector's steps are drawn in, from the sides of the lines facing them.
+        let mut steps: Vec<Option<&str>> = vec![None; sectors.len()];
+        for linedef in &linedefs {
+            let (right, left) = (sector_of(linedef.right), sector_of(linedef.left));
+            if right == left {
+                continue;
+            }
+            if let Some(right) = right {
+                edges[right].push((linedef.start, linedef.end));
+            }
+            if let Some(left) = left {
+                edges[left].push((linedef.end, linedef.start));
+            }
+            let (Some(a), Some(b), Some(a_side), Some(b_side)) =
+                (right, left, linedef.right, linedef.left)
+            else {
+                continue;
+            };
+            for (here, there, side) in [(a, b, a_side), (b, a, b_side)] {
+                let side = &sidedefs[side];
+                let texture = if sectors[there].floor > sectors[here].floor {
+                    &side.lower
+                } else if sectors[there].ceiling < sectors[here].ceiling {
+                    &side.upper
+                } else {
+                    continue;
+                };
+                if texture != NO_TEXTURE {
+                    steps[there].get_or_insert(texture);
+                }
+            }
+        }
+
+        let mut outlines = Vec::new();
+        for (index, sector) in sectors.iter().enumerate() {
+            let shade = |colour| shade(colour, sector.light);
+            let floor = shade(colours.flat(&sector.floor_flat));
+            let ceiling = if sector.ceiling_flat.starts_with("F_SKY") {
+                SKY
+            } else {
+                shade(colours.flat(&sector.ceiling_flat))
+            };
+            let colour = match steps[index] {
+                Some(texture) => shade(colours.wall(texture)),
+                None => floor,
+            };
+            for points in outlines_of(&edges[index], &vertices) {
+                outlines.push((
+                    area(&points).abs(),
+                    SectorMap {
+                        points,
+                        floor_height: sector.floor / SCALE,
+                        ceiling_height: sector.ceiling / SCALE,
+                        colour,
+                        texture: None,
+                        floor: Some(SurfaceMap {
+                            colour: floor,
+                            texture: None,
+                        }),
+                        ceiling: Some(SurfaceMap {
+                            colour: ceiling,
+                            texture: None,
+                        }),
+                    },
+                ));
+            }
+        }
+        // Sectors inside others have to come first to be found before the ones around them.
+        outlines.sort_by(|a, b| a.0.total_cmp(&b.0));
+        map.sectors = outlines.into_iter().map(|(_, sector)| sector).collect();
+
+        let mut doors = vec![false; sectors.len()];
+        for linedef in &linedefs {
+            let (start, end) = (vertices[linedef.start], vertices[linedef.end]);
+            let Some(right) = linedef.right.map(|s| &sidedefs[s]) else {
+                continue;
+            };
+            let light = sectors[right.sector].light;
+            let wall = |name: &str, colours: &mut Colours| shade(colours.wall(name), light);
+            if let (Some(lock), Some(door)) = (door_lock(linedef.special), linedef.left) {
+                // Doors are sectors whose ceilings rise, with a line facing them on each side.
+                // Only one of those is needed, since a door here can be used from either side.
+                let sector = sidedefs[door].sector;
+                if !std::mem::replace(&mut doors[sector], true) {
+                    map.doors.push(DoorMap {
+                        start,
+                        end,
+                        colour: wall(&right.upper, colours),
+                        texture: None,
+                        open_time: DOOR_OPEN_TIME,
+                        lock,
+                        id: None,
+                    });
+                }
+            }
+            if is_exit(linedef.special) && map.exit.is_none() {
+                let reach = Vec2::new(EXIT_REACH, EXIT_REACH);
+                map.exit = Some(Exit {
+                    region: Region {
+                        min: Vec2::new(start.x.min(end.x), start.y.min(end.y)) - reach,
+                        max: Vec2::new(start.x.max(end.x), start.y.max(end.y)) + reach,
+                    },
+                    next: None,
+                });
+            }
+            let opacity = if linedef.left.is_none() {
+                1.0
+            } else if linedef.flags & BLOCKING != 0 && right.middle != NO_TEXTURE {
+                GRATE_OPACITY
+            } else {
+                continue;
+            };
+            map.segments.push(Segment {
+                start,
+                end,
+                colour: wall(&right.middle, colours),
+                texture: None,
+                opacity,
+                mirror: false,
+                id: None,
+                hidden: false,
+            });
+        }
+
+        let mut spawn = None;
+        for thing in self.things.chunks_exact(10) {
+            let position = point(read_i16(thing, 0), read_i16(thing, 2))?;
+            let angle = read_i16(thing, 4)? as f32;
+            let kind = read_u16(thing, 6)?;
+            let flags = read_u16(thing, 8)?;
+            if kind == PLAYER_START {
+                spawn = Some(Spawn {
+                    position,
+                    rotation: angle.to_radians(),
+                });
+                continue;
+            }
+            if flags & MEDIUM_SKILL == 0 || flags & MULTIPLAYER != 0 {
+                continue;
+            }
+            if let Some((colour, enemy)) = enemy(kind) {
+                map.enemies.push(EnemyMap {
+                    position,
+                    colour,
+                    texture: None,
+                    size: 0.8,
+                    id: None,
+                    enemy,
+                });
+            } else if let Some(pickup) = pickup(kind) {
+                map.pickups.push(PickupMap {
+                    position,
+                    pickup,
+                    colour: None,
+                    texture: None,
+                    size: None,
+                });
+            } else if let Some((colour, size)) = decoration(kind) {
+                map.sprites.push(SpriteMap {
+                    position,
+                    colour,
+                    texture: None,
+                    size,
+                });
+            }
+        }
+        map.spawn = spawn.context("There's no player start")?;
+        Ok(map)
+    }
+}
+
+// Opens every shut sector, which in Doom are doors waiting to rise, to just under the lowest
+// ceiling beside it, so the level can be walked through.
+fn open_doors(
+    linedefs: &[Linedef],
+    sectors: &mut [Sector],
+    sector_of: impl Fn(Option<usize>) -> Option<usize>,
+) {
+    let mut lowest = vec![f32::INFINITY; sectors.len()];
+    for linedef in linedefs {
+        let (Some(a), Some(b)) = (sector_of(linedef.right), sector_of(linedef.left)) else {
+            continue;
+        };
+        lowest[a] = lowest[a].min(sectors[b].ceiling);
+        lowest[b] = lowest[b].min(sectors[a].ceiling);
+    }
+    for (sector, lowest) in sectors.iter_mut().zip(lowest) {
+        if sector.ceiling <= sector.floor && lowest.is_finite() {
+            sector.ceiling = (lowest - DOOR_LIP).max(sector.floor + DOOR_HEIGHT);
+        }
+    }
+}
+
+// The closed loops the edges make, in map units. Loops around holes are left out, since
+// whatever fills the hole is either another sector or solid.
+fn outlines_of(edges: &[(usize, usize)], vertices: &[Vec2]) -> Vec<Vec<Vec2>> {
+    let mut used = vec![false; edges.len()];
+    let mut outlines = Vec::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let mut loop_ = vec![edges[first]];
+        while let Some(&(from, to)) = loop_.last() {
+            if to == edges[first].0 {
+                let points: Vec<_> = loop_.iter().map(|&(v, _)| vertices[v]).collect();
+                if area(&points) < 0.0 {
+                    outlines.push(points);
+                }
+                break;
+            }
+            // Where a loop touches itself, keep turning right to stay on the sector's edge.
+            let heading = vertices[to] - vertices[from];
+            let next = (0..edges.len())
+                .filter(|&i| !used[i] && edges[i].0 == to)
+                .min_by(|&a, &b| {
+                    let turn = |i: usize| {
+                        let out = vertices[edges[i].1] - vertices[to];
+                        heading.cross(out).atan2(heading.dot(out))
+                    };
+                    turn(a).total_cmp(&turn(b))
+                });
+            let Some(next) = next else {
+                warn!("A sector's edges don't join up at {:?}", vertices[to]);
+                break;
+            };
+            used[next] = true;
+            loop_.push(edges[next]);
+        }
+    }
+    outlines
+}
+
+// Positive for corners going anticlockwise, negative for clockwise.
+fn area(points: &[Vec2]) -> f32 {
+    let next = points.iter().cycle().skip(1);
+    points
+        .iter()
+        .zip(next)
+        .map(|(a, b)| a.cross(*b))
+        .sum::<f32>()
+        / 2.0
+}
+
+// Doors a player opens by using them, with the key they need, if any.
+fn door_lock(special: u16) -> Option<Option<KeyColour>> {
+    match special {
+        1 | 31 | 117 | 118 => Some(None),
+        26 | 32 => Some(Some(KeyColour::Blue)),
+        27 | 34 => Some(Some(KeyColour::Yellow)),
+        28 | 33 => Some(Some(KeyColour::Red)),
+        _ => None,
+    }
+}
+
+// Exit switches and lines to walk over, including the secret exits.
+fn is_exit(special: u16) -> bool {
+    matches!(special, 11 | 51 | 52 | 124)
+}
+
+// Doom's monsters, with its hit points and roughly how fast they move, how often they shoot
+// and how hard they hit.
+fn enemy(kind: u16) -> Option<(Rgb, Enemy)> {
+    let (colour, health, speed, reload, damage) = match kind {
+        3004 => (Rgb::new(150, 140, 100), 20.0, 2.0, Some(1.5), 5.0),
+        9 => (Rgb::new(120, 120, 120), 30.0, 2.0, Some(1.5), 10.0),
+        3001 => (Rgb::new(140, 90, 50), 60.0, 2.0, Some(2.0), 10.0),
+        3002 => (Rgb::new(230, 120, 140), 150.0, 3.0, None, 15.0),
+        58 => (Rgb::new(90, 60, 70), 150.0, 3.0, None, 15.0),
+        3006 => (Rgb::new(255, 200, 60), 100.0, 4.0, None, 10.0),
+        3005 => (Rgb::new(200, 30, 30), 400.0, 1.5, Some(2.5), 20.0),
+        3003 => (Rgb::new(200, 110, 110), 1000.0, 2.0, Some(2.5), 30.0),
+        _ => return None,
+    };
+    let enemy = Enemy {
+        speed,
+        health,
+        reload,
+        damage,
+        ..Enemy::default()
+    };
+    Some((colour, enemy))
+}
+
+fn pickup(kind: u16) -> Option<Pickup> {
+    let item = |name: &str| Pickup::Item {
+        name: name.to_string(),
+        count: 1,
+    };
+    Some(match kind {
+        2014 => Pickup::Health { amount: 1.0 },
+        2011 => Pickup::Health { amount: 10.0 },
+        2012 => Pickup::Health { amount: 25.0 },
+        2013 => Pickup::Health { amount: 100.0 },
+        2007 => Pickup::Ammo { amount: 10 },
+        2048 => Pickup::Ammo { amount: 50 },
+        2008 => Pickup::Ammo { amount: 4 },
+        2049 => Pickup::Ammo { amount: 20 },
+        5 | 40 => Pickup::Key {
+            key: KeyColour::Blue,
+        },
+        6 | 39 => Pickup::Key {
+            key: KeyColour::Yellow,
+        },
+        13 | 38 => Pickup::Key {
+            key: KeyColour::Red,
+        },
+        2001 => item("shotgun"),
+        2002 => item("chaingun"),
+        2003 => item("rocket launcher"),
+        2004 => item("plasma rifle"),
+        2005 => item("chainsaw"),
+        2006 => item("BFG9000"),
+        2018 => item("armour"),
+        2019 => item("mega armour"),
+        8 => item("backpack"),
+        _ => return None,
+    })
+}
+
+// Barrels, lamps, columns and other things that are just there, by colour and size.
+fn decoration(kind: u16) -> Option<(Rgb, f32)> {
+    Some(match kind {
+        2035 => (Rgb::new(60, 140, 60), 0.4),
+        2028 | 85 | 86 => (Rgb::new(255, 250, 200), 0.7),
+        34 | 35 => (Rgb::new(255, 220, 120), 0.3),
+        44 | 55 => (Rgb::new(80, 80, 255), 0.7),
+        45 | 56 => (Rgb::new(60, 200, 60), 0.7),
+        46 | 57 => (Rgb::new(255, 80, 40), 0.7),
+        30 | 31 => (Rgb::new(40, 150, 40), 0.7),
+        32 | 33 => (Rgb::new(170, 40, 40), 0.7),
+        48 => (Rgb::new(150, 150, 160), 0.8),
+        43 | 47 | 54 => (Rgb::new(110, 80, 50), 0.8),
+        _ => return None,
+    })
+}
+
+// Darkens a colour by a Doom light level, from 0 for black to 255 for fully lit. Even the
+// darkest sectors are left a little visible.
+fn shade(colour: Rgb, light: u8) -> Rgb {
+    colour.scale(0.25 + 0.75 * light as f32 / 255.0)
+}
+
+// What colour each flat and wall texture looks from a distance: the average of its pixels
+// through the WAD's palette. WADs without the graphics, like most PWADs, get a colour from
+// the name instead.
+struct Colours<'a> {
+    wad: &'a Wad,
+    palette: Option<Vec<Rgb>>,
+    patches: Vec<String>,
+    // The patches each wall texture is made of.
+    textures: HashMap<String, Vec<usize>>,
+    fallback: Vec<Rgb>,
+    cache: HashMap<String, Rgb>,
+}
+
+impl<'a> Colours<'a> {
+    fn new(wad: &'a Wad) -> Self {
+        let palette = wad.lump("PLAYPAL").filter(|p| p.len() >= 768).map(|p| {
+            p[..768]
+                .chunks_exact(3)
+                .map(|c| Rgb::new(c[0], c[1], c[2]))
+                .collect()
+        });
+        let patches = wad.lump("PNAMES").map_or_else(Vec::new, |names| {
+            let count = read_size(names, 0).unwrap_or(0);
+            (0..count)
+                .map_while(|i| read_name(names, 4 + i * 8).ok())
+                .collect()
+        });
+        let mut textures = HashMap::new();
+        for lump in ["TEXTURE1", "TEXTURE2"]
+            .into_iter()
+            .filter_map(|n| wad.lump(n))
+        {
+            if read_textures(lump, &mut textures).is_none() {
+                warn!("Could not read the wall textures");
+            }
+        }
+        Self {
+            wad,
+            palette,
+            patches,
+            textures,
+            fallback: Theme::classic().walls,
+            cache: HashMap::new(),
+        }
+    }
+
+    fn flat(&mut self, name: &str) -> Rgb {
+        if let Some(&colour) = self.cache.get(name) {
+            return colour;
+        }
+        let pixels = self.wad.lump(name).filter(|flat| flat.len() == 64 * 64);
+        let colour = self.average(pixels.into_iter().flatten().copied(), name);
+        self.cache.insert(name.to_string(), colour);
+        colour
+    }
+
+    fn wall(&mut self, name: &str) -> Rgb {
+        if let Some(&colour) = self.cache.get(name) {
+            return colour;
+        }
+        let patches = self.textures.get(name).into_iter().flatten();
+        let pixels = patches
+            .filter_map(|&patch| self.wad.lump(self.patches.get(patch)?))
+            .flat_map(picture_pixels);
+        let colour = self.average(pixels, name);
+        self.cache.insert(name.to_string(), colour);
+        colour
+    }
+
+    fn average(&self, pixels: impl Iterator<Item = u8>, name: &str) -> Rgb {
+        let mut total = [0u64; 4];
+        if let Some(palette) = &self.palette {
+            for Rgb { r, g, b } in pixels.map(|pixel| palette[pixel as usize]) {
+                total[0] += r as u64;
+                total[1] += g as u64;
+                total[2] += b as u64;
+                total[3] += 1;
+            }
+        }
+        let [r, g, b, count] = total;
+        let average = |total: u64| total.checked_div(count).map(|c| c as u8);
+        if let (Some(r), Some(g), Some(b)) = (average(r), average(g), average(b)) {
+            return Rgb::new(r, g, b);
+        }
+        let hash = name.bytes().fold(0usize, |hash, b| hash * 31 + b as usize);
+        self.fallback[hash % self.fallback.len()]
+    }
+}
+
+// Adds the wall textures in a TEXTURE1 or TEXTURE2 lump, or gives up if it's cut short.
+fn read_textures(lump: &[u8], textures: &mut HashMap<String, Vec<usize>>) -> Option<()> {
+    let count = read_size(lump, 0).ok()?;
+    for i in 0..count {
+        let start = read_size(lump, 4 + i * 4)
+            .ok()
+            .filter(|&start| start < lump.len())?;
+        let name = read_name(lump, start).ok()?;
+        let patch_count = read_i16(lump, start + 20).ok()?.max(0) as usize;
+        let patches = (0..patch_count)
+            .map(|p| {
+                let patch = read_i16(lump, start + 22 + p * 10 + 4)?;
+                usize::try_from(patch).context(CORRUPT)
+            })
+            .collect::<Result<_>>()
+            .ok()?;
+        textures.insert(name, patches);
+    }
+    Some(())
+}
+
+// The palette indices of every pixel drawn in a picture, like a wall patch. Pictures are
+// stored as columns of runs of pixels, with gaps between where it's see-through.
+fn picture_pixels(picture: &[u8]) -> Vec<u8> {
+    let mut pixels = Vec::new();
+    let width = read_i16(picture, 0).unwrap_or(0).max(0) as usize;
+    for column in 0..width {
+        let Ok(mut at) = read_size(picture, 8 + column * 4) else {
+            break;
+        };
+        while let Some(&top) = picture.get(at) {
+            if top == 0xff {
+                break;
+            }
+            let Some(&length) = picture.get(at + 1) else {
+                break;
+            };
+            let Some(run) = picture.get(at + 3..at + 3 + length as usize) else {
+                break;
+            };
+            pixels.extend_from_slice(run);
+            at += length as usize + 4;
+        }
+    }
+    pixels
+}
+
+fn point(x: Result<i16>, y: Result<i16>) -> Result<Vec2> {
+    Ok(Vec2::new(x? as f32 / SCALE, y? as f32 / SCALE))
+}
+
+fn bytes<const N: usize>(data: &[u8], at: usize) -> Result<[u8; N]> {
+    at.checked_add(N)
+        .and_then(|end| data.get(at..end))
+        .and_then(|bytes| bytes.try_into().ok())
+        .context("The WAD is cut short")
+}
+
+fn read_i16(data: &[u8], at: usize) -> Result<i16> {
+    bytes(data, at).map(i16::from_le_bytes)
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16> {
+    bytes(data, at).map(u16::from_le_bytes)
+}
+
+fn read_i32(data: &[u8], at: usize) -> Result<i32> {
+    bytes(data, at).map(i32::from_le_bytes)
+}
+
+// Counts, offsets and sizes, which are stored signed but are never negative in a good WAD.
+fn read_size(data: &[u8], at: usize) -> Result<usize> {
+    usize::try_from(read_i32(data, at)?).context(CORRUPT)
+}
+
+// Names are up to 8 characters, padded with zeros, and matched without regard to case.
+fn read_name(data: &[u8], at: usize) -> Result<String> {
+    let name: [u8; 8] = bytes(data, at)?;
+    let end = name.iter().position(|&b| b == 0).unwrap_or(8);
+    Ok(String::from_utf8_lossy(&name[..end]).to_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::testing::scratch;
+
+    fn name(name: &str) -> Vec<u8> {
+        let mut bytes = name.as_bytes().to_vec();
+        bytes.resize(8, 0);
+        bytes
+    }
+
+    fn shorts(values: &[i16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    // A WAD of the lumps in order, with the directory at the end.
+    fn wad(lumps: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        let mut directory = Vec::new();
+        for (lump, bytes) in lumps {
+            directory.extend((12 + data.len() as i32).to_le_bytes());
+            directory.extend((bytes.len() as i32).to_le_bytes());
+            directory.extend(name(lump));
+            data.extend(bytes);
+        }
+        let mut wad = b"PWAD".to_vec();
+        wad.extend((lumps.len() as i32).to_le_bytes());
+        wad.extend((12 + data.len() as i32).to_le_bytes());
+        wad.extend(data);
+        wad.extend(directory);
+        wad
+    }
+
+    fn sidedef(upper: &str, lower: &str, middle: &str, sector: i16) -> Vec<u8> {
+        [
+            shorts(&[0, 0]),
+            name(upper),
+            name(lower),
+            name(middle),
+            shorts(&[sector]),
+        ]
+        .concat()
+    }
+
+    // A 4 by 4 room with a closed door on its east wall into a small sector beyond it, an exit
+    // switch on its west wall, and a grey palette so colours come out as palette indices.
+    fn level() -> Vec<(&'static str, Vec<u8>)> {
+        let vertexes = shorts(&[
+            0, 0, 0, 320, 320, 320, 320, 192, 320, 128, 320, 0, 336, 192, 336, 128,
+        ]);
+        // Start, end, flags, special, tag, right side and left side.
+        let linedefs = shorts(&[
+            0, 1, 1, 11, 0, 0, -1, //
+            1, 2, 1, 0, 0, 0, -1, //
+            2, 3, 1, 0, 0, 0, -1, //
+            3, 4, 0, 1, 0, 1, 2, //
+            4, 5, 1, 0, 0, 0, -1, //
+            5, 0, 1, 0, 0, 0, -1, //
+            3, 6, 1, 0, 0, 3, -1, //
+            6, 7, 1, 0, 0, 3, -1, //
+            7, 4, 1, 0, 0, 3, -1,
+        ]);
+        let sidedefs = [
+            sidedef("-", "-", "WALL", 0),
+            sidedef("WALL", "-", "-", 0),
+            sidedef("-", "-", "-", 1),
+            sidedef("-", "-", "WALL", 1),
+        ]
+        .concat();
+        let sector = |floor, ceiling| {
+            [
+                shorts(&[floor, ceiling]),
+                name("FLAT"),
+                name("FLAT"),
+                shorts(&[255, 0, 0]),
+            ]
+            .concat()
+        };
+        // X, y, angle, kind and flags: the player, an imp, a medikit, a barrel, and an imp
+        // each for only the easiest skill and only multiplayer.
+        let things = shorts(&[
+            64, 64, 90, 1, 7, //
+            160, 160, 0, 3001, 7, //
+            200, 60, 0, 2012, 7, //
+            250, 250, 0, 2035, 7, //
+            100, 100, 0, 3001, 1, //
+            100, 200, 0, 3001, 0x17,
+        ]);
+        let palette: Vec<u8> = (0..=255).flat_map(|i| [i, i, i]).collect();
+        let pnames = [1i32.to_le_bytes().to_vec(), name("PATCH")].concat();
+        let texture1 = [
+            shorts(&[1, 0, 8, 0]),
+            name("WALL"),
+            shorts(&[0, 0, 64, 64, 0, 0, 1]),
+            shorts(&[0, 0, 0, 1, 0]),
+        ]
+        .concat();
+        // A picture 1 pixel wide with one post of 2 pixels.
+        let patch = [
+            shorts(&[1, 2, 0, 0]),
+            12i32.to_le_bytes().to_vec(),
+            vec![0, 2, 0, 50, 50, 0, 0xff],
+        ]
+        .concat();
+        vec![
+            ("PLAYPAL", palette),
+            ("PNAMES", pnames),
+            ("TEXTURE1", texture1),
+            ("PATCH", patch),
+            ("E1M1", Vec::new()),
+            ("THINGS", things),
+            ("LINEDEFS", linedefs),
+            ("SIDEDEFS", sidedefs),
+            ("VERTEXES", vertexes),
+            ("SECTORS", [sector(0, 128), sector(0, 0)].concat()),
+            ("FLAT", vec![100; 64 * 64]),
+        ]
+    }
+
+    fn import_bytes(test: &str, bytes: &[u8], level: &str) -> Result<Map> {
+        let directory = scratch(test);
+        let path = directory.join("level.wad");
+        fs::write(&path, bytes).unwrap();
+        let map = import(&path, level);
+        fs::remove_dir_all(directory).unwrap();
+        map
+    }
+
+    #[test]
+    fn imports_sectors_and_walls() {
+        let map = import_bytes("level", &wad(&level()), "e1m1").unwrap();
+        let grey = |level| Rgb::new(level, level, level);
+        // The smaller door sector comes first, opened to just under the room's ceiling.
+        assert_eq!(map.sectors.len(), 2);
+        let (door, room) = (&map.sectors[0], &map.sectors[1]);
+        assert_eq!(door.points.len(), 4);
+        assert_eq!(door.ceiling_height, 124.0 / SCALE);
+        assert_eq!(room.points.len(), 6);
+        assert_eq!((room.floor_height, room.ceiling_height), (0.0, 1.6));
+        assert_eq!(room.floor.as_ref().unwrap().colour, grey(100));
+        assert_eq!(map.doors.len(), 1);
+        let door = &map.doors[0];
+        assert_eq!(
+            (door.start, door.end),
+            (Vec2::new(4.0, 2.4), Vec2::new(4.0, 1.6))
+        );
+        assert_eq!(door.colour, grey(50));
+        assert_eq!(door.lock, None);
+        // Every one-sided line is a wall, and the two-sided door line isn't.
+        assert_eq!(map.segments.len(), 8);
+        assert!(map.segments.iter().all(|s| s.colour == grey(50)));
+        let exit = map.exit.as_ref().unwrap().region;
+        assert_eq!(
+            (exit.min, exit.max),
+            (Vec2::new(-0.5, -0.5), Vec2::new(0.5, 4.5))
+        );
+        assert!(map.world().is_ok());
+    }
+
+    #[test]
+    fn imports_things_for_single_player_on_medium() {
+        let map = import_bytes("things", &wad(&level()), "E1M1").unwrap();
+        assert_eq!(map.spawn.position, Vec2::new(0.8, 0.8));
+        assert_eq!(map.spawn.rotation, FRAC_PI_2);
+        assert_eq!(map.enemies.len(), 1);
+        assert_eq!(map.enemies[0].position, Vec2::new(2.0, 2.0));
+        assert_eq!(map.enemies[0].enemy.health, 60.0);
+        assert_eq!(map.pickups.len(), 1);
+        assert_eq!(map.pickups[0].pickup, Pickup::Health { amount: 25.0 });
+        assert_eq!(map.sprites.len(), 1);
+    }
+
+    #[test]
+    fn colours_come_from_names_without_graphics() {
+        let lumps: Vec<_> = level().into_iter().skip(4).collect();
+        let map = import_bytes("no-graphics", &wad(&lumps), "E1M1").unwrap();
+        let walls = Theme::classic().walls;
+        assert!(map.segments.iter().all(|s| walls.contains(&s.colour)));
+    }
+
+    #[test]
+    fn rejects_what_it_cant_import() {
+        let level = level();
+        assert!(import_bytes("missing", &wad(&level), "MAP01").is_err());
+        assert!(import_bytes("not-a-wad", b"PK\x03\x04", "E1M1").is_err());
+        let mut hexen = level.clone();
+        hexen.insert(hexen.len() - 1, ("BEHAVIOR", Vec::new()));
+        assert!(import_bytes("hexen", &wad(&hexen), "E1M1").is_err());
+        let no_start: Vec<_> = level
+            .iter()
+            .map(|(lump, bytes)| match *lump {
+                "THINGS" => (*lump, Vec::new()),
+                _ => (*lump, bytes.clone()),
+            })
+            .collect();
+        assert!(import_bytes("no-start", &wad(&no_start), "E1M1").is_err());
+    }
+
+    #[test]
+    fn rejects_corrupt_directories() {
+        let good = wad(&level());
+        let directory = i32::from_le_bytes(good[8..12].try_into().unwrap()) as usize;
+        // A lump starting before the file does, a size running off the end of memory, and a
+        // directory past the end of the file.
+        for (at, value) in [(directory, -1), (directory + 4, i32::MAX), (8, -16)] {
+            let mut bad = good.clone();
+            bad[at..at + 4].copy_from_slice(&value.to_le_bytes());
+            assert!(
+                import_bytes("corrupt", &bad, "E1M1").is_err(),
+                "{value} at {at}"
+            );
+        }
+        let mut textures = 2i32.to_le_bytes().to_vec();
+        textures.extend((-8i32).to_le_bytes());
+        assert!(read_textures(&textures, &mut HashMap::new()).is_none());
+    }
+}
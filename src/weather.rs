@@ -0,0 +1,128 @@
+use std::f32::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    canvas::Canvas,
+    colour::Rgb,
+    render::{Camera, Projection},
+    world::World,
+};
+
+// How many canvas pixels there are for each drop at a density of 1.0.
+const PIXELS_PER_DROP: f32 = 150.0;
+// How far from the camera drops fall, nearer ones looking bigger and faster.
+const NEAR: f32 = 1.0;
+const FAR: f32 = 8.0;
+// How long a raindrop's streak is, as how far it falls in that many seconds.
+const STREAK_TIME: f32 = 0.015;
+// How far snowflakes sway side to side in world units, and how many times a second.
+const SWAY: f32 = 0.15;
+const SWAY_RATE: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precipitation {
+    Rain,
+    Snow,
+}
+
+impl Precipitation {
+    // World units a second it falls at.
+    fn speed(self) -> f32 {
+        match self {
+            Precipitation::Rain => 6.0,
+            Precipitation::Snow => 0.8,
+        }
+    }
+
+    // Its colour, and how much of the scene behind it is covered.
+    fn colour(self) -> (Rgb, f32) {
+        match self {
+            Precipitation::Rain => (Rgb::new(170, 185, 210), 0.6),
+            Precipitation::Snow => (Rgb::WHITE, 0.9),
+        }
+    }
+}
+
+// Rain or snow falling across the view, blown sideways by `wind` world units a second.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Weather {
+    pub kind: Precipitation,
+    // How many drops fall, 1.0 being a steady shower.
+    #[serde(default = "default_density")]
+    pub density: f32,
+    #[serde(default)]
+    pub wind: f32,
+}
+
+fn default_density() -> f32 {
+    1.0
+}
+
+// A repeatable random number from 0.0 up to 1.0 for drop `i`, different for each `salt`.
+fn noise(i: u32, salt: u32) -> f32 {
+    let mut x = i.wrapping_mul(0x9e37_79b9) ^ salt.wrapping_mul(0x85eb_ca6b);
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    (x >> 8) as f32 / (1 << 24) as f32
+}
+
+// Draws the world's weather over the finished scene. Each drop is somewhere between `NEAR`
+// and `FAR` from the camera, and is hidden in any column where `depth` has a wall in front of
+// it. Drops stay put on screen apart from falling, drifting with the wind and panning as the
+// camera turns, so the same clock always draws the same frame.
+pub fn draw_weather(
+    canvas: &mut Canvas,
+    camera: &Camera,
+    world: &World,
+    projection: &Projection,
+    depth: &[f32],
+) {
+    let Some(weather) = world.weather else {
+        return;
+    };
+    let (width, height) = (canvas.width as f32, canvas.height as f32);
+    let count = (weather.density.max(0.0) * width * height / PIXELS_PER_DROP) as u32;
+    let (colour, cover) = weather.kind.colour();
+    let colour = world.brightness_at(camera.position, None).apply(colour);
+    let pan = camera.rotation / camera.fov * width;
+    for i in 0..count {
+        let distance = NEAR + (FAR - NEAR) * noise(i, 0).powi(2);
+        let cover = cover * (1.0 - world.fog.density(distance));
+        // Pixels a world unit spans across and down the screen this far away.
+        let across = projection.width_of(1.0, distance);
+        let down = projection.wall_height(distance);
+        let fall = weather.kind.speed() * down;
+        let streak = match weather.kind {
+            Precipitation::Rain => (fall * STREAK_TIME).max(1.0),
+            Precipitation::Snow => 1.0,
+        };
+        // Each drop wraps from the bottom back to the top of a band a bit taller than the
+        // screen, so its whole streak is off screen before it reappears.
+        let span = height + streak;
+        let fallen = noise(i, 1) * span + world.clock * fall;
+        let y = fallen.rem_euclid(span) - streak;
+        let mut x = noise(i, 2) * width + pan + world.clock * weather.wind * across;
+        if weather.kind == Precipitation::Snow {
+            x += (world.clock * SWAY_RATE * TAU + noise(i, 3) * TAU).sin() * SWAY * across;
+        }
+        // How far sideways the streak goes for each pixel it goes down.
+        let slant = weather.wind * across / fall;
+        for step in 0..streak.ceil() as usize {
+            let (px, py) = (x + slant * step as f32, y + step as f32);
+            if py < 0.0 || py >= height {
+                continue;
+            }
+            let column = (px.rem_euclid(width) as usize).min(canvas.width - 1);
+            if depth.get(column).is_some_and(|d| *d < distance) {
+                continue;
+            }
+            let row = py as usize;
+            canvas.set(column, row, canvas.get(column, row).lerp(colour, cover));
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use ratatui::{buffer::Buffer, layout::Rect, style::Color};
+
+use crate::{
+    canvas::Canvas,
+    colour::Rgb,
+    framebuffer::FrameBuffer,
+    minimap::draw_minimap,
+    render::{draw_scene, encode, Camera, Output},
+    theme::Theme,
+    world::World,
+};
+
+// The 3D view as a ratatui widget, for embedding in a TUI with other panels around it. It keeps
+// its canvas between frames, so drawing one every frame doesn't allocate.
+pub struct RaycastView {
+    pub output: Output,
+    pub minimap: bool,
+    pub theme: Theme,
+    canvas: Canvas,
+    cells: FrameBuffer,
+}
+
+impl RaycastView {
+    pub fn new(output: Output) -> Self {
+        Self {
+            output,
+            minimap: false,
+            theme: Theme::classic(),
+            canvas: Canvas::new(0, 0),
+            cells: FrameBuffer::new(0, 0),
+        }
+    }
+
+    pub fn with_minimap(mut self, minimap: bool) -> Self {
+        self.minimap = minimap;
+        self
+    }
+
+    // Draws what `camera` sees into `area` of `buf`, over whatever was there.
+    pub fn render(&mut self, area: Rect, buf: &mut Buffer, world: &World, camera: &Camera) {
+        let area = area.intersection(buf.area);
+        if area.is_empty() {
+            return;
+        }
+        let (px, py) = self.output.pixels_per_cell();
+        self.canvas
+            .resize(area.width as usize * px, area.height as usize * py);
+        self.canvas.aspect = self.output.pixel_aspect();
+        draw_scene(&mut self.canvas, camera, world);
+        if self.minimap {
+            draw_minimap(&mut self.canvas, camera, world, &self.theme.hud);
+        }
+        self.cells.resize(area.width, area.height);
+        encode(&self.canvas, &mut self.cells, self.output);
+        for y in 0..area.height {
+            for x in 0..area.width {
+                let cell = self.cells.get(x, y);
+                let target = &mut buf[(area.x + x, area.y + y)];
+                target.set_char(cell.glyph);
+                target.fg = cell.fg.map_or(Color::Reset, colour);
+                target.bg = cell.bg.map_or(Color::Reset, colour);
+            }
+        }
+    }
+}
+
+fn colour(rgb: Rgb) -> Color {
+    Color::Rgb(rgb.r, rgb.g, rgb.b)
+}
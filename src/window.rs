@@ -0,0 +1,297 @@
+use std::{collections::VecDeque, thread, time::Duration};
+
+use anyhow::{anyhow, Result};
+use minifb::{Key as WindowKey, KeyRepeat, MouseButton, MouseMode, WindowOptions};
+
+use crate::{
+    backend::{
+        event::{
+            Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers,
+            MouseButton as Button, MouseEvent, MouseEventKind,
+        },
+        Backend,
+    },
+    canvas::Canvas,
+    colour::Rgb,
+    font::{glyph, GLYPH_HEIGHT, GLYPH_WIDTH},
+    framebuffer::{Cell, FrameBuffer},
+};
+
+// Pixels in a window taken up by one cell of the HUD, which fits a letter of the built-in font
+// drawn two pixels to each of its own with a little room around it.
+pub const CELL_WIDTH: usize = 12;
+pub const CELL_HEIGHT: usize = 18;
+const GLYPH_SCALE: usize = 2;
+
+// A desktop window the game is drawn into pixel for pixel, instead of as terminal cells. Its
+// keys and mouse come out as terminal events, so everything else plays the same either way.
+pub struct Window {
+    window: minifb::Window,
+    buffer: Vec<u32>,
+    // The whole window, for frames shown without a scene of their own.
+    blank: Canvas,
+    // Where the mouse was while its button was held, in cells.
+    dragging: Option<(u16, u16)>,
+    // What came in while the last frame was shown, to be handed out by `poll_event`.
+    events: VecDeque<Event>,
+    // The size in cells when the last frame was shown, to notice it being resized.
+    last_size: (u16, u16),
+}
+
+impl Window {
+    pub fn open(title: &str, width: usize, height: usize) -> Result<Self> {
+        let options = WindowOptions {
+            resize: true,
+            ..WindowOptions::default()
+        };
+        let window = minifb::Window::new(title, width, height, options)
+            .map_err(|e| anyhow!("Could not open a window: {e}"))?;
+        let mut window = Self {
+            window,
+            buffer: Vec::new(),
+            blank: Canvas::new(0, 0),
+            dragging: None,
+            events: VecDeque::new(),
+            last_size: (0, 0),
+        };
+        window.last_size = window.cells();
+        Ok(window)
+    }
+
+    // The window's size in HUD cells, which is what the rest of the game calls the screen size.
+    fn cells(&self) -> (u16, u16) {
+        let (width, height) = self.window.get_size();
+        (
+            (width / CELL_WIDTH).max(1) as u16,
+            (height / CELL_HEIGHT).max(1) as u16,
+        )
+    }
+
+    // Keys and mouse drags since the last frame was shown. Keys still down repeat every frame,
+    // so they count as held for as long as they're down.
+    fn input(&mut self) -> Vec<Event> {
+        let shift = self.window.is_key_down(WindowKey::LeftShift)
+            || self.window.is_key_down(WindowKey::RightShift);
+        let modifiers = if self.window.is_key_down(WindowKey::LeftCtrl)
+            || self.window.is_key_down(WindowKey::RightCtrl)
+        {
+            KeyModifiers::CONTROL
+        } else {
+            KeyModifiers::NONE
+        };
+        let pressed = self.window.get_keys_pressed(KeyRepeat::No);
+        let mut events = Vec::new();
+        for key in self.window.get_keys() {
+            let kind = if pressed.contains(&key) {
+                KeyEventKind::Press
+            } else {
+                KeyEventKind::Repeat
+            };
+            events.extend(key_event(key, kind, shift, modifiers));
+        }
+        for key in self.window.get_keys_released() {
+            events.extend(key_event(key, KeyEventKind::Release, shift, modifiers));
+        }
+        let down = self.window.get_mouse_down(MouseButton::Left);
+        let position = self.window.get_mouse_pos(MouseMode::Clamp).map(|(x, y)| {
+            (
+                (x as usize / CELL_WIDTH) as u16,
+                (y as usize / CELL_HEIGHT) as u16,
+            )
+        });
+        let kind = match (self.dragging, down) {
+            (None, true) => Some(MouseEventKind::Down(Button::Left)),
+            (Some(last), true) if Some(last) != position => {
+                Some(MouseEventKind::Drag(Button::Left))
+            }
+            (Some(_), false) => Some(MouseEventKind::Up(Button::Left)),
+            _ => None,
+        };
+        if let (Some(kind), Some((column, row))) = (kind, position) {
+            events.push(Event::Mouse(MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            }));
+        }
+        self.dragging = if down { position } else { None };
+        events
+    }
+}
+
+impl Backend for Window {
+    fn size(&self) -> Result<(u16, u16)> {
+        Ok(self.cells())
+    }
+
+    // Cells on their own are drawn over black.
+    fn present(&mut self, frame: &FrameBuffer) -> Result<()> {
+        let mut blank = std::mem::replace(&mut self.blank, Canvas::new(0, 0));
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        blank.resize(width * CELL_WIDTH, height * CELL_HEIGHT);
+        blank.pixels.fill(Rgb::BLACK);
+        let result = self.present_pixels(&mut blank, frame);
+        self.blank = blank;
+        result
+    }
+
+    // The window only takes in keys when a frame is shown, so this hands those out and then
+    // just waits.
+    fn poll_event(&mut self, timeout: Duration) -> Result<Option<Event>> {
+        if let Some(event) = self.events.pop_front() {
+            return Ok(Some(event));
+        }
+        thread::sleep(timeout);
+        Ok(None)
+    }
+
+    fn cell_pixels(&self) -> Option<(usize, usize)> {
+        Some((CELL_WIDTH, CELL_HEIGHT))
+    }
+
+    // Shows the scene with the HUD's cells drawn over it.
+    fn present_pixels(&mut self, scene: &mut Canvas, frame: &FrameBuffer) -> Result<()> {
+        draw_cells(scene, frame);
+        self.buffer.clear();
+        self.buffer.extend(
+            scene
+                .pixels
+                .iter()
+                .map(|c| (c.r as u32) << 16 | (c.g as u32) << 8 | c.b as u32),
+        );
+        self.window
+            .update_with_buffer(&self.buffer, scene.width, scene.height)
+            .map_err(|e| anyhow!("Could not draw to the window: {e}"))?;
+        let input = self.input();
+        self.events.extend(input);
+        let size = self.cells();
+        if size != self.last_size {
+            self.last_size = size;
+            self.events.push_back(Event::Resize(size.0, size.1));
+        }
+        Ok(())
+    }
+
+    fn closed(&self) -> bool {
+        !self.window.is_open()
+    }
+}
+
+fn key_event(
+    key: WindowKey,
+    kind: KeyEventKind,
+    shift: bool,
+    modifiers: KeyModifiers,
+) -> Option<Event> {
+    let code = match key {
+        WindowKey::Space => KeyCode::Char(' '),
+        WindowKey::Up => KeyCode::Up,
+        WindowKey::Down => KeyCode::Down,
+        WindowKey::Left => KeyCode::Left,
+        WindowKey::Right => KeyCode::Right,
+        WindowKey::Enter | WindowKey::NumPadEnter => KeyCode::Enter,
+        WindowKey::Escape => KeyCode::Esc,
+        WindowKey::Tab => KeyCode::Tab,
+        WindowKey::Backspace => KeyCode::Backspace,
+        WindowKey::Home => KeyCode::Home,
+        WindowKey::End => KeyCode::End,
+        WindowKey::PageUp => KeyCode::PageUp,
+        WindowKey::PageDown => KeyCode::PageDown,
+        WindowKey::Insert => KeyCode::Insert,
+        WindowKey::Delete => KeyCode::Delete,
+        WindowKey::Apostrophe => KeyCode::Char('\''),
+        WindowKey::Backquote => KeyCode::Char('`'),
+        WindowKey::Backslash => KeyCode::Char('\\'),
+        WindowKey::Comma => KeyCode::Char(','),
+        WindowKey::Equal => KeyCode::Char('='),
+        WindowKey::LeftBracket => KeyCode::Char('['),
+        WindowKey::Minus => KeyCode::Char('-'),
+        WindowKey::Period => KeyCode::Char('.'),
+        WindowKey::RightBracket => KeyCode::Char(']'),
+        WindowKey::Semicolon => KeyCode::Char(';'),
+        WindowKey::Slash => KeyCode::Char('/'),
+        key => {
+            let n = key as u32;
+            match n {
+                0..=9 => KeyCode::Char(char::from_digit(n, 10)?),
+                10..=35 => {
+                    let c = (b'a' + (n - 10) as u8) as char;
+                    KeyCode::Char(if shift { c.to_ascii_uppercase() } else { c })
+                }
+                _ => {
+                    let f = n.checked_sub(WindowKey::F1 as u32)?;
+                    if f >= 15 {
+                        return None;
+                    }
+                    KeyCode::F(f as u8 + 1)
+                }
+            }
+        }
+    };
+    Some(Event::Key(KeyEvent {
+        code,
+        modifiers,
+        kind,
+        state: KeyEventState::NONE,
+    }))
+}
+
+// Draws every cell that has something in it over the canvas, leaving empty ones see-through.
+fn draw_cells(canvas: &mut Canvas, cells: &FrameBuffer) {
+    for row in 0..cells.height {
+        for column in 0..cells.width {
+            let cell = cells.get(column, row);
+            if cell == Cell::default() {
+                continue;
+            }
+            let (x, y) = (column as usize * CELL_WIDTH, row as usize * CELL_HEIGHT);
+            if let Some(bg) = cell.bg {
+                fill(canvas, x, y, CELL_WIDTH, CELL_HEIGHT, bg);
+            }
+            let fg = cell.fg.unwrap_or(Rgb::WHITE);
+            draw_glyph(canvas, x, y, cell.glyph, fg);
+        }
+    }
+}
+
+fn draw_glyph(canvas: &mut Canvas, x: usize, y: usize, c: char, colour: Rgb) {
+    let half = CELL_HEIGHT / 2;
+    match c {
+        ' ' => {}
+        '█' => fill(canvas, x, y, CELL_WIDTH, CELL_HEIGHT, colour),
+        '▀' => fill(canvas, x, y, CELL_WIDTH, half, colour),
+        '▄' => fill(canvas, x, y + half, CELL_WIDTH, CELL_HEIGHT - half, colour),
+        // A quarter of the pixels, like the shade character.
+        '░' => {
+            for dy in 0..CELL_HEIGHT {
+                for dx in 0..CELL_WIDTH {
+                    if (dx + dy) % 4 == 0 {
+                        canvas.set(x + dx, y + dy, colour);
+                    }
+                }
+            }
+        }
+        c => {
+            let rows = glyph(c);
+            let left = x + (CELL_WIDTH - GLYPH_WIDTH * GLYPH_SCALE) / 2;
+            let top = y + (CELL_HEIGHT - GLYPH_HEIGHT * GLYPH_SCALE) / 2;
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        let (px, py) = (left + column * GLYPH_SCALE, top + row * GLYPH_SCALE);
+                        fill(canvas, px, py, GLYPH_SCALE, GLYPH_SCALE, colour);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn fill(canvas: &mut Canvas, x: usize, y: usize, width: usize, height: usize, colour: Rgb) {
+    for py in y..y + height {
+        for px in x..x + width {
+            canvas.set(px, py, colour);
+        }
+    }
+}
@@ -0,0 +1,1105 @@
+use std::path::PathBuf;
+
+use crate::{
+    cheat::Cheats,
+    collision::{slide, PLAYER_RADIUS},
+    colour::Rgb,
+    damage::{self, DamageNumber},
+    daylight::DayCycle,
+    door::{Door, DoorState},
+    enemy::{Enemy, BITE_RANGE, BITE_TIME, REACH},
+    entities::{Entities, Entity},
+    event::{GameEvent, Target},
+    geometry::{Arc, Hit, LineSegment},
+    grid::Grid,
+    health::{Health, PLAYER_HEALTH},
+    inventory::Inventory,
+    light::{self, Brightness, Light},
+    particle::{Effect, Particles},
+    pickup::{Pickup, PICKUP_RADIUS},
+    portal::{self, Portal},
+    projectile::{Owner, Projectile, PLAYER_DAMAGE, PLAYER_RELOAD, RADIUS},
+    sector::{Crossing, Heights, Reach, Sector},
+    shade::Fog,
+    sound::AmbientSound,
+    spatial::SegmentIndex,
+    sprite::Sprite,
+    switch::{Dormant, Switch, Wall},
+    teleporter::{Teleporter, TELEPORT_RADIUS},
+    texture::Texture,
+    trigger::{Action, Exit, TriggerRegion},
+    vec2::Vec2,
+    weather::Weather,
+};
+
+pub struct World {
+    pub segments: Vec<LineSegment>,
+    // Which segments are where, built once in `new`, so the segments shouldn't change after.
+    index: SegmentIndex,
+    // Curved walls, like round towers.
+    pub arcs: Vec<Arc>,
+    // How bright everything is without any lights, and the lights brightening it further.
+    pub ambient: f32,
+    pub lights: Vec<Light>,
+    pub grid: Option<Grid>,
+    pub textures: Vec<Texture>,
+    pub fog: Fog,
+    pub weather: Option<Weather>,
+    // Sprites, doors and anything else that isn't a wall.
+    pub entities: Entities,
+    pub particles: Particles,
+    pub damage_numbers: Vec<DamageNumber>,
+    // Areas of floor raised, lowered or with lower ceilings than everywhere else.
+    pub sectors: Vec<Sector>,
+    pub floor: Surface,
+    pub ceiling: Surface,
+    // Drawn in place of the ceiling when set.
+    pub sky: Option<Sky>,
+    // Slowly turns day into night and back, changing the ambient light and the sky.
+    pub day_cycle: Option<DayCycle>,
+    pub sounds: Vec<AmbientSound>,
+    pub music: Option<PathBuf>,
+    pub exit: Option<Exit>,
+    pub triggers: Vec<TriggerRegion>,
+    // Lines trigger regions want shown, waiting for whatever's showing them to take them.
+    pub messages: Vec<String>,
+    // A Lua file with the map's own logic in it.
+    pub script: Option<PathBuf>,
+    // WebAssembly modules with more of it.
+    pub plugins: Vec<PathBuf>,
+    // Time passed since the last tick.
+    ticked: f32,
+    // Seconds since the level started, which animated textures, lights and the time of day
+    // are timed by.
+    pub clock: f32,
+    // Seconds until the player can fire again.
+    reload: f32,
+    // The player's hit points.
+    pub health: Health,
+    pub cheats: Cheats,
+    pub inventory: Inventory,
+    // Every enemy the map placed, in order, so saves can tell which are still alive.
+    pub enemy_roster: Vec<Entity>,
+    // Likewise for pickups and which are still lying around.
+    pub pickup_roster: Vec<Entity>,
+    // Where the player was last update, to tell when they walk through a portal.
+    last_player: Option<Vec2>,
+}
+
+// A flat floor or ceiling, tiled with its texture once per world unit.
+#[derive(Debug, Clone, Copy)]
+pub struct Surface {
+    pub colour: Rgb,
+    pub texture: Option<usize>,
+}
+
+// A panorama wrapped once around the horizon, fading from `top` to `bottom` wherever the
+// texture is missing or transparent.
+#[derive(Debug, Clone, Copy)]
+pub struct Sky {
+    pub top: Rgb,
+    pub bottom: Rgb,
+    pub texture: Option<usize>,
+    // How much the texture is darkened, e.g. at night.
+    pub brightness: f32,
+}
+
+impl Surface {
+    pub fn new(colour: Rgb) -> Self {
+        Self {
+            colour,
+            texture: None,
+        }
+    }
+
+    pub fn with_texture(mut self, texture: Option<usize>) -> Self {
+        self.texture = texture;
+        self
+    }
+}
+
+// How close the player has to be to a door to open it.
+pub const USE_RANGE: f32 = 1.5;
+
+// How many times a ray drawing the scene can be reflected by mirrors or pass through portals.
+pub const MAX_BOUNCES: usize = 4;
+const MIRROR_OFFSET: f32 = 1e-3;
+// How far past a sector's edge a ray looks to see which sector it's gone into.
+const CROSSING_NUDGE: f32 = 1e-3;
+
+// How many specks of dust a shot knocks off a wall, and how many sparkles burst from a
+// teleporter the player arrives on.
+const DUST: usize = 8;
+const ARRIVAL_SPARKLES: usize = 24;
+// How high off the floor shots fly.
+const SHOT_HEIGHT: f32 = 0.5;
+// Particles a second given off by each teleporter pad and each moving door.
+const SPARKLE_RATE: f32 = 6.0;
+const SPARK_RATE: f32 = 30.0;
+
+// Seconds between ticks of the systems that need a steady pace, like enemies thinking and
+// moving.
+pub const TICK: f32 = 1.0 / 60.0;
+
+impl World {
+    pub fn new(segments: Vec<LineSegment>) -> Self {
+        Self {
+            index: SegmentIndex::new(&segments),
+            segments,
+            arcs: Vec::new(),
+            ambient: 1.0,
+            lights: Vec::new(),
+            grid: None,
+            textures: Vec::new(),
+            fog: Fog::default(),
+            weather: None,
+            entities: Entities::default(),
+            particles: Particles::default(),
+            damage_numbers: Vec::new(),
+            sectors: Vec::new(),
+            floor: Surface::new(Rgb::BLUE),
+            ceiling: Surface::new(Rgb::RED),
+            sky: None,
+            day_cycle: None,
+            sounds: Vec::new(),
+            music: None,
+            exit: None,
+            triggers: Vec::new(),
+            messages: Vec::new(),
+            script: None,
+            plugins: Vec::new(),
+            ticked: 0.0,
+            clock: 0.0,
+            reload: 0.0,
+            health: Health::new(PLAYER_HEALTH),
+            inventory: Inventory::default(),
+            cheats: Cheats::default(),
+            enemy_roster: Vec::new(),
+            pickup_roster: Vec::new(),
+            last_player: None,
+        }
+    }
+
+    pub fn with_grid(mut self, grid: Grid) -> Self {
+        self.grid = Some(grid);
+        self
+    }
+
+    pub fn with_textures(mut self, textures: Vec<Texture>) -> Self {
+        self.textures = textures;
+        self
+    }
+
+    pub fn with_sprites(mut self, sprites: Vec<(Vec2, Sprite)>) -> Self {
+        for (position, sprite) in sprites {
+            self.entities.spawn_sprite(position, sprite);
+        }
+        self
+    }
+
+    pub fn with_arcs(mut self, arcs: Vec<Arc>) -> Self {
+        self.arcs = arcs;
+        self
+    }
+
+    pub fn with_lights(mut self, ambient: f32, lights: Vec<Light>) -> Self {
+        self.ambient = ambient;
+        self.lights = lights;
+        self
+    }
+
+    pub fn with_sectors(mut self, sectors: Vec<Sector>) -> Self {
+        self.sectors = sectors;
+        self
+    }
+
+    pub fn with_fog(mut self, fog: Fog) -> Self {
+        self.fog = fog;
+        self
+    }
+
+    pub fn with_weather(mut self, weather: Option<Weather>) -> Self {
+        self.weather = weather;
+        self
+    }
+
+    pub fn with_doors(mut self, doors: Vec<Door>) -> Self {
+        for door in doors {
+            self.entities.spawn_door(door);
+        }
+        self
+    }
+
+    pub fn with_surfaces(mut self, floor: Surface, ceiling: Surface) -> Self {
+        self.floor = floor;
+        self.ceiling = ceiling;
+        self
+    }
+
+    pub fn with_sky(mut self, sky: Option<Sky>) -> Self {
+        self.sky = sky;
+        self
+    }
+
+    pub fn with_day_cycle(mut self, day_cycle: Option<DayCycle>) -> Self {
+        if let Some(cycle) = &day_cycle {
+            (self.ambient, self.sky) = cycle.at(self.clock);
+        }
+        self.day_cycle = day_cycle;
+        self
+    }
+
+    pub fn with_sounds(mut self, sounds: Vec<AmbientSound>) -> Self {
+        self.sounds = sounds;
+        self
+    }
+
+    pub fn with_music(mut self, music: Option<PathBuf>) -> Self {
+        self.music = music;
+        self
+    }
+
+    pub fn with_triggers(mut self, triggers: Vec<TriggerRegion>) -> Self {
+        self.triggers = triggers;
+        self
+    }
+
+    pub fn with_script(mut self, script: Option<PathBuf>) -> Self {
+        self.script = script;
+        self
+    }
+
+    pub fn with_plugins(mut self, plugins: Vec<PathBuf>) -> Self {
+        self.plugins = plugins;
+        self
+    }
+
+    pub fn with_enemies(mut self, enemies: Vec<(Vec2, Sprite, Enemy)>) -> Self {
+        for (position, sprite, enemy) in enemies {
+            let entity = self.entities.spawn_enemy(position, sprite, enemy);
+            self.enemy_roster.push(entity);
+        }
+        self
+    }
+
+    // Enemies that wait hidden until a switch wakes them, kept on the roster after the rest.
+    pub fn with_dormant(mut self, enemies: Vec<(Vec2, Dormant)>) -> Self {
+        for (position, dormant) in enemies {
+            let entity = self.entities.spawn_dormant(position, dormant);
+            self.enemy_roster.push(entity);
+        }
+        self
+    }
+
+    pub fn with_switches(mut self, switches: Vec<Switch>) -> Self {
+        for switch in switches {
+            self.entities.spawn_switch(switch);
+        }
+        self
+    }
+
+    pub fn with_walls(mut self, walls: Vec<Wall>) -> Self {
+        for wall in walls {
+            self.entities.spawn_wall(wall);
+        }
+        self
+    }
+
+    // Pairs of pads, each with the facing the player is turned to on arriving at it.
+    pub fn with_teleporters(mut self, pairs: Vec<[(Vec2, Sprite, Option<f32>); 2]>) -> Self {
+        for [(first, first_sprite, first_facing), (second, second_sprite, second_facing)] in pairs {
+            let a = self.entities.spawn_sprite(first, first_sprite);
+            let b = self.entities.spawn_sprite(second, second_sprite);
+            let teleporters = &mut self.entities.teleporters;
+            teleporters.insert(a, Teleporter::new(b, first_facing));
+            teleporters.insert(b, Teleporter::new(a, second_facing));
+        }
+        self
+    }
+
+    pub fn with_portals(mut self, pairs: Vec<[LineSegment; 2]>) -> Self {
+        for [first, second] in pairs {
+            let a = self.entities.spawn();
+            let b = self.entities.spawn();
+            let portals = &mut self.entities.portals;
+            portals.insert(
+                a,
+                Portal {
+                    segment: first,
+                    partner: b,
+                },
+            );
+            portals.insert(
+                b,
+                Portal {
+                    segment: second,
+                    partner: a,
+                },
+            );
+        }
+        self
+    }
+
+    pub fn with_pickups(mut self, pickups: Vec<(Vec2, Sprite, Pickup)>) -> Self {
+        for (position, sprite, pickup) in pickups {
+            let entity = self.entities.spawn_pickup(position, sprite, pickup);
+            self.pickup_roster.push(entity);
+        }
+        self
+    }
+
+    pub fn with_exit(mut self, exit: Option<Exit>) -> Self {
+        self.exit = exit;
+        self
+    }
+
+    // The nearest wall along the ray from `origin`, looking no further than `max_distance`.
+    pub fn cast(&self, origin: Vec2, angle: f32, max_distance: f32) -> Option<Hit> {
+        self.cast_counting(origin, angle, max_distance, &mut 0)
+    }
+
+    // Like `cast`, adding how many segments the ray was tested against to `tests`.
+    pub fn cast_counting(
+        &self,
+        origin: Vec2,
+        angle: f32,
+        max_distance: f32,
+        tests: &mut usize,
+    ) -> Option<Hit> {
+        self.hits(origin, angle, max_distance, tests)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    // Every wall along the ray, nearest first, up to and including the first one that can't be
+    // seen through. See-through walls block movement like any other, so only drawing needs
+    // these. Rays that hit a mirror carry on reflected, and ones that go through a portal carry
+    // on out of its partner, up to `MAX_BOUNCES` times. The distances of what they hit after are
+    // measured along the whole path, and the mirror or portal is included as a hit of its own.
+    pub fn cast_layers(
+        &self,
+        mut origin: Vec2,
+        mut angle: f32,
+        max_distance: f32,
+        tests: &mut usize,
+    ) -> Vec<Hit> {
+        let mut layers = Vec::new();
+        let mut travelled = 0.0;
+        for bounce in 0..=MAX_BOUNCES {
+            let mut hits: Vec<_> = self
+                .hits(origin, angle, max_distance - travelled, tests)
+                .collect();
+            hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+            if let Some(solid) = hits.iter().position(|h| !h.is_see_through()) {
+                hits.truncate(solid + 1);
+            }
+            let ray = LineSegment::ray(origin, angle, max_distance - travelled);
+            let portal = self
+                .entities
+                .portals
+                .iter()
+                .filter_map(|(_, portal)| Some((portal.segment.hit(&ray)?, portal)))
+                .min_by(|a, b| a.0.distance.total_cmp(&b.0.distance))
+                .filter(|(hit, _)| hits.last().is_none_or(|h| h.distance > hit.distance));
+            *tests += self.entities.portals.len();
+            let direction = Vec2::from_angle(angle);
+            let mut next = None;
+            if let Some((mut hit, portal)) = portal {
+                hits.retain(|h| h.distance < hit.distance);
+                hit.portal = true;
+                hits.push(hit);
+                if let Some(partner) = self.entities.portals.get(portal.partner) {
+                    let (from, to) = (&portal.segment, &partner.segment);
+                    let point = portal::carry(from, to, origin + direction * hit.distance);
+                    let turned = direction.rotate(portal::turn(from, to));
+                    next = Some((point, turned, hit.distance));
+                }
+            } else if let Some(mirror) = hits.last().filter(|h| h.mirror && !h.is_see_through()) {
+                let point = origin + direction * mirror.distance;
+                next = Some((point, direction.reflect(mirror.normal), mirror.distance));
+            }
+            if bounce == MAX_BOUNCES {
+                next = None;
+            }
+            for mut hit in hits {
+                // Walls run between the floor and ceiling on the side the ray came from.
+                let point = origin + direction * hit.distance + hit.normal * CROSSING_NUDGE;
+                let heights = self.heights_at(point);
+                hit.floor = heights.floor;
+                hit.ceiling = heights.ceiling;
+                hit.distance += travelled;
+                layers.push(hit);
+            }
+            let Some((point, direction, distance)) = next else {
+                // A mirror or portal the ray can't go on from is drawn like any other wall.
+                if let Some(last) = layers.last_mut() {
+                    last.mirror = false;
+                    last.portal = false;
+                }
+                break;
+            };
+            // Start just off the mirror or portal so the ray doesn't hit it again straight away.
+            origin = point + direction * MIRROR_OFFSET;
+            angle = direction.angle();
+            travelled += distance + MIRROR_OFFSET;
+        }
+        layers
+    }
+
+    fn hits<'a>(
+        &'a self,
+        origin: Vec2,
+        angle: f32,
+        max_distance: f32,
+        tests: &'a mut usize,
+    ) -> impl Iterator<Item = Hit> + 'a {
+        let ray = LineSegment::ray(origin, angle, max_distance);
+        let grid_hit = self
+            .grid
+            .as_ref()
+            .and_then(|grid| grid.cast(origin, angle, max_distance));
+        let segment_hits = self.index.hits(&self.segments, &ray, tests);
+        *tests += self.arcs.len();
+        let arc_hits: Vec<_> = self.arcs.iter().filter_map(|a| a.hit(&ray)).collect();
+        let entity_hits = self
+            .entity_segments()
+            .inspect(|_| *tests += 1)
+            .filter_map(move |s| s.hit(&ray));
+        grid_hit
+            .into_iter()
+            .chain(segment_hits)
+            .chain(arc_hits)
+            .chain(entity_hits)
+    }
+
+    // How brightly lit `point` is, on a wall facing `normal` if it's on one.
+    pub fn brightness_at(&self, point: Vec2, normal: Option<Vec2>) -> Brightness {
+        light::brightness(&self.lights, self.ambient, point, normal)
+    }
+
+    pub fn sector_at(&self, point: Vec2) -> Option<&Sector> {
+        self.sectors.iter().find(|s| s.contains(point))
+    }
+
+    pub fn heights_at(&self, point: Vec2) -> Heights {
+        self.sector_at(point)
+            .map_or(Heights::DEFAULT, Sector::heights)
+    }
+
+    pub fn floor_at(&self, point: Vec2) -> f32 {
+        self.heights_at(point).floor
+    }
+
+    // Everywhere the ray passes into a different sector, nearest first, with the sector it
+    // passes into.
+    pub fn crossings(&self, origin: Vec2, angle: f32, max_distance: f32) -> Vec<Crossing<'_>> {
+        if self.sectors.is_empty() {
+            return Vec::new();
+        }
+        let ray = LineSegment::ray(origin, angle, max_distance);
+        let mut hits: Vec<_> = self
+            .sectors
+            .iter()
+            .flat_map(Sector::edges)
+            .filter_map(|edge| edge.hit(&ray))
+            .collect();
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        // Sectors sharing an edge are both hit at once, and only the one entered matters.
+        hits.dedup_by(|later, earlier| later.distance - earlier.distance < CROSSING_NUDGE);
+        let direction = Vec2::from_angle(angle);
+        hits.into_iter()
+            .map(|hit| Crossing {
+                hit,
+                sector: self.sector_at(origin + direction * (hit.distance + CROSSING_NUDGE)),
+            })
+            .collect()
+    }
+
+    // The nearest step along the ray too high to climb, or ceiling too low to fit under, for
+    // something starting at `origin`.
+    pub fn ledge(&self, origin: Vec2, angle: f32, max_distance: f32, reach: Reach) -> Option<Hit> {
+        let mut here = self.heights_at(origin);
+        for crossing in self.crossings(origin, angle, max_distance) {
+            let next = crossing.sector.map_or(Heights::DEFAULT, Sector::heights);
+            if !here.can_reach(next, reach) {
+                return Some(crossing.hit);
+            }
+            here = next;
+        }
+        None
+    }
+
+    // Like `cast`, but also stopping at ledges, for things moving along the floor.
+    pub fn cast_movement(
+        &self,
+        origin: Vec2,
+        angle: f32,
+        max_distance: f32,
+        reach: Reach,
+    ) -> Option<Hit> {
+        let wall = self.cast(origin, angle, max_distance);
+        let ledge = self.ledge(origin, angle, max_distance, reach);
+        match (wall, ledge) {
+            (Some(wall), Some(ledge)) if ledge.distance < wall.distance => Some(ledge),
+            (None, ledge) => ledge,
+            (wall, _) => wall,
+        }
+    }
+
+    // Walls that belong to entities rather than the map: the closed part of every door, solid
+    // hidden walls and switches.
+    pub fn entity_segments(&self) -> impl Iterator<Item = LineSegment> + '_ {
+        let entities = &self.entities;
+        let doors = entities.doors.iter().filter_map(|(_, door)| door.segment());
+        let walls = entities.walls.iter().filter_map(|(_, wall)| wall.segment());
+        let switches = entities.switches.iter().map(|(_, switch)| switch.segment());
+        doors.chain(walls).chain(switches)
+    }
+
+    // The door or switch the player at `origin` is looking at, if it's the closest thing in
+    // front of them and within reach.
+    pub fn usable_in_front(&self, origin: Vec2, angle: f32) -> Option<Entity> {
+        let hit = self.cast(origin, angle, USE_RANGE)?;
+        let ray = LineSegment::ray(origin, angle, USE_RANGE);
+        let reached = |segment: Option<&LineSegment>| {
+            segment
+                .and_then(|s| s.hit(&ray))
+                .is_some_and(|h| h.distance <= hit.distance)
+        };
+        let doors = self.entities.doors.iter().map(|(e, d)| (e, d.segment()));
+        let switches = self
+            .entities
+            .switches
+            .iter()
+            .map(|(e, s)| (e, Some(s.segment())));
+        doors
+            .chain(switches)
+            .find(|(_, segment)| reached(segment.as_ref()))
+            .map(|(entity, _)| entity)
+    }
+
+    // Opens or closes the door in front of the player, or flips the switch.
+    pub fn use_in_front(&mut self, origin: Vec2, angle: f32, events: &mut Vec<GameEvent>) {
+        let Some(entity) = self.usable_in_front(origin, angle) else {
+            return;
+        };
+        if let Some(door) = self.entities.doors.get_mut(entity) {
+            if let Some(lock) = door.lock.filter(|&lock| !self.inventory.has_key(lock)) {
+                events.push(GameEvent::Locked(lock));
+                return;
+            }
+            let before = door.state;
+            door.activate();
+            events.extend(door_event(door, before));
+            events.push(GameEvent::Used(entity));
+        }
+        if let Some(switch) = self.entities.switches.get_mut(entity) {
+            if !switch.toggle() {
+                return;
+            }
+            events.push(GameEvent::Switched(switch.start.lerp(switch.end, 0.5)));
+            events.push(GameEvent::Used(entity));
+            for target in switch.targets.clone() {
+                self.trigger(&target, events);
+            }
+        }
+    }
+
+    // Sets off everything with the ID `id`: doors open or close whether or not they're locked,
+    // walls appear or disappear and dormant enemies wake up.
+    pub fn trigger(&mut self, id: &str, events: &mut Vec<GameEvent>) {
+        let entities = &mut self.entities;
+        for (_, door) in entities.doors.iter_mut() {
+            if door.id.as_deref() == Some(id) {
+                let before = door.state;
+                door.activate();
+                events.extend(door_event(door, before));
+            }
+        }
+        for (_, wall) in entities.walls.iter_mut() {
+            if wall.id == id {
+                wall.solid = !wall.solid;
+            }
+        }
+        let woken: Vec<_> = entities
+            .dormant
+            .iter()
+            .filter(|(_, dormant)| dormant.id == id)
+            .map(|(entity, _)| entity)
+            .collect();
+        for entity in woken {
+            entities.wake(entity);
+        }
+    }
+
+    // Fires a shot from the player, unless they're still reloading.
+    pub fn fire(&mut self, origin: Vec2, angle: f32, events: &mut Vec<GameEvent>) {
+        if self.reload > 0.0 {
+            return;
+        }
+        if !self.inventory.use_ammo(1) {
+            events.push(GameEvent::OutOfAmmo);
+            return;
+        }
+        self.reload = PLAYER_RELOAD;
+        let projectile = Projectile::new(Owner::Player, PLAYER_DAMAGE);
+        self.entities.spawn_projectile(origin, angle, projectile);
+        events.push(GameEvent::Fired);
+    }
+
+    // How much of the player's reload is left, from 1.0 just after firing to 0.0 when they can
+    // fire again.
+    pub fn recoil(&self) -> f32 {
+        self.reload / PLAYER_RELOAD
+    }
+
+    // Runs every system over the entities for one tick.
+    pub fn update(&mut self, dt: f32, player: Vec2, events: &mut Vec<GameEvent>) {
+        self.reload = (self.reload - dt).max(0.0);
+        self.clock += dt;
+        for texture in self.textures.iter_mut() {
+            texture.animate(self.clock);
+        }
+        for light in self.lights.iter_mut() {
+            light.animate(self.clock);
+        }
+        if let Some(cycle) = &self.day_cycle {
+            (self.ambient, self.sky) = cycle.at(self.clock);
+        }
+        self.ticked += dt;
+        while self.ticked >= TICK {
+            self.ticked -= TICK;
+            let start = events.len();
+            self.think(player, events);
+            self.projectiles(player, events);
+            self.damage(events, start);
+            self.movement(TICK);
+        }
+        self.collect(player, events);
+        self.portals(player, events);
+        self.teleport(player, events);
+        for (_, door) in self.entities.doors.iter_mut() {
+            let before = door.state;
+            let blocked = door.distance_to(player) < PLAYER_RADIUS;
+            door.update(dt, blocked);
+            events.extend(door_event(door, before));
+        }
+        self.emit_particles(dt);
+        for number in self.damage_numbers.iter_mut() {
+            number.age += dt;
+        }
+        self.damage_numbers.retain(|n| n.age < damage::LIFETIME);
+        self.regions(dt, player, events);
+        if self
+            .exit
+            .as_ref()
+            .is_some_and(|e| e.region.contains(player))
+        {
+            events.push(GameEvent::ExitReached);
+        }
+    }
+
+    // Reports the player walking into and out of trigger regions and runs their actions.
+    fn regions(&mut self, dt: f32, player: Vec2, events: &mut Vec<GameEvent>) {
+        let mut actions = Vec::new();
+        for (i, trigger) in self.triggers.iter_mut().enumerate() {
+            if trigger.spent {
+                continue;
+            }
+            let inside = trigger.shape.contains(player);
+            let centre = trigger.shape.centre();
+            match (trigger.occupied, inside) {
+                (false, true) => {
+                    events.push(GameEvent::Entered(i));
+                    actions.extend(trigger.on_enter.iter().map(|a| (a.clone(), centre)));
+                    trigger.stayed = 0.0;
+                }
+                (true, true) => {
+                    trigger.stayed += dt;
+                    while trigger.stayed >= trigger.interval {
+                        trigger.stayed -= trigger.interval;
+                        actions.extend(trigger.on_stay.iter().map(|a| (a.clone(), centre)));
+                    }
+                }
+                (true, false) => {
+                    events.push(GameEvent::Left(i));
+                    actions.extend(trigger.on_exit.iter().map(|a| (a.clone(), centre)));
+                    trigger.spent = trigger.once;
+                }
+                (false, false) => {}
+            }
+            trigger.occupied = inside;
+        }
+        for (action, centre) in actions {
+            self.act(action, centre, events);
+        }
+    }
+
+    fn act(&mut self, action: Action, centre: Vec2, events: &mut Vec<GameEvent>) {
+        match action {
+            Action::Message { text } => self.messages.push(text),
+            Action::Trigger { id } => self.trigger(&id, events),
+            Action::Enemy { position, enemy } => {
+                self.entities
+                    .spawn_enemy(position, enemy.sprite(), enemy.enemy);
+            }
+            Action::Pickup { position, pickup } => {
+                self.entities
+                    .spawn_pickup(position, pickup.sprite(), pickup);
+            }
+            Action::Sound { sound } => events.push(GameEvent::Cue(sound, centre)),
+        }
+    }
+
+    // Points every enemy that can see the player straight at them. Once they've reloaded, ones
+    // close enough hit the player and the rest shoot if they can.
+    fn think(&mut self, player: Vec2, events: &mut Vec<GameEvent>) {
+        let mut sightings = Vec::new();
+        for (entity, enemy) in self.entities.enemies.iter() {
+            let Some(&position) = self.entities.positions.get(entity) else {
+                continue;
+            };
+            let to_player = player - position;
+            let distance = to_player.length();
+            let seen = distance <= enemy.sight
+                && self.cast(position, to_player.angle(), distance).is_none();
+            sightings.push((entity, position, seen.then_some(to_player)));
+        }
+        let mut shots = Vec::new();
+        for (entity, position, sighting) in sightings {
+            let Some(enemy) = self.entities.enemies.get_mut(entity) else {
+                continue;
+            };
+            enemy.cooldown = (enemy.cooldown - TICK).max(0.0);
+            let velocity = match sighting {
+                Some(to_player) if to_player.length() > REACH => {
+                    to_player * (enemy.speed / to_player.length())
+                }
+                _ => Vec2::ZERO,
+            };
+            match (sighting, enemy.reload) {
+                _ if enemy.cooldown > 0.0 => {}
+                (Some(to_player), _) if to_player.length() <= BITE_RANGE => {
+                    enemy.cooldown = BITE_TIME;
+                    events.push(GameEvent::Hit {
+                        target: Target::Player,
+                        damage: enemy.damage,
+                        position: player,
+                    });
+                }
+                (Some(to_player), Some(reload)) => {
+                    enemy.cooldown = reload;
+                    shots.push((position, to_player.angle(), enemy.damage));
+                }
+                _ => {}
+            }
+            self.entities.velocities.insert(entity, velocity);
+        }
+        for (position, angle, damage) in shots {
+            let projectile = Projectile::new(Owner::Enemy, damage);
+            self.entities.spawn_projectile(position, angle, projectile);
+            events.push(GameEvent::Shot(position));
+        }
+    }
+
+    // Flies every projectile along its path for a tick, stopping it at the first wall or
+    // target in the way. Projectiles can't hit whoever fired them.
+    fn projectiles(&mut self, player: Vec2, events: &mut Vec<GameEvent>) {
+        let entities = &self.entities;
+        let mut finished = Vec::new();
+        let mut moves = Vec::new();
+        let mut impacts = Vec::new();
+        for (entity, projectile) in entities.projectiles.iter() {
+            let (Some(&position), Some(&velocity)) = (
+                entities.positions.get(entity),
+                entities.velocities.get(entity),
+            ) else {
+                continue;
+            };
+            let step = velocity * TICK;
+            let length = step.length();
+            let wall = self.cast(position, step.angle(), length);
+            let end = match wall {
+                Some(hit) => position + step * (hit.distance / length),
+                None => position + step,
+            };
+            let path = LineSegment::from_points(position, end, Rgb::WHITE);
+            let target = match projectile.owner {
+                Owner::Player => entities.enemies.iter().find_map(|(enemy, _)| {
+                    let &at = entities.positions.get(enemy)?;
+                    (path.distance_to(at) < RADIUS).then_some((Target::Entity(enemy), at))
+                }),
+                Owner::Enemy => {
+                    (path.distance_to(player) < RADIUS).then_some((Target::Player, player))
+                }
+            };
+            if let Some((target, at)) = target {
+                events.push(GameEvent::Hit {
+                    target,
+                    damage: projectile.damage,
+                    position: at,
+                });
+                finished.push(entity);
+            } else if let Some(hit) = wall {
+                events.push(GameEvent::Impact(end));
+                impacts.push((end, hit.normal));
+                finished.push(entity);
+            } else if projectile.lifetime <= TICK {
+                finished.push(entity);
+            } else {
+                moves.push((entity, end));
+            }
+        }
+        for (entity, position) in moves {
+            self.entities.positions.insert(entity, position);
+            if let Some(projectile) = self.entities.projectiles.get_mut(entity) {
+                projectile.lifetime -= TICK;
+            }
+        }
+        for entity in finished {
+            self.entities.despawn(entity);
+        }
+        for (position, normal) in impacts {
+            let ground = self.floor_at(position);
+            self.particles.burst(
+                Effect::Dust,
+                position,
+                SHOT_HEIGHT,
+                ground,
+                DUST,
+                Some(normal),
+            );
+        }
+    }
+
+    // Keeps teleporter pads sparkling and sparks flying from doors sliding open or shut, then
+    // moves every particle along.
+    fn emit_particles(&mut self, dt: f32) {
+        let mut sources = Vec::new();
+        for (entity, _) in self.entities.teleporters.iter() {
+            if let Some(&position) = self.entities.positions.get(entity) {
+                sources.push((Effect::Sparkle, position, TELEPORT_RADIUS, SPARKLE_RATE));
+            }
+        }
+        for (_, door) in self.entities.doors.iter() {
+            if matches!(door.state, DoorState::Opening | DoorState::Closing) {
+                let edge = door.start.lerp(door.end, door.openness);
+                sources.push((Effect::Spark, edge, 0.0, SPARK_RATE));
+            }
+        }
+        for (effect, position, radius, rate) in sources {
+            let ground = self.floor_at(position);
+            self.particles
+                .trickle(effect, position, radius, ground, rate, dt);
+        }
+        self.particles.update(dt);
+    }
+
+    // Hands the player every pickup they're standing on. Health is left where it is when
+    // they're already at full health.
+    fn collect(&mut self, player: Vec2, events: &mut Vec<GameEvent>) {
+        let mut taken = Vec::new();
+        for (entity, pickup) in self.entities.pickups.iter() {
+            let Some(&position) = self.entities.positions.get(entity) else {
+                continue;
+            };
+            if position.distance(player) > PICKUP_RADIUS {
+                continue;
+            }
+            match *pickup {
+                Pickup::Health { .. } if self.health.current >= self.health.max => continue,
+                Pickup::Health { amount } => {
+                    self.health.current = (self.health.current + amount).min(self.health.max);
+                }
+                Pickup::Ammo { amount } => self.inventory.add_ammo(amount),
+                Pickup::Key { key } => self.inventory.add_key(key),
+                Pickup::Item { ref name, count } => self.inventory.add_item(name, count),
+            }
+            taken.push(entity);
+            events.push(GameEvent::PickedUp(pickup.clone()));
+        }
+        for entity in taken {
+            self.entities.despawn(entity);
+        }
+    }
+
+    // Sends the player from an armed pad they're standing on to its partner. The player
+    // actually moves when the caller handles the event.
+    fn teleport(&mut self, player: Vec2, events: &mut Vec<GameEvent>) {
+        let entities = &mut self.entities;
+        let mut partner = None;
+        for (entity, teleporter) in entities.teleporters.iter_mut() {
+            let Some(&position) = entities.positions.get(entity) else {
+                continue;
+            };
+            if position.distance(player) > TELEPORT_RADIUS {
+                teleporter.armed = true;
+            } else if teleporter.armed && partner.is_none() {
+                partner = Some(teleporter.partner);
+            }
+        }
+        let Some(partner) = partner else {
+            return;
+        };
+        let (Some(&position), Some(teleporter)) = (
+            entities.positions.get(partner),
+            entities.teleporters.get_mut(partner),
+        ) else {
+            return;
+        };
+        teleporter.armed = false;
+        self.last_player = Some(position);
+        events.push(GameEvent::Teleported {
+            position,
+            facing: teleporter.facing,
+        });
+        let ground = self.floor_at(position);
+        self.particles.burst(
+            Effect::Sparkle,
+            position,
+            0.0,
+            ground,
+            ARRIVAL_SPARKLES,
+            None,
+        );
+    }
+
+    // Sends the player through any portal they walked through since the last update.
+    fn portals(&mut self, player: Vec2, events: &mut Vec<GameEvent>) {
+        let last = self.last_player.replace(player);
+        let Some(last) = last else {
+            return;
+        };
+        for (_, portal) in self.entities.portals.iter() {
+            if !portal::crossed(&portal.segment, last, player) {
+                continue;
+            }
+            let Some(partner) = self.entities.portals.get(portal.partner) else {
+                continue;
+            };
+            let (from, to) = (&portal.segment, &partner.segment);
+            let position = portal::carry(from, to, player);
+            self.last_player = Some(position);
+            events.push(GameEvent::Portalled {
+                position,
+                turn: portal::turn(from, to),
+            });
+            return;
+        }
+    }
+
+    // Takes hit points off whoever the hits since `start` in `events` landed on. Enemies that
+    // run out are removed, and the player running out ends the game.
+    fn damage(&mut self, events: &mut Vec<GameEvent>, start: usize) {
+        let hits: Vec<_> = events[start..]
+            .iter()
+            .filter_map(|event| match *event {
+                GameEvent::Hit { target, damage, .. } => Some((target, damage)),
+                _ => None,
+            })
+            .collect();
+        for (target, damage) in hits {
+            match target {
+                Target::Player if !self.health.is_dead() && !self.cheats.god => {
+                    self.health.damage(damage);
+                    if self.health.is_dead() {
+                        events.push(GameEvent::Died);
+                    }
+                }
+                Target::Player => {}
+                Target::Entity(entity) => {
+                    let Some(health) = self.entities.health.get_mut(entity) else {
+                        continue;
+                    };
+                    health.damage(damage);
+                    let dead = health.is_dead();
+                    if let Some(&position) = self.entities.positions.get(entity) {
+                        let top = self
+                            .entities
+                            .sprites
+                            .get(entity)
+                            .map_or(0.0, |s| s.elevation + s.size);
+                        let height = self.floor_at(position) + top;
+                        let number = DamageNumber::new(position, height, damage);
+                        self.damage_numbers.push(number);
+                        if dead {
+                            events.push(GameEvent::Killed(position));
+                        }
+                    }
+                    if dead {
+                        self.entities.despawn(entity);
+                    }
+                }
+            }
+        }
+    }
+
+    // Moves everything with a velocity, sliding along any wall in the way. Projectiles are
+    // moved by `projectiles` instead, since they stop at walls.
+    fn movement(&mut self, dt: f32) {
+        let mut moves = Vec::new();
+        for (entity, &velocity) in self.entities.velocities.iter() {
+            if self.entities.projectiles.get(entity).is_some() {
+                continue;
+            }
+            if let Some(&position) = self.entities.positions.get(entity) {
+                moves.push((entity, slide(self, position, velocity * dt, Reach::WALKING)));
+            }
+        }
+        for (entity, position) in moves {
+            self.entities.positions.insert(entity, position);
+        }
+    }
+
+    pub fn demo() -> Self {
+        Self::new(vec![
+            LineSegment::from_points(
+                Vec2::new(6.0, 6.0),
+                Vec2::new(4.0, 8.0),
+                Rgb::named("black").unwrap(),
+            ),
+            LineSegment::from_points(
+                Vec2::new(4.0, 8.0),
+                Vec2::new(7.0, 10.0),
+                Rgb::named("magenta").unwrap(),
+            ),
+            LineSegment::from_points(
+                Vec2::new(7.0, 10.0),
+                Vec2::new(6.0, 6.0),
+                Rgb::named("green").unwrap(),
+            ),
+            LineSegment::from_points(
+                Vec2::new(2.0, 6.1),
+                Vec2::new(-2.0, 6.0),
+                Rgb::named("white").unwrap(),
+            ),
+            LineSegment::from_points(
+                Vec2::new(-2.0, 6.0),
+                Vec2::new(-2.0, 10.0),
+                Rgb::named("magenta").unwrap(),
+            ),
+            LineSegment::from_points(
+                Vec2::new(-2.0, 10.0),
+                Vec2::new(2.0, 10.0),
+                Rgb::named("green").unwrap(),
+            ),
+            LineSegment::from_points(
+                Vec2::new(2.0, 10.0),
+                Vec2::new(2.0, 6.0),
+                Rgb::named("yellow").unwrap(),
+            ),
+        ])
+    }
+}
+
+fn door_event(door: &Door, before: DoorState) -> Option<GameEvent> {
+    let middle = door.start.lerp(door.end, 0.5);
+    match (before, door.state) {
+        (DoorState::Closed | DoorState::Closing, DoorState::Opening) => {
+            Some(GameEvent::DoorOpened(middle))
+        }
+        (DoorState::Opening | DoorState::Open(_), DoorState::Closing) => {
+            Some(GameEvent::DoorClosed(middle))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,62 @@
+// Runs the game in xterm.js: keys and mouse drags go in, and each frame's ANSI output comes out
+// and is written to the terminal.
+import init, { WebGame } from "./pkg/rhywbeth_web.js";
+
+const element = document.getElementById("terminal");
+const term = new Terminal({ fontSize: 10, lineHeight: 1, cursorBlink: false });
+const fit = new FitAddon.FitAddon();
+term.loadAddon(fit);
+term.open(element);
+fit.fit();
+term.focus();
+
+await init();
+const game = new WebGame(term.cols, term.rows);
+
+term.onResize(({ cols, rows }) => game.resize(cols, rows));
+window.addEventListener("resize", () => fit.fit());
+
+// The game takes every key, so none of them are typed into the terminal or used by the page.
+term.attachCustomKeyEventHandler((event) => {
+  if (event.type === "keydown") {
+    game.key_down(event.key, event.repeat);
+  } else if (event.type === "keyup") {
+    game.key_up(event.key);
+  }
+  event.preventDefault();
+  return false;
+});
+
+// Mouse positions are turned into cells, as a terminal would report them.
+const screen = element.querySelector(".xterm-screen");
+let dragging = false;
+function mouse(kind, event) {
+  const rect = screen.getBoundingClientRect();
+  const column = Math.floor(((event.clientX - rect.left) / rect.width) * term.cols);
+  const row = Math.floor(((event.clientY - rect.top) / rect.height) * term.rows);
+  game.mouse(kind, Math.max(column, 0), Math.max(row, 0));
+}
+screen.addEventListener("mousedown", (event) => {
+  dragging = true;
+  mouse("down", event);
+});
+window.addEventListener("mousemove", (event) => {
+  if (dragging) {
+    mouse("drag", event);
+  }
+});
+window.addEventListener("mouseup", (event) => {
+  if (dragging) {
+    dragging = false;
+    mouse("up", event);
+  }
+});
+
+let last = performance.now();
+function tick(now) {
+  const dt = Math.min((now - last) / 1000, 0.25);
+  last = now;
+  term.write(game.frame(dt));
+  requestAnimationFrame(tick);
+}
+requestAnimationFrame(tick);
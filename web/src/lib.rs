@@ -0,0 +1,263 @@
+use std::{collections::VecDeque, time::Duration};
+
+use anyhow::Result;
+use rhywbeth::{
+    backend::{
+        event::{
+            Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers, MouseButton,
+            MouseEvent, MouseEventKind,
+        },
+        AnsiWriter, Backend,
+    },
+    colour::{ColourMode, Rgb},
+    event::GameEvent,
+    framebuffer::FrameBuffer,
+    game::{Game, Level},
+    input::{Action, Input},
+    maze::MazeSize,
+    render::{Output, Renderer, Viewport},
+    shake,
+};
+use wasm_bindgen::prelude::*;
+
+// Exits lead on to a new maze this size, since there are no map files to go to.
+const MAZE_SIZE: MazeSize = MazeSize {
+    width: 12,
+    height: 12,
+};
+// Hides the cursor, which xterm.js would otherwise draw wherever the last cell was written.
+const HIDE_CURSOR: &str = "\x1b[?25l";
+
+// xterm.js in a web page. The page passes in keys and mouse drags as they happen and writes out
+// whatever was drawn after each frame.
+struct Xterm {
+    size: (u16, u16),
+    events: VecDeque<Event>,
+    ansi: AnsiWriter,
+    output: Vec<u8>,
+}
+
+impl Backend for Xterm {
+    fn size(&self) -> Result<(u16, u16)> {
+        Ok(self.size)
+    }
+
+    fn present(&mut self, frame: &FrameBuffer) -> Result<()> {
+        self.ansi.write(frame, &mut self.output)
+    }
+
+    // A page can't wait for input, so this only gives what has already come in.
+    fn poll_event(&mut self, _timeout: Duration) -> Result<Option<Event>> {
+        let event = self.events.pop_front();
+        if let Some(Event::Resize(columns, rows)) = event {
+            self.size = (columns, rows);
+            self.ansi.invalidate();
+        }
+        Ok(event)
+    }
+}
+
+// The game as the page sees it. It's stepped once per animation frame, and everything else the
+// terminal version does with files, like saves, screenshots and demos, is left out.
+#[wasm_bindgen]
+pub struct WebGame {
+    game: Game,
+    level: Level,
+    input: Input,
+    renderer: Renderer,
+    terminal: Xterm,
+    events: Vec<GameEvent>,
+}
+
+#[wasm_bindgen]
+impl WebGame {
+    // Starts the demo level in a terminal `columns` by `rows` cells.
+    #[wasm_bindgen(constructor)]
+    pub fn new(columns: u16, rows: u16) -> Result<WebGame, JsError> {
+        let level = Level::Demo;
+        let game = level.load().map_err(js_error)?;
+        let terminal = Xterm {
+            size: (columns, rows),
+            events: VecDeque::new(),
+            ansi: AnsiWriter::new(ColourMode::TrueColour),
+            output: HIDE_CURSOR.as_bytes().to_vec(),
+        };
+        Ok(Self {
+            game,
+            level,
+            input: Input::new(),
+            renderer: Renderer::new(Output::HalfBlocks),
+            terminal,
+            events: Vec::new(),
+        })
+    }
+
+    // `key` is a DOM key name, like "w", "ArrowUp" or "F3". Keys without a terminal equivalent
+    // are ignored.
+    pub fn key_down(&mut self, key: &str, repeat: bool) {
+        let kind = if repeat {
+            KeyEventKind::Repeat
+        } else {
+            KeyEventKind::Press
+        };
+        self.push_key(key, kind);
+    }
+
+    pub fn key_up(&mut self, key: &str) {
+        self.push_key(key, KeyEventKind::Release);
+    }
+
+    // `kind` is "down", "drag" or "up", and the position is in cells.
+    pub fn mouse(&mut self, kind: &str, column: u16, row: u16) {
+        let kind = match kind {
+            "down" => MouseEventKind::Down(MouseButton::Left),
+            "drag" => MouseEventKind::Drag(MouseButton::Left),
+            "up" => MouseEventKind::Up(MouseButton::Left),
+            _ => return,
+        };
+        self.terminal.events.push_back(Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }));
+    }
+
+    pub fn resize(&mut self, columns: u16, rows: u16) {
+        self.terminal.events.push_back(Event::Resize(columns, rows));
+    }
+
+    // Plays `dt` seconds and returns what to write to the terminal to show it.
+    pub fn frame(&mut self, dt: f32) -> Result<String, JsError> {
+        self.step(dt).map_err(js_error)?;
+        let output = std::mem::take(&mut self.terminal.output);
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+}
+
+impl WebGame {
+    fn push_key(&mut self, key: &str, kind: KeyEventKind) {
+        let Some(code) = key_code(key) else {
+            return;
+        };
+        self.terminal.events.push_back(Event::Key(KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
+            kind,
+            state: KeyEventState::NONE,
+        }));
+    }
+
+    fn step(&mut self, dt: f32) -> Result<()> {
+        while let Some(event) = self.terminal.poll_event(Duration::ZERO)? {
+            if let Event::Resize(..) = event {
+                continue;
+            }
+            let action = self.input.handle(event, &mut self.game.camera);
+            self.act(action);
+        }
+
+        let (game, events) = (&mut self.game, &mut self.events);
+        self.input.update(&mut game.camera, &game.world, dt, events);
+        game.world.update(dt, game.camera.position, events);
+        for message in game.world.messages.drain(..) {
+            self.renderer.notify(message);
+        }
+        let mut next = None;
+        for event in events.drain(..) {
+            if let Some(magnitude) = shake::strength(&event, game.camera.position) {
+                self.renderer.shake.add(magnitude);
+            }
+            match event {
+                GameEvent::Died => next = Some(self.level.clone()),
+                GameEvent::Teleported { position, facing } => {
+                    game.camera.position = position;
+                    game.camera.rotation = facing.unwrap_or(game.camera.rotation);
+                    self.renderer.flash(Rgb::WHITE);
+                }
+                GameEvent::Portalled { position, turn } => {
+                    game.camera.position = position;
+                    game.camera.rotate(turn);
+                }
+                GameEvent::PickedUp(ref pickup) => {
+                    self.renderer.notify(format!("Picked up {pickup}"));
+                    self.renderer.flash(pickup.sprite().colour);
+                }
+                GameEvent::Locked(lock) => self.renderer.notify(format!("Requires the {lock} key")),
+                GameEvent::OutOfAmmo => self.renderer.notify("Out of ammo"),
+                GameEvent::ExitReached if next.is_none() => {
+                    self.renderer.notify("Level clear");
+                    next = Some(Level::Maze {
+                        size: MAZE_SIZE,
+                        seed: rand::random(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        if let Some(level) = next {
+            self.game = level.load()?;
+            self.level = level;
+        }
+
+        let size = self.terminal.size()?;
+        let view = (Viewport::full(size), self.game.camera);
+        self.renderer
+            .render_views(size, &[view], &self.game.world, None, &mut self.terminal)
+    }
+
+    // Quitting, pausing and anything that needs files or the console don't apply in a page.
+    fn act(&mut self, action: Action) {
+        let camera = self.game.camera;
+        match action {
+            Action::ToggleMinimap => self.renderer.minimap = !self.renderer.minimap,
+            Action::ToggleStats => self.renderer.stats = !self.renderer.stats,
+            Action::ToggleOverhead => self.renderer.overhead = !self.renderer.overhead,
+            Action::Use => {
+                self.game
+                    .world
+                    .use_in_front(camera.position, camera.rotation, &mut self.events)
+            }
+            Action::Fire => {
+                self.game
+                    .world
+                    .fire(camera.position, camera.rotation, &mut self.events)
+            }
+            Action::ToggleCheat(cheat) => {
+                let on = self.game.world.cheats.toggle(cheat);
+                let state = if on { "on" } else { "off" };
+                self.renderer.notify(format!("Cheat {cheat} {state}"));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn key_code(key: &str) -> Option<KeyCode> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let code = match key {
+        "ArrowUp" => KeyCode::Up,
+        "ArrowDown" => KeyCode::Down,
+        "ArrowLeft" => KeyCode::Left,
+        "ArrowRight" => KeyCode::Right,
+        "Enter" => KeyCode::Enter,
+        "Escape" => KeyCode::Esc,
+        "Tab" => KeyCode::Tab,
+        "Backspace" => KeyCode::Backspace,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Insert" => KeyCode::Insert,
+        "Delete" => KeyCode::Delete,
+        _ => KeyCode::F(key.strip_prefix('F')?.parse().ok()?),
+    };
+    Some(code)
+}
+
+fn js_error(e: anyhow::Error) -> JsError {
+    JsError::new(&format!("{e:#}"))
+}